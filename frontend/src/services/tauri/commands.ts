@@ -12,6 +12,13 @@ import type {
   ChatMessage,
   BackendChatSession,
   BackendChatMessage,
+  CompareRequest,
+  CompareTarget,
+  ScheduledJob,
+  ScheduledJobInput,
+  AiRequestRecord,
+  WorkspaceBudget,
+  WorkspaceUsage,
 } from '@/utils/types';
 
 export type CodeagentWrapperExecResult = {
@@ -112,6 +119,8 @@ export async function sendChatMessageStreaming(
   directCli?: boolean,
   cliCommand?: string,
   cliArgs?: string[],
+  planMode?: boolean,
+  autoContext?: boolean,
 ): Promise<string> {
   return invoke('send_chat_message_streaming', {
     message,
@@ -126,6 +135,38 @@ export async function sendChatMessageStreaming(
     directCli,
     cliCommand,
     cliArgs,
+    planMode,
+    autoContext,
+  });
+}
+
+export async function executePlan(
+  plan: string,
+  message: string,
+  sessionId: string,
+  contextFiles?: string[],
+  codeCli?: string,
+  codexModel?: string,
+  workspaceId?: string,
+  workspaceDir?: string,
+  codeCliTaskId?: string | null,
+  directCli?: boolean,
+  cliCommand?: string,
+  cliArgs?: string[],
+): Promise<string> {
+  return invoke('execute_plan', {
+    plan,
+    message,
+    sessionId,
+    contextFiles,
+    codeCli,
+    codexModel,
+    workspaceId,
+    workspaceDir,
+    codeCliTaskId,
+    directCli,
+    cliCommand,
+    cliArgs,
   });
 }
 
@@ -368,6 +409,7 @@ function normalizeChatMessage(message: BackendChatMessage): ChatMessage {
     files: message.files || [],
     model: message.model,
     fileMetadata: message.fileMetadata || message.file_metadata,
+    metadata: message.metadata,
   };
 }
 
@@ -419,6 +461,301 @@ export async function updateChatSessionName(sessionId: string, name: string): Pr
   return normalizeChatSession(session);
 }
 
+// Importing Claude Code / Codex / Gemini CLI transcripts into the chat history
+export type TranscriptSource = 'claude' | 'codex' | 'gemini';
+
+export interface SessionImportReport {
+  imported: number;
+  updated: number;
+  unchanged: number;
+  unmapped: number;
+  duplicates: number;
+  errors: string[];
+}
+
+export async function importCliSessions(sources?: TranscriptSource[]): Promise<SessionImportReport> {
+  return invoke('import_cli_sessions', { sources });
+}
+
+// Inline (ghost text) completions for the editor
+export interface InlineCompletionRequest {
+  file_path: string;
+  language?: string;
+  prefix: string;
+  suffix: string;
+  line: number;
+  column: number;
+  workspace_dir?: string;
+}
+
+export interface InlineCompletion {
+  text: string;
+  cached: boolean;
+  skipped: 'disabled' | 'ignored' | 'superseded' | 'timeout' | null;
+  elapsed_ms: number;
+}
+
+export async function requestInlineCompletion(request: InlineCompletionRequest): Promise<InlineCompletion> {
+  return invoke('request_inline_completion', { request });
+}
+
+export async function cancelInlineCompletion(filePath: string): Promise<void> {
+  return invoke('cancel_inline_completion', { filePath });
+}
+
+// Rewriting an editor selection with an instruction; nothing is written to disk
+export interface SelectionEditRequest {
+  file_path: string;
+  start_line: number;
+  end_line: number;
+  instruction: string;
+  content?: string;
+  native_model?: string;
+  code_cli?: string;
+  codex_model?: string;
+  workspace_dir?: string;
+  max_attempts?: number;
+}
+
+export interface SelectionEdit {
+  file_path: string;
+  start_line: number;
+  end_line: number;
+  original: string;
+  replacement: string;
+  diff: string;
+  attempts: number;
+}
+
+export async function editSelection(request: SelectionEditRequest): Promise<SelectionEdit> {
+  return invoke('edit_selection', { request });
+}
+
+// Test-fix loops: fix requests stream into the chat session; progress arrives as
+// `test-fix-progress` / `test-fix-finished` events. Cancel with cancelStreamingRequest(runId).
+export interface TestFixRequest {
+  test_command: string;
+  shell?: string;
+  workspace_dir: string;
+  workspace_id?: string;
+  session_id?: string;
+  code_cli: string;
+  codex_model?: string;
+  direct_cli?: boolean;
+  cli_command?: string;
+  cli_args?: string[];
+  max_iterations?: number;
+}
+
+export interface TestFixIteration {
+  iteration: number;
+  passed: boolean;
+  exit_code: number | null;
+  failures: string;
+  fix_request_id: string | null;
+}
+
+export type TestFixStatus = 'passed' | 'failed' | 'budget_exhausted' | 'cancelled' | 'error';
+
+export async function startTestFixLoop(
+  request: TestFixRequest,
+): Promise<{ run_id: string; session_id: string }> {
+  return invoke('start_test_fix_loop', { request });
+}
+
+// Commit messages drafted from the staged diff; commitStagedChanges resolves to the short hash
+export type DraftKind = 'commit' | 'pull_request';
+
+export interface CommitMessageRequest {
+  workspace_dir: string;
+  kind?: DraftKind;
+  native_model?: string;
+  code_cli?: string;
+  codex_model?: string;
+  hint?: string;
+}
+
+export interface CommitMessageDraft {
+  kind: DraftKind;
+  message: string;
+  files: string[];
+  excluded_files: string[];
+  truncated: boolean;
+}
+
+export async function generateCommitMessage(request: CommitMessageRequest): Promise<CommitMessageDraft> {
+  return invoke('generate_commit_message', { request });
+}
+
+export async function commitStagedChanges(workspaceDir: string, message: string): Promise<string> {
+  return invoke('commit_staged_changes', { workspaceDir, message });
+}
+
+// AI code review of the uncommitted changes (or a commit range); reviews are stored per commit
+export type ReviewSeverity = 'error' | 'warning' | 'info';
+
+export interface CodeReviewRequest {
+  workspace_dir: string;
+  range?: string;
+  native_model?: string;
+  code_cli?: string;
+  codex_model?: string;
+  focus?: string;
+}
+
+export interface ReviewFinding {
+  file: string;
+  path: string;
+  line: number;
+  end_line?: number | null;
+  reviewed_line: number;
+  severity: ReviewSeverity;
+  message: string;
+  suggested_fix?: string | null;
+  anchor_text: string;
+  outdated: boolean;
+}
+
+export interface CodeReview {
+  id: string;
+  workspace_dir: string;
+  commit_hash?: string | null;
+  range?: string | null;
+  backend: string;
+  findings: ReviewFinding[];
+  files: string[];
+  excluded_files: string[];
+  truncated: boolean;
+  dropped: number;
+  created_at: number;
+}
+
+export async function reviewChanges(request: CodeReviewRequest): Promise<CodeReview> {
+  return invoke('review_changes', { request });
+}
+
+export async function listCodeReviews(
+  workspaceDir: string,
+  commitHash?: string,
+  limit?: number,
+): Promise<CodeReview[]> {
+  return invoke('list_code_reviews', { workspaceDir, commitHash, limit });
+}
+
+// Findings come back moved to the current lines of their files
+export async function getCodeReview(id: string): Promise<CodeReview | null> {
+  return invoke('get_code_review', { id });
+}
+
+export async function deleteCodeReview(id: string): Promise<boolean> {
+  return invoke('delete_code_review', { id });
+}
+
+// Build or update the search index auto context reads from; it then follows file changes
+export async function refreshSearchIndex(workspaceDir: string): Promise<void> {
+  await invoke('refresh_search_index', { workspaceDir });
+}
+
+// Comparison runs: one prompt streamed to several Code CLIs, each under its own request id.
+export async function sendCompareRequest(
+  message: string,
+  targets: CompareTarget[],
+  contextFiles?: string[],
+  sessionId?: string | null,
+  workspaceId?: string,
+  workspaceDir?: string,
+): Promise<CompareRequest> {
+  return invoke('send_compare_request', {
+    message,
+    targets,
+    contextFiles,
+    sessionId,
+    workspaceId,
+    workspaceDir,
+  });
+}
+
+export async function selectCompareWinner(
+  sessionId: string,
+  compareId: string,
+  messageId: string,
+): Promise<{ session: ChatSession; codeCli: string | null; codeCliTaskId: string | null }> {
+  const result = await invoke<{
+    session: BackendChatSession;
+    code_cli: string | null;
+    code_cli_task_id: string | null;
+  }>('select_compare_winner', { sessionId, compareId, messageId });
+  return {
+    session: normalizeChatSession(result.session),
+    codeCli: result.code_cli,
+    codeCliTaskId: result.code_cli_task_id,
+  };
+}
+
+export async function respondPermissionRequest(permissionId: string, allow: boolean): Promise<void> {
+  return invoke('respond_permission_request', { permissionId, allow });
+}
+
 export async function cancelStreamingRequest(requestId: string): Promise<void> {
   return invoke('cancel_streaming_request', { requestId });
 }
+
+// Scheduled job commands
+export async function listScheduledJobs(workspaceId?: string): Promise<ScheduledJob[]> {
+  return invoke('list_scheduled_jobs', { workspaceId });
+}
+
+export async function createScheduledJob(input: ScheduledJobInput): Promise<ScheduledJob> {
+  return invoke('create_scheduled_job', { input });
+}
+
+export async function updateScheduledJob(id: string, input: ScheduledJobInput): Promise<ScheduledJob> {
+  return invoke('update_scheduled_job', { id, input });
+}
+
+export async function deleteScheduledJob(id: string): Promise<void> {
+  return invoke('delete_scheduled_job', { id });
+}
+
+export async function runScheduledJobNow(id: string): Promise<void> {
+  return invoke('run_scheduled_job_now', { id });
+}
+
+export async function previewSchedule(schedule: string, count?: number): Promise<string[]> {
+  return invoke('preview_schedule', { schedule, count });
+}
+
+// Request journal commands
+export async function listAiRequests(
+  status?: AiRequestRecord['status'],
+  sessionId?: string,
+  limit?: number
+): Promise<AiRequestRecord[]> {
+  return invoke('list_ai_requests', { status, sessionId, limit });
+}
+
+export async function resumeAiRequest(requestId: string): Promise<string> {
+  return invoke('resume_ai_request', { requestId });
+}
+
+export async function dismissAiRequest(requestId: string): Promise<void> {
+  return invoke('dismiss_ai_request', { requestId });
+}
+
+// Workspace budget commands
+export async function getWorkspaceBudget(workspaceId: string): Promise<WorkspaceBudget | null> {
+  return invoke('get_workspace_budget', { workspaceId });
+}
+
+export async function saveWorkspaceBudget(workspaceId: string, budget: WorkspaceBudget | null): Promise<void> {
+  return invoke('save_workspace_budget', { workspaceId, budget });
+}
+
+export async function getWorkspaceUsage(workspaceId: string): Promise<WorkspaceUsage> {
+  return invoke('get_workspace_usage', { workspaceId });
+}
+
+// Allow requests past the workspace's hard limits for a while; resolves to the end time (ms)
+export async function overrideWorkspaceBudget(workspaceId: string, minutes?: number): Promise<number> {
+  return invoke('override_workspace_budget', { workspaceId, minutes });
+}
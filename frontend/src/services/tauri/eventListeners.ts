@@ -1,6 +1,14 @@
 import type { useAppStore } from '@/stores';
 import { useChatStore } from '@/stores/chatStore';
-import type { AiResponseEventPayload } from '@/utils/types';
+import { useFileStore } from '@/stores/filesStore';
+import { showWarning } from '@/utils/toast';
+import type {
+  AiContextEventPayload,
+  AiResponseEventPayload,
+  CompareCompleteEventPayload,
+  FilesChangedByRunEventPayload,
+  PermissionRequestEventPayload,
+} from '@/utils/types';
 
 import { eventService } from './events';
 
@@ -29,6 +37,48 @@ export async function initTauriEventListeners(context: ListenerContext): Promise
         console.error('Failed to handle ai-response event:', error);
       }
     }),
+    eventService.subscribe('ai-context', (payload) => {
+      const parsed = parseJsonPayload<AiContextEventPayload>(payload);
+      if (parsed?.request_id && parsed.manifest) {
+        context.chatStore.handleAiContext(parsed);
+      }
+    }),
+    eventService.subscribe('ai-compare-complete', (payload) => {
+      const parsed = parseJsonPayload<CompareCompleteEventPayload>(payload);
+      if (parsed?.compare_id) {
+        void context.chatStore.handleCompareComplete(parsed);
+      }
+    }),
+    eventService.subscribe('permission-request', (payload) => {
+      const parsed = parseJsonPayload<PermissionRequestEventPayload>(payload);
+      if (parsed?.permission_id) {
+        context.chatStore.handlePermissionRequest(parsed);
+      }
+    }),
+    eventService.subscribe('files-changed-by-run', (payload) => {
+      const parsed = parseJsonPayload<FilesChangedByRunEventPayload>(payload);
+      if (parsed?.changes?.length) {
+        context.chatStore.handleFilesChanged(parsed);
+        void refreshChangedFiles(parsed);
+      }
+    }),
+    eventService.subscribe('open-file-request', (payload) => {
+      const parsed = parseJsonPayload<{ path: string; line?: number | null }>(payload);
+      if (parsed?.path) {
+        useFileStore()
+          .openFile(parsed.path)
+          .catch((error) => console.error('Failed to open file requested by local API:', error));
+      }
+    }),
+    eventService.subscribe('secrets-redacted', (payload) => {
+      const parsed = parseJsonPayload<{
+        redactions: { kind: string; placeholder: string; occurrences: number }[];
+      }>(payload);
+      if (parsed?.redactions?.length) {
+        const kinds = [...new Set(parsed.redactions.map((r) => r.kind))].join(', ');
+        showWarning(`已在发送前隐藏 ${parsed.redactions.length} 个疑似密钥：${kinds}`);
+      }
+    }),
     eventService.subscribe('settings:updated', () => {
       void context.appStore.loadSettings();
     }),
@@ -39,6 +89,24 @@ export async function initTauriEventListeners(context: ListenerContext): Promise
   };
 }
 
+// Reload the explorer directories that contain files changed by a run
+async function refreshChangedFiles(payload: FilesChangedByRunEventPayload) {
+  const fileStore = useFileStore();
+  const reloaded = new Set<string>();
+  for (const change of payload.changes) {
+    const parent = change.path.substring(0, change.path.lastIndexOf('/'));
+    if (reloaded.has(parent)) {
+      continue;
+    }
+    reloaded.add(parent);
+    try {
+      await fileStore.reloadDirectory(change.path, false);
+    } catch (error) {
+      console.error('Failed to reload directory after agent run:', error);
+    }
+  }
+}
+
 export function disposeTauriEventListeners() {
   if (!cleanupFns.length) {
     return;
@@ -55,21 +123,25 @@ export function disposeTauriEventListeners() {
 }
 
 function parseAiResponsePayload(payload: unknown): AiResponseEventPayload | null {
+  return parseJsonPayload<AiResponseEventPayload>(payload);
+}
+
+function parseJsonPayload<T>(payload: unknown): T | null {
   if (!payload) {
     return null;
   }
 
   if (typeof payload === 'string') {
     try {
-      return JSON.parse(payload) as AiResponseEventPayload;
+      return JSON.parse(payload) as T;
     } catch (error) {
-      console.error('Failed to parse ai-response payload:', error);
+      console.error('Failed to parse event payload:', error);
       return null;
     }
   }
 
   if (typeof payload === 'object') {
-    return payload as AiResponseEventPayload;
+    return payload as T;
   }
 
   return null;
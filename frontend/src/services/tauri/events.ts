@@ -16,6 +16,13 @@ export type EventType =
   | 'chat:message:received'
   | 'chat:message:sent'
   | 'ai-response'
+  | 'ai-context'
+  | 'ai-compare-complete'
+  | 'permission-request'
+  | 'files-changed-by-run'
+  | 'open-file-request'
+  | 'secrets-redacted'
+  | 'scheduled-job-finished'
   | 'settings:updated'
   | 'workspace:changed'
   | 'app:error'
@@ -51,6 +58,13 @@ class EventService {
       'chat:message:received',
       'chat:message:sent',
       'ai-response',
+      'ai-context',
+      'ai-compare-complete',
+      'permission-request',
+      'files-changed-by-run',
+      'open-file-request',
+      'secrets-redacted',
+      'scheduled-job-finished',
       'settings:updated',
       'workspace:changed',
       'app:error',
@@ -47,8 +47,9 @@ export const useAppStore = defineStore('app', () => {
       temperature: 0.7,
       topP: 1.0,
       model_list: ['claude-4', 'gpt-5', 'deepseek'],
-      code_cli: ['claude-cli', 'codex-cli', 'gemini-cli'],
+      code_cli: ['claude-cli', 'codex-cli', 'gemini-cli', ...(import.meta.env.DEV ? ['mock-cli'] : [])],
       useDirectCodeCli: false,
+      autoContext: false,
     },
     paths: {
       nodejs: '',
@@ -59,6 +60,8 @@ export const useAppStore = defineStore('app', () => {
       { name: 'claude-cli', command: '/usr/bin/claude', args: '' },
       { name: 'codex-cli', command: '/usr/bin/codex', args: '' },
       { name: 'gemini-cli', command: '/usr/bin/gemini', args: '' },
+      // Scripted replies for UI development without real CLIs (see src-tauri/fixtures/mock)
+      ...(import.meta.env.DEV ? [{ name: 'mock-cli', command: '', args: '' }] : []),
     ],
     models: [
       { id: 'claude-4-5', name: 'Claude 4.5', provider: 'Anthropic', endpoint: 'https://api.anthropic.com' },
@@ -7,13 +7,29 @@ import {
   deleteChatSession,
   updateChatSessionName,
   cancelStreamingRequest,
+  sendCompareRequest,
+  selectCompareWinner,
+  listAiRequests,
+  resumeAiRequest,
+  dismissAiRequest,
+  executePlan,
+  respondPermissionRequest,
 } from '@/services/tauri/commands';
 import type {
+  AiContextEventPayload,
+  AiRequestRecord,
   ChatMessage,
   ChatSession,
+  ContextManifest,
   SendMessageOptions,
   AiResponseEventPayload,
+  CompareCompleteEventPayload,
+  CompareTarget,
+  FileChange,
   FileMetadata,
+  FilesChangedByRunEventPayload,
+  PendingPlan,
+  PermissionRequestEventPayload,
 } from '@/utils/types';
 
 export const useChatStore = defineStore('chat', () => {
@@ -29,6 +45,17 @@ export const useChatStore = defineStore('chat', () => {
   const codeCliTaskIds = ref<Record<string, string>>({});
   const pendingCodeCliByRequestId = new Map<string, string>();
   const pendingUserMessageId = ref<string>('');
+  // Requests that were still running when the app last exited
+  const interruptedRequests = ref<AiRequestRecord[]>([]);
+  // Sub-request ids of the running comparison; streaming ends when all are done
+  const pendingCompareRequestIds = new Set<string>();
+  // Plan run of plan-then-execute mode, until it is approved or discarded
+  const pendingPlan = ref<PendingPlan | null>(null);
+  // CLI permission prompts waiting for the user, oldest first
+  const permissionRequests = ref<PermissionRequestEventPayload[]>([]);
+  const runFileChanges = ref<Record<string, FileChange[]>>({});
+  // Context manifests of requests, by request id; may arrive before the reply's message exists
+  const requestContexts = ref<Record<string, ContextManifest>>({});
 
   function setAssociatedFiles(paths: string[]) {
     associatedFiles.value = [...paths];
@@ -112,12 +139,17 @@ export const useChatStore = defineStore('chat', () => {
         resumeTaskId || null,
         options.directCli,
         options.cliCommand,
-        options.cliArgs
+        options.cliArgs,
+        options.planMode,
+        options.autoContext
       );
       currentRequestId.value = requestId;
       if (codeCli) {
         pendingCodeCliByRequestId.set(requestId, codeCli);
       }
+      if (options.planMode) {
+        pendingPlan.value = { requestId, sessionId: currentSessionId.value, options: { ...options, files } };
+      }
       messages.value.push({
         id: requestId,
         role: 'assistant',
@@ -127,15 +159,249 @@ export const useChatStore = defineStore('chat', () => {
         model,
         sessionId: currentSessionId.value || '',
         workspaceId: options.workspaceId,
+        metadata: options.planMode ? { plan: true } : undefined,
       });
     } catch (error) {
       isStreaming.value = false;
       currentRequestId.value = '';
+      // Nothing was sent (unresolved @ reference, budget limit, ...); the input keeps the text.
+      messages.value = messages.value.filter((msg) => msg.id !== userMessageId);
+      pendingUserMessageId.value = '';
       console.error('Failed to send message:', error);
       throw error;
     }
   }
 
+  async function sendCompare(
+    options: Pick<SendMessageOptions, 'content' | 'files' | 'workspaceId' | 'workspaceDir'>,
+    targets: CompareTarget[]
+  ): Promise<void> {
+    const content = options.content.trim();
+    if (!content || targets.length < 2) {
+      return;
+    }
+
+    const userMessageId = `${Date.now()}-user`;
+    messages.value.push({
+      id: userMessageId,
+      role: 'user',
+      content,
+      timestamp: new Date().toISOString(),
+      files: options.files,
+      sessionId: currentSessionId.value || '',
+      workspaceId: options.workspaceId,
+    });
+    pendingUserMessageId.value = userMessageId;
+
+    try {
+      isStreaming.value = true;
+      const compare = await sendCompareRequest(
+        content,
+        targets,
+        options.files,
+        currentSessionId.value || null,
+        options.workspaceId,
+        options.workspaceDir
+      );
+      currentSessionId.value = compare.session_id;
+      const userMessage = messages.value.find((msg) => msg.id === userMessageId);
+      if (userMessage) {
+        userMessage.metadata = { compare_id: compare.compare_id };
+      }
+      for (const run of compare.runs) {
+        pendingCompareRequestIds.add(run.request_id);
+        messages.value.push({
+          id: run.request_id,
+          role: 'assistant',
+          content: '',
+          timestamp: new Date().toISOString(),
+          sessionId: compare.session_id,
+          workspaceId: options.workspaceId,
+          metadata: { compare_id: compare.compare_id, code_cli: run.code_cli },
+        });
+      }
+    } catch (error) {
+      isStreaming.value = false;
+      pendingUserMessageId.value = '';
+      messages.value = messages.value.filter((msg) => msg.id !== userMessageId);
+      console.error('Failed to send comparison:', error);
+      throw error;
+    }
+  }
+
+  function handleCompareComplete(payload: CompareCompleteEventPayload): void {
+    for (const run of payload.runs) {
+      pendingCompareRequestIds.delete(run.request_id);
+      if (run.status === 'cancelled') {
+        messages.value = messages.value.filter((msg) => msg.id !== run.request_id);
+      }
+    }
+    if (!pendingCompareRequestIds.size) {
+      void finalizeStreaming(payload.session_id);
+    }
+  }
+
+  // Continue the conversation on the backend that produced the chosen reply.
+  async function pickCompareWinner(messageId: string): Promise<void> {
+    const message = messages.value.find((msg) => msg.id === messageId);
+    const compareId = message?.metadata?.compare_id;
+    if (!compareId || !currentSessionId.value) {
+      return;
+    }
+
+    const result = await selectCompareWinner(currentSessionId.value, compareId, messageId);
+    for (const msg of messages.value) {
+      if (msg.metadata?.compare_id === compareId && msg.metadata.code_cli) {
+        msg.metadata = { ...msg.metadata, winner: msg.id === messageId };
+      }
+    }
+    codeCliTaskIds.value = { ...(result.session.codeCliTaskIds || {}) };
+    if (result.codeCli) {
+      currentCodeCli.value = result.codeCli;
+    }
+  }
+
+  // Execute the (possibly edited) plan, continuing the CLI session the plan was made in.
+  async function approvePlan(plan: string): Promise<void> {
+    const pending = pendingPlan.value;
+    if (!pending?.plan || !pending.sessionId || isStreaming.value) {
+      return;
+    }
+
+    const { options } = pending;
+    const codeCli = options.codeCli;
+    const resumeTaskId = pending.codeCliTaskId || (codeCli ? codeCliTaskIds.value[codeCli] : undefined);
+    const userMessageId = `${Date.now()}-user`;
+    messages.value.push({
+      id: userMessageId,
+      role: 'user',
+      content: options.content,
+      timestamp: new Date().toISOString(),
+      files: options.files,
+      model: options.model,
+      sessionId: pending.sessionId,
+      workspaceId: options.workspaceId,
+    });
+    pendingUserMessageId.value = userMessageId;
+
+    try {
+      isStreaming.value = true;
+      const requestId = await executePlan(
+        plan,
+        options.content,
+        pending.sessionId,
+        options.files,
+        codeCli,
+        options.model,
+        options.workspaceId,
+        options.workspaceDir,
+        resumeTaskId || null,
+        options.directCli,
+        options.cliCommand,
+        options.cliArgs
+      );
+      pendingPlan.value = null;
+      currentRequestId.value = requestId;
+      if (codeCli) {
+        pendingCodeCliByRequestId.set(requestId, codeCli);
+        currentCodeCli.value = codeCli;
+      }
+      messages.value.push({
+        id: requestId,
+        role: 'assistant',
+        content: '',
+        timestamp: new Date().toISOString(),
+        files: options.files,
+        model: options.model,
+        sessionId: pending.sessionId,
+        workspaceId: options.workspaceId,
+      });
+    } catch (error) {
+      isStreaming.value = false;
+      pendingUserMessageId.value = '';
+      messages.value = messages.value.filter((msg) => msg.id !== userMessageId);
+      console.error('Failed to execute plan:', error);
+      throw error;
+    }
+  }
+
+  function discardPlan(): void {
+    pendingPlan.value = null;
+  }
+
+  // Attach the files a run changed to its reply; the diffs are kept per request.
+  function handleFilesChanged(payload: FilesChangedByRunEventPayload): void {
+    runFileChanges.value = { ...runFileChanges.value, [payload.request_id]: payload.changes };
+    const message = messages.value.find((msg) => msg.id === payload.request_id);
+    if (message) {
+      const changed = payload.changes.filter((change) => change.kind !== 'deleted').map((change) => change.path);
+      message.files = changed.length ? changed : message.files;
+    }
+  }
+
+  function handleAiContext(payload: AiContextEventPayload): void {
+    requestContexts.value = { ...requestContexts.value, [payload.request_id]: payload.manifest };
+  }
+
+  function handlePermissionRequest(payload: PermissionRequestEventPayload): void {
+    permissionRequests.value = [...permissionRequests.value, payload];
+  }
+
+  async function answerPermissionRequest(permissionId: string, allow: boolean): Promise<void> {
+    permissionRequests.value = permissionRequests.value.filter((item) => item.permission_id !== permissionId);
+    await respondPermissionRequest(permissionId, allow);
+  }
+
+  async function loadInterruptedRequests(): Promise<number> {
+    interruptedRequests.value = await listAiRequests('orphaned');
+    return interruptedRequests.value.length;
+  }
+
+  // Run an interrupted request again; it continues the stored code CLI session.
+  async function resumeInterruptedRequest(requestId: string): Promise<void> {
+    const record = interruptedRequests.value.find((item) => item.request_id === requestId);
+    if (!record) {
+      return;
+    }
+
+    const newRequestId = await resumeAiRequest(requestId);
+    interruptedRequests.value = interruptedRequests.value.filter((item) => item.request_id !== requestId);
+    if (record.session_id !== currentSessionId.value) {
+      return;
+    }
+
+    const userMessageId = `${Date.now()}-user`;
+    messages.value.push({
+      id: userMessageId,
+      role: 'user',
+      content: record.message,
+      timestamp: new Date().toISOString(),
+      files: record.context_files,
+      sessionId: record.session_id,
+      workspaceId: record.workspace_id || '',
+    });
+    messages.value.push({
+      id: newRequestId,
+      role: 'assistant',
+      content: '',
+      timestamp: new Date().toISOString(),
+      sessionId: record.session_id,
+      workspaceId: record.workspace_id || '',
+    });
+    pendingUserMessageId.value = userMessageId;
+    currentRequestId.value = newRequestId;
+    isStreaming.value = true;
+    if (record.code_cli) {
+      pendingCodeCliByRequestId.set(newRequestId, record.code_cli);
+      currentCodeCli.value = record.code_cli;
+    }
+  }
+
+  async function dismissInterruptedRequest(requestId: string): Promise<void> {
+    await dismissAiRequest(requestId);
+    interruptedRequests.value = interruptedRequests.value.filter((item) => item.request_id !== requestId);
+  }
+
   function handleAiResponse(payload: AiResponseEventPayload): void {
     if (!payload?.request_id) {
       return;
@@ -150,7 +416,29 @@ export const useChatStore = defineStore('chat', () => {
       message.content += payload.delta || '';
     }
 
+    if (payload.done && pendingCompareRequestIds.has(payload.request_id)) {
+      // Replies and resume ids are stored once the whole comparison completes.
+      return;
+    }
+
+    if (payload.done && pendingPlan.value?.requestId === payload.request_id) {
+      const plan = message?.content.trim() || '';
+      const failed = !plan || plan.startsWith('[AI error]') || plan.endsWith('CLI exited with errors');
+      pendingPlan.value = failed
+        ? null
+        : {
+            ...pendingPlan.value,
+            sessionId: payload.session_id || pendingPlan.value.sessionId,
+            codeCliTaskId: payload.code_cli_task_id || undefined,
+            plan,
+          };
+    }
+
     if (payload.done) {
+      // The run is over; its unanswered prompts can no longer be answered.
+      permissionRequests.value = permissionRequests.value.filter(
+        (item) => item.request_id !== payload.request_id
+      );
       const pendingCodeCli = pendingCodeCliByRequestId.get(payload.request_id);
       if (pendingCodeCli) {
         pendingCodeCliByRequestId.delete(payload.request_id);
@@ -199,6 +487,8 @@ export const useChatStore = defineStore('chat', () => {
     associatedFiles.value = [];
     codeCliTaskIds.value = { ...(session.codeCliTaskIds || {}) };
     pendingCodeCliByRequestId.clear();
+    pendingCompareRequestIds.clear();
+    pendingPlan.value = null;
     pendingUserMessageId.value = '';
   }
 
@@ -210,6 +500,8 @@ export const useChatStore = defineStore('chat', () => {
     isStreaming.value = false;
     codeCliTaskIds.value = {};
     pendingCodeCliByRequestId.clear();
+    pendingCompareRequestIds.clear();
+    pendingPlan.value = null;
     pendingUserMessageId.value = '';
   }
 
@@ -248,6 +540,17 @@ export const useChatStore = defineStore('chat', () => {
   }
 
   async function cancelStreaming(): Promise<void> {
+    if (pendingCompareRequestIds.size) {
+      // The backend still stores the prompt and emits ai-compare-complete.
+      await Promise.all(
+        [...pendingCompareRequestIds].map((requestId) =>
+          cancelStreamingRequest(requestId).catch((error) =>
+            console.warn('Failed to cancel comparison run:', error)
+          )
+        )
+      );
+      return;
+    }
     if (!currentRequestId.value) {
       return;
     }
@@ -265,6 +568,12 @@ export const useChatStore = defineStore('chat', () => {
       return;
     }
 
+    if (pendingPlan.value?.requestId === currentRequestId.value) {
+      pendingPlan.value = null;
+    }
+    permissionRequests.value = permissionRequests.value.filter(
+      (item) => item.request_id !== currentRequestId.value
+    );
     const assistantIndex = messages.value.findIndex((msg) => msg.id === currentRequestId.value);
     if (assistantIndex !== -1) {
       messages.value.splice(assistantIndex, 1);
@@ -292,6 +601,11 @@ export const useChatStore = defineStore('chat', () => {
     sessions,
     isSessionsLoading,
     codeCliTaskIds,
+    interruptedRequests,
+    pendingPlan,
+    permissionRequests,
+    runFileChanges,
+    requestContexts,
 
     // Actions
     getCurrentSessionId,
@@ -300,6 +614,18 @@ export const useChatStore = defineStore('chat', () => {
     setAssociatedFiles,
     removeAssociatedFile,
     sendMessage,
+    sendCompare,
+    handleCompareComplete,
+    pickCompareWinner,
+    approvePlan,
+    discardPlan,
+    handleFilesChanged,
+    handleAiContext,
+    handlePermissionRequest,
+    answerPermissionRequest,
+    loadInterruptedRequests,
+    resumeInterruptedRequest,
+    dismissInterruptedRequest,
     handleAiResponse,
     loadSessionFromHistory,
     clearChat,
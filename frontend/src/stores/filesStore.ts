@@ -454,6 +454,7 @@ export const useFileStore = defineStore('files', () => {
     setRootDirectory,
     clearDirectoryCache,
     removeDirectoryCache,
+    reloadDirectory,
     updateFileContent,
   };
 });
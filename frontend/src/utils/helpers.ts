@@ -460,3 +460,11 @@ export function withRetry<T>(
 export function sleep(ms: number): Promise<void> {
   return new Promise((resolve) => setTimeout(resolve, ms));
 }
+
+const BUDGET_EXCEEDED_PREFIX = 'Budget exceeded: ';
+
+// Reason of a request refused by its workspace budget, or null for any other error
+export function budgetExceededReason(error: unknown): string | null {
+  const message = typeof error === 'string' ? error : error instanceof Error ? error.message : '';
+  return message.startsWith(BUDGET_EXCEEDED_PREFIX) ? message.slice(BUDGET_EXCEEDED_PREFIX.length) : null;
+}
@@ -0,0 +1,60 @@
+// Monaco 行内补全（ghost text）：防抖、取消与缓存由后端 request_inline_completion 负责
+import monaco from '@/utils/monaco';
+import { cancelInlineCompletion, requestInlineCompletion } from '@/services/tauri/commands';
+
+export interface InlineCompletionTarget {
+  filePath: string;
+  workspaceDir?: string;
+}
+
+/**
+ * 为所有语言注册行内补全，`getTarget` 返回当前编辑的文件；没有文件时不请求补全。
+ * 返回值用于在编辑器销毁时注销。
+ */
+export function registerInlineCompletions(getTarget: () => InlineCompletionTarget | null): monaco.IDisposable {
+  return monaco.languages.registerInlineCompletionsProvider(
+    { pattern: '**' },
+    {
+      async provideInlineCompletions(model, position, _context, token) {
+        const target = getTarget();
+        if (!target) return { items: [] };
+
+        const lastLine = model.getLineCount();
+        const prefix = model.getValueInRange(new monaco.Range(1, 1, position.lineNumber, position.column));
+        const suffix = model.getValueInRange(
+          new monaco.Range(position.lineNumber, position.column, lastLine, model.getLineMaxColumn(lastLine))
+        );
+        const cancelListener = token.onCancellationRequested(() => {
+          cancelInlineCompletion(target.filePath).catch(() => {});
+        });
+
+        try {
+          const completion = await requestInlineCompletion({
+            file_path: target.filePath,
+            language: model.getLanguageId(),
+            prefix,
+            suffix,
+            line: position.lineNumber,
+            column: position.column,
+            workspace_dir: target.workspaceDir,
+          });
+          if (token.isCancellationRequested || !completion.text) return { items: [] };
+          return {
+            items: [
+              {
+                insertText: completion.text,
+                range: new monaco.Range(position.lineNumber, position.column, position.lineNumber, position.column),
+              },
+            ],
+          };
+        } catch {
+          // A failed request just shows no suggestion.
+          return { items: [] };
+        } finally {
+          cancelListener.dispose();
+        }
+      },
+      freeInlineCompletions() {},
+    }
+  );
+}
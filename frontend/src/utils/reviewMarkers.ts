@@ -0,0 +1,45 @@
+// 代码审查结果以 Monaco 诊断标记显示在编辑器中；当前显示的审查在各组件间共享
+import { ref } from 'vue';
+import monaco from '@/utils/monaco';
+import { normalizePath } from '@/utils/pathUtils';
+import type { CodeReview, ReviewFinding, ReviewSeverity } from '@/services/tauri/commands';
+
+const MARKER_OWNER = 'ai-code-review';
+
+/** 当前显示的审查；为 null 时不显示标记 */
+export const activeReview = ref<CodeReview | null>(null);
+
+const SEVERITIES: Record<ReviewSeverity, monaco.MarkerSeverity> = {
+  error: monaco.MarkerSeverity.Error,
+  warning: monaco.MarkerSeverity.Warning,
+  info: monaco.MarkerSeverity.Info,
+};
+
+function toMarker(model: monaco.editor.ITextModel, finding: ReviewFinding): monaco.editor.IMarkerData {
+  const lastLine = model.getLineCount();
+  const startLine = Math.min(Math.max(finding.line, 1), lastLine);
+  const endLine = Math.min(Math.max(finding.end_line ?? startLine, startLine), lastLine);
+  let message = finding.message;
+  if (finding.suggested_fix) message += `\n\n建议修改：\n${finding.suggested_fix}`;
+  if (finding.outdated) message = `[已过期] ${message}`;
+  return {
+    severity: SEVERITIES[finding.severity] ?? monaco.MarkerSeverity.Warning,
+    message,
+    source: 'AI Review',
+    startLineNumber: startLine,
+    startColumn: model.getLineFirstNonWhitespaceColumn(startLine) || 1,
+    endLineNumber: endLine,
+    endColumn: model.getLineMaxColumn(endLine),
+  };
+}
+
+/** 把当前审查中属于 `filePath` 的结果设置为 `model` 的标记；没有审查时清除标记 */
+export function applyReviewMarkers(model: monaco.editor.ITextModel, filePath: string): void {
+  const path = normalizePath(filePath);
+  const findings = activeReview.value?.findings.filter((finding) => normalizePath(finding.path) === path) ?? [];
+  monaco.editor.setModelMarkers(
+    model,
+    MARKER_OWNER,
+    findings.map((finding) => toMarker(model, finding))
+  );
+}
@@ -80,6 +80,8 @@ export interface AISettings {
   model_list?: string[];
   code_cli?: string[];
   useDirectCodeCli?: boolean;
+  // Send the workspace snippets that best match each message along with it
+  autoContext?: boolean;
 }
 
 export interface PathSettings {
@@ -201,6 +203,20 @@ export interface SendMessageOptions {
   directCli?: boolean;
   cliCommand?: string;
   cliArgs?: string[];
+  // Ask for a plan first; nothing runs with write access until it is approved
+  planMode?: boolean;
+  autoContext?: boolean;
+}
+
+// Plan run waiting for approval, with what is needed to execute it
+export interface PendingPlan {
+  requestId: string;
+  sessionId: string;
+  options: SendMessageOptions;
+  // Resume id reported by the plan run
+  codeCliTaskId?: string;
+  // Plan text once the run has finished
+  plan?: string;
 }
 
 export interface ChatMessage {
@@ -213,9 +229,129 @@ export interface ChatMessage {
   files?: string[];
   model?: string;
   fileMetadata?: Record<string, FileMetadata>;
+  metadata?: MessageMetadata;
+}
+
+// Backend message metadata (kept in snake_case so it round-trips unchanged)
+export interface MessageMetadata {
+  cached?: boolean;
+  cache_key?: string;
+  compare_id?: string;
+  code_cli?: string;
+  winner?: boolean;
+  plan?: boolean;
+}
+
+export interface CompareTarget {
+  code_cli: string;
+  direct_cli?: boolean;
+  cli_command?: string;
+  cli_args?: string[];
+  codex_model?: string;
+}
+
+export interface CompareRequest {
+  compare_id: string;
+  session_id: string;
+  runs: { request_id: string; code_cli: string }[];
+}
+
+// Scheduled agent jobs (snake_case, as returned by the backend)
+export interface ScheduledJobInput {
+  name: string;
+  workspace_id?: string | null;
+  workspace_dir: string;
+  prompt: string;
+  schedule: string;
+  code_cli?: string | null;
+  direct_cli?: boolean;
+  cli_command?: string | null;
+  cli_args?: string[];
+  native_model?: string | null;
+  auto_context?: boolean;
+  enabled?: boolean;
+}
+
+export interface ScheduledJob extends Required<ScheduledJobInput> {
+  id: string;
+  session_id: string;
+  next_run_at: number | null;
+  last_run_at: number | null;
+  last_status: 'running' | 'success' | 'failed' | 'interrupted' | null;
+  last_error: string | null;
+  last_request_id: string | null;
+  created_at: number;
+  updated_at: number;
+}
+
+// Monthly budget and hourly request limit of a workspace (snake_case, as stored by the backend)
+export interface WorkspaceBudget {
+  monthly_token_limit: number | null;
+  monthly_cost_limit: number | null;
+  cost_per_million_tokens: number;
+  hourly_request_limit: number | null;
+  warn_thresholds: number[];
+}
+
+export interface WorkspaceUsage {
+  month: string;
+  tokens: number;
+  cost: number;
+  requests: number;
+  recent_requests: number[];
+  warned_threshold: number;
+  override_until: number | null;
+}
+
+// Journaled chat request (snake_case, as returned by the backend)
+export interface AiRequestRecord {
+  request_id: string;
+  session_id: string;
+  workspace_id: string | null;
+  workspace_dir: string | null;
+  message: string;
+  context_files: string[];
+  code_cli: string | null;
+  status: 'queued' | 'running' | 'finished' | 'failed' | 'cancelled' | 'orphaned' | 'resumed';
+  partial_output: string;
+  cli_session_id: string | null;
+  error: string | null;
+  created_at: number;
+  updated_at: number;
+}
+
+// A CLI asking for permission during a direct CLI run
+export interface PermissionRequestEventPayload {
+  permission_id: string;
+  request_id: string;
+  session_id: string;
+  message: string;
+  tool?: string | null;
+  input?: unknown;
+  timestamp: string;
 }
 
-export interface ChatResponse {
+// A workspace file created, modified or deleted by an agent run
+export interface FileChange {
+  path: string;
+  relative_path: string;
+  kind: 'created' | 'modified' | 'deleted';
+  diff?: string | null;
+}
+
+export interface FilesChangedByRunEventPayload {
+  request_id: string;
+  session_id: string;
+  changes: FileChange[];
+  timestamp: string;
+}
+
+export interface CompareCompleteEventPayload {
+  compare_id: string;
+  session_id: string;
+  runs: { request_id: string; code_cli: string; status: 'completed' | 'failed' | 'cancelled' }[];
+  timestamp: string;
+}
   message: ChatMessage;
   usage?: {
     promptTokens: number;
@@ -250,6 +386,21 @@ export interface ChatSession {
   codeCliTaskIds?: Record<string, string>;
 }
 
+// What went into a request's context (snake_case, as emitted by the backend)
+export interface ContextManifest {
+  files: string[];
+  images: string[];
+  snippets: { path: string; start_line: number; end_line: number; score: number }[];
+  mentions: string[];
+  blocked: { path: string; via: string }[];
+}
+
+export interface AiContextEventPayload {
+  request_id: string;
+  manifest: ContextManifest;
+  timestamp: string;
+}
+
 export interface AiResponseEventPayload {
   request_id: string;
   delta: string;
@@ -0,0 +1,672 @@
+//! Headless command-line mode
+//!
+//! `code-ai-assistant chat ...` and `code-ai-assistant sessions ...` run without
+//! starting the UI. They share the AI services, session files and settings
+//! database with the desktop app, so sessions started here show up in the UI
+//! and vice versa.
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+use serde::Deserialize;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::config::AppConfig;
+use crate::database::repositories::ai_invocation_repository::{AiInvocationFilter, AiInvocationRepository};
+use crate::database::repositories::settings_repository::SettingsRepository;
+use crate::database::repositories::workspace_repository::WorkspaceRepository;
+use crate::services::ai::{AiChatOptions, AiService};
+use crate::services::ai_ignore::AiIgnore;
+use crate::services::audit::AuditContext;
+use crate::services::chat_session::{self, ChatMessage};
+use crate::services::context::split_attachments;
+use crate::services::history;
+use crate::services::mentions::{parse_mentions, MentionResolver, ResolvedMentions};
+use crate::services::direct_cli::{
+    build_direct_cli_plan, derive_backend_from_command, run_direct_cli, CliStream, DirectCliRun,
+};
+use crate::services::mock_backend::{self, run_mock_backend};
+use crate::services::redaction::SecretRedactor;
+use crate::services::search_index::SearchIndexService;
+use crate::services::session_import::{import_sessions, ImportWorkspace, TranscriptSource};
+use crate::utils::error::{AppError, AppResult};
+
+/// First arguments that switch the binary into headless mode
+const HEADLESS_COMMANDS: &[&str] = &["chat", "sessions", "invocations", "help", "--help", "-h", "--version", "-V"];
+
+#[derive(Debug, Parser)]
+#[command(name = "code-ai-assistant", version, about = "Code AI Assistant (headless mode)")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: HeadlessCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HeadlessCommand {
+    /// Send a prompt to a code CLI and stream the reply to stdout
+    Chat(ChatArgs),
+    /// Inspect saved chat sessions
+    Sessions {
+        #[command(subcommand)]
+        command: SessionsCommand,
+    },
+    /// Inspect the AI invocation audit log
+    Invocations {
+        #[command(subcommand)]
+        command: InvocationsCommand,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct ChatArgs {
+    /// Prompt text; read from stdin when omitted
+    pub prompt: Option<String>,
+    /// Workspace directory the CLI runs in (defaults to the current directory)
+    #[arg(short, long)]
+    pub workspace: Option<PathBuf>,
+    /// Code CLI to use (e.g. `claude`, `codex`, `gemini` or a configured CLI name)
+    #[arg(long)]
+    pub cli: Option<String>,
+    /// Continue an existing chat session
+    #[arg(short, long)]
+    pub session: Option<String>,
+    /// Files attached as context
+    #[arg(short, long = "file")]
+    pub files: Vec<String>,
+    /// Add snippets retrieved from the workspace search index
+    #[arg(long)]
+    pub auto_context: bool,
+    /// Number of snippets retrieved with `--auto-context`
+    #[arg(long, default_value_t = 5)]
+    pub top_k: usize,
+    /// Run the configured CLI command directly instead of codeagent-wrapper
+    #[arg(long)]
+    pub direct: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SessionsCommand {
+    /// List sessions of a workspace, newest first
+    List {
+        /// Workspace directory (defaults to the current directory)
+        #[arg(short, long)]
+        workspace: Option<PathBuf>,
+        /// Workspace ID, if the directory is not a registered workspace
+        #[arg(long)]
+        workspace_id: Option<String>,
+        #[arg(short, long)]
+        limit: Option<usize>,
+    },
+    /// Print the messages of a session
+    Show {
+        session_id: String,
+        /// Print the raw session JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Import Claude Code, Codex and Gemini CLI transcripts of registered workspaces
+    Import {
+        /// Only these CLIs (`claude`, `codex`, `gemini`); all by default
+        #[arg(long = "source")]
+        sources: Vec<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum InvocationsCommand {
+    /// List recorded invocations, newest first
+    List {
+        /// Only runs that failed or were cancelled
+        #[arg(long)]
+        failed: bool,
+        #[arg(long)]
+        backend: Option<String>,
+        /// Chat session ID
+        #[arg(short, long)]
+        session: Option<String>,
+        #[arg(long)]
+        request_id: Option<String>,
+        #[arg(short, long)]
+        limit: Option<u64>,
+    },
+    /// Print one invocation including its captured output
+    Show {
+        id: i32,
+        /// Print the raw record JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Subset of the UI's `user_config` setting used in headless mode
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct UserConfig {
+    ai: UserAiConfig,
+    code_cli: Vec<CodeCliEntry>,
+    user_preferences: UserPreferences,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct UserAiConfig {
+    use_direct_code_cli: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct UserPreferences {
+    current_code_cli: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct CodeCliEntry {
+    name: String,
+    command: String,
+    args: String,
+}
+
+/// Parse headless arguments if the first argument is a headless subcommand.
+///
+/// Returns `None` for every other invocation (including deep links and the
+/// single-instance relaunch arguments) so the UI starts as before.
+pub fn parse_headless_args() -> Option<Cli> {
+    let first = std::env::args().nth(1)?;
+    if HEADLESS_COMMANDS.contains(&first.as_str()) {
+        Some(Cli::parse())
+    } else {
+        None
+    }
+}
+
+/// Run a headless command to completion and return the process exit code
+pub fn run(cli: Cli) -> i32 {
+    crate::utils::logging::init_headless_tracing();
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("error: failed to start async runtime: {}", e);
+            return 1;
+        }
+    };
+
+    let result = runtime.block_on(async {
+        match cli.command {
+            HeadlessCommand::Chat(args) => run_chat(args).await,
+            HeadlessCommand::Sessions { command } => run_sessions(command).await,
+            HeadlessCommand::Invocations { command } => run_invocations(command).await,
+        }
+    });
+
+    match result {
+        Ok(code) => code,
+        Err(AppError::Cancelled(_)) => 130,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+fn load_config() -> AppResult<AppConfig> {
+    let config = crate::config::load_config()?;
+    crate::utils::fs::init_dir(&config.app.data_dir)?;
+    Ok(config)
+}
+
+async fn load_user_config(db: &sea_orm::DatabaseConnection) -> AppResult<UserConfig> {
+    let setting = SettingsRepository::get_by_key(db, "user_config").await?;
+    Ok(setting
+        .and_then(|s| serde_json::from_str(&s.value).ok())
+        .unwrap_or_default())
+}
+
+fn resolve_workspace_dir(workspace: Option<PathBuf>) -> AppResult<String> {
+    let dir = match workspace {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    };
+    let dir = dir.canonicalize().map_err(|e| {
+        AppError::ValidationError(format!("工作区目录不存在: {} ({})", dir.display(), e))
+    })?;
+    Ok(dir.to_string_lossy().to_string())
+}
+
+/// ID of the registered workspace whose path matches `dir`
+async fn find_workspace_id(db: &sea_orm::DatabaseConnection, dir: &str) -> AppResult<Option<String>> {
+    let target = PathBuf::from(dir);
+    let workspaces = WorkspaceRepository::get_all(db).await?;
+    Ok(workspaces
+        .into_iter()
+        .find(|w| {
+            PathBuf::from(&w.path)
+                .canonicalize()
+                .map(|p| p == target)
+                .unwrap_or(false)
+        })
+        .map(|w| w.id.to_string()))
+}
+
+/// Pick the configured code CLI matching `requested` (exact name first, then substring)
+fn resolve_code_cli(user_config: &UserConfig, requested: Option<&str>) -> Option<CodeCliEntry> {
+    let wanted = requested
+        .map(|s| s.trim().to_string())
+        .or_else(|| user_config.user_preferences.current_code_cli.clone())?;
+    let wanted_lower = wanted.to_lowercase();
+    user_config
+        .code_cli
+        .iter()
+        .find(|c| c.name.eq_ignore_ascii_case(&wanted))
+        .or_else(|| {
+            user_config
+                .code_cli
+                .iter()
+                .find(|c| c.name.to_lowercase().contains(&wanted_lower))
+        })
+        .cloned()
+        .or_else(|| {
+            Some(CodeCliEntry {
+                name: wanted,
+                ..Default::default()
+            })
+        })
+}
+
+fn read_prompt(prompt: Option<String>) -> AppResult<String> {
+    let prompt = match prompt {
+        Some(prompt) => prompt,
+        None => std::io::read_to_string(std::io::stdin())?,
+    };
+    let prompt = prompt.trim().to_string();
+    if prompt.is_empty() {
+        return Err(AppError::ValidationError("提示词不能为空".to_string()));
+    }
+    Ok(prompt)
+}
+
+async fn run_chat(args: ChatArgs) -> AppResult<i32> {
+    let config = load_config()?;
+    let db = crate::database::connection::connect_standalone(&config).await?;
+    let user_config = load_user_config(&db).await?;
+
+    let prompt = read_prompt(args.prompt)?;
+    let workspace_dir = resolve_workspace_dir(args.workspace)?;
+    let workspace_id = find_workspace_id(&db, &workspace_dir).await?;
+    let code_cli = resolve_code_cli(&user_config, args.cli.as_deref());
+    let code_cli_name = code_cli.as_ref().map(|c| c.name.clone());
+
+    let session_id = args
+        .session
+        .clone()
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let (resume_task_id, handoff) = match args.session.as_deref() {
+        Some(id) => {
+            let session = chat_session::load_session_by_id(id).map_err(AppError::GenericError)?;
+            let resume_task_id = code_cli_name
+                .as_ref()
+                .and_then(|cli| session.code_cli_task_ids.get(cli).cloned());
+            // Without its own session the CLI gets the transcript; when resumed, the turns it missed.
+            let handoff = history::handoff_context(&session, resume_task_id.as_ref().and(code_cli_name.as_deref()))
+                .filter(|_| !mock_backend::is_mock_cli(code_cli_name.as_deref()));
+            (resume_task_id, handoff)
+        }
+        None => (None, None),
+    };
+    let code_cli_changed = Some(resume_task_id.is_none());
+
+    let snippets = if args.auto_context {
+        let data_dir = config.app.data_dir.clone();
+        let root = workspace_dir.clone();
+        let query = prompt.clone();
+        let top_k = args.top_k;
+        tokio::task::spawn_blocking(move || {
+            SearchIndexService::new().search(&data_dir, &root, &query, top_k)
+        })
+        .await
+        .map_err(|e| AppError::GenericError(format!("检索上下文任务失败: {}", e)))??
+    } else {
+        Vec::new()
+    };
+
+    let ai_ignore = AiIgnore::load(Some(&config.app.data_dir), Some(&workspace_dir));
+    let mentions = parse_mentions(&prompt);
+    let mentions = if mentions.is_empty() {
+        ResolvedMentions::default()
+    } else {
+        let root = workspace_dir.clone();
+        let ignore = ai_ignore.clone();
+        tokio::task::spawn_blocking(move || MentionResolver::new(Some(&root), None, &ignore).resolve_all(&mentions))
+            .await
+            .map_err(|e| AppError::GenericError(format!("解析引用任务失败: {}", e)))??
+    };
+    let blocked_files = args
+        .files
+        .iter()
+        .filter(|file| ai_ignore.is_ignored(std::path::Path::new(file)));
+    for path in blocked_files.chain(mentions.blocked.iter().map(|blocked| &blocked.path)) {
+        eprintln!("warning: {} is excluded by .aiignore", path);
+    }
+
+    let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            let _ = cancel_tx.send(());
+        }
+    });
+
+    let direct_command = code_cli
+        .as_ref()
+        .map(|c| c.command.trim().to_string())
+        .filter(|c| !c.is_empty());
+    let use_direct = args.direct || user_config.ai.use_direct_code_cli;
+    let direct_plan = direct_command
+        .as_deref()
+        .filter(|_| use_direct && !mock_backend::is_mock_cli(code_cli_name.as_deref()))
+        .map(|command| {
+            let user_args = code_cli
+                .as_ref()
+                .map(|c| c.args.split_whitespace().map(|s| s.to_string()).collect::<Vec<_>>())
+                .unwrap_or_default();
+            let backend = code_cli_name
+                .as_deref()
+                .and_then(AiService::derive_backend_from_code_cli)
+                .or_else(|| derive_backend_from_command(command));
+            let (_, image_files) = split_attachments(&args.files);
+            let plan = build_direct_cli_plan(
+                backend.as_deref(),
+                &user_args,
+                resume_task_id.as_deref(),
+                code_cli_changed,
+                &ai_ignore.allowed(&image_files),
+            );
+            (backend, plan)
+        });
+    // Images handed over as `--image` are not listed in the prompt as well.
+    let prompt_files = match &direct_plan {
+        Some((_, plan)) if plan.images_as_args => split_attachments(&args.files).0,
+        _ => args.files.clone(),
+    };
+
+    let task = AiService::build_task_with_context(
+        &prompt,
+        Some(prompt_files.as_slice()),
+        &mentions.blocks,
+        &snippets,
+        &ai_ignore,
+    );
+    let task = history::with_handoff(handoff.as_deref(), task);
+    let mut redactor = SecretRedactor::new(&config.redaction, &config.env_vars);
+    let task = redactor.redact(&task);
+    for redaction in redactor.redactions() {
+        eprintln!(
+            "warning: redacted {} ({} occurrence(s)) as {}",
+            redaction.kind, redaction.occurrences, redaction.placeholder
+        );
+    }
+
+    let print_output = |stream: CliStream, delta: &str| match stream {
+        CliStream::Stdout => {
+            print!("{}", delta);
+            let _ = std::io::stdout().flush();
+        }
+        CliStream::Stderr => eprint!("{}", delta),
+    };
+
+    let (response, task_id, exit_code) = match (use_direct, direct_command, direct_plan) {
+        _ if mock_backend::is_mock_cli(code_cli_name.as_deref()) => {
+            let scripts = mock_backend::load_scripts(&mock_backend::fixtures_dir(&config.app.data_dir));
+            let script = mock_backend::select_script(&scripts, &task);
+            let outcome = run_mock_backend(
+                &script,
+                &task,
+                resume_task_id.as_deref(),
+                Some(cancel_rx),
+                Some(AuditContext::new(db.clone(), None, Some(&session_id))),
+                print_output,
+            )
+            .await?;
+            (outcome.output, outcome.task_id, outcome.exit_code)
+        }
+        (true, Some(command), Some((backend, plan))) => {
+            let outcome = run_direct_cli(
+                DirectCliRun {
+                    command: &command,
+                    args: &plan.args,
+                    workdir: &workspace_dir,
+                    env: &config.env_vars,
+                    input: &task,
+                    backend: backend.as_deref(),
+                    task_id: plan.task_id.clone(),
+                    audit: Some(AuditContext::new(db.clone(), None, Some(&session_id))),
+                    permissions: None,
+                },
+                Some(cancel_rx),
+                print_output,
+            )
+            .await?;
+            (outcome.output, outcome.task_id, outcome.exit_code)
+        }
+        (true, ..) => {
+            return Err(AppError::ConfigError(format!(
+                "未配置 {} 的命令路径，无法直接运行",
+                code_cli_name.as_deref().unwrap_or("code CLI")
+            )));
+        }
+        (false, ..) => {
+            let (output_tx, mut output_rx) = mpsc::unbounded_channel::<String>();
+            let printer = tokio::spawn(async move {
+                let mut streamed = false;
+                while let Some(text) = output_rx.recv().await {
+                    print_output(CliStream::Stdout, &text);
+                    streamed = true;
+                }
+                streamed
+            });
+            let result = AiService::new()
+                .send_message_with_options(
+                    &prompt,
+                    Some(args.files.clone()),
+                    AiChatOptions {
+                        code_cli: code_cli_name.clone(),
+                        resume_session_id: resume_task_id.clone(),
+                        workspace_dir: Some(workspace_dir.clone()),
+                        code_cli_changed,
+                        env: config.env_vars.clone(),
+                        prepared_task: Some(task),
+                        cancel_rx: Some(cancel_rx),
+                        audit: Some(AuditContext::new(db.clone(), None, Some(&session_id))),
+                        output_tx: Some(output_tx),
+                        ..Default::default()
+                    },
+                )
+                .await;
+            let streamed = printer.await.unwrap_or(false);
+            let result = result?;
+            if !streamed {
+                println!("{}", result.message);
+            }
+            (result.message, result.codeagent_session_id, 0)
+        }
+    };
+
+    if exit_code == 0 {
+        let files = Some(args.files).filter(|f| !f.is_empty());
+        let now = chrono::Local::now().to_rfc3339();
+        let user_message = ChatMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            role: "user".to_string(),
+            content: prompt,
+            timestamp: now.clone(),
+            files,
+            session_id: Some(session_id.clone()),
+            workspace_id: workspace_id.clone(),
+            model: None,
+            metadata: None,
+        };
+        let assistant_message = ChatMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            role: "assistant".to_string(),
+            content: response,
+            timestamp: now,
+            files: None,
+            session_id: Some(session_id.clone()),
+            workspace_id,
+            model: None,
+            metadata: None,
+        };
+        chat_session::append_message_to_session(
+            &session_id,
+            vec![user_message, assistant_message],
+            code_cli_name,
+            task_id,
+        )
+        .map_err(AppError::GenericError)?;
+        eprintln!("session: {}", session_id);
+    } else {
+        eprintln!("[exit {}] CLI exited with errors", exit_code);
+    }
+
+    Ok(exit_code)
+}
+
+async fn run_sessions(command: SessionsCommand) -> AppResult<i32> {
+    match command {
+        SessionsCommand::List {
+            workspace,
+            workspace_id,
+            limit,
+        } => {
+            let workspace_id = match workspace_id {
+                Some(id) => id,
+                None => {
+                    let config = load_config()?;
+                    let db = crate::database::connection::connect_standalone(&config).await?;
+                    let dir = resolve_workspace_dir(workspace)?;
+                    find_workspace_id(&db, &dir).await?.ok_or_else(|| {
+                        AppError::ValidationError(format!("目录不是已注册的工作区: {}", dir))
+                    })?
+                }
+            };
+            let sessions =
+                chat_session::load_all_sessions(workspace_id, limit).map_err(AppError::GenericError)?;
+            for session in sessions {
+                let name = session
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| session.first_message_preview.clone());
+                println!(
+                    "{}\t{}\t{} messages\t{}",
+                    session.id, session.updated_at, session.message_count, name
+                );
+            }
+        }
+        SessionsCommand::Show { session_id, json } => {
+            let session =
+                chat_session::load_session_by_id(&session_id).map_err(AppError::GenericError)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&session)?);
+            } else {
+                for message in &session.messages {
+                    println!("[{}] {}:", message.timestamp, message.role);
+                    println!("{}\n", message.content.trim_end());
+                }
+            }
+        }
+        SessionsCommand::Import { sources } => {
+            let sources = if sources.is_empty() {
+                TranscriptSource::ALL.to_vec()
+            } else {
+                sources
+                    .iter()
+                    .map(|source| {
+                        serde_json::from_value::<TranscriptSource>(serde_json::Value::String(source.to_lowercase()))
+                            .map_err(|_| AppError::ValidationError(format!("未知的会话来源: {}", source)))
+                    })
+                    .collect::<AppResult<Vec<_>>>()?
+            };
+            let config = load_config()?;
+            let db = crate::database::connection::connect_standalone(&config).await?;
+            let workspaces: Vec<ImportWorkspace> = WorkspaceRepository::list_all(&db)
+                .await?
+                .into_iter()
+                .map(|workspace| ImportWorkspace {
+                    id: workspace.id.to_string(),
+                    path: workspace.path,
+                })
+                .collect();
+            let report = tokio::task::spawn_blocking(move || import_sessions(&sources, &workspaces))
+                .await
+                .map_err(|e| AppError::GenericError(format!("导入会话任务失败: {}", e)))?;
+            println!(
+                "imported {}, updated {}, unchanged {}, outside workspaces {}, already in history {}",
+                report.imported, report.updated, report.unchanged, report.unmapped, report.duplicates
+            );
+            for error in &report.errors {
+                eprintln!("warning: {}", error);
+            }
+        }
+    }
+    Ok(0)
+}
+
+async fn run_invocations(command: InvocationsCommand) -> AppResult<i32> {
+    let config = load_config()?;
+    let db = crate::database::connection::connect_standalone(&config).await?;
+    match command {
+        InvocationsCommand::List {
+            failed,
+            backend,
+            session,
+            request_id,
+            limit,
+        } => {
+            let filter = AiInvocationFilter {
+                failed_only: failed,
+                backend,
+                session_id: session,
+                request_id,
+                limit,
+                ..Default::default()
+            };
+            for invocation in AiInvocationRepository::list(&db, &filter).await? {
+                let started = chrono::DateTime::from_timestamp_millis(invocation.started_at)
+                    .map(|t| t.with_timezone(&chrono::Local).to_rfc3339())
+                    .unwrap_or_default();
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\texit {}\t{} ms",
+                    invocation.id,
+                    started,
+                    invocation.kind,
+                    invocation.backend.as_deref().unwrap_or("-"),
+                    invocation.status,
+                    invocation
+                        .exit_code
+                        .map(|c| c.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    invocation.duration_ms
+                );
+            }
+        }
+        InvocationsCommand::Show { id, json } => {
+            let invocation = AiInvocationRepository::get(&db, id)
+                .await?
+                .ok_or_else(|| AppError::ValidationError(format!("AI invocation not found: {}", id)))?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&invocation)?);
+            } else {
+                println!("{} {} ({})", invocation.kind, invocation.binary, invocation.status);
+                println!("args: {:?}", invocation.args);
+                println!("workdir: {}", invocation.workdir.as_deref().unwrap_or("-"));
+                if let Some(error) = &invocation.error {
+                    println!("error: {}", error);
+                }
+                println!("--- stdout ---\n{}", invocation.stdout.trim_end());
+                println!("--- stderr ---\n{}", invocation.stderr.trim_end());
+            }
+        }
+    }
+    Ok(0)
+}
@@ -5,4 +5,6 @@ pub mod schema;
 
 /// Re-exports
 pub use loader::{save_config, get_default_data_dir, get_user_home, load_config, load_settings};
-pub use schema::{AppConfig, AppSettings, DatabaseSettings};
\ No newline at end of file
+pub use schema::{
+    AppConfig, AppSettings, DatabaseSettings, InlineCompletionSettings, RedactionSettings, ResponseCacheSettings,
+};
\ No newline at end of file
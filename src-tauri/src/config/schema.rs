@@ -18,6 +18,15 @@ pub struct AppConfig {
     pub logging: LoggingSettings,
     /// Environment variables
     pub env_vars: Vec<(String, String)>,
+    /// Response cache settings
+    #[serde(default)]
+    pub response_cache: ResponseCacheSettings,
+    /// Outgoing prompt secret redaction settings
+    #[serde(default)]
+    pub redaction: RedactionSettings,
+    /// Editor inline completion settings
+    #[serde(default)]
+    pub inline_completion: InlineCompletionSettings,
 }
 
 /// Application settings
@@ -46,6 +55,12 @@ pub struct DeploymentSettings {
     pub host: String,
     /// Port number
     pub port: u16,
+    /// Serve the local HTTP/WebSocket API on `host:port` (loopback hosts only)
+    #[serde(default)]
+    pub api_enabled: bool,
+    /// Bearer token for the local API; generated and stored in the data directory when empty
+    #[serde(default)]
+    pub api_token: Option<String>,
 }
 
 /// logging settings
@@ -76,6 +91,84 @@ pub struct FileRotationSettings {
     pub log_file_max_age_days: u32,
 }
 
+/// Response cache settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseCacheSettings {
+    /// Replay identical requests from the cache (opt-in)
+    pub enabled: bool,
+    /// Also cache code CLI runs; a replayed run does not repeat the file edits the original run made
+    pub cache_cli_backends: bool,
+    /// Entry lifetime in seconds
+    pub ttl_secs: u64,
+    /// Maximum number of cached responses
+    pub max_entries: u64,
+    /// Maximum total size of cached responses in bytes
+    pub max_bytes: u64,
+}
+
+impl Default for ResponseCacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cache_cli_backends: false,
+            ttl_secs: 24 * 60 * 60,
+            max_entries: 500,
+            max_bytes: 50 * 1024 * 1024,
+        }
+    }
+}
+
+/// Outgoing prompt secret redaction settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionSettings {
+    /// Replace detected secrets before a task is sent to any backend
+    pub enabled: bool,
+    /// Also redact high-entropy tokens that match no known format
+    pub entropy: bool,
+}
+
+impl Default for RedactionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            entropy: true,
+        }
+    }
+}
+
+/// Editor inline (ghost text) completion settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InlineCompletionSettings {
+    /// Request suggestions while typing (opt-in)
+    pub enabled: bool,
+    /// Native model used for suggestions
+    pub model: Option<String>,
+    /// Quiet time after the last keystroke before the provider is called
+    pub debounce_ms: u64,
+    /// A suggestion not ready this long after the keystroke is dropped
+    pub deadline_ms: u64,
+    /// Completion budget of one suggestion
+    pub max_tokens: u32,
+    /// Number of recent suggestions kept in memory
+    pub cache_entries: usize,
+}
+
+impl Default for InlineCompletionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model: None,
+            debounce_ms: 150,
+            deadline_ms: 1500,
+            max_tokens: 128,
+            cache_entries: 64,
+        }
+    }
+}
+
 /// Database settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseSettings {
@@ -108,6 +201,8 @@ impl Default for AppConfig {
                 debug: true,
                 host: "127.0.0.1".to_string(),
                 port: 8080,
+                api_enabled: false,
+                api_token: None,
             },
             logging: LoggingSettings {
                 log_level: "debug".to_string(),
@@ -122,6 +217,9 @@ impl Default for AppConfig {
                 },
             },
             env_vars: vec![],
+            response_cache: ResponseCacheSettings::default(),
+            redaction: RedactionSettings::default(),
+            inline_completion: InlineCompletionSettings::default(),
         }
     }
 }
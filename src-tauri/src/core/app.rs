@@ -10,6 +10,7 @@ use tauri::{App, AppHandle, Manager, State};
 
 use crate::utils::error::AppResult;
 use crate::config::schema::AppConfig;
+use crate::services::search_index::SearchIndexService;
 use crate::services::terminal::TerminalService;
 
 /// Application state shared across the application
@@ -39,6 +40,8 @@ pub struct AppState {
     pub terminal: TerminalService,
     /// Active streaming tasks for cancellation
     pub streaming_tasks: Mutex<HashMap<String, Arc<StreamingTaskHandle>>>,
+    /// Per-workspace lexical search indexes for auto context
+    pub search_index: SearchIndexService,
 }
 
 impl AppState {
@@ -54,6 +57,7 @@ impl AppState {
             db_pool,
             terminal: TerminalService::new(),
             streaming_tasks: Mutex::new(HashMap::new()),
+            search_index: SearchIndexService::new(),
         }
     }
 }
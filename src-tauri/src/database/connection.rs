@@ -104,6 +104,19 @@ pub fn init(app: &mut App) -> AppResult<()> {
     Ok(())
 }
 
+/// Connect without a running Tauri app (headless mode) and apply pending migrations
+pub async fn connect_standalone(config: &AppConfig) -> AppResult<DatabaseConnection> {
+    let db = DatabasePool::new()
+        .get_connection(
+            &config.database.url,
+            config.database.max_connections,
+            config.database.min_connections,
+        )
+        .await?;
+    migration::run_migrations(&db).await?;
+    Ok(db)
+}
+
 /// Get database connection from Tauri state with config
 pub async fn get_db_connection(app_handle: &AppHandle) -> AppResult<DatabaseConnection> {
     let config = app_handle.state::<AppConfig>();
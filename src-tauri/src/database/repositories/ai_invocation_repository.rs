@@ -0,0 +1,204 @@
+//! AI invocation audit log repository
+
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use crate::utils::error::AppResult;
+
+const DEFAULT_LIST_LIMIT: u64 = 50;
+const MAX_LIST_LIMIT: u64 = 500;
+
+const COLUMNS: &str = "id, request_id, session_id, kind, backend, binary, args, env, workdir, status, \
+                       exit_code, error, stdout, stderr, cli_session_id, started_at, duration_ms";
+
+/// One recorded invocation of codeagent-wrapper, a direct CLI or a native provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiInvocation {
+    pub id: i32,
+    pub request_id: Option<String>,
+    /// Chat session the invocation belongs to
+    pub session_id: Option<String>,
+    /// `wrapper`, `direct_cli` or `native`
+    pub kind: String,
+    pub backend: Option<String>,
+    /// Executable path, or the endpoint for native providers
+    pub binary: String,
+    pub args: Vec<String>,
+    /// Environment passed to the process, values masked
+    pub env: Vec<(String, String)>,
+    pub workdir: Option<String>,
+    /// `success`, `failed` or `cancelled`
+    pub status: String,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    /// Truncated stdout (reply text for native providers)
+    pub stdout: String,
+    /// Truncated stderr
+    pub stderr: String,
+    /// Session id reported by the CLI / wrapper
+    pub cli_session_id: Option<String>,
+    /// Unix timestamp in milliseconds
+    pub started_at: i64,
+    pub duration_ms: i64,
+}
+
+/// Filters for browsing the audit log; all fields are optional
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiInvocationFilter {
+    pub request_id: Option<String>,
+    pub session_id: Option<String>,
+    pub kind: Option<String>,
+    pub backend: Option<String>,
+    pub status: Option<String>,
+    /// Only invocations that did not succeed
+    pub failed_only: bool,
+    /// Only invocations started at or after this Unix timestamp in milliseconds
+    pub since: Option<i64>,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+/// AI invocation repository
+pub struct AiInvocationRepository;
+
+impl AiInvocationRepository {
+    /// Insert a record and return its id; `record.id` is ignored
+    pub async fn insert(db: &DatabaseConnection, record: &AiInvocation) -> AppResult<i64> {
+        let result = db
+            .execute(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                "INSERT INTO ai_invocations \
+                 (request_id, session_id, kind, backend, binary, args, env, workdir, status, exit_code, \
+                  error, stdout, stderr, cli_session_id, started_at, duration_ms) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                vec![
+                    record.request_id.clone().into(),
+                    record.session_id.clone().into(),
+                    record.kind.clone().into(),
+                    record.backend.clone().into(),
+                    record.binary.clone().into(),
+                    serde_json::to_string(&record.args)?.into(),
+                    serde_json::to_string(&record.env)?.into(),
+                    record.workdir.clone().into(),
+                    record.status.clone().into(),
+                    record.exit_code.into(),
+                    record.error.clone().into(),
+                    record.stdout.clone().into(),
+                    record.stderr.clone().into(),
+                    record.cli_session_id.clone().into(),
+                    record.started_at.into(),
+                    record.duration_ms.into(),
+                ],
+            ))
+            .await?;
+        Ok(result.last_insert_id() as i64)
+    }
+
+    /// List invocations matching `filter`, newest first
+    pub async fn list(db: &DatabaseConnection, filter: &AiInvocationFilter) -> AppResult<Vec<AiInvocation>> {
+        let mut sql = format!("SELECT {} FROM ai_invocations WHERE 1 = 1", COLUMNS);
+        let mut values: Vec<Value> = Vec::new();
+
+        let equals = [
+            ("request_id", &filter.request_id),
+            ("session_id", &filter.session_id),
+            ("kind", &filter.kind),
+            ("backend", &filter.backend),
+            ("status", &filter.status),
+        ];
+        for (column, value) in equals {
+            if let Some(value) = value.as_deref().filter(|v| !v.trim().is_empty()) {
+                sql.push_str(&format!(" AND {} = ?", column));
+                values.push(value.trim().into());
+            }
+        }
+        if filter.failed_only {
+            sql.push_str(" AND status <> 'success'");
+        }
+        if let Some(since) = filter.since {
+            sql.push_str(" AND started_at >= ?");
+            values.push(since.into());
+        }
+
+        let limit = filter.limit.unwrap_or(DEFAULT_LIST_LIMIT).clamp(1, MAX_LIST_LIMIT);
+        sql.push_str(" ORDER BY started_at DESC, id DESC LIMIT ? OFFSET ?");
+        values.push((limit as i64).into());
+        values.push((filter.offset.unwrap_or(0) as i64).into());
+
+        let rows = db
+            .query_all(Statement::from_sql_and_values(DatabaseBackend::Sqlite, &sql, values))
+            .await?;
+        rows.iter().map(Self::from_row).collect()
+    }
+
+    /// Get one invocation by id
+    pub async fn get(db: &DatabaseConnection, id: i32) -> AppResult<Option<AiInvocation>> {
+        let row = db
+            .query_one(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                &format!("SELECT {} FROM ai_invocations WHERE id = ?", COLUMNS),
+                vec![id.into()],
+            ))
+            .await?;
+        row.as_ref().map(Self::from_row).transpose()
+    }
+
+    /// Delete records started before `before` (Unix ms) and all but the newest `keep`
+    pub async fn prune(db: &DatabaseConnection, before: i64, keep: i64) -> AppResult<u64> {
+        let expired = db
+            .execute(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                "DELETE FROM ai_invocations WHERE started_at < ?",
+                vec![before.into()],
+            ))
+            .await?
+            .rows_affected();
+
+        let over_count = db
+            .execute(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                "DELETE FROM ai_invocations WHERE id NOT IN \
+                 (SELECT id FROM ai_invocations ORDER BY started_at DESC, id DESC LIMIT ?)",
+                vec![keep.into()],
+            ))
+            .await?
+            .rows_affected();
+
+        Ok(expired + over_count)
+    }
+
+    /// Remove all records
+    pub async fn clear(db: &DatabaseConnection) -> AppResult<u64> {
+        let result = db
+            .execute(Statement::from_string(
+                DatabaseBackend::Sqlite,
+                "DELETE FROM ai_invocations".to_string(),
+            ))
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    fn from_row(row: &QueryResult) -> AppResult<AiInvocation> {
+        let args: String = row.try_get("", "args")?;
+        let env: String = row.try_get("", "env")?;
+        Ok(AiInvocation {
+            id: row.try_get("", "id")?,
+            request_id: row.try_get("", "request_id")?,
+            session_id: row.try_get("", "session_id")?,
+            kind: row.try_get("", "kind")?,
+            backend: row.try_get("", "backend")?,
+            binary: row.try_get("", "binary")?,
+            args: serde_json::from_str(&args).unwrap_or_default(),
+            env: serde_json::from_str(&env).unwrap_or_default(),
+            workdir: row.try_get("", "workdir")?,
+            status: row.try_get("", "status")?,
+            exit_code: row.try_get("", "exit_code")?,
+            error: row.try_get("", "error")?,
+            stdout: row.try_get("", "stdout")?,
+            stderr: row.try_get("", "stderr")?,
+            cli_session_id: row.try_get("", "cli_session_id")?,
+            started_at: row.try_get("", "started_at")?,
+            duration_ms: row.try_get("", "duration_ms")?,
+        })
+    }
+}
@@ -0,0 +1,256 @@
+//! Chat request journal repository
+
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use crate::utils::error::AppResult;
+
+const DEFAULT_LIST_LIMIT: u64 = 50;
+const MAX_LIST_LIMIT: u64 = 500;
+/// Characters of partial output kept per request (the tail is kept)
+pub const MAX_PARTIAL_OUTPUT_CHARS: i64 = 256 * 1024;
+
+const COLUMNS: &str = "request_id, session_id, workspace_id, workspace_dir, message, context_files, code_cli, \
+                       codex_model, direct_cli, cli_command, cli_args, native_model, auto_context, status, \
+                       partial_output, cli_session_id, error, created_at, updated_at";
+
+/// Request states; `queued` and `running` are active
+pub mod status {
+    pub const QUEUED: &str = "queued";
+    pub const RUNNING: &str = "running";
+    pub const FINISHED: &str = "finished";
+    pub const FAILED: &str = "failed";
+    pub const CANCELLED: &str = "cancelled";
+    /// Was active when the app exited
+    pub const ORPHANED: &str = "orphaned";
+    /// Orphaned request that was started again under a new request id
+    pub const RESUMED: &str = "resumed";
+}
+
+/// A chat request with everything needed to run it again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiRequestRecord {
+    pub request_id: String,
+    pub session_id: String,
+    pub workspace_id: Option<String>,
+    pub workspace_dir: Option<String>,
+    pub message: String,
+    pub context_files: Vec<String>,
+    pub code_cli: Option<String>,
+    pub codex_model: Option<String>,
+    pub direct_cli: bool,
+    pub cli_command: Option<String>,
+    pub cli_args: Vec<String>,
+    pub native_model: Option<String>,
+    pub auto_context: bool,
+    pub status: String,
+    /// Output streamed so far
+    pub partial_output: String,
+    /// Resume id of the code CLI session (the one the request started from until the CLI reports its own)
+    pub cli_session_id: Option<String>,
+    pub error: Option<String>,
+    /// Unix timestamps in milliseconds
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Chat request journal repository
+pub struct AiRequestRepository;
+
+impl AiRequestRepository {
+    /// Insert a new request
+    pub async fn insert(db: &DatabaseConnection, record: &AiRequestRecord) -> AppResult<()> {
+        db.execute(Statement::from_sql_and_values(
+            DatabaseBackend::Sqlite,
+            "INSERT INTO ai_requests \
+             (request_id, session_id, workspace_id, workspace_dir, message, context_files, code_cli, codex_model, \
+              direct_cli, cli_command, cli_args, native_model, auto_context, status, partial_output, \
+              cli_session_id, error, created_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            vec![
+                record.request_id.clone().into(),
+                record.session_id.clone().into(),
+                record.workspace_id.clone().into(),
+                record.workspace_dir.clone().into(),
+                record.message.clone().into(),
+                serde_json::to_string(&record.context_files)?.into(),
+                record.code_cli.clone().into(),
+                record.codex_model.clone().into(),
+                record.direct_cli.into(),
+                record.cli_command.clone().into(),
+                serde_json::to_string(&record.cli_args)?.into(),
+                record.native_model.clone().into(),
+                record.auto_context.into(),
+                record.status.clone().into(),
+                record.partial_output.clone().into(),
+                record.cli_session_id.clone().into(),
+                record.error.clone().into(),
+                record.created_at.into(),
+                record.updated_at.into(),
+            ],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Append streamed output, keeping at most `MAX_PARTIAL_OUTPUT_CHARS`
+    pub async fn append_output(db: &DatabaseConnection, request_id: &str, output: &str, now: i64) -> AppResult<()> {
+        db.execute(Statement::from_sql_and_values(
+            DatabaseBackend::Sqlite,
+            "UPDATE ai_requests SET partial_output = substr(partial_output || ?, ?), updated_at = ? \
+             WHERE request_id = ?",
+            vec![
+                output.into(),
+                (-MAX_PARTIAL_OUTPUT_CHARS).into(),
+                now.into(),
+                request_id.into(),
+            ],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Update the status (and error) of a request
+    pub async fn set_status(
+        db: &DatabaseConnection,
+        request_id: &str,
+        status: &str,
+        error: Option<&str>,
+        now: i64,
+    ) -> AppResult<()> {
+        db.execute(Statement::from_sql_and_values(
+            DatabaseBackend::Sqlite,
+            "UPDATE ai_requests SET status = ?, error = ?, updated_at = ? WHERE request_id = ?",
+            vec![status.into(), error.map(|e| e.to_string()).into(), now.into(), request_id.into()],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Record the session id reported by the code CLI
+    pub async fn set_cli_session_id(
+        db: &DatabaseConnection,
+        request_id: &str,
+        cli_session_id: &str,
+        now: i64,
+    ) -> AppResult<()> {
+        db.execute(Statement::from_sql_and_values(
+            DatabaseBackend::Sqlite,
+            "UPDATE ai_requests SET cli_session_id = ?, updated_at = ? WHERE request_id = ?",
+            vec![cli_session_id.into(), now.into(), request_id.into()],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Mark requests still active from a previous app instance as orphaned
+    pub async fn mark_orphaned(db: &DatabaseConnection, now: i64) -> AppResult<u64> {
+        let result = db
+            .execute(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                "UPDATE ai_requests SET status = ?, error = 'Application exited while the request was active', \
+                 updated_at = ? WHERE status IN (?, ?)",
+                vec![
+                    status::ORPHANED.into(),
+                    now.into(),
+                    status::QUEUED.into(),
+                    status::RUNNING.into(),
+                ],
+            ))
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Get one request by id
+    pub async fn get(db: &DatabaseConnection, request_id: &str) -> AppResult<Option<AiRequestRecord>> {
+        let row = db
+            .query_one(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                &format!("SELECT {} FROM ai_requests WHERE request_id = ?", COLUMNS),
+                vec![request_id.into()],
+            ))
+            .await?;
+        row.as_ref().map(Self::from_row).transpose()
+    }
+
+    /// List requests, newest first, optionally filtered by status and session
+    pub async fn list(
+        db: &DatabaseConnection,
+        status: Option<&str>,
+        session_id: Option<&str>,
+        limit: Option<u64>,
+    ) -> AppResult<Vec<AiRequestRecord>> {
+        let mut sql = format!("SELECT {} FROM ai_requests WHERE 1 = 1", COLUMNS);
+        let mut values: Vec<Value> = Vec::new();
+        if let Some(status) = status.filter(|s| !s.trim().is_empty()) {
+            sql.push_str(" AND status = ?");
+            values.push(status.trim().into());
+        }
+        if let Some(session_id) = session_id.filter(|s| !s.trim().is_empty()) {
+            sql.push_str(" AND session_id = ?");
+            values.push(session_id.trim().into());
+        }
+        let limit = limit.unwrap_or(DEFAULT_LIST_LIMIT).clamp(1, MAX_LIST_LIMIT);
+        sql.push_str(" ORDER BY created_at DESC LIMIT ?");
+        values.push((limit as i64).into());
+
+        let rows = db
+            .query_all(Statement::from_sql_and_values(DatabaseBackend::Sqlite, &sql, values))
+            .await?;
+        rows.iter().map(Self::from_row).collect()
+    }
+
+    /// Delete one request
+    pub async fn delete(db: &DatabaseConnection, request_id: &str) -> AppResult<bool> {
+        let result = db
+            .execute(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                "DELETE FROM ai_requests WHERE request_id = ?",
+                vec![request_id.into()],
+            ))
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Delete inactive requests last updated before `before` (Unix ms); orphaned ones are kept
+    pub async fn prune(db: &DatabaseConnection, before: i64) -> AppResult<u64> {
+        let result = db
+            .execute(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                "DELETE FROM ai_requests WHERE updated_at < ? AND status NOT IN (?, ?, ?)",
+                vec![
+                    before.into(),
+                    status::QUEUED.into(),
+                    status::RUNNING.into(),
+                    status::ORPHANED.into(),
+                ],
+            ))
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    fn from_row(row: &QueryResult) -> AppResult<AiRequestRecord> {
+        let context_files: String = row.try_get("", "context_files")?;
+        let cli_args: String = row.try_get("", "cli_args")?;
+        Ok(AiRequestRecord {
+            request_id: row.try_get("", "request_id")?,
+            session_id: row.try_get("", "session_id")?,
+            workspace_id: row.try_get("", "workspace_id")?,
+            workspace_dir: row.try_get("", "workspace_dir")?,
+            message: row.try_get("", "message")?,
+            context_files: serde_json::from_str(&context_files).unwrap_or_default(),
+            code_cli: row.try_get("", "code_cli")?,
+            codex_model: row.try_get("", "codex_model")?,
+            direct_cli: row.try_get("", "direct_cli")?,
+            cli_command: row.try_get("", "cli_command")?,
+            cli_args: serde_json::from_str(&cli_args).unwrap_or_default(),
+            native_model: row.try_get("", "native_model")?,
+            auto_context: row.try_get("", "auto_context")?,
+            status: row.try_get("", "status")?,
+            partial_output: row.try_get("", "partial_output")?,
+            cli_session_id: row.try_get("", "cli_session_id")?,
+            error: row.try_get("", "error")?,
+            created_at: row.try_get("", "created_at")?,
+            updated_at: row.try_get("", "updated_at")?,
+        })
+    }
+}
@@ -0,0 +1,134 @@
+//! Code review repository
+
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use crate::services::code_review::ReviewFinding;
+use crate::utils::error::AppResult;
+
+const DEFAULT_LIST_LIMIT: u64 = 50;
+const MAX_LIST_LIMIT: u64 = 500;
+
+const COLUMNS: &str = "id, workspace_dir, commit_hash, commit_range, backend, findings, files, excluded_files, truncated, \
+                       dropped, created_at";
+
+/// A stored review and its findings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeReview {
+    pub id: String,
+    pub workspace_dir: String,
+    /// Commit the review belongs to: `HEAD` at review time for uncommitted changes,
+    /// the reviewed end of the range otherwise
+    pub commit_hash: Option<String>,
+    /// Reviewed commit range; `None` for uncommitted changes
+    pub range: Option<String>,
+    /// Model or code CLI that reviewed the changes
+    pub backend: String,
+    pub findings: Vec<ReviewFinding>,
+    /// Reviewed files, relative to the repository root
+    pub files: Vec<String>,
+    /// Changed files left out of the prompt by `.aiignore`
+    pub excluded_files: Vec<String>,
+    /// Whether part of the diff was cut to fit the size budget
+    pub truncated: bool,
+    /// Findings dropped for naming a file or line outside the review
+    pub dropped: i64,
+    /// Unix timestamp in milliseconds
+    pub created_at: i64,
+}
+
+/// Code review repository
+pub struct CodeReviewRepository;
+
+impl CodeReviewRepository {
+    /// Insert a new review
+    pub async fn insert(db: &DatabaseConnection, review: &CodeReview) -> AppResult<()> {
+        db.execute(Statement::from_sql_and_values(
+            DatabaseBackend::Sqlite,
+            "INSERT INTO code_reviews \
+             (id, workspace_dir, commit_hash, commit_range, backend, findings, files, excluded_files, truncated, \
+              dropped, created_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            vec![
+                review.id.clone().into(),
+                review.workspace_dir.clone().into(),
+                review.commit_hash.clone().into(),
+                review.range.clone().into(),
+                review.backend.clone().into(),
+                serde_json::to_string(&review.findings)?.into(),
+                serde_json::to_string(&review.files)?.into(),
+                serde_json::to_string(&review.excluded_files)?.into(),
+                review.truncated.into(),
+                review.dropped.into(),
+                review.created_at.into(),
+            ],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Get one review by id
+    pub async fn get(db: &DatabaseConnection, id: &str) -> AppResult<Option<CodeReview>> {
+        let row = db
+            .query_one(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                &format!("SELECT {} FROM code_reviews WHERE id = ?", COLUMNS),
+                vec![id.into()],
+            ))
+            .await?;
+        row.as_ref().map(Self::from_row).transpose()
+    }
+
+    /// List the reviews of a workspace, newest first, optionally only those of one commit
+    pub async fn list(
+        db: &DatabaseConnection,
+        workspace_dir: &str,
+        commit_hash: Option<&str>,
+        limit: Option<u64>,
+    ) -> AppResult<Vec<CodeReview>> {
+        let mut sql = format!("SELECT {} FROM code_reviews WHERE workspace_dir = ?", COLUMNS);
+        let mut values: Vec<Value> = vec![workspace_dir.into()];
+        if let Some(commit_hash) = commit_hash.filter(|c| !c.trim().is_empty()) {
+            sql.push_str(" AND commit_hash = ?");
+            values.push(commit_hash.trim().into());
+        }
+        let limit = limit.unwrap_or(DEFAULT_LIST_LIMIT).clamp(1, MAX_LIST_LIMIT);
+        sql.push_str(" ORDER BY created_at DESC LIMIT ?");
+        values.push((limit as i64).into());
+
+        let rows = db
+            .query_all(Statement::from_sql_and_values(DatabaseBackend::Sqlite, &sql, values))
+            .await?;
+        rows.iter().map(Self::from_row).collect()
+    }
+
+    /// Delete one review
+    pub async fn delete(db: &DatabaseConnection, id: &str) -> AppResult<bool> {
+        let result = db
+            .execute(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                "DELETE FROM code_reviews WHERE id = ?",
+                vec![id.into()],
+            ))
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    fn from_row(row: &QueryResult) -> AppResult<CodeReview> {
+        let findings: String = row.try_get("", "findings")?;
+        let files: String = row.try_get("", "files")?;
+        let excluded_files: String = row.try_get("", "excluded_files")?;
+        Ok(CodeReview {
+            id: row.try_get("", "id")?,
+            workspace_dir: row.try_get("", "workspace_dir")?,
+            commit_hash: row.try_get("", "commit_hash")?,
+            range: row.try_get("", "commit_range")?,
+            backend: row.try_get("", "backend")?,
+            findings: serde_json::from_str(&findings).unwrap_or_default(),
+            files: serde_json::from_str(&files).unwrap_or_default(),
+            excluded_files: serde_json::from_str(&excluded_files).unwrap_or_default(),
+            truncated: row.try_get("", "truncated")?,
+            dropped: row.try_get("", "dropped")?,
+            created_at: row.try_get("", "created_at")?,
+        })
+    }
+}
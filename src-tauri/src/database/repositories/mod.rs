@@ -3,3 +3,8 @@
 pub mod settings_repository;
 pub mod recent_directories_repository;
 pub mod workspace_repository;
+pub mod response_cache_repository;
+pub mod ai_invocation_repository;
+pub mod scheduled_job_repository;
+pub mod ai_request_repository;
+pub mod code_review_repository;
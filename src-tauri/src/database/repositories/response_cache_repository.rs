@@ -0,0 +1,157 @@
+//! Response cache repository
+
+use sea_orm::*;
+use crate::utils::error::AppResult;
+
+/// A cached AI response
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub cache_key: String,
+    pub backend: String,
+    pub model: Option<String>,
+    pub response: String,
+    pub hit_count: i64,
+    /// Unix timestamp (seconds) the response was stored
+    pub created_at: i64,
+}
+
+/// Number and total size of cached responses
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct ResponseCacheStats {
+    pub entries: i64,
+    pub total_bytes: i64,
+}
+
+/// Response cache repository
+pub struct ResponseCacheRepository;
+
+impl ResponseCacheRepository {
+    /// Get a response stored after `min_created_at`, recording the hit
+    pub async fn get(db: &DatabaseConnection, cache_key: &str, min_created_at: i64) -> AppResult<Option<CachedResponse>> {
+        let row = db
+            .query_one(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                "SELECT cache_key, backend, model, response, hit_count, created_at FROM response_cache \
+                 WHERE cache_key = ? AND created_at >= ?",
+                vec![cache_key.into(), min_created_at.into()],
+            ))
+            .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        db.execute(Statement::from_sql_and_values(
+            DatabaseBackend::Sqlite,
+            "UPDATE response_cache SET hit_count = hit_count + 1, last_used_at = ? WHERE cache_key = ?",
+            vec![chrono::Utc::now().timestamp().into(), cache_key.into()],
+        ))
+        .await?;
+
+        Ok(Some(CachedResponse {
+            cache_key: row.try_get("", "cache_key")?,
+            backend: row.try_get("", "backend")?,
+            model: row.try_get("", "model")?,
+            response: row.try_get("", "response")?,
+            hit_count: row.try_get("", "hit_count")?,
+            created_at: row.try_get("", "created_at")?,
+        }))
+    }
+
+    /// Insert or replace a response
+    pub async fn put(
+        db: &DatabaseConnection,
+        cache_key: &str,
+        backend: &str,
+        model: Option<&str>,
+        response: &str,
+    ) -> AppResult<()> {
+        let now = chrono::Utc::now().timestamp();
+        db.execute(Statement::from_sql_and_values(
+            DatabaseBackend::Sqlite,
+            "INSERT OR REPLACE INTO response_cache \
+             (cache_key, backend, model, response, size_bytes, hit_count, created_at, last_used_at) \
+             VALUES (?, ?, ?, ?, ?, 0, ?, ?)",
+            vec![
+                cache_key.into(),
+                backend.into(),
+                model.map(|m| m.to_string()).into(),
+                response.into(),
+                (response.len() as i64).into(),
+                now.into(),
+                now.into(),
+            ],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Drop expired entries, then the least recently used ones beyond the count and size limits
+    pub async fn prune(
+        db: &DatabaseConnection,
+        min_created_at: i64,
+        max_entries: i64,
+        max_bytes: i64,
+    ) -> AppResult<u64> {
+        let expired = db
+            .execute(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                "DELETE FROM response_cache WHERE created_at < ?",
+                vec![min_created_at.into()],
+            ))
+            .await?
+            .rows_affected();
+
+        let over_count = db
+            .execute(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                "DELETE FROM response_cache WHERE id NOT IN \
+                 (SELECT id FROM response_cache ORDER BY last_used_at DESC, id DESC LIMIT ?)",
+                vec![max_entries.into()],
+            ))
+            .await?
+            .rows_affected();
+
+        let over_size = db
+            .execute(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                "DELETE FROM response_cache WHERE id IN \
+                 (SELECT id FROM (SELECT id, SUM(size_bytes) OVER (ORDER BY last_used_at DESC, id DESC) AS running \
+                 FROM response_cache) WHERE running > ?)",
+                vec![max_bytes.into()],
+            ))
+            .await?
+            .rows_affected();
+
+        Ok(expired + over_count + over_size)
+    }
+
+    /// Entry count and total size
+    pub async fn stats(db: &DatabaseConnection) -> AppResult<ResponseCacheStats> {
+        let row = db
+            .query_one(Statement::from_string(
+                DatabaseBackend::Sqlite,
+                "SELECT COUNT(*) AS entries, COALESCE(SUM(size_bytes), 0) AS total_bytes FROM response_cache".to_string(),
+            ))
+            .await?;
+
+        Ok(match row {
+            Some(row) => ResponseCacheStats {
+                entries: row.try_get("", "entries")?,
+                total_bytes: row.try_get("", "total_bytes")?,
+            },
+            None => ResponseCacheStats::default(),
+        })
+    }
+
+    /// Remove all cached responses
+    pub async fn clear(db: &DatabaseConnection) -> AppResult<u64> {
+        let result = db
+            .execute(Statement::from_string(
+                DatabaseBackend::Sqlite,
+                "DELETE FROM response_cache".to_string(),
+            ))
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
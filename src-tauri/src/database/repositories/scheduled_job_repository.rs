@@ -0,0 +1,253 @@
+//! Scheduled agent job repository
+
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use crate::utils::error::AppResult;
+
+const COLUMNS: &str = "id, name, workspace_id, workspace_dir, prompt, schedule, code_cli, direct_cli, \
+                       cli_command, cli_args, native_model, auto_context, enabled, session_id, next_run_at, \
+                       last_run_at, last_status, last_error, last_request_id, created_at, updated_at";
+
+/// A prompt run unattended against a workspace on a cron-like schedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledJob {
+    pub id: String,
+    pub name: String,
+    pub workspace_id: Option<String>,
+    pub workspace_dir: String,
+    pub prompt: String,
+    /// Cron expression, see `services::scheduler::CronSchedule`
+    pub schedule: String,
+    pub code_cli: Option<String>,
+    pub direct_cli: bool,
+    pub cli_command: Option<String>,
+    pub cli_args: Vec<String>,
+    /// Native provider model; takes precedence over the code CLI like in chat
+    pub native_model: Option<String>,
+    pub auto_context: bool,
+    pub enabled: bool,
+    /// Chat session the job's prompts and results are appended to
+    pub session_id: String,
+    /// Unix timestamps in milliseconds
+    pub next_run_at: Option<i64>,
+    pub last_run_at: Option<i64>,
+    /// `running`, `success`, `failed` or `interrupted`
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
+    pub last_request_id: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Scheduled job repository
+pub struct ScheduledJobRepository;
+
+impl ScheduledJobRepository {
+    /// Insert a new job
+    pub async fn insert(db: &DatabaseConnection, job: &ScheduledJob) -> AppResult<()> {
+        db.execute(Statement::from_sql_and_values(
+            DatabaseBackend::Sqlite,
+            "INSERT INTO scheduled_jobs \
+             (id, name, workspace_id, workspace_dir, prompt, schedule, code_cli, direct_cli, cli_command, \
+              cli_args, native_model, auto_context, enabled, session_id, next_run_at, last_run_at, \
+              last_status, last_error, last_request_id, created_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            vec![
+                job.id.clone().into(),
+                job.name.clone().into(),
+                job.workspace_id.clone().into(),
+                job.workspace_dir.clone().into(),
+                job.prompt.clone().into(),
+                job.schedule.clone().into(),
+                job.code_cli.clone().into(),
+                job.direct_cli.into(),
+                job.cli_command.clone().into(),
+                serde_json::to_string(&job.cli_args)?.into(),
+                job.native_model.clone().into(),
+                job.auto_context.into(),
+                job.enabled.into(),
+                job.session_id.clone().into(),
+                job.next_run_at.into(),
+                job.last_run_at.into(),
+                job.last_status.clone().into(),
+                job.last_error.clone().into(),
+                job.last_request_id.clone().into(),
+                job.created_at.into(),
+                job.updated_at.into(),
+            ],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Update the user-editable fields and the next run time of a job
+    pub async fn update(db: &DatabaseConnection, job: &ScheduledJob) -> AppResult<()> {
+        db.execute(Statement::from_sql_and_values(
+            DatabaseBackend::Sqlite,
+            "UPDATE scheduled_jobs SET name = ?, workspace_id = ?, workspace_dir = ?, prompt = ?, schedule = ?, \
+             code_cli = ?, direct_cli = ?, cli_command = ?, cli_args = ?, native_model = ?, auto_context = ?, \
+             enabled = ?, next_run_at = ?, updated_at = ? WHERE id = ?",
+            vec![
+                job.name.clone().into(),
+                job.workspace_id.clone().into(),
+                job.workspace_dir.clone().into(),
+                job.prompt.clone().into(),
+                job.schedule.clone().into(),
+                job.code_cli.clone().into(),
+                job.direct_cli.into(),
+                job.cli_command.clone().into(),
+                serde_json::to_string(&job.cli_args)?.into(),
+                job.native_model.clone().into(),
+                job.auto_context.into(),
+                job.enabled.into(),
+                job.next_run_at.into(),
+                job.updated_at.into(),
+                job.id.clone().into(),
+            ],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Get one job by id
+    pub async fn get(db: &DatabaseConnection, id: &str) -> AppResult<Option<ScheduledJob>> {
+        let row = db
+            .query_one(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                &format!("SELECT {} FROM scheduled_jobs WHERE id = ?", COLUMNS),
+                vec![id.into()],
+            ))
+            .await?;
+        row.as_ref().map(Self::from_row).transpose()
+    }
+
+    /// List jobs, optionally only those of one workspace, by name
+    pub async fn list(db: &DatabaseConnection, workspace_id: Option<&str>) -> AppResult<Vec<ScheduledJob>> {
+        let statement = match workspace_id {
+            Some(workspace_id) => Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                &format!("SELECT {} FROM scheduled_jobs WHERE workspace_id = ? ORDER BY name", COLUMNS),
+                vec![workspace_id.into()],
+            ),
+            None => Statement::from_string(
+                DatabaseBackend::Sqlite,
+                format!("SELECT {} FROM scheduled_jobs ORDER BY name", COLUMNS),
+            ),
+        };
+        let rows = db.query_all(statement).await?;
+        rows.iter().map(Self::from_row).collect()
+    }
+
+    /// Enabled jobs whose next run time is at or before `now` (Unix ms)
+    pub async fn list_due(db: &DatabaseConnection, now: i64) -> AppResult<Vec<ScheduledJob>> {
+        let rows = db
+            .query_all(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                &format!(
+                    "SELECT {} FROM scheduled_jobs WHERE enabled = 1 AND next_run_at IS NOT NULL \
+                     AND next_run_at <= ? ORDER BY next_run_at",
+                    COLUMNS
+                ),
+                vec![now.into()],
+            ))
+            .await?;
+        rows.iter().map(Self::from_row).collect()
+    }
+
+    /// Record that a run started and when the job runs next
+    pub async fn mark_started(
+        db: &DatabaseConnection,
+        id: &str,
+        started_at: i64,
+        next_run_at: Option<i64>,
+    ) -> AppResult<()> {
+        db.execute(Statement::from_sql_and_values(
+            DatabaseBackend::Sqlite,
+            "UPDATE scheduled_jobs SET last_run_at = ?, last_status = 'running', last_error = NULL, \
+             last_request_id = NULL, next_run_at = ? WHERE id = ?",
+            vec![started_at.into(), next_run_at.into(), id.into()],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Record the request id of a run once it was dispatched
+    pub async fn set_last_request_id(db: &DatabaseConnection, id: &str, request_id: &str) -> AppResult<()> {
+        db.execute(Statement::from_sql_and_values(
+            DatabaseBackend::Sqlite,
+            "UPDATE scheduled_jobs SET last_request_id = ? WHERE id = ?",
+            vec![request_id.into(), id.into()],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Record the outcome of the last run
+    pub async fn mark_finished(
+        db: &DatabaseConnection,
+        id: &str,
+        status: &str,
+        error: Option<&str>,
+    ) -> AppResult<()> {
+        db.execute(Statement::from_sql_and_values(
+            DatabaseBackend::Sqlite,
+            "UPDATE scheduled_jobs SET last_status = ?, last_error = ? WHERE id = ?",
+            vec![status.into(), error.map(|e| e.to_string()).into(), id.into()],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Mark runs left `running` by a previous app instance as interrupted
+    pub async fn mark_interrupted(db: &DatabaseConnection) -> AppResult<u64> {
+        let result = db
+            .execute(Statement::from_string(
+                DatabaseBackend::Sqlite,
+                "UPDATE scheduled_jobs SET last_status = 'interrupted', \
+                 last_error = 'Application exited while the job was running' \
+                 WHERE last_status = 'running'"
+                    .to_string(),
+            ))
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Delete a job
+    pub async fn delete(db: &DatabaseConnection, id: &str) -> AppResult<bool> {
+        let result = db
+            .execute(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                "DELETE FROM scheduled_jobs WHERE id = ?",
+                vec![id.into()],
+            ))
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    fn from_row(row: &QueryResult) -> AppResult<ScheduledJob> {
+        let cli_args: String = row.try_get("", "cli_args")?;
+        Ok(ScheduledJob {
+            id: row.try_get("", "id")?,
+            name: row.try_get("", "name")?,
+            workspace_id: row.try_get("", "workspace_id")?,
+            workspace_dir: row.try_get("", "workspace_dir")?,
+            prompt: row.try_get("", "prompt")?,
+            schedule: row.try_get("", "schedule")?,
+            code_cli: row.try_get("", "code_cli")?,
+            direct_cli: row.try_get("", "direct_cli")?,
+            cli_command: row.try_get("", "cli_command")?,
+            cli_args: serde_json::from_str(&cli_args).unwrap_or_default(),
+            native_model: row.try_get("", "native_model")?,
+            auto_context: row.try_get("", "auto_context")?,
+            enabled: row.try_get("", "enabled")?,
+            session_id: row.try_get("", "session_id")?,
+            next_run_at: row.try_get("", "next_run_at")?,
+            last_run_at: row.try_get("", "last_run_at")?,
+            last_status: row.try_get("", "last_status")?,
+            last_error: row.try_get("", "last_error")?,
+            last_request_id: row.try_get("", "last_request_id")?,
+            created_at: row.try_get("", "created_at")?,
+            updated_at: row.try_get("", "updated_at")?,
+        })
+    }
+}
@@ -42,6 +42,15 @@ impl WorkspaceRepository {
         Ok(workspaces)
     }
 
+    /// All workspaces, without the limit of `get_all`
+    pub async fn list_all(db: &DatabaseConnection) -> AppResult<Vec<WorkspaceModel>> {
+        Workspace::find()
+            .order_by(workspace::Column::Id, Order::Asc)
+            .all(db)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()))
+    }
+
     /// Save a new workspace
     pub async fn upsert(
         db: &DatabaseConnection,
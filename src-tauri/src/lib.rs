@@ -2,6 +2,7 @@
 //!
 //! This crate contains the core functionality for the Code AI Assistant application.
 
+pub mod cli;
 pub mod config;
 pub mod core;
 pub mod database;
@@ -15,6 +15,7 @@ use std::sync::{
     atomic::AtomicBool,
 };
 
+mod cli;
 mod config;
 mod core;
 mod database;
@@ -24,8 +25,35 @@ mod services;
 mod tauri_module;
 mod utils;
 
+/// Release builds on Windows use the GUI subsystem and start without a
+/// console, so headless output would go nowhere. Attach to the console of the
+/// shell that launched us; handles it already redirected are kept.
+#[cfg(all(not(debug_assertions), target_os = "windows"))]
+fn attach_parent_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+
+    // Fails when there is no parent console (e.g. started from Explorer); output is dropped then.
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(all(not(debug_assertions), target_os = "windows")))]
+fn attach_parent_console() {}
+
 /// Main entry point for the application
 fn main() {
+    // `chat` / `sessions` subcommands run headless and never start the UI
+    if let Some(cli) = cli::parse_headless_args() {
+        attach_parent_console();
+        std::process::exit(cli::run(cli));
+    }
+
     tauri::Builder::default()
         // Register Tauri plugins
         .plugin(tauri_plugin_store::Builder::default().build())
@@ -86,7 +114,46 @@ fn main() {
             tauri_module::chat_session_commands::load_chat_sessions,
             tauri_module::chat_session_commands::delete_chat_session,
             tauri_module::chat_session_commands::update_chat_session_name,
+            tauri_module::chat_session_commands::get_chat_session_summary,
+            tauri_module::chat_session_commands::update_chat_session_summary,
+            tauri_module::chat_session_commands::compact_chat_session,
+            tauri_module::chat_session_commands::import_cli_sessions,
             tauri_module::notification_commands::show_system_notification,
+            tauri_module::search_index_commands::refresh_search_index,
+            tauri_module::search_index_commands::search_workspace_index,
+            tauri_module::search_index_commands::clear_search_index,
+            tauri_module::response_cache_commands::get_response_cache_stats,
+            tauri_module::response_cache_commands::clear_response_cache,
+            tauri_module::audit_commands::list_ai_invocations,
+            tauri_module::audit_commands::get_ai_invocation,
+            tauri_module::audit_commands::clear_ai_invocations,
+            tauri_module::compare_commands::send_compare_request,
+            tauri_module::compare_commands::select_compare_winner,
+            tauri_module::plan_commands::execute_plan,
+            tauri_module::permissions::respond_permission_request,
+            tauri_module::scheduler_commands::list_scheduled_jobs,
+            tauri_module::scheduler_commands::create_scheduled_job,
+            tauri_module::scheduler_commands::update_scheduled_job,
+            tauri_module::scheduler_commands::delete_scheduled_job,
+            tauri_module::scheduler_commands::run_scheduled_job_now,
+            tauri_module::scheduler_commands::preview_schedule,
+            tauri_module::request_journal::list_ai_requests,
+            tauri_module::request_journal::resume_ai_request,
+            tauri_module::request_journal::dismiss_ai_request,
+            tauri_module::workspace_budget::get_workspace_budget,
+            tauri_module::workspace_budget::save_workspace_budget,
+            tauri_module::workspace_budget::get_workspace_usage,
+            tauri_module::workspace_budget::override_workspace_budget,
+            tauri_module::inline_completion::request_inline_completion,
+            tauri_module::inline_completion::cancel_inline_completion,
+            tauri_module::selection_edit_commands::edit_selection,
+            tauri_module::test_fix_commands::start_test_fix_loop,
+            tauri_module::commit_message_commands::generate_commit_message,
+            tauri_module::commit_message_commands::commit_staged_changes,
+            tauri_module::code_review_commands::review_changes,
+            tauri_module::code_review_commands::list_code_reviews,
+            tauri_module::code_review_commands::get_code_review,
+            tauri_module::code_review_commands::delete_code_review,
         ])
 
         // Setup application state
@@ -115,6 +182,27 @@ fn main() {
             // Register event handlers
             tauri_module::event_handlers::register_event_handlers(app)?;
 
+            // Track CLI permission prompts waiting for the user
+            tauri_module::permissions::init(app)?;
+
+            // Persist chat requests so interrupted ones can be resumed
+            tauri_module::request_journal::init(app)?;
+
+            // Meter AI usage against workspace budgets
+            tauri_module::workspace_budget::init(app)?;
+
+            // Editor ghost-text completions
+            tauri_module::inline_completion::init(app)?;
+
+            // Start the opt-in local HTTP/WebSocket API
+            tauri_module::api_server::init(app)?;
+
+            // Start running scheduled agent jobs
+            tauri_module::scheduler::init(app)?;
+
+            // Follow file changes in the loaded workspace search indexes
+            tauri_module::search_index_commands::init(app)?;
+
             info!("Application setup completed successfully");
             Ok(())
         })
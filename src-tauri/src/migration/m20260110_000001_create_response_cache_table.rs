@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ResponseCache::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ResponseCache::Id).integer().not_null().auto_increment().primary_key())
+                    .col(ColumnDef::new(ResponseCache::CacheKey).string().not_null().unique_key())
+                    .col(ColumnDef::new(ResponseCache::Backend).string().not_null())
+                    .col(ColumnDef::new(ResponseCache::Model).string().null())
+                    .col(ColumnDef::new(ResponseCache::Response).text().not_null())
+                    .col(ColumnDef::new(ResponseCache::SizeBytes).big_integer().not_null())
+                    .col(ColumnDef::new(ResponseCache::HitCount).integer().not_null().default(0))
+                    .col(ColumnDef::new(ResponseCache::CreatedAt).big_integer().not_null())
+                    .col(ColumnDef::new(ResponseCache::LastUsedAt).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Pruning walks entries from least to most recently used
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_response_cache_last_used_at")
+                    .table(ResponseCache::Table)
+                    .col(ResponseCache::LastUsedAt)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_response_cache_last_used_at")
+                    .table(ResponseCache::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ResponseCache::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+/// Define the table and column identifiers
+#[derive(DeriveIden)]
+enum ResponseCache {
+    Table,
+    Id,
+    CacheKey,
+    Backend,
+    Model,
+    Response,
+    SizeBytes,
+    HitCount,
+    CreatedAt,
+    LastUsedAt,
+}
@@ -0,0 +1,106 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AiInvocations::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(AiInvocations::Id).integer().not_null().auto_increment().primary_key())
+                    .col(ColumnDef::new(AiInvocations::RequestId).string().null())
+                    .col(ColumnDef::new(AiInvocations::SessionId).string().null())
+                    .col(ColumnDef::new(AiInvocations::Kind).string().not_null())
+                    .col(ColumnDef::new(AiInvocations::Backend).string().null())
+                    .col(ColumnDef::new(AiInvocations::Binary).string().not_null())
+                    .col(ColumnDef::new(AiInvocations::Args).text().not_null())
+                    .col(ColumnDef::new(AiInvocations::Env).text().not_null())
+                    .col(ColumnDef::new(AiInvocations::Workdir).string().null())
+                    .col(ColumnDef::new(AiInvocations::Status).string().not_null())
+                    .col(ColumnDef::new(AiInvocations::ExitCode).integer().null())
+                    .col(ColumnDef::new(AiInvocations::Error).text().null())
+                    .col(ColumnDef::new(AiInvocations::Stdout).text().not_null())
+                    .col(ColumnDef::new(AiInvocations::Stderr).text().not_null())
+                    .col(ColumnDef::new(AiInvocations::CliSessionId).string().null())
+                    .col(ColumnDef::new(AiInvocations::StartedAt).big_integer().not_null())
+                    .col(ColumnDef::new(AiInvocations::DurationMs).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_ai_invocations_started_at")
+                    .table(AiInvocations::Table)
+                    .col(AiInvocations::StartedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_ai_invocations_request_id")
+                    .table(AiInvocations::Table)
+                    .col(AiInvocations::RequestId)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_ai_invocations_request_id")
+                    .table(AiInvocations::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_ai_invocations_started_at")
+                    .table(AiInvocations::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(AiInvocations::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+/// Define the table and column identifiers
+#[derive(DeriveIden)]
+enum AiInvocations {
+    Table,
+    Id,
+    RequestId,
+    SessionId,
+    Kind,
+    Backend,
+    Binary,
+    Args,
+    Env,
+    Workdir,
+    Status,
+    ExitCode,
+    Error,
+    Stdout,
+    Stderr,
+    CliSessionId,
+    StartedAt,
+    DurationMs,
+}
@@ -0,0 +1,94 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ScheduledJobs::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ScheduledJobs::Id).string().not_null().primary_key())
+                    .col(ColumnDef::new(ScheduledJobs::Name).string().not_null())
+                    .col(ColumnDef::new(ScheduledJobs::WorkspaceId).string().null())
+                    .col(ColumnDef::new(ScheduledJobs::WorkspaceDir).string().not_null())
+                    .col(ColumnDef::new(ScheduledJobs::Prompt).text().not_null())
+                    .col(ColumnDef::new(ScheduledJobs::Schedule).string().not_null())
+                    .col(ColumnDef::new(ScheduledJobs::CodeCli).string().null())
+                    .col(ColumnDef::new(ScheduledJobs::DirectCli).boolean().not_null().default(false))
+                    .col(ColumnDef::new(ScheduledJobs::CliCommand).string().null())
+                    .col(ColumnDef::new(ScheduledJobs::CliArgs).text().not_null())
+                    .col(ColumnDef::new(ScheduledJobs::NativeModel).string().null())
+                    .col(ColumnDef::new(ScheduledJobs::AutoContext).boolean().not_null().default(false))
+                    .col(ColumnDef::new(ScheduledJobs::Enabled).boolean().not_null().default(true))
+                    .col(ColumnDef::new(ScheduledJobs::SessionId).string().not_null())
+                    .col(ColumnDef::new(ScheduledJobs::NextRunAt).big_integer().null())
+                    .col(ColumnDef::new(ScheduledJobs::LastRunAt).big_integer().null())
+                    .col(ColumnDef::new(ScheduledJobs::LastStatus).string().null())
+                    .col(ColumnDef::new(ScheduledJobs::LastError).text().null())
+                    .col(ColumnDef::new(ScheduledJobs::LastRequestId).string().null())
+                    .col(ColumnDef::new(ScheduledJobs::CreatedAt).big_integer().not_null())
+                    .col(ColumnDef::new(ScheduledJobs::UpdatedAt).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_scheduled_jobs_next_run_at")
+                    .table(ScheduledJobs::Table)
+                    .col(ScheduledJobs::NextRunAt)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_scheduled_jobs_next_run_at")
+                    .table(ScheduledJobs::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ScheduledJobs::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+/// Define the table and column identifiers
+#[derive(DeriveIden)]
+enum ScheduledJobs {
+    Table,
+    Id,
+    Name,
+    WorkspaceId,
+    WorkspaceDir,
+    Prompt,
+    Schedule,
+    CodeCli,
+    DirectCli,
+    CliCommand,
+    CliArgs,
+    NativeModel,
+    AutoContext,
+    Enabled,
+    SessionId,
+    NextRunAt,
+    LastRunAt,
+    LastStatus,
+    LastError,
+    LastRequestId,
+    CreatedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,90 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AiRequests::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(AiRequests::RequestId).string().not_null().primary_key())
+                    .col(ColumnDef::new(AiRequests::SessionId).string().not_null())
+                    .col(ColumnDef::new(AiRequests::WorkspaceId).string().null())
+                    .col(ColumnDef::new(AiRequests::WorkspaceDir).string().null())
+                    .col(ColumnDef::new(AiRequests::Message).text().not_null())
+                    .col(ColumnDef::new(AiRequests::ContextFiles).text().not_null())
+                    .col(ColumnDef::new(AiRequests::CodeCli).string().null())
+                    .col(ColumnDef::new(AiRequests::CodexModel).string().null())
+                    .col(ColumnDef::new(AiRequests::DirectCli).boolean().not_null().default(false))
+                    .col(ColumnDef::new(AiRequests::CliCommand).string().null())
+                    .col(ColumnDef::new(AiRequests::CliArgs).text().not_null())
+                    .col(ColumnDef::new(AiRequests::NativeModel).string().null())
+                    .col(ColumnDef::new(AiRequests::AutoContext).boolean().not_null().default(false))
+                    .col(ColumnDef::new(AiRequests::Status).string().not_null())
+                    .col(ColumnDef::new(AiRequests::PartialOutput).text().not_null())
+                    .col(ColumnDef::new(AiRequests::CliSessionId).string().null())
+                    .col(ColumnDef::new(AiRequests::Error).text().null())
+                    .col(ColumnDef::new(AiRequests::CreatedAt).big_integer().not_null())
+                    .col(ColumnDef::new(AiRequests::UpdatedAt).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_ai_requests_status")
+                    .table(AiRequests::Table)
+                    .col(AiRequests::Status)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_ai_requests_status")
+                    .table(AiRequests::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(AiRequests::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+/// Define the table and column identifiers
+#[derive(DeriveIden)]
+enum AiRequests {
+    Table,
+    RequestId,
+    SessionId,
+    WorkspaceId,
+    WorkspaceDir,
+    Message,
+    ContextFiles,
+    CodeCli,
+    CodexModel,
+    DirectCli,
+    CliCommand,
+    CliArgs,
+    NativeModel,
+    AutoContext,
+    Status,
+    PartialOutput,
+    CliSessionId,
+    Error,
+    CreatedAt,
+    UpdatedAt,
+}
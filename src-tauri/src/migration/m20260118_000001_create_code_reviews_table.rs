@@ -0,0 +1,76 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(CodeReviews::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(CodeReviews::Id).string().not_null().primary_key())
+                    .col(ColumnDef::new(CodeReviews::WorkspaceDir).string().not_null())
+                    .col(ColumnDef::new(CodeReviews::CommitHash).string().null())
+                    .col(ColumnDef::new(CodeReviews::CommitRange).string().null())
+                    .col(ColumnDef::new(CodeReviews::Backend).string().not_null())
+                    .col(ColumnDef::new(CodeReviews::Findings).text().not_null())
+                    .col(ColumnDef::new(CodeReviews::Files).text().not_null())
+                    .col(ColumnDef::new(CodeReviews::ExcludedFiles).text().not_null())
+                    .col(ColumnDef::new(CodeReviews::Truncated).boolean().not_null().default(false))
+                    .col(ColumnDef::new(CodeReviews::Dropped).integer().not_null().default(0))
+                    .col(ColumnDef::new(CodeReviews::CreatedAt).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Reviews are looked up by workspace and commit
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_code_reviews_workspace_commit")
+                    .table(CodeReviews::Table)
+                    .col(CodeReviews::WorkspaceDir)
+                    .col(CodeReviews::CommitHash)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_code_reviews_workspace_commit")
+                    .table(CodeReviews::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(CodeReviews::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+/// Define the table and column identifiers
+#[derive(DeriveIden)]
+enum CodeReviews {
+    Table,
+    Id,
+    WorkspaceDir,
+    CommitHash,
+    CommitRange,
+    Backend,
+    Findings,
+    Files,
+    ExcludedFiles,
+    Truncated,
+    Dropped,
+    CreatedAt,
+}
@@ -5,6 +5,11 @@ use sea_orm_migration::prelude::*;
 mod m20250101_000001_create_settings_table;
 mod m20251219_132921_create_workspace_table;
 mod m20251221_101715_create_conversion_table;
+mod m20260110_000001_create_response_cache_table;
+mod m20260112_000001_create_ai_invocations_table;
+mod m20260114_000001_create_scheduled_jobs_table;
+mod m20260116_000001_create_ai_requests_table;
+mod m20260118_000001_create_code_reviews_table;
 
 
 pub struct Migrator;
@@ -16,6 +21,11 @@ impl MigratorTrait for Migrator {
             Box::new(m20250101_000001_create_settings_table::Migration),
             Box::new(m20251219_132921_create_workspace_table::Migration),
             Box::new(m20251221_101715_create_conversion_table::Migration),
+            Box::new(m20260110_000001_create_response_cache_table::Migration),
+            Box::new(m20260112_000001_create_ai_invocations_table::Migration),
+            Box::new(m20260114_000001_create_scheduled_jobs_table::Migration),
+            Box::new(m20260116_000001_create_ai_requests_table::Migration),
+            Box::new(m20260118_000001_create_code_reviews_table::Migration),
         ]
     }
 }
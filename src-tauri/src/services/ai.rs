@@ -4,15 +4,18 @@
 
 use serde::{Deserialize, Serialize};
 use std::fmt;
-use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::process::Command;
-use tokio::sync::oneshot;
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::{sleep, Duration};
 use tracing::{debug, info, warn};
 
+use crate::services::ai_ignore::AiIgnore;
+use crate::services::audit::{AuditContext, InvocationRecorder};
+use crate::services::context::{ContextBlock, ContextBuilder, ContextSnippet};
+use crate::services::plan_mode;
 use crate::utils::error::{AppError, AppResult};
 
 const CREATE_NO_WINDOW: u32 = 0x08000000;
@@ -52,8 +55,19 @@ pub struct AiChatOptions {
     pub code_cli_changed: Option<bool>,
     /// Environment variables (mapped to CODEAGENT_ENV).
     pub env: Vec<(String, String)>,
+    /// Snippets retrieved by auto context, appended after attached files.
+    pub context_snippets: Vec<ContextSnippet>,
+    /// Fully built (and redacted) task; when set, it is sent as-is instead of
+    /// being built from the message, context files and snippets.
+    pub prepared_task: Option<String>,
     /// Cancellation signal used to terminate codeagent-wrapper.
     pub cancel_rx: Option<oneshot::Receiver<()>>,
+    /// Where to record the invocation in the audit log (not recorded when `None`).
+    pub audit: Option<AuditContext>,
+    /// Plan run: permission prompts are never skipped, whatever the wrapper config says.
+    pub plan_mode: bool,
+    /// Receives the wrapper's stdout line by line as it is printed, without the session trailer.
+    pub output_tx: Option<mpsc::UnboundedSender<String>>,
 }
 
 impl fmt::Debug for AiChatOptions {
@@ -66,7 +80,12 @@ impl fmt::Debug for AiChatOptions {
             .field("workspace_dir", &self.workspace_dir)
             .field("code_cli_changed", &self.code_cli_changed)
             .field("env_len", &self.env.len())
+            .field("context_snippets", &self.context_snippets.len())
+            .field("has_prepared_task", &self.prepared_task.is_some())
             .field("has_cancel_rx", &self.cancel_rx.is_some())
+            .field("has_audit", &self.audit.is_some())
+            .field("plan_mode", &self.plan_mode)
+            .field("has_output_tx", &self.output_tx.is_some())
             .finish()
     }
 }
@@ -211,14 +230,31 @@ impl AiService {
                     .or_else(|| self.get_codeagent_config().workdir.clone())
                     .unwrap_or_else(|| ".".to_string());
 
-        let task = Self::build_task_with_context(message, _context_files.as_deref());
+        let task = options.prepared_task.unwrap_or_else(|| {
+            let data_dir = crate::config::get_default_data_dir().ok();
+            let ignore = AiIgnore::load(data_dir.as_deref(), options.workspace_dir.as_deref());
+            Self::build_task_with_context(
+                message,
+                _context_files.as_deref(),
+                &[],
+                &options.context_snippets,
+                &ignore,
+            )
+        });
+
+        if options.plan_mode {
+            // The wrapper has no read-only flag; plan runs rely on the CLI asking for (and,
+            // unattended, never getting) permission, so backends not known to do so are refused.
+            plan_mode::ensure_read_only(Some(&backend))?;
+        }
 
         let result = self
             .run_codeagent_wrapper(CodeagentRunSpec {
                 task,
                 backend,
                 workdir,
-                skip_permissions: self.codeagent.skip_permissions,
+                skip_permissions: self.codeagent.skip_permissions && !options.plan_mode,
+                read_only: options.plan_mode,
                 timeout_ms: self.codeagent.timeout_ms,
                 max_parallel_workers: self.codeagent.max_parallel_workers,
                 binary_path: self.codeagent.binary_path.clone(),
@@ -228,6 +264,8 @@ impl AiService {
                 env: options.env,
                 code_cli_changed: options.code_cli_changed,
                 cancel_rx: options.cancel_rx,
+                audit: options.audit,
+                output_tx: options.output_tx,
             })
             .await?;
 
@@ -237,6 +275,11 @@ impl AiService {
         })
     }
 
+    /// Find a configured model by name
+    pub fn find_model(&self, name: &str) -> Option<AiModel> {
+        self.models.iter().find(|m| m.name == name).cloned()
+    }
+
     /// Add a new model
     pub fn add_model(&mut self, model: AiModel) {
         self.models.push(model);
@@ -279,34 +322,17 @@ impl AiService {
     pub(crate) fn build_task_with_context(
         message: &str,
         context_files: Option<&[String]>,
+        blocks: &[ContextBlock],
+        snippets: &[ContextSnippet],
+        ignore: &AiIgnore,
     ) -> String {
+        let mut builder = ContextBuilder::with_ignore(ignore.clone());
         if let Some(files) = context_files {
-            if !files.is_empty() {
-                let mut segments: Vec<String> = Vec::new();
-                for file_path in files {
-                    match fs::read_to_string(file_path) {
-                        Ok(content) => {
-                            segments.push(format!("@{}\n{}", file_path, content));
-                        }
-                        Err(err) => {
-                            warn!(
-                                path = %file_path,
-                                error = %err,
-                                "Failed to read context file"
-                            );
-                        }
-                    }
-                }
-
-                if !segments.is_empty() {
-                    let mut prefix = segments.join("\n");
-                    prefix.push_str("\n\n");
-                    return format!("{}{}", prefix, message);
-                }
-            }
+            builder.add_files(files);
         }
-
-        message.to_string()
+        builder.add_blocks(blocks);
+        builder.add_snippets(snippets);
+        builder.build(message).0
     }
 
     fn is_executable_file(path: &Path) -> bool {
@@ -440,17 +466,22 @@ impl AiService {
             .stderr(Stdio::piped())
             .current_dir(&spec.workdir);
 
+        // Mirrors every `cmd.env` call for the audit log.
+        let mut audit_env: Vec<(String, String)> = Vec::new();
         if let Some(timeout_ms) = spec.timeout_ms {
             // myclaude wrapper reads CODEX_TIMEOUT in milliseconds.
             cmd.env("CODEX_TIMEOUT", timeout_ms.to_string());
+            audit_env.push(("CODEX_TIMEOUT".to_string(), timeout_ms.to_string()));
         }
         if spec.skip_permissions {
             // Also enable via env for wrapper default.
             cmd.env("CODEAGENT_SKIP_PERMISSIONS", "1");
+            audit_env.push(("CODEAGENT_SKIP_PERMISSIONS".to_string(), "1".to_string()));
         }
 
         if let Some(max_workers) = spec.max_parallel_workers {
             cmd.env("CODEAGENT_MAX_PARALLEL_WORKERS", max_workers.to_string());
+            audit_env.push(("CODEAGENT_MAX_PARALLEL_WORKERS".to_string(), max_workers.to_string()));
         }
 
         // Upstream wrapper currently does not accept `--model` for codex.
@@ -460,6 +491,7 @@ impl AiService {
                 let m = m.trim();
                 if !m.is_empty() {
                     cmd.env("CODEX_MODEL", m);
+                    audit_env.push(("CODEX_MODEL".to_string(), m.to_string()));
                 }
             }
         }
@@ -496,7 +528,44 @@ impl AiService {
                 cmd.env(key, value);
             }
         }
+        audit_env.extend(spec.env.iter().cloned());
+        if spec.read_only {
+            // Neither the configured nor the inherited environment may skip permissions.
+            cmd.env_remove("CODEAGENT_SKIP_PERMISSIONS");
+            audit_env.retain(|(key, _)| key != "CODEAGENT_SKIP_PERMISSIONS");
+        }
+
+        let mut recorder = InvocationRecorder::start(
+            spec.audit,
+            "wrapper",
+            Some(spec.backend.trim()).filter(|b| !b.is_empty()),
+            &bin.display().to_string(),
+            &args,
+            &audit_env,
+            Some(&spec.workdir),
+        );
+        let result = Self::execute_codeagent_wrapper(
+            &mut cmd,
+            &bin,
+            &spec.task,
+            spec.cancel_rx,
+            spec.output_tx,
+            &mut recorder,
+        )
+        .await;
+        recorder.finish(&result).await;
+        result
+    }
 
+    /// Spawn the prepared wrapper command and wait for its result, filling in `recorder`
+    async fn execute_codeagent_wrapper(
+        cmd: &mut Command,
+        bin: &Path,
+        task: &str,
+        cancel_rx: Option<oneshot::Receiver<()>>,
+        output_tx: Option<mpsc::UnboundedSender<String>>,
+        recorder: &mut InvocationRecorder,
+    ) -> AppResult<CodeagentRunResult> {
         let mut child = cmd.spawn().map_err(|e| {
             AppError::AiServiceError(format!(
                 "启动 codeagent-wrapper 失败: {} (bin={})",
@@ -507,7 +576,7 @@ impl AiService {
 
         if let Some(mut stdin) = child.stdin.take() {
             stdin
-                .write_all(spec.task.as_bytes())
+                .write_all(task.as_bytes())
                 .await
                 .map_err(|e| AppError::AiServiceError(format!("写入 codeagent-wrapper stdin 失败: {}", e)))?;
         }
@@ -515,10 +584,30 @@ impl AiService {
         let stdout_task = child.stdout.take().map(|mut stdout_pipe| {
             tokio::spawn(async move {
                 let mut buf = Vec::new();
-                stdout_pipe
-                    .read_to_end(&mut buf)
-                    .await
-                    .map_err(|e| AppError::AiServiceError(format!("读取 codeagent-wrapper stdout 失败: {}", e)))?;
+                let mut chunk = [0u8; 8192];
+                let mut lines = WrapperOutputLines::default();
+                loop {
+                    let n = stdout_pipe
+                        .read(&mut chunk)
+                        .await
+                        .map_err(|e| AppError::AiServiceError(format!("读取 codeagent-wrapper stdout 失败: {}", e)))?;
+                    if n == 0 {
+                        break;
+                    }
+                    buf.extend_from_slice(&chunk[..n]);
+                    if let Some(tx) = output_tx.as_ref() {
+                        let text = lines.push(&chunk[..n]);
+                        if !text.is_empty() {
+                            let _ = tx.send(text);
+                        }
+                    }
+                }
+                if let Some(tx) = output_tx.as_ref() {
+                    let text = lines.finish();
+                    if !text.is_empty() {
+                        let _ = tx.send(text);
+                    }
+                }
                 Ok(buf)
             })
         });
@@ -533,7 +622,7 @@ impl AiService {
             })
         });
 
-        let mut cancel_rx = spec.cancel_rx;
+        let mut cancel_rx = cancel_rx;
         let exit_status = loop {
             if let Some(status) = child
                 .try_wait()
@@ -562,6 +651,8 @@ impl AiService {
         let stdout = String::from_utf8_lossy(&stdout_bytes).into_owned();
         let stderr = String::from_utf8_lossy(&stderr_bytes).into_owned();
         let exit_code = exit_status.code().unwrap_or(-1);
+        recorder.set_output(&stdout, &stderr);
+        recorder.set_exit_code(exit_code);
 
         debug!(
             exit_code,
@@ -587,6 +678,7 @@ impl AiService {
         }
 
         let (message, session_id) = parse_codeagent_stdout(&stdout);
+        recorder.set_cli_session_id(session_id.as_deref());
         debug!(
             parsed_session_id = ?session_id,
             message_len = message.len(),
@@ -603,9 +695,6 @@ impl AiService {
         Ok(CodeagentRunResult {
             message,
             session_id,
-            raw_stdout: stdout,
-            raw_stderr: stderr,
-            exit_code,
         })
     }
 }
@@ -631,6 +720,8 @@ struct CodeagentRunSpec {
     backend: String,
     workdir: String,
     skip_permissions: bool,
+    /// Plan run: never skip permissions, even through the environment
+    read_only: bool,
     timeout_ms: Option<u64>,
     max_parallel_workers: Option<u32>,
     binary_path: Option<String>,
@@ -640,19 +731,72 @@ struct CodeagentRunSpec {
     env: Vec<(String, String)>,
     code_cli_changed: Option<bool>,
     cancel_rx: Option<oneshot::Receiver<()>>,
+    audit: Option<AuditContext>,
+    output_tx: Option<mpsc::UnboundedSender<String>>,
 }
 
 #[derive(Debug, Clone)]
 struct CodeagentRunResult {
     message: String,
-    #[allow(dead_code)]
     session_id: Option<String>,
-    #[allow(dead_code)]
-    raw_stdout: String,
-    #[allow(dead_code)]
-    raw_stderr: String,
-    #[allow(dead_code)]
-    exit_code: i32,
+}
+
+/// Splits wrapper stdout into complete lines for streaming, dropping the
+/// `---` / `SESSION_ID:` trailer that `parse_codeagent_stdout` strips.
+#[derive(Default)]
+struct WrapperOutputLines {
+    /// Bytes of the line that is still being printed
+    partial: Vec<u8>,
+    /// A `---` line held back until the next line shows whether it starts the trailer
+    held: Option<String>,
+    trailer_seen: bool,
+}
+
+impl WrapperOutputLines {
+    /// Feed raw stdout bytes and return the text that can be shown so far
+    fn push(&mut self, bytes: &[u8]) -> String {
+        self.partial.extend_from_slice(bytes);
+        let mut out = String::new();
+        while let Some(idx) = self.partial.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=idx).collect();
+            self.line(&String::from_utf8_lossy(&line), &mut out);
+        }
+        out
+    }
+
+    /// Return whatever is left once stdout has closed
+    fn finish(&mut self) -> String {
+        let mut out = String::new();
+        if !self.partial.is_empty() {
+            let line = String::from_utf8_lossy(&std::mem::take(&mut self.partial)).into_owned();
+            self.line(&line, &mut out);
+        }
+        if let Some(held) = self.held.take() {
+            out.push_str(&held);
+        }
+        out
+    }
+
+    fn line(&mut self, line: &str, out: &mut String) {
+        if self.trailer_seen {
+            return;
+        }
+        let trimmed = line.trim();
+        if let Some(held) = self.held.take() {
+            if trimmed.starts_with("SESSION_ID:") {
+                self.trailer_seen = true;
+                return;
+            }
+            out.push_str(&held);
+        }
+        if trimmed == "---" {
+            self.held = Some(line.to_string());
+        } else if trimmed.starts_with("SESSION_ID:") {
+            self.trailer_seen = true;
+        } else {
+            out.push_str(line);
+        }
+    }
 }
 
 fn parse_codeagent_stdout(stdout: &str) -> (String, Option<String>) {
@@ -739,3 +883,26 @@ impl Default for AiService {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrapper_output_lines_drop_session_trailer() {
+        let mut lines = WrapperOutputLines::default();
+        let mut out = lines.push(b"first li");
+        assert_eq!(out, "");
+        out.push_str(&lines.push(b"ne\n---\nnot a trailer\n---\r\nSESSION_ID: abc\r\n"));
+        out.push_str(&lines.finish());
+        assert_eq!(out, "first line\n---\nnot a trailer\n");
+    }
+
+    #[test]
+    fn wrapper_output_lines_flush_unterminated_output() {
+        let mut lines = WrapperOutputLines::default();
+        let mut out = lines.push("答案\n---".as_bytes());
+        out.push_str(&lines.finish());
+        assert_eq!(out, "答案\n---");
+    }
+}
@@ -0,0 +1,320 @@
+//! `.aiignore` module
+//!
+//! gitignore-style rules for files that must never be sent to a model. Rules are
+//! read from `.aiignore` in the data directory (global) and at the workspace
+//! root; workspace rules come last, so they can re-include a file with `!`.
+//!
+//! Patterns match paths relative to the workspace root, after `.` and `..` are
+//! resolved lexically; relative paths are taken to be relative to the root.
+//! Files outside the workspace are matched by their absolute path, so only
+//! unanchored patterns such as `*.pem` apply to them. Supported syntax: `#`
+//! comments, `!` negation, a trailing `/` for directories, a leading or inner `/`
+//! to anchor a pattern, `*`, `?`, `[a-z]` / `[!a-z]` and `**`. As in git, a file
+//! below an ignored directory cannot be re-included.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// File name of the rules, in the data directory and at the workspace root
+pub const AIIGNORE_FILE: &str = ".aiignore";
+
+#[derive(Debug, Clone)]
+struct Rule {
+    glob: Vec<char>,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the whole relative path rather than the file name
+    anchored: bool,
+}
+
+/// The `.aiignore` rules of a workspace
+#[derive(Debug, Clone, Default)]
+pub struct AiIgnore {
+    root: Option<PathBuf>,
+    rules: Vec<Rule>,
+}
+
+impl AiIgnore {
+    /// Read the global rules from `data_dir` and the workspace rules from `workspace_dir`
+    pub fn load(data_dir: Option<&str>, workspace_dir: Option<&str>) -> Self {
+        let root = workspace_dir
+            .map(str::trim)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from);
+        let mut rules = Vec::new();
+        for dir in [data_dir.map(Path::new), root.as_deref()].into_iter().flatten() {
+            if let Ok(text) = fs::read_to_string(dir.join(AIIGNORE_FILE)) {
+                rules.extend(parse_rules(&text));
+            }
+        }
+        Self { root, rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether the file or directory at `path` must not be sent to a model
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        // Resolve `.` and `..` first so `src/../config.toml` cannot slip past `/config.toml`.
+        let root = self.root.as_deref().map(normalize_lexically);
+        let path = match root.as_deref() {
+            Some(root) if path.is_relative() => normalize_lexically(&root.join(path)),
+            _ => normalize_lexically(path),
+        };
+        let relative = root
+            .as_deref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(&path);
+        let components: Vec<String> = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        let path_is_dir = path.is_dir();
+
+        // A path is ignored when it or one of its parent directories is.
+        (1..=components.len()).any(|len| {
+            let is_dir = len < components.len() || path_is_dir;
+            self.matches(&components[..len].join("/"), &components[len - 1], is_dir)
+        })
+    }
+
+    /// The paths that are not ignored
+    pub fn allowed(&self, paths: &[String]) -> Vec<String> {
+        paths
+            .iter()
+            .filter(|path| !self.is_ignored(Path::new(path)))
+            .cloned()
+            .collect()
+    }
+
+    /// Whether the last matching rule ignores `relative` (`name` is its last component)
+    fn matches(&self, relative: &str, name: &str, is_dir: bool) -> bool {
+        let relative: Vec<char> = relative.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        let mut ignored = false;
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let target = if rule.anchored { &relative } else { &name };
+            if glob_match(&rule.glob, target) {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+}
+
+/// `path` with `.` components dropped and `..` applied to the preceding component,
+/// without touching the file system
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if matches!(normalized.components().next_back(), Some(Component::Normal(_))) {
+                    normalized.pop();
+                } else if !normalized.has_root() {
+                    normalized.push(component);
+                }
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn parse_rules(text: &str) -> Vec<Rule> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            let line = line.trim_start_matches('/');
+            if line.is_empty() {
+                return None;
+            }
+            Some(Rule {
+                glob: line.chars().collect(),
+                negated,
+                dir_only,
+                anchored,
+            })
+        })
+        .collect()
+}
+
+/// gitignore glob match; `*` and `?` stop at `/`, `**` does not
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+            // `**/` also matches no directory at all.
+            if rest.first() == Some(&'/') && glob_match(&rest[1..], text) {
+                return true;
+            }
+            (0..=text.len()).any(|skip| glob_match(rest, &text[skip..]))
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            for skip in 0..=text.len() {
+                if glob_match(rest, &text[skip..]) {
+                    return true;
+                }
+                if text.get(skip) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => text.first().is_some_and(|c| *c != '/') && glob_match(&pattern[1..], &text[1..]),
+        Some('[') => match class_match(&pattern[1..], text.first()) {
+            Some((matched, len)) => matched && glob_match(&pattern[1 + len..], &text[1..]),
+            // No closing bracket: a literal `[`
+            None => text.first() == Some(&'[') && glob_match(&pattern[1..], &text[1..]),
+        },
+        Some('\\') if pattern.len() > 1 => {
+            text.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &text[1..])
+        }
+        Some(c) => text.first() == Some(c) && glob_match(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Match `c` against the character class starting after `[`; returns
+/// (matched, characters consumed including the closing `]`)
+fn class_match(class: &[char], c: Option<&char>) -> Option<(bool, usize)> {
+    let negated = matches!(class.first(), Some('!') | Some('^'));
+    let mut idx = usize::from(negated);
+    let start = idx;
+    let mut matched = false;
+    while idx < class.len() {
+        if class[idx] == ']' && idx > start {
+            let matched = c.is_some_and(|c| *c != '/') && matched != negated;
+            return Some((matched, idx + 1));
+        }
+        if idx + 2 < class.len() && class[idx + 1] == '-' && class[idx + 2] != ']' {
+            if c.is_some_and(|c| class[idx] <= *c && *c <= class[idx + 2]) {
+                matched = true;
+            }
+            idx += 3;
+        } else {
+            if c == Some(&class[idx]) {
+                matched = true;
+            }
+            idx += 1;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignore(rules: &str) -> AiIgnore {
+        AiIgnore {
+            root: Some(PathBuf::from("/work")),
+            rules: parse_rules(rules),
+        }
+    }
+
+    fn ignored(ignore: &AiIgnore, path: &str) -> bool {
+        ignore.is_ignored(&Path::new("/work").join(path))
+    }
+
+    #[test]
+    fn unanchored_patterns_match_names_at_any_depth() {
+        let rules = ignore("# secrets\n*.pem\n.env\n");
+        assert!(ignored(&rules, "key.pem"));
+        assert!(ignored(&rules, "certs/deep/key.pem"));
+        assert!(ignored(&rules, ".env"));
+        assert!(ignored(&rules, "app/.env"));
+        assert!(!ignored(&rules, ".env.example"));
+        assert!(rules.is_ignored(Path::new("/elsewhere/id.pem")));
+    }
+
+    #[test]
+    fn anchored_patterns_match_from_the_root() {
+        let rules = ignore("/config.toml\nsecrets/*.json\ndocs/**/private.md\n");
+        assert!(ignored(&rules, "config.toml"));
+        assert!(!ignored(&rules, "sub/config.toml"));
+        assert!(ignored(&rules, "secrets/a.json"));
+        assert!(!ignored(&rules, "secrets/nested/a.json"));
+        assert!(ignored(&rules, "docs/private.md"));
+        assert!(ignored(&rules, "docs/a/b/private.md"));
+    }
+
+    #[test]
+    fn ignored_directories_cover_their_files() {
+        let rules = ignore("build/\n");
+        assert!(ignored(&rules, "build/out/main.js"));
+        assert!(ignored(&rules, "app/build/x"));
+        // `build` as a file is not matched by a directory rule.
+        assert!(!ignored(&rules, "build"));
+    }
+
+    #[test]
+    fn negation_re_includes_unless_a_parent_is_ignored() {
+        let rules = ignore("*.key\n!public.key\nvault/\n!vault/readme.md\n");
+        assert!(ignored(&rules, "private.key"));
+        assert!(!ignored(&rules, "public.key"));
+        assert!(ignored(&rules, "vault/readme.md"));
+    }
+
+    #[test]
+    fn wildcards_and_classes() {
+        let rules = ignore("log?.txt\n[!a-c]*.bak\n\\!important\n");
+        assert!(ignored(&rules, "log1.txt"));
+        assert!(!ignored(&rules, "log10.txt"));
+        assert!(ignored(&rules, "dump.bak"));
+        assert!(!ignored(&rules, "backup.bak"));
+        assert!(ignored(&rules, "!important"));
+        assert_eq!(ignore("").allowed(&["/work/a".to_string()]).len(), 1);
+    }
+
+    #[test]
+    fn dot_segments_are_resolved_before_matching() {
+        let rules = ignore(".env\n/config.toml\nsecrets/\n");
+        assert!(ignored(&rules, "src/../.env"));
+        assert!(ignored(&rules, "./config.toml"));
+        assert!(ignored(&rules, "src/./../config.toml"));
+        assert!(ignored(&rules, "docs/../secrets/./a.json"));
+        assert!(!ignored(&rules, "src/../src/config.toml"));
+        assert!(rules.is_ignored(Path::new("src/../config.toml")));
+        assert!(rules.is_ignored(Path::new("/elsewhere/../work/secrets/a.json")));
+    }
+
+    #[test]
+    fn paths_leaving_the_workspace_are_not_anchored_to_it() {
+        let rules = ignore("/config.toml\n*.pem\n");
+        assert!(!ignored(&rules, "../other/config.toml"));
+        assert!(!rules.is_ignored(Path::new("../other/config.toml")));
+        assert!(ignored(&rules, "../other/id.pem"));
+    }
+
+    #[test]
+    fn lexical_normalization() {
+        assert_eq!(normalize_lexically(Path::new("/a/./b/../c")), PathBuf::from("/a/c"));
+        assert_eq!(normalize_lexically(Path::new("/../a")), PathBuf::from("/a"));
+        assert_eq!(normalize_lexically(Path::new("a/../../b")), PathBuf::from("../b"));
+    }
+}
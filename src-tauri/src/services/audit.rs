@@ -0,0 +1,171 @@
+//! AI invocation audit module
+//!
+//! Records every run of codeagent-wrapper, a direct CLI or a native provider
+//! in the `ai_invocations` table so failed runs can be inspected afterwards.
+//! Recording is best effort: failures are logged and never fail the request.
+
+use sea_orm::DatabaseConnection;
+use std::time::Instant;
+use tracing::warn;
+
+use crate::database::repositories::ai_invocation_repository::{AiInvocation, AiInvocationRepository};
+use crate::utils::error::{AppError, AppResult};
+
+/// Maximum bytes kept of stdout and stderr each
+const MAX_OUTPUT_BYTES: usize = 16 * 1024;
+/// Maximum bytes kept of a single argument (the wrapper receives the whole task as one)
+const MAX_ARG_BYTES: usize = 512;
+/// Records older than this are pruned
+const RETENTION_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+/// At most this many records are kept
+const MAX_RECORDS: i64 = 5000;
+/// Environment variables whose values are safe to keep in the log
+const PLAIN_ENV_KEYS: &[&str] = &[
+    "CODEX_TIMEOUT",
+    "CODEX_MODEL",
+    "CODEAGENT_SKIP_PERMISSIONS",
+    "CODEAGENT_MAX_PARALLEL_WORKERS",
+];
+
+/// Where and for which request an invocation is recorded
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    pub db: DatabaseConnection,
+    pub request_id: Option<String>,
+    pub session_id: Option<String>,
+}
+
+impl AuditContext {
+    pub fn new(db: DatabaseConnection, request_id: Option<&str>, session_id: Option<&str>) -> Self {
+        Self {
+            db,
+            request_id: request_id.map(|s| s.to_string()),
+            session_id: session_id.map(|s| s.to_string()),
+        }
+    }
+}
+
+/// Collects the details of one invocation while it runs
+#[derive(Debug)]
+pub struct InvocationRecorder {
+    context: Option<AuditContext>,
+    record: AiInvocation,
+    started: Instant,
+}
+
+impl InvocationRecorder {
+    /// Start recording; with `context == None` every call is a no-op
+    pub fn start(
+        context: Option<AuditContext>,
+        kind: &str,
+        backend: Option<&str>,
+        binary: &str,
+        args: &[String],
+        env: &[(String, String)],
+        workdir: Option<&str>,
+    ) -> Self {
+        let record = AiInvocation {
+            id: 0,
+            request_id: context.as_ref().and_then(|c| c.request_id.clone()),
+            session_id: context.as_ref().and_then(|c| c.session_id.clone()),
+            kind: kind.to_string(),
+            backend: backend.map(|b| b.to_string()),
+            binary: binary.to_string(),
+            args: args.iter().map(|a| truncate_head(a, MAX_ARG_BYTES)).collect(),
+            env: mask_env(env),
+            workdir: workdir.map(|w| w.to_string()),
+            status: "success".to_string(),
+            exit_code: None,
+            error: None,
+            stdout: String::new(),
+            stderr: String::new(),
+            cli_session_id: None,
+            started_at: chrono::Utc::now().timestamp_millis(),
+            duration_ms: 0,
+        };
+        Self {
+            context,
+            record,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn set_output(&mut self, stdout: &str, stderr: &str) {
+        self.record.stdout = truncate_tail(stdout, MAX_OUTPUT_BYTES);
+        self.record.stderr = truncate_tail(stderr, MAX_OUTPUT_BYTES);
+    }
+
+    pub fn set_exit_code(&mut self, exit_code: i32) {
+        self.record.exit_code = Some(exit_code);
+    }
+
+    pub fn set_cli_session_id(&mut self, session_id: Option<&str>) {
+        self.record.cli_session_id = session_id.map(|s| s.to_string());
+    }
+
+    /// Store the record with a status derived from `result` and the exit code
+    pub async fn finish<T>(mut self, result: &AppResult<T>) {
+        let Some(context) = self.context.take() else {
+            return;
+        };
+
+        self.record.duration_ms = self.started.elapsed().as_millis() as i64;
+        match result {
+            Err(AppError::Cancelled(_)) => self.record.status = "cancelled".to_string(),
+            Err(e) => {
+                self.record.status = "failed".to_string();
+                self.record.error = Some(e.to_string());
+            }
+            Ok(_) if self.record.exit_code.is_some_and(|code| code != 0) => {
+                self.record.status = "failed".to_string();
+            }
+            Ok(_) => {}
+        }
+
+        if let Err(e) = AiInvocationRepository::insert(&context.db, &self.record).await {
+            warn!("Failed to record AI invocation: {}", e);
+            return;
+        }
+        let before = self.record.started_at - RETENTION_MS;
+        if let Err(e) = AiInvocationRepository::prune(&context.db, before, MAX_RECORDS).await {
+            warn!("Failed to prune AI invocation log: {}", e);
+        }
+    }
+}
+
+/// Keep variable names, mask values unless they are known to be harmless
+fn mask_env(env: &[(String, String)]) -> Vec<(String, String)> {
+    env.iter()
+        .map(|(key, value)| {
+            let shown = if PLAIN_ENV_KEYS.contains(&key.as_str()) {
+                value.clone()
+            } else {
+                format!("<redacted, {} chars>", value.chars().count())
+            };
+            (key.clone(), shown)
+        })
+        .collect()
+}
+
+fn truncate_head(s: &str, max_bytes: usize) -> String {
+    if s.len() <= max_bytes {
+        return s.to_string();
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}… ({} bytes)", &s[..end], s.len())
+}
+
+/// Keep the end of the output, where errors usually are
+fn truncate_tail(s: &str, max_bytes: usize) -> String {
+    if s.len() <= max_bytes {
+        return s.to_string();
+    }
+    let mut start = s.len() - max_bytes;
+    while !s.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("… ({} bytes truncated)\n{}", start, &s[start..])
+}
@@ -21,6 +21,56 @@ pub struct ChatMessage {
     pub model: Option<String>,
     pub session_id: Option<String>,
     pub workspace_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<MessageMetadata>,
+}
+
+/// Extra information recorded with a message
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageMetadata {
+    /// Response was replayed from the response cache
+    #[serde(default)]
+    pub cached: bool,
+    /// Response cache key the response was stored or replayed under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_key: Option<String>,
+    /// Comparison run this message belongs to; its replies are sibling messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compare_id: Option<String>,
+    /// Code CLI that produced the reply (a comparison reply, or a reply stored
+    /// under that CLI's resume id)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_cli: Option<String>,
+    /// Comparison reply picked to continue the conversation
+    #[serde(default)]
+    pub winner: bool,
+    /// Reply of a plan run, waiting for approval before anything is executed
+    #[serde(default)]
+    pub plan: bool,
+}
+
+impl MessageMetadata {
+    /// Comparison reply that was not picked as the winner (left out of history)
+    pub fn is_unselected_compare_reply(&self) -> bool {
+        self.compare_id.is_some() && self.code_cli.is_some() && !self.winner
+    }
+}
+
+/// Model-generated summary replacing the oldest turns of a session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    /// Summary text (editable by the user)
+    pub content: String,
+    /// Number of leading messages covered by this summary
+    pub summarized_count: usize,
+    /// ID of the last message covered by this summary
+    pub last_message_id: Option<String>,
+    /// Model that produced the summary
+    pub model: Option<String>,
+    /// Whether the user edited the summary by hand
+    #[serde(default)]
+    pub edited: bool,
+    pub updated_at: String,
 }
 
 /// Chat session structure
@@ -38,6 +88,9 @@ pub struct ChatSession {
     pub first_message_preview: String,
     #[serde(default)]
     pub code_cli_task_ids: HashMap<String, String>,
+    /// Compacted history for native providers
+    #[serde(default)]
+    pub summary: Option<SessionSummary>,
 }
 
 /// Get the chat sessions directory path
@@ -60,8 +113,37 @@ fn ensure_sessions_dir_exists() -> Result<PathBuf, String> {
     Ok(dir)
 }
 
+/// Preview of the first message shown in session lists
+pub fn first_message_preview(messages: &[ChatMessage]) -> String {
+    messages
+        .first()
+        .map(|m| {
+            let content = &m.content;
+            let mut chars = content.chars();
+            let preview: String = chars.by_ref().take(100).collect();
+            if chars.next().is_some() {
+                format!("{}...", preview)
+            } else {
+                preview
+            }
+        })
+        .unwrap_or_default()
+}
+
+/// Write a session file as is, keeping its timestamps
+pub fn write_session(session: &ChatSession) -> Result<(), String> {
+    let dir = ensure_sessions_dir_exists()?;
+    let file_path = dir.join(format!("{}.json", session.id));
+
+    let json = serde_json::to_string_pretty(session)
+        .map_err(|e| format!("Failed to serialize session: {}", e))?;
+
+    fs::write(&file_path, json)
+        .map_err(|e| format!("Failed to write session file: {}", e))
+}
+
 /// Load a single session by ID
-fn load_session_by_id(session_id: &str) -> Result<ChatSession, String> {
+pub fn load_session_by_id(session_id: &str) -> Result<ChatSession, String> {
     let dir = get_sessions_dir()?;
     let file_path = dir.join(format!("{}.json", session_id));
 
@@ -96,39 +178,28 @@ pub fn save_session(
     // Build session object
     let now = Local::now().to_rfc3339();
 
-    // Generate first message preview
-    let first_message_preview = messages
-        .first()
-        .map(|m| {
-            let content = &m.content;
-            let mut chars = content.chars();
-            let preview: String = chars.by_ref().take(100).collect();
-            if chars.next().is_some() {
-                format!("{}...", preview)
-            } else {
-                preview
-            }
-        })
-        .unwrap_or_default();
+    let first_message_preview = first_message_preview(&messages);
 
-    // Preserve created_at if updating existing session
-    let (created_at, preserved_session_id) = if file_path.exists() {
+    // Preserve created_at and summary if updating existing session
+    let (created_at, preserved_session_id, summary) = if file_path.exists() {
         match load_session_by_id(&session_id) {
             Ok(existing) => {
                 let preserved = existing
                     .session_id
                     .clone()
                     .or_else(|| Some(session_id.to_string()));
-                (existing.created_at, preserved)
+                (existing.created_at, preserved, existing.summary)
             }
             Err(_) => {
                 warn!("Failed to load existing session, using current time as created_at");
-                (now.clone(), Some(session_id.to_string()))
+                (now.clone(), Some(session_id.to_string()), None)
             }
         }
     } else {
-        (now.clone(), Some(session_id.to_string()))
+        (now.clone(), Some(session_id.to_string()), None)
     };
+    // Drop a summary that no longer matches the stored messages (e.g. history was cleared)
+    let summary = summary.filter(|s| s.summarized_count <= messages.len());
 
     let message_count = messages.len();
 
@@ -143,6 +214,7 @@ pub fn save_session(
         message_count,
         first_message_preview,
         code_cli_task_ids: code_cli_task_ids.unwrap_or_default(),
+        summary,
     };
 
     // Write to file
@@ -157,14 +229,24 @@ pub fn save_session(
 }
 
 /// append a message to a chat session
+///
+/// With a resume id, assistant replies are tagged with `code_cli` so a later
+/// handoff knows which turns that CLI's own session has seen.
 pub fn append_message_to_session(
     session_id: &str,
-    messages: Vec<ChatMessage>,
+    mut messages: Vec<ChatMessage>,
     code_cli: Option<String>,
     code_cli_task_id: Option<String>,
 ) -> Result<(), String> {
     info!("Appending message to session: {}", session_id);
 
+    if let (Some(cli), Some(_)) = (code_cli.as_ref(), code_cli_task_id.as_ref()) {
+        for message in messages.iter_mut().filter(|m| m.role == "assistant") {
+            let metadata = message.metadata.get_or_insert_with(MessageMetadata::default);
+            metadata.code_cli.get_or_insert_with(|| cli.clone());
+        }
+    }
+
     let mut session = match load_session_by_id(session_id) {
         Ok(existing) => existing,
         Err(err) => {
@@ -186,6 +268,7 @@ pub fn append_message_to_session(
                 message_count: 0,
                 first_message_preview: String::new(),
                 code_cli_task_ids: HashMap::new(),
+                summary: None,
             }
         }
     };
@@ -328,3 +411,90 @@ pub fn update_session_name(session_id: &str, name: String) -> Result<ChatSession
     debug!("Session name updated successfully: {}", session_id);
     Ok(session)
 }
+
+/// Replace (or clear) the history summary of a chat session
+pub fn update_session_summary(
+    session_id: &str,
+    summary: Option<SessionSummary>,
+) -> Result<ChatSession, String> {
+    debug!("Updating session summary: {}", session_id);
+
+    let mut session = load_session_by_id(session_id)?;
+    session.summary = summary;
+    session.updated_at = Local::now().to_rfc3339();
+
+    let dir = get_sessions_dir()?;
+    let file_path = dir.join(format!("{}.json", session_id));
+
+    let json = serde_json::to_string_pretty(&session)
+        .map_err(|e| format!("Failed to serialize session: {}", e))?;
+
+    fs::write(&file_path, json)
+        .map_err(|e| format!("Failed to write session file: {}", e))?;
+
+    debug!("Session summary updated successfully: {}", session_id);
+    Ok(session)
+}
+
+/// Store CLI session ids for several code CLIs at once
+pub fn update_code_cli_task_ids(
+    session_id: &str,
+    task_ids: Vec<(String, String)>,
+) -> Result<ChatSession, String> {
+    debug!("Updating code CLI task ids: {}", session_id);
+
+    let mut session = load_session_by_id(session_id)?;
+    session.code_cli_task_ids.extend(task_ids);
+    session.updated_at = Local::now().to_rfc3339();
+
+    let dir = get_sessions_dir()?;
+    let file_path = dir.join(format!("{}.json", session_id));
+
+    let json = serde_json::to_string_pretty(&session)
+        .map_err(|e| format!("Failed to serialize session: {}", e))?;
+
+    fs::write(&file_path, json)
+        .map_err(|e| format!("Failed to write session file: {}", e))?;
+
+    Ok(session)
+}
+
+/// Mark `message_id` as the winner of comparison `compare_id`, clearing its siblings
+pub fn select_compare_winner(
+    session_id: &str,
+    compare_id: &str,
+    message_id: &str,
+) -> Result<ChatSession, String> {
+    debug!(
+        "Selecting comparison winner: session={}, compare={}, message={}",
+        session_id, compare_id, message_id
+    );
+
+    let mut session = load_session_by_id(session_id)?;
+    let mut found = false;
+    for message in session.messages.iter_mut() {
+        let Some(metadata) = message.metadata.as_mut() else {
+            continue;
+        };
+        if metadata.compare_id.as_deref() != Some(compare_id) || metadata.code_cli.is_none() {
+            continue;
+        }
+        metadata.winner = message.id == message_id;
+        found |= metadata.winner;
+    }
+    if !found {
+        return Err(format!("Comparison reply not found: {}", message_id));
+    }
+    session.updated_at = Local::now().to_rfc3339();
+
+    let dir = get_sessions_dir()?;
+    let file_path = dir.join(format!("{}.json", session_id));
+
+    let json = serde_json::to_string_pretty(&session)
+        .map_err(|e| format!("Failed to serialize session: {}", e))?;
+
+    fs::write(&file_path, json)
+        .map_err(|e| format!("Failed to write session file: {}", e))?;
+
+    Ok(session)
+}
@@ -0,0 +1,662 @@
+//! Code review module
+//!
+//! Reviews the uncommitted changes of a workspace repository (or a commit range)
+//! with a native provider or a code CLI. The diff goes out with new-side line
+//! numbers and the backend answers with JSON findings. Findings that name a file
+//! outside the review or a line the file does not have are dropped; the rest are
+//! tied to the text of their line, so they can be moved to where that line is
+//! now once the file has been edited, or marked outdated when it is gone.
+//! Untracked files that git does not ignore are reviewed with the working tree
+//! as if they had been added.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::services::ai_ignore::AiIgnore;
+use crate::services::audit::AuditContext;
+use crate::services::git::{self, run_git, FileDiff};
+use crate::services::native_provider::ProviderMessage;
+use crate::services::redaction::SecretRedactor;
+use crate::services::selection_edit::EditBackend;
+use crate::utils::error::{AppError, AppResult};
+
+/// Diff sent with one request, in characters
+const MAX_DIFF_CHARS: usize = 40_000;
+/// Diff kept per file before the remaining files are considered
+const MAX_FILE_DIFF_CHARS: usize = 10_000;
+/// Untracked files reviewed with the working tree, at most
+const MAX_UNTRACKED_FILES: usize = 200;
+/// Untracked files larger than this are left out of the review
+const MAX_UNTRACKED_FILE_BYTES: u64 = 1024 * 1024;
+/// Replies that are not valid JSON are sent back this many times
+const MAX_ATTEMPTS: u32 = 2;
+/// Completion budget of one attempt
+const MAX_OUTPUT_TOKENS: u32 = 8192;
+/// Findings kept from one reply
+const MAX_FINDINGS: usize = 100;
+
+const SYSTEM_PROMPT: &str = "You review code changes. Look for bugs, security problems, missing error handling, \
+race conditions and changes that break callers; mention style only when it hides a real problem. Comment on the \
+added or changed lines. Every diff line is prefixed with its line number in the new version of the file; removed \
+lines have none. Reply with JSON only, without code fences or commentary, in this shape: \
+{\"findings\":[{\"file\":\"path/as/in/the/diff\",\"line\":12,\"end_line\":14,\"severity\":\"error|warning|info\",\
+\"message\":\"what is wrong and why\",\"suggested_fix\":\"replacement code or a short instruction\"}]}. \
+`end_line` and `suggested_fix` are optional. Reply with {\"findings\":[]} when nothing needs attention.";
+
+/// How serious a finding is; maps to the editor's marker severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl ReviewSeverity {
+    /// Read the severity a model wrote; unknown values count as warnings
+    fn parse(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "error" | "critical" | "high" | "blocker" | "bug" => ReviewSeverity::Error,
+            "info" | "low" | "nit" | "note" | "suggestion" | "hint" => ReviewSeverity::Info,
+            _ => ReviewSeverity::Warning,
+        }
+    }
+}
+
+/// What to review and which backend to ask
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeReviewRequest {
+    pub workspace_dir: String,
+    /// Commit range (`main..feature`) or a single commit; the uncommitted changes when not set
+    #[serde(default)]
+    pub range: Option<String>,
+    /// Native provider model; takes precedence over `code_cli`
+    #[serde(default)]
+    pub native_model: Option<String>,
+    #[serde(default)]
+    pub code_cli: Option<String>,
+    #[serde(default)]
+    pub codex_model: Option<String>,
+    /// What to pay particular attention to, e.g. "error handling"
+    #[serde(default)]
+    pub focus: Option<String>,
+}
+
+/// One problem found by a review, anchored to a line of a file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewFinding {
+    /// Path relative to the repository root
+    pub file: String,
+    /// Absolute path, for opening the file in the editor
+    pub path: String,
+    /// Current line, 1-based
+    pub line: usize,
+    /// Current last line, 1-based and inclusive, when the finding spans several lines
+    #[serde(default)]
+    pub end_line: Option<usize>,
+    /// Line in the reviewed version of the file
+    pub reviewed_line: usize,
+    pub severity: ReviewSeverity,
+    pub message: String,
+    #[serde(default)]
+    pub suggested_fix: Option<String>,
+    /// Text of the reviewed line, used to find it again after edits
+    pub anchor_text: String,
+    /// The line can no longer be found in the file
+    #[serde(default)]
+    pub outdated: bool,
+}
+
+/// The changes as sent to the backend
+#[derive(Debug, Clone, Default)]
+pub struct ReviewChanges {
+    pub toplevel: PathBuf,
+    /// Commit the review belongs to: `HEAD` for uncommitted changes, the reviewed end of a range otherwise
+    pub commit: Option<String>,
+    /// Revision the new side of the diff is read from; the working tree when not set
+    pub reviewed_rev: Option<String>,
+    /// Diff with new-side line numbers
+    pub diff: String,
+    /// Changed files, relative to the repository root
+    pub files: Vec<String>,
+    /// Changed files left out of the prompt by `.aiignore`
+    pub excluded_files: Vec<String>,
+    pub truncated: bool,
+}
+
+/// A finding as the backend reported it, before it is tied to a line
+#[derive(Debug, Clone)]
+pub struct ProposedFinding {
+    pub file: String,
+    pub line: usize,
+    pub end_line: Option<usize>,
+    pub severity: ReviewSeverity,
+    pub message: String,
+    pub suggested_fix: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawFinding {
+    #[serde(default, alias = "path")]
+    file: String,
+    #[serde(default, alias = "start_line")]
+    line: serde_json::Value,
+    #[serde(default)]
+    end_line: serde_json::Value,
+    #[serde(default)]
+    severity: String,
+    #[serde(default, alias = "comment")]
+    message: String,
+    #[serde(default, alias = "suggestion")]
+    suggested_fix: Option<String>,
+}
+
+/// Collect the diff of the repository at `root`, without ignored files and within the size budget
+pub fn collect_changes(root: &Path, ignore: &AiIgnore, range: Option<&str>) -> AppResult<ReviewChanges> {
+    let toplevel = git::toplevel(root).map_err(AppError::ProcessError)?;
+    let head = run_git(root, &["rev-parse", "--verify", "--quiet", "HEAD"])
+        .ok()
+        .map(|hash| hash.trim().to_string())
+        .filter(|hash| !hash.is_empty());
+
+    let range = range.map(str::trim).filter(|range| !range.is_empty());
+    let mut excluded_files = Vec::new();
+    let mut skipped_untracked = false;
+    let (diff, commit, reviewed_rev) = match range {
+        None => {
+            // Staged and unstaged changes against HEAD; only the staged ones before the first commit
+            let args: &[&str] = if head.is_some() {
+                &["diff", "--no-color", "--no-ext-diff", "HEAD"]
+            } else {
+                &["diff", "--cached", "--no-color", "--no-ext-diff"]
+            };
+            let mut diff = run_git(root, args).map_err(AppError::ProcessError)?;
+            // Ignored untracked files are never read
+            let mut untracked = Vec::new();
+            for path in git::untracked_files(&toplevel).map_err(AppError::ProcessError)? {
+                let full_path = toplevel.join(&path);
+                if ignore.is_ignored(&full_path) {
+                    excluded_files.push(path);
+                } else if untracked.len() >= MAX_UNTRACKED_FILES
+                    || std::fs::metadata(&full_path).map_or(true, |m| m.len() > MAX_UNTRACKED_FILE_BYTES)
+                {
+                    skipped_untracked = true;
+                } else {
+                    untracked.push(path);
+                }
+            }
+            for path in &untracked {
+                diff.push_str(&git::untracked_diff(&toplevel, path).map_err(AppError::ProcessError)?);
+            }
+            (diff, head, None)
+        }
+        Some(range) => {
+            if range.starts_with('-') || range.contains(char::is_whitespace) {
+                return Err(AppError::ValidationError(format!("无效的提交范围: {}", range)));
+            }
+            let (diff, end) = match range.rsplit_once("..") {
+                Some((_, end)) => {
+                    let end = end.trim_start_matches('.');
+                    let end = if end.is_empty() { "HEAD" } else { end };
+                    let diff = run_git(root, &["diff", "--no-color", "--no-ext-diff", range]);
+                    (diff, end)
+                }
+                // A single commit is reviewed against its first parent
+                None => {
+                    let diff = run_git(
+                        root,
+                        &["show", "--format=", "--no-color", "--no-ext-diff", "--first-parent", range],
+                    );
+                    (diff, range)
+                }
+            };
+            let diff = diff.map_err(AppError::ProcessError)?;
+            let commit = run_git(root, &["rev-parse", "--verify", &format!("{}^{{commit}}", end)])
+                .map_err(AppError::ProcessError)?
+                .trim()
+                .to_string();
+            (diff, Some(commit.clone()), Some(commit))
+        }
+    };
+
+    let mut changes = ReviewChanges {
+        toplevel,
+        commit,
+        reviewed_rev,
+        excluded_files,
+        ..Default::default()
+    };
+    let mut allowed = Vec::new();
+    for file in git::split_diff(&diff) {
+        if ignore.is_ignored(&changes.toplevel.join(&file.path)) {
+            changes.excluded_files.push(file.path);
+        } else {
+            changes.files.push(file.path.clone());
+            allowed.push(FileDiff {
+                text: number_lines(&file.text),
+                path: file.path,
+            });
+        }
+    }
+    if allowed.is_empty() {
+        return Err(AppError::ValidationError(if changes.excluded_files.is_empty() {
+            "没有可审查的改动".to_string()
+        } else {
+            "改动的文件都已被 .aiignore 排除，不能发送给模型".to_string()
+        }));
+    }
+
+    (changes.diff, changes.truncated) = git::limit_diff(allowed, MAX_DIFF_CHARS, MAX_FILE_DIFF_CHARS);
+    changes.truncated |= skipped_untracked;
+    Ok(changes)
+}
+
+/// Prefix the lines of a file diff's hunks with their new-side line number
+fn number_lines(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + text.len() / 4);
+    let mut next_line: Option<usize> = None;
+    for line in text.lines() {
+        if let Some(header) = line.strip_prefix("@@ ") {
+            // `@@ -a,b +c,d @@`; the new side starts at line c
+            next_line = header
+                .split_whitespace()
+                .find_map(|part| part.strip_prefix('+'))
+                .and_then(|start| start.split(',').next())
+                .and_then(|start| start.parse().ok());
+            out.push_str(line);
+        } else if let Some(n) = next_line {
+            if line.starts_with('+') || line.starts_with(' ') {
+                out.push_str(&format!("{:>5} {}", n, line));
+                next_line = Some(n + 1);
+            } else {
+                out.push_str(&format!("      {}", line));
+            }
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Ask `backend` for findings on the changes, sending replies that are not valid
+/// JSON back once. Secrets are redacted from the prompt and put back into the findings.
+/// Every attempt is recorded under `audit`.
+pub async fn request_findings(
+    backend: &EditBackend,
+    request: &CodeReviewRequest,
+    changes: &ReviewChanges,
+    redactor: &mut SecretRedactor,
+    audit: Option<AuditContext>,
+) -> AppResult<Vec<ProposedFinding>> {
+    let mut conversation = vec![ProviderMessage::user(redactor.redact(&build_prompt(request, changes)))];
+    let mut last_problem = String::new();
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let reply = backend
+            .ask(SYSTEM_PROMPT, &conversation, MAX_OUTPUT_TOKENS, audit.clone())
+            .await?;
+        match parse_findings(&reply) {
+            Ok(raw) => {
+                debug!(attempt, findings = raw.len(), "Code review reply accepted");
+                return Ok(raw
+                    .into_iter()
+                    .filter_map(|finding| propose(finding, &changes.files, redactor))
+                    .take(MAX_FINDINGS)
+                    .collect());
+            }
+            Err(problem) => {
+                warn!(attempt, problem = %problem, "Rejected code review reply");
+                conversation.push(ProviderMessage::assistant(reply));
+                conversation.push(ProviderMessage::user(format!(
+                    "Your reply could not be used: {} Reply again with only the JSON object.",
+                    problem
+                )));
+                last_problem = problem;
+            }
+        }
+    }
+
+    Err(AppError::AiServiceError(format!(
+        "{} 次尝试后仍未得到有效的审查结果: {}",
+        MAX_ATTEMPTS, last_problem
+    )))
+}
+
+fn build_prompt(request: &CodeReviewRequest, changes: &ReviewChanges) -> String {
+    let mut prompt = String::new();
+    if let Some(focus) = request.focus.as_deref().map(str::trim).filter(|focus| !focus.is_empty()) {
+        prompt.push_str(&format!("<focus>\n{}\n</focus>\n\n", focus));
+    }
+    if !changes.excluded_files.is_empty() {
+        prompt.push_str(&format!(
+            "{} more changed file(s) are not shown; do not guess what changed in them.\n\n",
+            changes.excluded_files.len()
+        ));
+    }
+    if changes.truncated {
+        prompt.push_str("Parts of the diff were cut off to fit; only review what is shown.\n\n");
+    }
+    prompt.push_str(&format!(
+        "<files>\n{}\n</files>\n\n<diff>\n{}</diff>\n\nReview these changes.",
+        changes.files.join("\n"),
+        changes.diff
+    ));
+    prompt
+}
+
+/// The findings of a reply: a `{"findings": [...]}` object or a bare array,
+/// possibly wrapped in a code fence or prose
+fn parse_findings(reply: &str) -> Result<Vec<RawFinding>, String> {
+    let trimmed = reply.trim();
+    let json = match (trimmed.find(['{', '[']), trimmed.rfind(['}', ']'])) {
+        (Some(start), Some(end)) if start < end => &trimmed[start..=end],
+        _ => return Err("The reply contains no JSON.".to_string()),
+    };
+    let value: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("The reply is not valid JSON ({}).", e))?;
+    let findings = match value {
+        serde_json::Value::Array(_) => value,
+        serde_json::Value::Object(mut object) => object
+            .remove("findings")
+            .ok_or_else(|| "The JSON object has no `findings` array.".to_string())?,
+        _ => return Err("The reply is not a JSON object.".to_string()),
+    };
+    serde_json::from_value(findings).map_err(|e| format!("The findings do not match the schema ({}).", e))
+}
+
+/// Check the file and line of a finding; `None` drops it
+fn propose(raw: RawFinding, files: &[String], redactor: &SecretRedactor) -> Option<ProposedFinding> {
+    let message = redactor.restore(raw.message.trim());
+    let line = line_number(&raw.line).filter(|&line| line > 0)?;
+    if message.is_empty() {
+        return None;
+    }
+    let file = match_file(&raw.file, files)?;
+    Some(ProposedFinding {
+        file,
+        line,
+        end_line: line_number(&raw.end_line).filter(|&end| end > line),
+        severity: ReviewSeverity::parse(&raw.severity),
+        message,
+        suggested_fix: raw
+            .suggested_fix
+            .map(|fix| redactor.restore(fix.trim()))
+            .filter(|fix| !fix.is_empty()),
+    })
+}
+
+/// A line number written as a number or a string
+fn line_number(value: &serde_json::Value) -> Option<usize> {
+    match value {
+        serde_json::Value::Number(n) => n.as_u64().map(|n| n as usize),
+        serde_json::Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// The reviewed file a model meant: the same path without `a/`, `b/` or `./`,
+/// or the only reviewed file ending with it
+fn match_file(file: &str, files: &[String]) -> Option<String> {
+    let file = file.trim().replace('\\', "/");
+    let file = ["a/", "b/", "./"]
+        .iter()
+        .find_map(|prefix| file.strip_prefix(prefix))
+        .unwrap_or(&file)
+        .to_string();
+    if file.is_empty() {
+        return None;
+    }
+    if files.contains(&file) {
+        return Some(file);
+    }
+    let suffix = format!("/{}", file);
+    let mut candidates = files.iter().filter(|path| path.ends_with(&suffix));
+    match (candidates.next(), candidates.next()) {
+        (Some(path), None) => Some(path.clone()),
+        _ => None,
+    }
+}
+
+/// Tie findings to the text of their line in the reviewed version, then move
+/// them to where that text is now; returns the findings and how many were dropped
+pub fn locate_findings(changes: &ReviewChanges, proposed: Vec<ProposedFinding>) -> (Vec<ReviewFinding>, usize) {
+    let mut reviewed: HashMap<String, Option<Vec<String>>> = HashMap::new();
+    let mut seen = HashSet::new();
+    let mut findings = Vec::new();
+    let mut dropped = 0;
+
+    for finding in proposed {
+        let lines = reviewed
+            .entry(finding.file.clone())
+            .or_insert_with(|| reviewed_lines(changes, &finding.file));
+        let Some(anchor_text) = lines.as_ref().and_then(|lines| lines.get(finding.line - 1)) else {
+            // Deleted files and lines past the end cannot be shown in the editor
+            dropped += 1;
+            continue;
+        };
+        if !seen.insert((finding.file.clone(), finding.line, finding.message.clone())) {
+            dropped += 1;
+            continue;
+        }
+        let line_count = lines.as_ref().map_or(0, Vec::len);
+        findings.push(ReviewFinding {
+            path: changes.toplevel.join(&finding.file).to_string_lossy().into_owned(),
+            anchor_text: anchor_text.clone(),
+            file: finding.file,
+            line: finding.line,
+            end_line: finding.end_line.map(|end| end.min(line_count)).filter(|&end| end > finding.line),
+            reviewed_line: finding.line,
+            severity: finding.severity,
+            message: finding.message,
+            suggested_fix: finding.suggested_fix,
+            outdated: false,
+        });
+    }
+
+    reanchor(&mut findings);
+    (findings, dropped)
+}
+
+/// Lines of a file in the reviewed version; `None` when it does not exist there
+fn reviewed_lines(changes: &ReviewChanges, file: &str) -> Option<Vec<String>> {
+    let content = match changes.reviewed_rev.as_deref() {
+        Some(rev) => run_git(&changes.toplevel, &["show", &format!("{}:{}", rev, file)]).ok()?,
+        None => std::fs::read_to_string(changes.toplevel.join(file)).ok()?,
+    };
+    Some(content.lines().map(str::to_string).collect())
+}
+
+/// Move findings to the current line of their anchor text: the reviewed line if
+/// it still holds it, the nearest line that does otherwise. Findings whose text
+/// is gone (or whose file is) are marked outdated and keep their last line.
+pub fn reanchor(findings: &mut [ReviewFinding]) {
+    let mut current: HashMap<String, Option<Vec<String>>> = HashMap::new();
+    for finding in findings.iter_mut() {
+        let lines = current.entry(finding.path.clone()).or_insert_with(|| {
+            std::fs::read_to_string(&finding.path)
+                .ok()
+                .map(|content| content.lines().map(str::to_string).collect())
+        });
+        let found = lines
+            .as_ref()
+            .and_then(|lines| find_anchor(lines, &finding.anchor_text, finding.reviewed_line));
+        match found {
+            Some(line) => {
+                let span = finding.end_line.map(|end| end - finding.line);
+                finding.line = line;
+                finding.end_line = span.map(|span| line + span);
+                finding.outdated = false;
+            }
+            None => finding.outdated = true,
+        }
+    }
+}
+
+/// Line (1-based) holding `anchor`, searching outwards from `origin`
+fn find_anchor(lines: &[String], anchor: &str, origin: usize) -> Option<usize> {
+    let anchor = anchor.trim();
+    let matches = |idx: usize| lines.get(idx).is_some_and(|line| line.trim() == anchor);
+    let origin = origin.saturating_sub(1);
+    if matches(origin) {
+        return Some(origin + 1);
+    }
+    // Blank lines and lone braces match too much to be moved reliably
+    if anchor.len() < 3 {
+        return None;
+    }
+    (1..lines.len()).find_map(|distance| {
+        [origin.checked_sub(distance), origin.checked_add(distance)]
+            .into_iter()
+            .flatten()
+            .find(|&idx| matches(idx))
+            .map(|idx| idx + 1)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RedactionSettings;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn numbers_new_side_lines_of_hunks() {
+        let diff = "diff --git a/f.rs b/f.rs\n--- a/f.rs\n+++ b/f.rs\n@@ -1,3 +10,4 @@ fn x\n ctx\n-old\n+new\n+added\n end\n\\ No newline at end of file\n";
+        let expected = "diff --git a/f.rs b/f.rs\n--- a/f.rs\n+++ b/f.rs\n@@ -1,3 +10,4 @@ fn x\n   10  ctx\n      -old\n   11 +new\n   12 +added\n   13  end\n      \\ No newline at end of file\n";
+        assert_eq!(number_lines(diff), expected);
+
+        let new_file = "@@ -0,0 +1 @@\n+only\n@@ -7 +8,2 @@\n+a\n b\n";
+        assert_eq!(
+            number_lines(new_file),
+            "@@ -0,0 +1 @@\n    1 +only\n@@ -7 +8,2 @@\n    8 +a\n    9  b\n"
+        );
+    }
+
+    #[test]
+    fn finds_anchors_nearest_to_the_reviewed_line() {
+        let file = lines(&["a", "fn main() {", "}", "let x = 1;", "  let x = 1;"]);
+        assert_eq!(find_anchor(&file, "fn main() {", 2), Some(2));
+        assert_eq!(find_anchor(&file, "let x = 1;", 1), Some(4));
+        assert_eq!(find_anchor(&file, "let x = 1;", 5), Some(5));
+        assert_eq!(find_anchor(&file, "let x = 1;", 9), Some(5));
+        // Short anchors only match in place.
+        assert_eq!(find_anchor(&file, "}", 3), Some(3));
+        assert_eq!(find_anchor(&file, "}", 1), None);
+        assert_eq!(find_anchor(&file, "removed();", 2), None);
+    }
+
+    fn finding(path: &Path, line: usize, end_line: Option<usize>, anchor: &str) -> ReviewFinding {
+        ReviewFinding {
+            file: "lib.rs".to_string(),
+            path: path.to_string_lossy().into_owned(),
+            line,
+            end_line,
+            reviewed_line: line,
+            severity: ReviewSeverity::Warning,
+            message: "check this".to_string(),
+            suggested_fix: None,
+            anchor_text: anchor.to_string(),
+            outdated: false,
+        }
+    }
+
+    #[test]
+    fn reanchors_findings_after_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        std::fs::write(&path, "// new header\n// more\nfn a() {}\nlet b = 2;\nlet c = 3;\n").unwrap();
+
+        let mut findings = vec![
+            finding(&path, 2, Some(3), "let b = 2;"),
+            finding(&path, 3, None, "let gone = 0;"),
+            finding(&dir.path().join("missing.rs"), 1, None, "fn a() {}"),
+        ];
+        reanchor(&mut findings);
+        assert_eq!(
+            (findings[0].line, findings[0].end_line, findings[0].outdated),
+            (4, Some(5), false)
+        );
+        assert_eq!((findings[1].line, findings[1].outdated), (3, true));
+        assert!(findings[2].outdated);
+        assert_eq!(findings[0].reviewed_line, 2);
+    }
+
+    #[test]
+    fn parses_findings_in_several_shapes() {
+        let fenced = "Here you go:\n```json\n{\"findings\":[{\"file\":\"src/a.rs\",\"line\":3,\"severity\":\"high\",\"message\":\"bug\"}]}\n```";
+        let findings = parse_findings(fenced).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].file, "src/a.rs");
+
+        let array =
+            "[{\"path\":\"b/src/a.rs\",\"start_line\":\"7\",\"end_line\":9,\"comment\":\"x\",\"suggestion\":\"y\"}]";
+        let findings = parse_findings(array).unwrap();
+        assert_eq!(findings[0].file, "b/src/a.rs");
+        assert_eq!(line_number(&findings[0].line), Some(7));
+        assert_eq!(findings[0].message, "x");
+        assert_eq!(findings[0].suggested_fix.as_deref(), Some("y"));
+
+        assert!(parse_findings("{\"findings\":[]}").unwrap().is_empty());
+        assert!(parse_findings("no findings").is_err());
+        assert!(parse_findings("{\"findings\": [}").is_err());
+        assert!(parse_findings("{\"issues\":[]}").is_err());
+    }
+
+    #[test]
+    fn proposes_only_findings_on_reviewed_files() {
+        let files = lines(&["src/a.rs", "src/util/b.rs", "tests/b.rs"]);
+        let redactor = SecretRedactor::new(&RedactionSettings::default(), &[]);
+        let raw = |file: &str, line: serde_json::Value, end_line: serde_json::Value| RawFinding {
+            file: file.to_string(),
+            line,
+            end_line,
+            severity: "nit".to_string(),
+            message: " msg ".to_string(),
+            suggested_fix: Some("  ".to_string()),
+        };
+
+        let proposed = propose(raw("b/src/a.rs", 4.into(), 6.into()), &files, &redactor).unwrap();
+        assert_eq!(
+            (proposed.file.as_str(), proposed.line, proposed.end_line),
+            ("src/a.rs", 4, Some(6))
+        );
+        assert_eq!(proposed.severity, ReviewSeverity::Info);
+        assert_eq!(proposed.message, "msg");
+        assert_eq!(proposed.suggested_fix, None);
+
+        let proposed = propose(raw("util/b.rs", "2".into(), 1.into()), &files, &redactor).unwrap();
+        assert_eq!((proposed.file.as_str(), proposed.end_line), ("src/util/b.rs", None));
+        // `b.rs` ends two reviewed paths, so it is ambiguous.
+        assert!(propose(raw("b.rs", 1.into(), serde_json::Value::Null), &files, &redactor).is_none());
+        assert!(propose(raw("src/a.rs", 0.into(), serde_json::Value::Null), &files, &redactor).is_none());
+        assert!(propose(raw("other.rs", 1.into(), serde_json::Value::Null), &files, &redactor).is_none());
+    }
+
+    #[test]
+    fn reviews_untracked_files_as_added() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        run_git(root, &["init", "--quiet"]).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.log\n.aiignore\n").unwrap();
+        std::fs::write(root.join(".aiignore"), "*.pem\n").unwrap();
+        std::fs::write(root.join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(root.join("key.pem"), "secret\n").unwrap();
+        std::fs::write(root.join("debug.log"), "noise\n").unwrap();
+
+        let ignore = AiIgnore::load(None, root.to_str());
+        let changes = collect_changes(root, &ignore, None).unwrap();
+        let mut files = changes.files.clone();
+        files.sort();
+        assert_eq!(files, vec![".gitignore", "main.rs"]);
+        assert_eq!(changes.excluded_files, vec!["key.pem"]);
+        assert!(changes.diff.contains("    1 +fn main() {}"));
+        assert!(!changes.diff.contains("secret"));
+    }
+}
@@ -0,0 +1,215 @@
+//! Commit message module
+//!
+//! Drafts a commit message or pull request description from the staged diff of
+//! a workspace repository. The subjects of recent commits go along with the diff
+//! so the draft follows the repository's conventions. Files matched by
+//! `.aiignore` are left out of the diff (only their count is mentioned), and
+//! large diffs are cut down per file so every staged file is at least named.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::services::ai_ignore::AiIgnore;
+use crate::services::audit::AuditContext;
+use crate::services::git::{self, run_git};
+use crate::services::native_provider::ProviderMessage;
+use crate::services::redaction::SecretRedactor;
+use crate::services::selection_edit::EditBackend;
+use crate::utils::error::{AppError, AppResult};
+
+/// Diff sent with one request, in characters
+const MAX_DIFF_CHARS: usize = 24_000;
+/// Diff kept per file before the remaining files are considered
+const MAX_FILE_DIFF_CHARS: usize = 6_000;
+/// Recent commit subjects shown as style examples
+const STYLE_COMMIT_COUNT: usize = 15;
+/// Completion budget of a draft
+const MAX_OUTPUT_TOKENS: u32 = 2048;
+
+const COMMIT_SYSTEM_PROMPT: &str = "You write git commit messages. Follow the style of the repository's recent \
+commits; when they do not show a clear style, use Conventional Commits (`type(scope): summary`). Write a subject \
+line of at most 72 characters in the imperative mood, then a blank line and a short body that explains what \
+changed and why, wrapped at 72 characters. Leave the body out for trivial changes. Reply with the commit message \
+only, without code fences or commentary.";
+
+const PR_SYSTEM_PROMPT: &str = "You write pull request descriptions. Reply with a title line of at most 72 \
+characters, a blank line, then a Markdown description: one or two sentences on what the change does and why, the \
+notable changes as a short list, and how to test it. Describe only what the diff shows. Reply with the title and \
+description only, without code fences or commentary.";
+
+/// What to draft
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DraftKind {
+    #[default]
+    Commit,
+    PullRequest,
+}
+
+/// Workspace and backend of a draft
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitMessageRequest {
+    pub workspace_dir: String,
+    #[serde(default)]
+    pub kind: DraftKind,
+    /// Native provider model; takes precedence over `code_cli`
+    #[serde(default)]
+    pub native_model: Option<String>,
+    #[serde(default)]
+    pub code_cli: Option<String>,
+    #[serde(default)]
+    pub codex_model: Option<String>,
+    /// Extra guidance, e.g. "mention the issue number"
+    #[serde(default)]
+    pub hint: Option<String>,
+}
+
+/// Drafted message, to be edited before committing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitMessageDraft {
+    pub kind: DraftKind,
+    pub message: String,
+    /// Staged files, relative to the repository root
+    pub files: Vec<String>,
+    /// Staged files left out of the prompt by `.aiignore`
+    pub excluded_files: Vec<String>,
+    /// Whether part of the diff was cut to fit the size budget
+    pub truncated: bool,
+}
+
+/// The staged changes as sent to the backend
+#[derive(Debug, Clone, Default)]
+pub struct StagedChanges {
+    /// `git diff --cached --stat` of the files that may be sent
+    pub stat: String,
+    pub diff: String,
+    pub files: Vec<String>,
+    pub excluded_files: Vec<String>,
+    pub truncated: bool,
+}
+
+/// Collect the staged diff of the repository at `root`, without ignored files and within the size budget
+pub fn staged_changes(root: &Path, ignore: &AiIgnore) -> AppResult<StagedChanges> {
+    let diff = run_git(root, &["diff", "--cached", "--no-color", "--no-ext-diff"]).map_err(AppError::ProcessError)?;
+    let toplevel = git::toplevel(root).map_err(AppError::ProcessError)?;
+
+    let mut changes = StagedChanges::default();
+    let mut allowed = Vec::new();
+    for file in git::split_diff(&diff) {
+        changes.files.push(file.path.clone());
+        if ignore.is_ignored(&toplevel.join(&file.path)) {
+            changes.excluded_files.push(file.path);
+        } else {
+            allowed.push(file);
+        }
+    }
+    if changes.files.is_empty() {
+        return Err(AppError::ValidationError("暂存区没有改动".to_string()));
+    }
+    if allowed.is_empty() {
+        return Err(AppError::ValidationError(
+            "暂存的文件都已被 .aiignore 排除，不能发送给模型".to_string(),
+        ));
+    }
+
+    // Pathspecs are relative to `root`; run from the repository root so the diff paths apply.
+    // `:(literal)` keeps names with `*`, `?` or `[` from matching other files.
+    let pathspecs: Vec<String> = allowed.iter().map(|file| format!(":(literal){}", file.path)).collect();
+    let mut stat_args = vec!["diff", "--cached", "--stat=120", "--no-color", "--"];
+    stat_args.extend(pathspecs.iter().map(String::as_str));
+    changes.stat = run_git(&toplevel, &stat_args).map_err(AppError::ProcessError)?;
+
+    (changes.diff, changes.truncated) = git::limit_diff(allowed, MAX_DIFF_CHARS, MAX_FILE_DIFF_CHARS);
+    Ok(changes)
+}
+
+/// Subjects of the most recent commits, newest first; empty in a repository without commits
+pub fn recent_subjects(root: &Path) -> Vec<String> {
+    run_git(root, &["log", &format!("-n{}", STYLE_COMMIT_COUNT), "--no-merges", "--format=%s"])
+        .map(|out| out.lines().map(str::to_string).filter(|line| !line.trim().is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// Ask `backend` for a draft of the staged changes; secrets are redacted from
+/// the prompt and put back into the draft. The call is recorded under `audit`.
+pub async fn draft_message(
+    backend: &EditBackend,
+    request: &CommitMessageRequest,
+    changes: StagedChanges,
+    subjects: &[String],
+    redactor: &mut SecretRedactor,
+    audit: Option<AuditContext>,
+) -> AppResult<CommitMessageDraft> {
+    let system_prompt = match request.kind {
+        DraftKind::Commit => COMMIT_SYSTEM_PROMPT,
+        DraftKind::PullRequest => PR_SYSTEM_PROMPT,
+    };
+    let prompt = redactor.redact(&build_prompt(request, &changes, subjects));
+    let reply = backend
+        .ask(system_prompt, &[ProviderMessage::user(prompt)], MAX_OUTPUT_TOKENS, audit)
+        .await?;
+    let message = clean_reply(&redactor.restore(&reply));
+    if message.is_empty() {
+        return Err(AppError::AiServiceError("模型没有返回提交信息".to_string()));
+    }
+    debug!(kind = ?request.kind, files = changes.files.len(), truncated = changes.truncated, "Drafted commit message");
+    Ok(CommitMessageDraft {
+        kind: request.kind,
+        message,
+        files: changes.files,
+        excluded_files: changes.excluded_files,
+        truncated: changes.truncated,
+    })
+}
+
+/// Commit the staged changes with `message`; returns the short hash of the new commit
+pub fn commit_staged(root: &Path, message: &str) -> AppResult<String> {
+    if message.trim().is_empty() {
+        return Err(AppError::ValidationError("提交信息不能为空".to_string()));
+    }
+    run_git(root, &["commit", "-m", message]).map_err(AppError::ProcessError)?;
+    run_git(root, &["rev-parse", "--short", "HEAD"])
+        .map(|hash| hash.trim().to_string())
+        .map_err(AppError::ProcessError)
+}
+
+fn build_prompt(request: &CommitMessageRequest, changes: &StagedChanges, subjects: &[String]) -> String {
+    let mut prompt = String::new();
+    if !subjects.is_empty() {
+        prompt.push_str(&format!("<recent_commits>\n{}\n</recent_commits>\n\n", subjects.join("\n")));
+    }
+    if let Some(hint) = request.hint.as_deref().map(str::trim).filter(|hint| !hint.is_empty()) {
+        prompt.push_str(&format!("<guidance>\n{}\n</guidance>\n\n", hint));
+    }
+    if !changes.excluded_files.is_empty() {
+        prompt.push_str(&format!(
+            "{} more staged file(s) are not shown; do not guess what changed in them.\n\n",
+            changes.excluded_files.len()
+        ));
+    }
+    if changes.truncated {
+        prompt.push_str("Parts of the diff were cut off to fit; the stat lists every file.\n\n");
+    }
+    prompt.push_str(&format!(
+        "<stat>\n{}</stat>\n\n<staged_diff>\n{}</staged_diff>\n\n",
+        changes.stat, changes.diff
+    ));
+    prompt.push_str(match request.kind {
+        DraftKind::Commit => "Write the commit message for these staged changes.",
+        DraftKind::PullRequest => "Write the pull request title and description for these changes.",
+    });
+    prompt
+}
+
+/// Drop a wrapping code fence and surrounding blank lines
+fn clean_reply(reply: &str) -> String {
+    let trimmed = reply.trim();
+    let unfenced = trimmed
+        .strip_prefix("```")
+        .and_then(|rest| rest.strip_suffix("```"))
+        .and_then(|inner| inner.split_once('\n').map(|(_, body)| body))
+        .unwrap_or(trimmed);
+    unfenced.trim().to_string()
+}
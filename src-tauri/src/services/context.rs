@@ -0,0 +1,307 @@
+//! Context builder module
+//!
+//! Assembles the task text sent to AI backends from the user message,
+//! attached files and automatically retrieved snippets, and records
+//! what was included in a manifest the UI can display. Files matched by
+//! `.aiignore` are never read; they are listed as blocked instead.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+use crate::services::ai_ignore::AiIgnore;
+
+/// Image formats accepted as chat attachments, with their media types
+const IMAGE_MEDIA_TYPES: &[(&str, &str)] = &[
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+];
+
+/// Media type of an image attachment, or None if `path` is not a supported image
+pub fn image_media_type(path: &str) -> Option<&'static str> {
+    let ext = Path::new(path).extension()?.to_str()?.to_lowercase();
+    IMAGE_MEDIA_TYPES
+        .iter()
+        .find(|(e, _)| *e == ext)
+        .map(|(_, media_type)| *media_type)
+}
+
+/// Split attached paths into (text files, image files)
+pub fn split_attachments(files: &[String]) -> (Vec<String>, Vec<String>) {
+    files
+        .iter()
+        .cloned()
+        .partition(|f| image_media_type(f).is_none())
+}
+
+/// Lines appended to a task that list image attachments by path, for CLIs
+/// that open them from the prompt; empty without images
+pub fn attached_images_note(images: &[String]) -> String {
+    let mut note = String::new();
+    if !images.is_empty() {
+        note.push_str("\n\nAttached images:\n");
+        for image in images {
+            note.push_str(&format!("@{}\n", image));
+        }
+    }
+    note
+}
+
+/// A snippet of a workspace file selected as context
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextSnippet {
+    /// File path (absolute)
+    pub path: String,
+    /// First line of the snippet (1-based, inclusive)
+    pub start_line: usize,
+    /// Last line of the snippet (1-based, inclusive)
+    pub end_line: usize,
+    /// Snippet text
+    pub content: String,
+    /// Retrieval score
+    pub score: f64,
+}
+
+/// Reference to a snippet in the manifest (content omitted)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextSnippetRef {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub score: f64,
+}
+
+impl From<&ContextSnippet> for ContextSnippetRef {
+    fn from(snippet: &ContextSnippet) -> Self {
+        Self {
+            path: snippet.path.clone(),
+            start_line: snippet.start_line,
+            end_line: snippet.end_line,
+            score: snippet.score,
+        }
+    }
+}
+
+/// Context resolved from an `@` reference in the message (see `services::mentions`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextBlock {
+    /// The reference as written, e.g. `@git:diff`
+    pub mention: String,
+    /// Heading of the block in the task (a path for files)
+    pub label: String,
+    pub content: String,
+}
+
+/// Context left out of a request because `.aiignore` matches it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockedContext {
+    pub path: String,
+    /// How it would have been included: `attachment`, `mention` or `auto_context`
+    pub via: String,
+}
+
+impl BlockedContext {
+    pub fn new(path: impl Into<String>, via: &str) -> Self {
+        Self {
+            path: path.into(),
+            via: via.to_string(),
+        }
+    }
+}
+
+/// Summary of everything that went into a request's context
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContextManifest {
+    /// Files attached in full
+    pub files: Vec<String>,
+    /// Image attachments
+    #[serde(default)]
+    pub images: Vec<String>,
+    /// Snippets selected by auto context
+    pub snippets: Vec<ContextSnippetRef>,
+    /// `@` references resolved from the message
+    #[serde(default)]
+    pub mentions: Vec<String>,
+    /// Context withheld by `.aiignore`
+    #[serde(default)]
+    pub blocked: Vec<BlockedContext>,
+}
+
+impl ContextManifest {
+    /// Describe the context of a request without reading any file contents
+    pub fn describe(
+        files: &[String],
+        blocks: &[ContextBlock],
+        snippets: &[ContextSnippet],
+        ignore: &AiIgnore,
+    ) -> Self {
+        let mut blocked = Vec::new();
+        let (files, images) = split_attachments(files);
+        let mut keep = |path: &String, via: &str| {
+            let ignored = ignore.is_ignored(Path::new(path));
+            if ignored {
+                blocked.push(BlockedContext::new(path.as_str(), via));
+            }
+            !ignored
+        };
+        let files = files.into_iter().filter(|file| keep(file, "attachment")).collect();
+        let images = images.into_iter().filter(|image| keep(image, "attachment")).collect();
+        let snippets = snippets
+            .iter()
+            .filter(|snippet| keep(&snippet.path, "auto_context"))
+            .map(ContextSnippetRef::from)
+            .collect();
+        Self {
+            files,
+            images,
+            snippets,
+            mentions: blocks.iter().map(|block| block.mention.clone()).collect(),
+            blocked,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+            && self.images.is_empty()
+            && self.snippets.is_empty()
+            && self.mentions.is_empty()
+            && self.blocked.is_empty()
+    }
+}
+
+/// Builds the task text for a single AI request
+#[derive(Debug, Default)]
+pub struct ContextBuilder {
+    segments: Vec<String>,
+    manifest: ContextManifest,
+    ignore: AiIgnore,
+}
+
+impl ContextBuilder {
+    /// Create an empty builder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty builder that skips files matched by `ignore`
+    pub fn with_ignore(ignore: AiIgnore) -> Self {
+        Self {
+            ignore,
+            ..Self::default()
+        }
+    }
+
+    /// Record `path` as blocked when `.aiignore` matches it
+    fn blocked(&mut self, path: &str, via: &str) -> bool {
+        let ignored = self.ignore.is_ignored(Path::new(path));
+        if ignored {
+            self.manifest.blocked.push(BlockedContext::new(path, via));
+        }
+        ignored
+    }
+
+    /// Attach a whole file; unreadable files are skipped with a warning.
+    ///
+    /// Images are not inlined: they are listed by path at the end of the task so
+    /// CLI backends can open them (see [`ContextBuilder::add_image`]).
+    pub fn add_file(&mut self, file_path: &str) -> &mut Self {
+        if self.blocked(file_path, "attachment") {
+            return self;
+        }
+        if image_media_type(file_path).is_some() {
+            return self.add_image(file_path);
+        }
+        match fs::read_to_string(file_path) {
+            Ok(content) => {
+                self.segments.push(format!("@{}\n{}", file_path, content));
+                self.manifest.files.push(file_path.to_string());
+            }
+            Err(err) => {
+                warn!(
+                    path = %file_path,
+                    error = %err,
+                    "Failed to read context file"
+                );
+            }
+        }
+        self
+    }
+
+    /// Attach an image by path reference
+    pub fn add_image(&mut self, image_path: &str) -> &mut Self {
+        if self.blocked(image_path, "attachment") {
+            return self;
+        }
+        if Path::new(image_path).is_file() {
+            self.manifest.images.push(image_path.to_string());
+        } else {
+            warn!(path = %image_path, "Image attachment not found");
+        }
+        self
+    }
+
+    /// Attach several files
+    pub fn add_files(&mut self, files: &[String]) -> &mut Self {
+        for file_path in files {
+            self.add_file(file_path);
+        }
+        self
+    }
+
+    /// Attach a block resolved from an `@` reference
+    pub fn add_block(&mut self, block: &ContextBlock) -> &mut Self {
+        self.segments.push(format!("@{}\n{}", block.label, block.content));
+        self.manifest.mentions.push(block.mention.clone());
+        self
+    }
+
+    /// Attach several resolved blocks
+    pub fn add_blocks(&mut self, blocks: &[ContextBlock]) -> &mut Self {
+        for block in blocks {
+            self.add_block(block);
+        }
+        self
+    }
+
+    /// Attach a retrieved snippet
+    pub fn add_snippet(&mut self, snippet: &ContextSnippet) -> &mut Self {
+        if self.blocked(&snippet.path, "auto_context") {
+            return self;
+        }
+        self.segments.push(format!(
+            "@{}#L{}-L{}\n{}",
+            snippet.path, snippet.start_line, snippet.end_line, snippet.content
+        ));
+        self.manifest.snippets.push(ContextSnippetRef::from(snippet));
+        self
+    }
+
+    /// Attach several snippets
+    pub fn add_snippets(&mut self, snippets: &[ContextSnippet]) -> &mut Self {
+        for snippet in snippets {
+            self.add_snippet(snippet);
+        }
+        self
+    }
+
+    /// Manifest of what has been added so far
+    pub fn manifest(&self) -> &ContextManifest {
+        &self.manifest
+    }
+
+    /// Produce the final task text and manifest
+    pub fn build(self, message: &str) -> (String, ContextManifest) {
+        let mut task = String::new();
+        if !self.segments.is_empty() {
+            task.push_str(&self.segments.join("\n"));
+            task.push_str("\n\n");
+        }
+        task.push_str(message);
+        task.push_str(&attached_images_note(&self.manifest.images));
+        (task, self.manifest)
+    }
+}
@@ -0,0 +1,628 @@
+//! Direct CLI module
+//!
+//! Builds argument lists for running `claude`, `codex` and `gemini` directly
+//! (without codeagent-wrapper) and parses session ids out of their output.
+
+use serde_json::Value;
+use std::process::Stdio;
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::{ChildStdin, Command};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{sleep, Duration};
+use tracing::{debug, warn};
+
+use crate::services::audit::{AuditContext, InvocationRecorder};
+use crate::services::permission_prompt::{self, PermissionPrompt, PermissionRequest};
+use crate::utils::error::{AppError, AppResult};
+
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// Quiet time after which an unfinished output line is checked for a permission question
+const PROMPT_IDLE: Duration = Duration::from_millis(400);
+
+/// Add the flags each backend needs for non-interactive, plain-text output
+fn build_direct_cli_args(backend: Option<&str>, user_args: &[String]) -> Vec<String> {
+    let mut args = user_args.to_vec();
+    match backend.map(|b| b.to_lowercase()) {
+        Some(ref backend) if backend == "claude" => {
+            if !has_cli_arg(&args, "-p") && !has_cli_arg(&args, "--print") {
+                args.push("--print".to_string());
+            }
+            if !has_cli_arg(&args, "--output-format") {
+                args.push("--output-format".to_string());
+                args.push("text".to_string());
+            }
+        }
+        Some(ref backend) if backend == "codex" => {
+            if !has_codex_subcommand(&args) {
+                args.insert(0, "exec".to_string());
+            }
+        }
+        Some(ref backend) if backend == "gemini" => {
+            if !has_cli_arg(&args, "--output-format") && !has_cli_arg(&args, "-o") {
+                args.push("--output-format".to_string());
+                args.push("text".to_string());
+            }
+        }
+        _ => {}
+    }
+    args
+}
+
+/// Arguments and expected resume id for a direct CLI run
+pub(crate) struct DirectCliPlan {
+    pub args: Vec<String>,
+    /// Session id the run is expected to continue or create (`last`/`latest` if unknown)
+    pub task_id: Option<String>,
+    /// Images were passed as `--image`, so the prompt must not list them again
+    pub images_as_args: bool,
+}
+
+/// Build the argument list for a direct CLI run, adding resume flags when allowed
+pub(crate) fn build_direct_cli_plan(
+    backend: Option<&str>,
+    user_args: &[String],
+    resume_session_id: Option<&str>,
+    code_cli_changed: Option<bool>,
+    images: &[String],
+) -> DirectCliPlan {
+    let mut args = build_direct_cli_args(backend, user_args);
+    let allow_resume = resume_session_id.filter(|_| !code_cli_changed.unwrap_or(false));
+    let mut task_id: Option<String> = None;
+    let mut images_as_args = false;
+
+    match backend.map(|b| b.to_lowercase()) {
+        Some(ref backend) if backend == "claude" => {
+            let existing_resume = get_flag_value(&args, "--resume")
+                .or_else(|| get_flag_value(&args, "-r"));
+            let existing_session_id = get_flag_value(&args, "--session-id");
+            let has_continue = has_cli_arg(&args, "--continue") || has_cli_arg(&args, "-c");
+            if let Some(id) = existing_session_id {
+                task_id = Some(id);
+            } else if let Some(id) = existing_resume {
+                task_id = Some(id);
+            } else if has_continue {
+                task_id = Some("latest".to_string());
+            } else if let Some(id) = allow_resume {
+                args.push("--resume".to_string());
+                args.push(id.to_string());
+                task_id = Some(id.to_string());
+            } else {
+                let id = uuid::Uuid::new_v4().to_string();
+                args.push("--session-id".to_string());
+                args.push(id.clone());
+                task_id = Some(id);
+            }
+        }
+        Some(ref backend) if backend == "codex" => {
+            let has_resume = has_codex_resume_subcommand(&args);
+            if let Some(id) = allow_resume {
+                if !has_codex_subcommand(&args) {
+                    args.insert(0, "resume".to_string());
+                } else if has_codex_exec_subcommand(&args) {
+                    replace_codex_subcommand(&mut args, "resume");
+                }
+                let using_resume = has_codex_resume_subcommand(&args);
+                if using_resume {
+                    if id.eq_ignore_ascii_case("last") {
+                        if !has_cli_arg(&args, "--last") {
+                            args.push("--last".to_string());
+                        }
+                    } else {
+                        args.push(id.to_string());
+                    }
+                }
+                task_id = Some(id.to_string());
+            } else if has_resume {
+                task_id = Some("last".to_string());
+            } else {
+                task_id = Some("last".to_string());
+            }
+            // codex accepts image files via `--image`; other CLIs read the paths listed in the prompt.
+            if has_codex_exec_subcommand(&args) {
+                for image in images {
+                    args.push("--image".to_string());
+                    args.push(image.clone());
+                }
+                images_as_args = !images.is_empty();
+            }
+        }
+        Some(ref backend) if backend == "gemini" => {
+            let existing_resume = get_flag_value(&args, "--resume")
+                .or_else(|| get_flag_value(&args, "-r"));
+            if let Some(id) = existing_resume {
+                task_id = Some(id);
+            } else if let Some(id) = allow_resume {
+                args.push("--resume".to_string());
+                args.push(id.to_string());
+                task_id = Some(id.to_string());
+            } else {
+                task_id = Some("latest".to_string());
+            }
+        }
+        _ => {}
+    }
+
+    DirectCliPlan {
+        args,
+        task_id,
+        images_as_args,
+    }
+}
+
+fn has_cli_arg(args: &[String], name: &str) -> bool {
+    args.iter().any(|arg| arg == name || arg.starts_with(&format!("{}=", name)))
+}
+
+fn has_codex_subcommand(args: &[String]) -> bool {
+    let cmd = args.iter().find(|arg| !arg.starts_with('-'));
+    if let Some(cmd) = cmd {
+        matches!(
+            cmd.as_str(),
+            "exec"
+                | "review"
+                | "login"
+                | "logout"
+                | "mcp"
+                | "mcp-server"
+                | "app-server"
+                | "completion"
+                | "sandbox"
+                | "apply"
+                | "resume"
+                | "cloud"
+                | "features"
+                | "help"
+        )
+    } else {
+        false
+    }
+}
+
+fn has_codex_exec_subcommand(args: &[String]) -> bool {
+    let cmd = args.iter().find(|arg| !arg.starts_with('-'));
+    matches!(cmd.map(|s| s.as_str()), Some("exec"))
+}
+
+fn has_codex_resume_subcommand(args: &[String]) -> bool {
+    let cmd = args.iter().find(|arg| !arg.starts_with('-'));
+    matches!(cmd.map(|s| s.as_str()), Some("resume"))
+}
+
+fn replace_codex_subcommand(args: &mut Vec<String>, replacement: &str) {
+    if let Some((idx, _)) = args.iter().enumerate().find(|(_, arg)| !arg.starts_with('-')) {
+        args[idx] = replacement.to_string();
+    } else {
+        args.insert(0, replacement.to_string());
+    }
+}
+
+fn get_flag_value(args: &[String], name: &str) -> Option<String> {
+    let flag = name.to_string();
+    for (idx, arg) in args.iter().enumerate() {
+        if arg == &flag {
+            return args.get(idx + 1).cloned();
+        }
+        if let Some(rest) = arg.strip_prefix(&(flag.clone() + "=")) {
+            return Some(rest.to_string());
+        }
+    }
+    None
+}
+
+pub(crate) fn should_replace_task_id(current: Option<&str>, incoming: &str) -> bool {
+    if incoming.trim().is_empty() {
+        return false;
+    }
+    match current {
+        None => true,
+        Some(current) => matches!(current, "latest" | "last"),
+    }
+}
+
+/// Extract a CLI session id from a line of output (JSON event or `session id:` text)
+pub(crate) fn parse_cli_session_id(line: &str, backend: Option<&str>) -> Option<String> {
+    let trimmed = line.trim();
+    if trimmed.starts_with('{') && trimmed.ends_with('}') {
+        if let Ok(value) = serde_json::from_str::<Value>(trimmed) {
+            if let Some(id) = parse_session_id_from_json(&value, backend) {
+                return Some(id);
+            }
+        }
+    }
+
+    let lowered = trimmed.to_lowercase();
+    if lowered.starts_with("session id:") {
+        return Some(trimmed["session id:".len()..].trim().to_string());
+    }
+
+    if let Some(idx) = lowered.find("session id:") {
+        return Some(trimmed[idx + "session id:".len()..].trim().to_string());
+    }
+
+    None
+}
+
+fn parse_session_id_from_json(value: &Value, backend: Option<&str>) -> Option<String> {
+    let backend = backend.unwrap_or("").to_lowercase();
+
+    let session_id = value
+        .get("session_id")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    if session_id.is_some() {
+        return session_id;
+    }
+
+    if backend == "codex" {
+        if let Some(thread_id) = value.get("thread_id").and_then(|v| v.as_str()) {
+            return Some(thread_id.to_string());
+        }
+    }
+
+    if let Some(value_type) = value.get("type").and_then(|v| v.as_str()) {
+        if value_type == "thread.started" {
+            if let Some(thread_id) = value.get("thread_id").and_then(|v| v.as_str()) {
+                return Some(thread_id.to_string());
+            }
+        }
+    }
+
+    None
+}
+
+/// Guess the backend from the configured CLI command path
+pub(crate) fn derive_backend_from_command(command: &str) -> Option<String> {
+    let normalized = command.to_lowercase();
+    if normalized.contains("claude") {
+        Some("claude".to_string())
+    } else if normalized.contains("codex") {
+        Some("codex".to_string())
+    } else if normalized.contains("gemini") {
+        Some("gemini".to_string())
+    } else {
+        None
+    }
+}
+
+/// Output stream a chunk of CLI output came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CliStream {
+    Stdout,
+    Stderr,
+}
+
+/// A single direct CLI invocation
+pub(crate) struct DirectCliRun<'a> {
+    pub command: &'a str,
+    pub args: &'a [String],
+    pub workdir: &'a str,
+    pub env: &'a [(String, String)],
+    /// Task text written to the CLI's stdin
+    pub input: &'a str,
+    pub backend: Option<&'a str>,
+    /// Session id expected from the plan; replaced by the id the CLI reports
+    pub task_id: Option<String>,
+    /// Where to record the run in the audit log (not recorded when `None`)
+    pub audit: Option<AuditContext>,
+    /// Where to ask the user about permission prompts; without it stdin is closed after the task
+    pub permissions: Option<mpsc::UnboundedSender<PermissionRequest>>,
+}
+
+/// How the CLI's stdin is used after the task is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StdinMode {
+    /// Closed after the task (the CLI reads its task until end of input)
+    Closed,
+    /// Kept open for answers to plain text questions
+    Text,
+    /// Kept open for claude `stream-json` control responses until the `result` event;
+    /// only the assistant text of the output events is passed on
+    Json,
+}
+
+impl StdinMode {
+    fn for_run(run: &DirectCliRun<'_>) -> Self {
+        if run.permissions.is_none() {
+            StdinMode::Closed
+        } else if permission_prompt::uses_json_prompts(run.backend, run.args) {
+            StdinMode::Json
+        } else if run.backend.is_some() {
+            // claude -p, codex exec and gemini read stdin to EOF before starting.
+            StdinMode::Closed
+        } else {
+            StdinMode::Text
+        }
+    }
+}
+
+/// Result of a finished direct CLI run
+#[derive(Debug, Clone)]
+pub(crate) struct DirectCliOutcome {
+    /// Everything streamed to `on_output`, stderr lines prefixed with `[stderr] `
+    pub output: String,
+    pub task_id: Option<String>,
+    pub exit_code: i32,
+}
+
+/// Run a CLI directly, passing each output line to `on_output` as it arrives.
+///
+/// Returns `AppError::Cancelled` if `cancel_rx` fires before the process exits.
+pub(crate) async fn run_direct_cli(
+    mut run: DirectCliRun<'_>,
+    cancel_rx: Option<oneshot::Receiver<()>>,
+    on_output: impl FnMut(CliStream, &str),
+) -> AppResult<DirectCliOutcome> {
+    let stdin_mode = StdinMode::for_run(&run);
+    let args = match stdin_mode {
+        StdinMode::Json => permission_prompt::json_prompt_args(run.args),
+        _ => run.args.to_vec(),
+    };
+    let mut recorder = InvocationRecorder::start(
+        run.audit.take(),
+        "direct_cli",
+        run.backend,
+        run.command,
+        &args,
+        run.env,
+        Some(run.workdir),
+    );
+    let result = execute_direct_cli(run, &args, stdin_mode, cancel_rx, on_output, &mut recorder).await;
+    if let Ok(outcome) = &result {
+        recorder.set_exit_code(outcome.exit_code);
+        recorder.set_cli_session_id(outcome.task_id.as_deref());
+    }
+    recorder.finish(&result).await;
+    result
+}
+
+async fn execute_direct_cli(
+    run: DirectCliRun<'_>,
+    args: &[String],
+    stdin_mode: StdinMode,
+    mut cancel_rx: Option<oneshot::Receiver<()>>,
+    mut on_output: impl FnMut(CliStream, &str),
+    recorder: &mut InvocationRecorder,
+) -> AppResult<DirectCliOutcome> {
+    let mut cmd = Command::new(run.command);
+    #[cfg(windows)]
+    {
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+    cmd.args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .current_dir(run.workdir);
+    for (key, value) in run.env {
+        cmd.env(key, value);
+    }
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| AppError::ProcessError(format!("Failed to start CLI: {}", e)))?;
+
+    let mut stdin = child.stdin.take();
+    if let Some(writer) = stdin.as_mut() {
+        let input = if stdin_mode == StdinMode::Json {
+            permission_prompt::json_user_message(run.input)
+        } else {
+            let mut input = run.input.to_string();
+            if !input.ends_with('\n') {
+                input.push('\n');
+            }
+            input
+        };
+        if let Err(e) = writer.write_all(input.as_bytes()).await {
+            warn!("Failed to write CLI stdin: {}", e);
+        }
+    }
+    if stdin_mode == StdinMode::Closed {
+        stdin = None;
+    }
+
+    let mut stdout_reader = child.stdout.take().map(BufReader::new);
+    let mut stderr_reader = child.stderr.take().map(BufReader::new);
+    let mut stdout_done = stdout_reader.is_none();
+    let mut stderr_done = stderr_reader.is_none();
+    // Bytes of the current line; reads append to them, so an interrupted read loses nothing.
+    let mut stdout_buf: Vec<u8> = Vec::new();
+    let mut stderr_buf: Vec<u8> = Vec::new();
+    let mut output = String::new();
+    let mut stdout_text = String::new();
+    let mut stderr_text = String::new();
+    let mut task_id = run.task_id;
+    let mut pending_prompt: Option<(PermissionPrompt, oneshot::Receiver<bool>)> = None;
+    // Buffer lengths already checked for a question, so each partial line is checked once
+    let mut checked_partial = (0, 0);
+
+    while !stdout_done || !stderr_done {
+        // Pending reads fill the buffers without completing, so this wakes up periodically to look.
+        let check_partial = stdin_mode == StdinMode::Text && pending_prompt.is_none();
+        tokio::select! {
+            _ = async {
+                match cancel_rx.as_mut() {
+                    Some(rx) => {
+                        let _ = rx.await;
+                    }
+                    None => std::future::pending::<()>().await,
+                }
+            } => {
+                if let Err(e) = child.kill().await {
+                    warn!("Failed to kill direct CLI after cancellation: {}", e);
+                }
+                recorder.set_output(&stdout_text, &stderr_text);
+                return Err(AppError::Cancelled("direct CLI cancelled".to_string()));
+            }
+            answer = async { (&mut pending_prompt.as_mut().unwrap().1).await }, if pending_prompt.is_some() => {
+                let (prompt, _) = pending_prompt.take().unwrap();
+                // A dropped request (nobody to ask) denies.
+                let allow = answer.unwrap_or(false);
+                debug!(allow, "Answering CLI permission prompt");
+                write_stdin(&mut stdin, &prompt.reply(allow)).await;
+            }
+            _ = sleep(PROMPT_IDLE), if check_partial => {
+                if (stdout_buf.len(), stderr_buf.len()) == checked_partial {
+                    continue;
+                }
+                checked_partial = (stdout_buf.len(), stderr_buf.len());
+                for (stream, buf) in [(CliStream::Stdout, &mut stdout_buf), (CliStream::Stderr, &mut stderr_buf)] {
+                    let partial = String::from_utf8_lossy(buf).to_string();
+                    if let Some(prompt) = permission_prompt::detect_text_prompt(&partial) {
+                        buf.clear();
+                        let delta = emit_line(stream, &partial, &mut output, &mut stdout_text, &mut stderr_text);
+                        on_output(stream, &delta);
+                        pending_prompt = ask_permission(run.permissions.as_ref(), prompt);
+                        checked_partial = (0, 0);
+                        break;
+                    }
+                }
+            }
+            read = read_until_newline(&mut stdout_reader, &mut stdout_buf), if !stdout_done => {
+                match read {
+                    Ok(0) => stdout_done = true,
+                    Ok(_) => {
+                        let line = String::from_utf8_lossy(&stdout_buf).to_string();
+                        stdout_buf.clear();
+                        if let Some(id) = parse_cli_session_id(&line, run.backend) {
+                            if should_replace_task_id(task_id.as_deref(), &id) {
+                                task_id = Some(id);
+                            }
+                        }
+                        if stdin_mode == StdinMode::Json {
+                            stdout_text.push_str(&line);
+                            if let Some(text) = permission_prompt::json_output_text(&line) {
+                                output.push_str(&text);
+                                on_output(CliStream::Stdout, &text);
+                            }
+                        } else {
+                            let delta = emit_line(CliStream::Stdout, &line, &mut output, &mut stdout_text, &mut stderr_text);
+                            on_output(CliStream::Stdout, &delta);
+                        }
+                        match stdin_mode {
+                            StdinMode::Json if permission_prompt::is_json_result(&line) => stdin = None,
+                            StdinMode::Json if pending_prompt.is_none() => {
+                                if let Some(prompt) = permission_prompt::detect_json_prompt(&line) {
+                                    pending_prompt = ask_permission(run.permissions.as_ref(), prompt);
+                                }
+                            }
+                            StdinMode::Text if pending_prompt.is_none() => {
+                                if let Some(prompt) = permission_prompt::detect_text_prompt(&line) {
+                                    pending_prompt = ask_permission(run.permissions.as_ref(), prompt);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to read CLI stdout: {}", e);
+                        stdout_done = true;
+                    }
+                }
+            }
+            read = read_until_newline(&mut stderr_reader, &mut stderr_buf), if !stderr_done => {
+                match read {
+                    Ok(0) => stderr_done = true,
+                    Ok(_) => {
+                        let line = String::from_utf8_lossy(&stderr_buf).to_string();
+                        stderr_buf.clear();
+                        let delta = emit_line(CliStream::Stderr, &line, &mut output, &mut stdout_text, &mut stderr_text);
+                        on_output(CliStream::Stderr, &delta);
+                        if stdin_mode == StdinMode::Text && pending_prompt.is_none() {
+                            if let Some(prompt) = permission_prompt::detect_text_prompt(&line) {
+                                pending_prompt = ask_permission(run.permissions.as_ref(), prompt);
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to read CLI stderr: {}", e);
+                        stderr_done = true;
+                    }
+                }
+            }
+        }
+    }
+    drop(stdin);
+
+    recorder.set_output(&stdout_text, &stderr_text);
+    let exit_status = child
+        .wait()
+        .await
+        .map_err(|e| AppError::ProcessError(format!("Failed to wait for CLI: {}", e)))?;
+
+    Ok(DirectCliOutcome {
+        output,
+        task_id,
+        exit_code: exit_status.code().unwrap_or(-1),
+    })
+}
+
+/// Record a line of output; returns the delta passed to `on_output` (stderr lines are prefixed)
+fn emit_line(
+    stream: CliStream,
+    line: &str,
+    output: &mut String,
+    stdout_text: &mut String,
+    stderr_text: &mut String,
+) -> String {
+    let delta = match stream {
+        CliStream::Stdout => {
+            stdout_text.push_str(line);
+            line.to_string()
+        }
+        CliStream::Stderr => {
+            stderr_text.push_str(line);
+            format!("[stderr] {}", line)
+        }
+    };
+    output.push_str(&delta);
+    delta
+}
+
+/// Hand a prompt to the user; `None` (no prompt pending) if nobody can answer
+fn ask_permission(
+    permissions: Option<&mpsc::UnboundedSender<PermissionRequest>>,
+    prompt: PermissionPrompt,
+) -> Option<(PermissionPrompt, oneshot::Receiver<bool>)> {
+    let (reply, rx) = oneshot::channel();
+    permissions?
+        .send(PermissionRequest {
+            prompt: prompt.clone(),
+            reply,
+        })
+        .ok()?;
+    Some((prompt, rx))
+}
+
+async fn write_stdin(stdin: &mut Option<ChildStdin>, text: &str) {
+    let Some(writer) = stdin.as_mut() else {
+        warn!("CLI stdin already closed, permission answer dropped");
+        return;
+    };
+    if let Err(e) = async {
+        writer.write_all(text.as_bytes()).await?;
+        writer.flush().await
+    }
+    .await
+    {
+        warn!("Failed to write permission answer to CLI stdin: {}", e);
+    }
+}
+
+/// Read up to and including the next newline, appending to `buf`; `Ok(0)` at end of output.
+///
+/// Cancel safe: bytes read before the future is dropped stay in `buf`.
+async fn read_until_newline<R: AsyncRead + Unpin>(
+    reader: &mut Option<BufReader<R>>,
+    buf: &mut Vec<u8>,
+) -> std::io::Result<usize> {
+    let Some(reader) = reader.as_mut() else {
+        return Ok(0);
+    };
+    let read = reader.read_until(b'\n', buf).await?;
+    // At end of output, an unfinished last line is still a line.
+    Ok(if read == 0 { buf.len() } else { read })
+}
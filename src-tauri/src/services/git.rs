@@ -0,0 +1,334 @@
+//! Git module
+//!
+//! Runs git in a workspace and splits its diffs by file, for `@git:` mentions,
+//! commit messages and code reviews.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// The diff of one file, as printed by `git diff`
+#[derive(Debug, Clone)]
+pub struct FileDiff {
+    /// Path relative to the repository root; the new path of a rename
+    pub path: String,
+    /// Everything from the `diff --git` line to the next one
+    pub text: String,
+}
+
+/// Settings that keep paths in diffs as `a/<path>` / `b/<path>` and unescaped
+/// (only paths with quotes, backslashes or control characters stay quoted)
+const OUTPUT_CONFIG: &[&str] = &[
+    "-c",
+    "core.quotePath=false",
+    "-c",
+    "diff.noprefix=false",
+    "-c",
+    "diff.mnemonicPrefix=false",
+];
+
+/// Run git in `root` and return its stdout
+pub fn run_git(root: &Path, args: &[&str]) -> Result<String, String> {
+    run_git_allowing(root, args, &[0])
+}
+
+/// Run git in `root` and return its stdout when it exits with one of `ok_codes`
+fn run_git_allowing(root: &Path, args: &[&str], ok_codes: &[i32]) -> Result<String, String> {
+    let mut cmd = Command::new("git");
+    #[cfg(windows)]
+    {
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = cmd
+        .arg("-C")
+        .arg(root)
+        .args(OUTPUT_CONFIG)
+        .args(args)
+        .output()
+        .map_err(|e| format!("无法运行 git：{}", e))?;
+    if !output.status.code().is_some_and(|code| ok_codes.contains(&code)) {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git 执行失败：{}", stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Root of the repository `root` belongs to; diff paths are relative to it
+pub fn toplevel(root: &Path) -> Result<PathBuf, String> {
+    run_git(root, &["rev-parse", "--show-toplevel"]).map(|out| PathBuf::from(out.trim()))
+}
+
+/// Untracked files that are not ignored by git, relative to the repository root `toplevel`
+pub fn untracked_files(toplevel: &Path) -> Result<Vec<String>, String> {
+    let out = run_git(toplevel, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+    Ok(out
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Diff of an untracked file as if it had been added, in the form `git diff` prints
+pub fn untracked_diff(toplevel: &Path, path: &str) -> Result<String, String> {
+    // `--no-index` exits with 1 when the files differ, which they always do here
+    let mut args = vec!["diff", "--no-index", "--no-color", "--no-ext-diff"];
+    args.extend(["--", "/dev/null", path]);
+    run_git_allowing(toplevel, &args, &[0, 1])
+}
+
+/// Split a `git diff` into its files; text before the first file is dropped.
+///
+/// The path is first guessed from the `diff --git` line, which is ambiguous when
+/// a path contains ` b/`, and then taken from the `rename to`, `copy to` or
+/// `+++ b/` line of the file's header when there is one.
+pub fn split_diff(diff: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    // Whether the current file is still in its header (before the first hunk)
+    let mut in_header = false;
+    for line in diff.split_inclusive('\n') {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            files.push(FileDiff {
+                path: header_path(paths.trim_end_matches(['\n', '\r'])),
+                text: String::new(),
+            });
+            in_header = true;
+        } else if in_header && line.starts_with("@@") {
+            in_header = false;
+        } else if in_header {
+            let value = line.trim_end_matches(['\n', '\r']);
+            let path = if let Some(path) = value.strip_prefix("rename to ").or_else(|| value.strip_prefix("copy to ")) {
+                Some(unquote_path(path))
+            } else if let Some(path) = value.strip_prefix("+++ ") {
+                // git adds a tab after a path with a space in it.
+                let path = unquote_path(path.trim_end_matches('\t'));
+                path.strip_prefix("b/").map(str::to_string)
+            } else {
+                None
+            };
+            if let (Some(path), Some(file)) = (path, files.last_mut()) {
+                file.path = path;
+            }
+        }
+        if let Some(file) = files.last_mut() {
+            file.text.push_str(line);
+        }
+    }
+    files
+}
+
+/// The new path of a `diff --git a/<old> b/<new>` line (without the prefix)
+fn header_path(paths: &str) -> String {
+    // A quoted new path; a quote inside a quoted path is always escaped.
+    if paths.ends_with('"') {
+        if let Some(idx) = paths.rfind(" \"b/") {
+            if let Some((path, rest)) = unquote(&paths[idx + 1..]) {
+                if rest.is_empty() {
+                    return path.strip_prefix("b/").unwrap_or(&path).to_string();
+                }
+            }
+        }
+    }
+    // A quoted old path followed by a plain new one
+    if paths.starts_with('"') {
+        if let Some((_, rest)) = unquote(paths) {
+            if let Some(path) = rest.strip_prefix(" b/") {
+                return path.to_string();
+            }
+        }
+    }
+    // The same path on both sides: `a/<path> b/<path>`
+    if paths.len() > 5 && (paths.len() - 5) % 2 == 0 {
+        let len = (paths.len() - 5) / 2;
+        if let (Some(old), Some(sep), Some(new)) = (
+            paths.get(2..2 + len),
+            paths.get(2 + len..5 + len),
+            paths.get(5 + len..),
+        ) {
+            if paths.starts_with("a/") && sep == " b/" && old == new {
+                return new.to_string();
+            }
+        }
+    }
+    paths.rsplit_once(" b/").map_or(paths, |(_, path)| path).to_string()
+}
+
+/// A path as git prints it, with C-style quotes removed when it has them
+fn unquote_path(path: &str) -> String {
+    match unquote(path) {
+        Some((unquoted, "")) => unquoted,
+        _ => path.to_string(),
+    }
+}
+
+/// Decode the C-style quoted string at the start of `text`; returns it and the
+/// text after the closing quote
+fn unquote(text: &str) -> Option<(String, &str)> {
+    let body = text.strip_prefix('"')?;
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut chars = body.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => return Some((String::from_utf8_lossy(&bytes).into_owned(), &body[idx + 1..])),
+            '\\' => {
+                let (_, escaped) = chars.next()?;
+                let byte = match escaped {
+                    'a' => 0x07,
+                    'b' => 0x08,
+                    'f' => 0x0c,
+                    'n' => b'\n',
+                    'r' => b'\r',
+                    't' => b'\t',
+                    'v' => 0x0b,
+                    '0'..='7' => {
+                        let mut value = escaped.to_digit(8)?;
+                        for _ in 0..2 {
+                            let (_, digit) = chars.next()?;
+                            value = value * 8 + digit.to_digit(8)?;
+                        }
+                        u8::try_from(value).ok()?
+                    }
+                    other => {
+                        let mut buf = [0; 4];
+                        bytes.extend_from_slice(other.encode_utf8(&mut buf).as_bytes());
+                        continue;
+                    }
+                };
+                bytes.push(byte);
+            }
+            other => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(other.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+    None
+}
+
+/// Keep the files of a `git diff` for which `keep` (given the absolute path) is true
+pub fn filter_diff(root: &Path, diff: &str, mut keep: impl FnMut(&Path) -> bool) -> Result<String, String> {
+    let toplevel = toplevel(root)?;
+    Ok(split_diff(diff)
+        .into_iter()
+        .filter(|file| keep(&toplevel.join(&file.path)))
+        .map(|file| file.text)
+        .collect())
+}
+
+/// Join file diffs, cutting each at `max_file_chars` and the whole at `max_chars`;
+/// the flag tells whether anything was cut
+pub fn limit_diff(files: Vec<FileDiff>, max_chars: usize, max_file_chars: usize) -> (String, bool) {
+    let mut out = String::new();
+    let mut truncated = false;
+    let mut budget = max_chars;
+    for file in files {
+        if budget == 0 {
+            truncated = true;
+            break;
+        }
+        let limit = budget.min(max_file_chars);
+        let text = if file.text.len() > limit {
+            truncated = true;
+            let mut cut = limit;
+            while !file.text.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            format!("{}\n... (diff of {} truncated)\n", &file.text[..cut], file.path)
+        } else {
+            file.text
+        };
+        budget = budget.saturating_sub(text.len());
+        out.push_str(&text);
+    }
+    (out, truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(diff: &str) -> Vec<String> {
+        split_diff(diff).into_iter().map(|file| file.path).collect()
+    }
+
+    #[test]
+    fn splits_plain_and_spaced_paths() {
+        let diff = "warning: text before the first file\n\
+diff --git a/src/main.rs b/src/main.rs\n\
+index 1..2 100644\n\
+--- a/src/main.rs\n\
++++ b/src/main.rs\n\
+@@ -1 +1 @@\n\
+-old\n\
++new\n\
+diff --git a/docs/a b/c.md b/docs/a b/c.md\n\
+new file mode 100644\n\
+--- /dev/null\n\
++++ b/docs/a b/c.md\t\n\
+@@ -0,0 +1 @@\n\
++++ not a header\n";
+        let files = split_diff(diff);
+        assert_eq!(paths(diff), vec!["src/main.rs", "docs/a b/c.md"]);
+        assert!(files[0].text.starts_with("diff --git a/src/main.rs"));
+        assert!(files[1].text.ends_with("+++ not a header\n"));
+    }
+
+    #[test]
+    fn decodes_quoted_paths() {
+        let diff = "diff --git \"a/\\346\\226\\207.env\" \"b/\\346\\226\\207.env\"\n\
+index 1..2 100644\n\
+--- \"a/\\346\\226\\207.env\"\n\
++++ \"b/\\346\\226\\207.env\"\n\
+diff --git \"a/say \\\"hi\\\".txt\" \"b/say \\\"hi\\\".txt\"\n\
+deleted file mode 100644\n\
+--- \"a/say \\\"hi\\\".txt\"\n\
++++ /dev/null\n\
+Binary files differ\n";
+        assert_eq!(paths(diff), vec!["文.env", "say \"hi\".txt"]);
+    }
+
+    #[test]
+    fn uses_the_new_path_of_renames_and_copies() {
+        let diff = "diff --git a/old name.rs b/new b/name.rs\n\
+similarity index 100%\n\
+rename from old name.rs\n\
+rename to new b/name.rs\n\
+diff --git a/x.rs \"b/tab\\there.rs\"\n\
+similarity index 90%\n\
+copy from x.rs\n\
+copy to \"tab\\there.rs\"\n";
+        assert_eq!(paths(diff), vec!["new b/name.rs", "tab\there.rs"]);
+    }
+
+    #[test]
+    fn guesses_paths_from_the_header_alone() {
+        assert_eq!(header_path("a/x b/y b/x b/y"), "x b/y");
+        assert_eq!(header_path("\"a/\\303\\251\" b/e.txt"), "e.txt");
+        assert_eq!(header_path("a/e.txt \"b/\\303\\251\""), "é");
+        assert_eq!(unquote("\"a\\tb\" rest"), Some(("a\tb".to_string(), " rest")));
+        assert_eq!(unquote("\"unterminated"), None);
+    }
+
+    #[test]
+    fn diffs_untracked_files_like_added_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        run_git(root, &["init", "--quiet"]).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(root.join("src/new file.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(root.join("debug.log"), "noise\n").unwrap();
+
+        let mut files = untracked_files(root).unwrap();
+        files.sort();
+        assert_eq!(files, vec![".gitignore", "src/new file.rs"]);
+
+        let diff = untracked_diff(root, "src/new file.rs").unwrap();
+        assert_eq!(paths(&diff), vec!["src/new file.rs"]);
+        assert!(diff.contains("new file mode"));
+        assert!(diff.contains("+fn main() {}"));
+    }
+}
@@ -0,0 +1,293 @@
+//! Conversation history module
+//!
+//! CLI backends keep their own history behind resume ids; native providers do not.
+//! This module turns a stored `ChatSession` into the message list sent to a native
+//! provider, windowing it to a token budget and compacting older turns into a
+//! model-generated summary that is stored alongside the session.
+//!
+//! When the user switches code CLI mid-conversation, the same session is also
+//! turned into a handoff: a compact transcript prepended to the task of a CLI
+//! that has not seen the earlier turns.
+
+use chrono::Local;
+use tracing::{debug, info};
+
+use crate::services::chat_session::{ChatMessage, ChatSession, SessionSummary};
+use crate::services::native_provider::{ImageData, NativeProvider, ProviderMessage};
+use crate::utils::error::AppResult;
+
+/// Limits applied when building history for a native provider
+#[derive(Debug, Clone, Copy)]
+pub struct HistoryPolicy {
+    /// Estimated tokens of history (summary + turns) allowed per request
+    pub token_budget: usize,
+    /// Most recent messages that are never folded into the summary
+    pub keep_recent: usize,
+    /// Completion budget for the summarization request
+    pub summary_max_tokens: u32,
+}
+
+impl Default for HistoryPolicy {
+    fn default() -> Self {
+        Self {
+            token_budget: 24_000,
+            keep_recent: 8,
+            summary_max_tokens: 1024,
+        }
+    }
+}
+
+/// Estimated tokens of transcript handed to a code CLI
+const HANDOFF_TOKEN_BUDGET: usize = 8_000;
+/// Characters kept per message in a handoff; longer messages are cut off
+const HANDOFF_MESSAGE_CHARS: usize = 2_000;
+
+/// Rough token estimate (≈4 characters per token)
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+fn is_conversation_turn(message: &ChatMessage) -> bool {
+    matches!(message.role.as_str(), "user" | "assistant")
+        && !message
+            .metadata
+            .as_ref()
+            .is_some_and(|m| m.is_unselected_compare_reply())
+}
+
+/// Messages not yet covered by the session summary
+fn unsummarized(session: &ChatSession) -> &[ChatMessage] {
+    let start = session
+        .summary
+        .as_ref()
+        .map(|s| s.summarized_count.min(session.messages.len()))
+        .unwrap_or(0);
+    &session.messages[start..]
+}
+
+fn history_tokens(session: &ChatSession) -> usize {
+    let summary_tokens = session
+        .summary
+        .as_ref()
+        .map(|s| estimate_tokens(&s.content))
+        .unwrap_or(0);
+    summary_tokens
+        + unsummarized(session)
+            .iter()
+            .filter(|m| is_conversation_turn(m))
+            .map(|m| estimate_tokens(&m.content))
+            .sum::<usize>()
+}
+
+/// Whether the session's history exceeds the budget and has turns that can be folded
+pub fn needs_compaction(session: &ChatSession, policy: &HistoryPolicy) -> bool {
+    unsummarized(session).len() > policy.keep_recent && history_tokens(session) > policy.token_budget
+}
+
+/// Summarize everything except the last `keep_recent` messages.
+///
+/// Returns `None` when there is nothing to fold. The caller persists the result.
+pub async fn compact(
+    session: &ChatSession,
+    provider: &NativeProvider,
+    policy: &HistoryPolicy,
+) -> AppResult<Option<SessionSummary>> {
+    let total = session.messages.len();
+    let already = session
+        .summary
+        .as_ref()
+        .map(|s| s.summarized_count.min(total))
+        .unwrap_or(0);
+    let fold_until = total.saturating_sub(policy.keep_recent);
+    if fold_until <= already {
+        return Ok(None);
+    }
+
+    let to_fold = &session.messages[already..fold_until];
+    let mut transcript = String::new();
+    for message in to_fold.iter().filter(|m| is_conversation_turn(m)) {
+        transcript.push_str(&format!("{}: {}\n\n", message.role, message.content));
+    }
+
+    let mut prompt = String::from(
+        "Summarize the conversation below so it can replace the original turns as context for \
+         future requests. Keep decisions, requirements, file paths, code identifiers, commands, \
+         errors and open questions. Be concise and factual; do not add commentary.\n\n",
+    );
+    if let Some(existing) = session.summary.as_ref() {
+        prompt.push_str("Existing summary of even earlier turns:\n");
+        prompt.push_str(&existing.content);
+        prompt.push_str("\n\n");
+    }
+    prompt.push_str("Conversation:\n");
+    prompt.push_str(&transcript);
+
+    info!(
+        session_id = %session.id,
+        folded = to_fold.len(),
+        "Compacting chat history"
+    );
+    let content = provider
+        .complete(None, &[ProviderMessage::user(prompt)], policy.summary_max_tokens)
+        .await?;
+
+    Ok(Some(SessionSummary {
+        content: content.trim().to_string(),
+        summarized_count: fold_until,
+        last_message_id: session.messages.get(fold_until - 1).map(|m| m.id.clone()),
+        model: Some(provider.model().to_string()),
+        edited: false,
+        updated_at: Local::now().to_rfc3339(),
+    }))
+}
+
+/// Build the system prompt and message list for a native provider request.
+///
+/// The summary (if any) becomes the system prompt; the newest turns that fit the
+/// remaining budget follow, oldest first. Consecutive same-role turns are merged
+/// because some providers require strict user/assistant alternation.
+pub fn build_history(session: &ChatSession, policy: &HistoryPolicy) -> (Option<String>, Vec<ProviderMessage>) {
+    let system = session
+        .summary
+        .as_ref()
+        .filter(|s| !s.content.trim().is_empty())
+        .map(|s| format!("Summary of the earlier conversation:\n{}", s.content));
+
+    let mut remaining = policy
+        .token_budget
+        .saturating_sub(system.as_deref().map(estimate_tokens).unwrap_or(0));
+
+    let mut window: Vec<&ChatMessage> = Vec::new();
+    for message in unsummarized(session).iter().rev().filter(|m| is_conversation_turn(m)) {
+        let cost = estimate_tokens(&message.content);
+        if cost > remaining {
+            break;
+        }
+        remaining -= cost;
+        window.push(message);
+    }
+    window.reverse();
+
+    let mut messages: Vec<ProviderMessage> = Vec::new();
+    for message in window {
+        match messages.last_mut() {
+            Some(last) if last.role == message.role => {
+                last.content.push_str("\n\n");
+                last.content.push_str(&message.content);
+            }
+            _ => messages.push(ProviderMessage {
+                role: message.role.clone(),
+                content: message.content.clone(),
+                images: Vec::new(),
+            }),
+        }
+    }
+    // Conversations sent to providers must start with a user turn.
+    while messages.first().map(|m| m.role != "user").unwrap_or(false) {
+        messages.remove(0);
+    }
+
+    debug!(
+        session_id = %session.id,
+        has_summary = system.is_some(),
+        turns = messages.len(),
+        "Built native provider history"
+    );
+    (system, messages)
+}
+
+/// Append the new user turn to the history, merging with a trailing user turn if present
+pub fn push_user_turn(messages: &mut Vec<ProviderMessage>, content: String, images: Vec<ImageData>) {
+    match messages.last_mut() {
+        Some(last) if last.role == "user" => {
+            last.content.push_str("\n\n");
+            last.content.push_str(&content);
+            last.images.extend(images);
+        }
+        _ => {
+            let mut message = ProviderMessage::user(content);
+            message.images = images;
+            messages.push(message);
+        }
+    }
+}
+
+/// Transcript for a code CLI that joins the conversation without the earlier turns.
+///
+/// With `resumed_cli` the CLI resumes its own session, so only the turns after its
+/// last reply are handed over; without it the CLI starts fresh and gets the session
+/// summary plus the most recent turns. Returns `None` when there is nothing to hand
+/// over, including when the resumed CLI's last reply cannot be found.
+pub fn handoff_context(session: &ChatSession, resumed_cli: Option<&str>) -> Option<String> {
+    let (summary, turns, intro) = match resumed_cli {
+        Some(cli) => {
+            let last_seen = session.messages.iter().rposition(|m| {
+                m.role == "assistant" && m.metadata.as_ref().and_then(|m| m.code_cli.as_deref()) == Some(cli)
+            })?;
+            (
+                None,
+                &session.messages[last_seen + 1..],
+                "Since your last reply, this conversation continued with another assistant.",
+            )
+        }
+        None => (
+            session.summary.as_ref().map(|s| s.content.trim()).filter(|s| !s.is_empty()),
+            unsummarized(session),
+            "This conversation started with another assistant.",
+        ),
+    };
+
+    let mut remaining = HANDOFF_TOKEN_BUDGET.saturating_sub(summary.map(estimate_tokens).unwrap_or(0));
+    let turns: Vec<&ChatMessage> = turns.iter().filter(|m| is_conversation_turn(m)).collect();
+    let mut window: Vec<String> = Vec::new();
+    for message in turns.iter().rev() {
+        let mut content: String = message.content.trim().chars().take(HANDOFF_MESSAGE_CHARS).collect();
+        if content.len() < message.content.trim().len() {
+            content.push_str(" ... (truncated)");
+        }
+        let turn = format!("{}: {}", message.role, content);
+        let cost = estimate_tokens(&turn);
+        if cost > remaining {
+            break;
+        }
+        remaining -= cost;
+        window.push(turn);
+    }
+    window.reverse();
+    if summary.is_none() && window.is_empty() {
+        return None;
+    }
+
+    let mut handoff = format!(
+        "<conversation-handoff>\n{} Read it as context; do not answer it.\n",
+        intro
+    );
+    if let Some(summary) = summary {
+        handoff.push_str(&format!("\nSummary of the earlier conversation:\n{}\n", summary));
+    }
+    let omitted = turns.len() - window.len();
+    if omitted > 0 {
+        handoff.push_str(&format!("\n({} earlier messages omitted)\n", omitted));
+    }
+    if !window.is_empty() {
+        handoff.push_str(&format!("\n{}\n", window.join("\n\n")));
+    }
+    handoff.push_str("</conversation-handoff>");
+
+    debug!(
+        session_id = %session.id,
+        resumed_cli = ?resumed_cli,
+        turns = window.len(),
+        omitted,
+        "Built code CLI handoff"
+    );
+    Some(handoff)
+}
+
+/// Prepend a handoff (if any) to the task of a code CLI run
+pub fn with_handoff(handoff: Option<&str>, task: String) -> String {
+    match handoff {
+        Some(handoff) => format!("{}\n\n{}", handoff, task),
+        None => task,
+    }
+}
@@ -0,0 +1,243 @@
+//! Inline completion module
+//!
+//! Fill-in-the-middle suggestions for the editor's ghost text. The code around
+//! the cursor is cut to a window and sent to a native provider: as a FIM request
+//! when the endpoint takes one, otherwise as a chat prompt with a cursor marker.
+//! The reply is trimmed to the text that belongs at the cursor.
+//!
+//! Debouncing, superseding and the deadline are handled by the command layer;
+//! this module only builds requests, cleans replies and caches suggestions.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::services::native_provider::{NativeProvider, ProviderMessage};
+use crate::services::redaction::SecretRedactor;
+use crate::utils::error::AppResult;
+
+/// Code before the cursor sent to the provider
+pub const MAX_PREFIX_CHARS: usize = 6_000;
+/// Code after the cursor sent to the provider
+pub const MAX_SUFFIX_CHARS: usize = 2_000;
+
+const CURSOR_MARKER: &str = "<CURSOR>";
+
+const CHAT_SYSTEM_PROMPT: &str = "You are a code completion engine. Reply with only the code that belongs at \
+<CURSOR>: no explanations, no markdown fences and none of the code already before or after the cursor. \
+Reply with nothing when no completion fits.";
+
+/// Editor state at the cursor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InlineCompletionRequest {
+    pub file_path: String,
+    /// Monaco language id
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Document text before the cursor
+    pub prefix: String,
+    /// Document text after the cursor
+    pub suffix: String,
+    /// 1-based cursor position, as reported by the editor
+    pub line: u32,
+    pub column: u32,
+    #[serde(default)]
+    pub workspace_dir: Option<String>,
+}
+
+/// Suggestion returned to the editor
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InlineCompletion {
+    /// Text to insert at the cursor; empty when there is no suggestion
+    pub text: String,
+    /// Served from the cache of recent suggestions
+    pub cached: bool,
+    /// Why no provider reply was used: `disabled`, `ignored`, `superseded` or `timeout`
+    pub skipped: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+impl InlineCompletion {
+    pub fn skipped(reason: &str, elapsed_ms: u64) -> Self {
+        Self {
+            skipped: Some(reason.to_string()),
+            elapsed_ms,
+            ..Default::default()
+        }
+    }
+}
+
+/// The end of `prefix` and the start of `suffix` that fit the request window.
+///
+/// A cut prefix starts at the next line boundary, unless that would drop more
+/// than half of the window, so the model does not see half a line.
+pub fn window<'a>(prefix: &'a str, suffix: &'a str) -> (&'a str, &'a str) {
+    let prefix = if prefix.chars().count() > MAX_PREFIX_CHARS {
+        let start = prefix
+            .char_indices()
+            .rev()
+            .nth(MAX_PREFIX_CHARS - 1)
+            .map(|(idx, _)| idx)
+            .unwrap_or(0);
+        let cut = &prefix[start..];
+        match cut.find('\n') {
+            Some(newline) if newline < cut.len() / 2 => &cut[newline + 1..],
+            _ => cut,
+        }
+    } else {
+        prefix
+    };
+    let suffix = match suffix.char_indices().nth(MAX_SUFFIX_CHARS) {
+        Some((end, _)) => &suffix[..end],
+        None => suffix,
+    };
+    (prefix, suffix)
+}
+
+/// Ask the provider for the text between `prefix` and `suffix`; secrets are
+/// redacted from the request and put back into the suggestion
+pub async fn suggest(
+    provider: &NativeProvider,
+    file_path: &str,
+    language: Option<&str>,
+    prefix: &str,
+    suffix: &str,
+    max_tokens: u32,
+    redactor: &mut SecretRedactor,
+) -> AppResult<String> {
+    let outgoing_prefix = redactor.redact(prefix);
+    let outgoing_suffix = redactor.redact(suffix);
+    let raw = if provider.supports_fim() {
+        provider
+            .fill_in_middle(&outgoing_prefix, &outgoing_suffix, max_tokens)
+            .await?
+    } else {
+        let header = match language.filter(|l| !l.is_empty() && *l != "plaintext") {
+            Some(language) => format!("File: {} ({})", file_path, language),
+            None => format!("File: {}", file_path),
+        };
+        let prompt = format!(
+            "{}\n<code>\n{}{}{}\n</code>",
+            header, outgoing_prefix, CURSOR_MARKER, outgoing_suffix
+        );
+        provider
+            .complete(Some(CHAT_SYSTEM_PROMPT), &[ProviderMessage::user(prompt)], max_tokens)
+            .await?
+    };
+    Ok(clean_suggestion(&redactor.restore(&raw), prefix, suffix))
+}
+
+/// Reduce a provider reply to the text to insert at the cursor
+pub fn clean_suggestion(raw: &str, prefix: &str, suffix: &str) -> String {
+    let mut text = strip_fences(raw).replace(CURSOR_MARKER, "");
+
+    // Chat models like to repeat the partial line before the cursor.
+    let line_before = prefix.rsplit('\n').next().unwrap_or_default();
+    if !line_before.trim().is_empty() {
+        if let Some(rest) = text.strip_prefix(line_before) {
+            text = rest.to_string();
+        }
+    }
+
+    // In the middle of a line only the rest of that line is suggested.
+    let line_after = suffix.split('\n').next().unwrap_or_default();
+    if !line_after.trim().is_empty() {
+        text = text.split('\n').next().unwrap_or_default().to_string();
+        if let Some(rest) = text.strip_suffix(line_after) {
+            text = rest.to_string();
+        }
+    } else if let Some(next_line) = suffix.lines().map(str::trim).find(|l| !l.is_empty()) {
+        // Drop the code the suffix already has, such as a closing brace.
+        let mut offset = 0;
+        let mut cut = None;
+        for line in text.split_inclusive('\n') {
+            if line.trim() == next_line {
+                cut = Some(offset);
+                break;
+            }
+            offset += line.len();
+        }
+        if let Some(cut) = cut {
+            text.truncate(cut);
+        }
+    }
+
+    text.trim_end().to_string()
+}
+
+/// Inner text of a reply wrapped in a single markdown code fence
+fn strip_fences(raw: &str) -> &str {
+    let trimmed = raw.trim_matches('\n');
+    if !trimmed.trim_start().starts_with("```") {
+        return trimmed;
+    }
+    let body = trimmed
+        .trim_start()
+        .split_once('\n')
+        .map(|(_, body)| body)
+        .unwrap_or_default();
+    body.trim_end()
+        .strip_suffix("```")
+        .unwrap_or(body)
+        .trim_end_matches('\n')
+}
+
+struct CacheEntry {
+    file_path: String,
+    prefix: String,
+    suffix: String,
+    text: String,
+}
+
+/// Recent suggestions, newest last
+pub struct CompletionCache {
+    capacity: usize,
+    entries: VecDeque<CacheEntry>,
+}
+
+impl CompletionCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Suggestion for the cursor state; also matches when the user has typed
+    /// the start of an earlier suggestion, returning the part not yet typed
+    pub fn get(&self, file_path: &str, prefix: &str, suffix: &str) -> Option<String> {
+        self.entries.iter().rev().find_map(|entry| {
+            if entry.file_path != file_path || entry.suffix != suffix {
+                return None;
+            }
+            let typed = prefix.strip_prefix(entry.prefix.as_str())?;
+            let rest = entry.text.strip_prefix(typed)?;
+            (!rest.is_empty() || typed.is_empty()).then(|| rest.to_string())
+        })
+    }
+
+    pub fn put(&mut self, file_path: &str, prefix: &str, suffix: &str, text: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.entries
+            .retain(|entry| !(entry.file_path == file_path && entry.prefix == prefix && entry.suffix == suffix));
+        while self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(CacheEntry {
+            file_path: file_path.to_string(),
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+            text: text.to_string(),
+        });
+    }
+
+    /// Apply a new capacity, dropping the oldest entries
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+    }
+}
@@ -0,0 +1,571 @@
+//! Chat mention module
+//!
+//! Resolves `@` references in a chat message into context blocks:
+//!
+//! - `@path/to/file`, optionally with a line range (`@src/main.rs#L10-L20`);
+//!   relative paths are taken from the workspace root
+//! - `@folder:path` lists the files below a directory
+//! - `@terminal:N` the recent commands and output of the N-th open terminal
+//! - `@git:diff`, `@git:staged`, `@git:status` and `@git:log`
+//! - `@session:<id>` the transcript of another chat session
+//! - `@symbol:Name` the definitions of a symbol in the workspace
+//!
+//! A bare `@word` is only a file reference when it looks like a path (it has a
+//! separator or an extension) and names an existing file, so `@param`,
+//! `@types/node` or `@app.route` stay plain text. References inside fenced
+//! code blocks and inline code spans are ignored. Every prefixed reference that
+//! cannot be resolved is reported at once, before the request is sent. Paths
+//! must stay inside the workspace.
+//!
+//! Files matched by `.aiignore` are withheld: a reference to one resolves to
+//! nothing, and folder listings, symbol search and diffs leave them out. The
+//! withheld paths are returned so the context manifest can show them.
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::services::ai_ignore::AiIgnore;
+use crate::services::chat_session;
+use crate::services::context::{BlockedContext, ContextBlock};
+use crate::services::git::{self, run_git};
+use crate::services::terminal::TerminalService;
+use crate::utils::error::{AppError, AppResult};
+use crate::utils::fs::{collect_workspace_files, looks_binary, normalize_path, relative_path};
+
+/// Characters kept per block; longer content is cut off
+const MAX_BLOCK_CHARS: usize = 64 * 1024;
+/// Files larger than this are refused
+const MAX_FILE_BYTES: u64 = 512 * 1024;
+/// Entries listed by `@folder:`
+const MAX_FOLDER_ENTRIES: usize = 500;
+/// Files scanned by `@symbol:` (same limit as the search index)
+const MAX_SYMBOL_FILES: usize = 20_000;
+/// Definitions included per `@symbol:`
+const MAX_SYMBOL_MATCHES: usize = 3;
+/// Lines shown from a definition onwards
+const SYMBOL_SNIPPET_LINES: usize = 40;
+/// Commits listed by `@git:log`
+const GIT_LOG_COUNT: usize = 20;
+/// Keywords that introduce a definition, most specific first
+const DEFINITION_KEYWORDS: &[&str] = &[
+    "struct", "enum", "trait", "class", "interface", "type", "fn", "function", "def", "const", "mod",
+];
+/// Characters that end a reference when they trail it
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', ')', ']', '}', '>', '"', '\'', '`'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MentionKind {
+    File,
+    Folder,
+    Terminal,
+    Git,
+    Session,
+    Symbol,
+}
+
+/// An `@` reference found in a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mention {
+    /// As written, e.g. `@git:diff`
+    pub raw: String,
+    pub kind: MentionKind,
+    /// The part after the prefix, e.g. `diff`
+    pub target: String,
+    /// Written without a prefix; left as text when it does not resolve
+    pub bare: bool,
+}
+
+/// Find the `@` references in a message, in order and without duplicates
+pub fn parse_mentions(message: &str) -> Vec<Mention> {
+    let mut mentions: Vec<Mention> = Vec::new();
+    let mut in_fence = false;
+    for line in message.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let mut prev: Option<char> = None;
+        // Length of the backtick run that opened the current inline code span
+        let mut code_ticks: Option<usize> = None;
+        let mut ticks = 0;
+        for (idx, ch) in line.char_indices() {
+            let starts_token = prev.map_or(true, |p| p.is_whitespace() || "([{\"'".contains(p));
+            prev = Some(ch);
+            if ch == '`' {
+                ticks += 1;
+                continue;
+            }
+            if ticks > 0 {
+                code_ticks = match code_ticks {
+                    None => Some(ticks),
+                    Some(open) if open == ticks => None,
+                    open => open,
+                };
+                ticks = 0;
+            }
+            if ch != '@' || !starts_token || code_ticks.is_some() {
+                continue;
+            }
+            let token: String = line[idx + 1..].chars().take_while(|c| !c.is_whitespace()).collect();
+            let token = token.trim_end_matches(TRAILING_PUNCTUATION);
+            if let Some(mention) = classify(token) {
+                if !mentions.iter().any(|m| m.raw == mention.raw) {
+                    mentions.push(mention);
+                }
+            }
+        }
+    }
+    mentions
+}
+
+fn classify(token: &str) -> Option<Mention> {
+    const PREFIXES: &[(&str, MentionKind)] = &[
+        ("file:", MentionKind::File),
+        ("folder:", MentionKind::Folder),
+        ("terminal:", MentionKind::Terminal),
+        ("git:", MentionKind::Git),
+        ("session:", MentionKind::Session),
+        ("symbol:", MentionKind::Symbol),
+    ];
+    let (kind, target, bare) = PREFIXES
+        .iter()
+        .find_map(|(prefix, kind)| token.strip_prefix(prefix).map(|target| (*kind, target, false)))
+        .or_else(|| looks_like_path(token).then_some((MentionKind::File, token, true)))?;
+    if target.is_empty() {
+        return None;
+    }
+    Some(Mention {
+        raw: format!("@{}", token),
+        kind,
+        target: target.to_string(),
+        bare,
+    })
+}
+
+fn looks_like_path(token: &str) -> bool {
+    if token.contains('/') || token.contains('\\') {
+        return true;
+    }
+    let path = token.split('#').next().unwrap_or(token);
+    match path.rsplit_once('.') {
+        Some((stem, ext)) => {
+            !stem.is_empty()
+                && !ext.is_empty()
+                && ext.len() <= 10
+                && ext.chars().all(|c| c.is_ascii_alphanumeric())
+                && ext.chars().any(|c| c.is_ascii_alphabetic())
+        }
+        None => false,
+    }
+}
+
+/// The mentions of a message, resolved
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedMentions {
+    pub blocks: Vec<ContextBlock>,
+    /// Files left out because of `.aiignore`
+    pub blocked: Vec<BlockedContext>,
+}
+
+/// Resolves mentions against a workspace, its terminals, git and the chat history
+pub struct MentionResolver<'a> {
+    workspace_dir: Option<PathBuf>,
+    terminal: Option<&'a TerminalService>,
+    ignore: &'a AiIgnore,
+    blocked: RefCell<Vec<BlockedContext>>,
+}
+
+impl<'a> MentionResolver<'a> {
+    /// `terminal` is `None` where no terminal sessions exist (the headless CLI)
+    pub fn new(
+        workspace_dir: Option<&str>,
+        terminal: Option<&'a TerminalService>,
+        ignore: &'a AiIgnore,
+    ) -> Self {
+        Self {
+            workspace_dir: workspace_dir
+                .map(str::trim)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+            terminal,
+            ignore,
+            blocked: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Resolve every mention (blocking); fails with all unresolved prefixed mentions listed
+    pub fn resolve_all(self, mentions: &[Mention]) -> AppResult<ResolvedMentions> {
+        let mut blocks = Vec::new();
+        let mut errors = Vec::new();
+        for mention in mentions {
+            match self.resolve(mention) {
+                Ok(Some(block)) => blocks.push(block),
+                Ok(None) => {}
+                // A bare `@token` that is not a file is ordinary text.
+                Err(_) if mention.bare => {}
+                Err(reason) => errors.push(format!("{}（{}）", mention.raw, reason)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(ResolvedMentions {
+                blocks,
+                blocked: self.blocked.into_inner(),
+            })
+        } else {
+            Err(AppError::ValidationError(format!(
+                "无法解析消息中的引用：{}",
+                errors.join("；")
+            )))
+        }
+    }
+
+    /// `None` when `.aiignore` withholds the whole reference
+    fn resolve(&self, mention: &Mention) -> Result<Option<ContextBlock>, String> {
+        if matches!(mention.kind, MentionKind::File | MentionKind::Folder) {
+            let target = mention.target.split_once("#L").map_or(mention.target.as_str(), |(path, _)| path);
+            let path = self.path(target)?;
+            if mention.bare && !path.exists() {
+                return Err("文件不存在".to_string());
+            }
+            if self.is_ignored(&path) {
+                return Ok(None);
+            }
+        }
+        let (label, content) = match mention.kind {
+            MentionKind::File => self.file(&mention.target)?,
+            MentionKind::Folder => self.folder(&mention.target)?,
+            MentionKind::Terminal => self.terminal(&mention.target)?,
+            MentionKind::Git => self.git(&mention.target)?,
+            MentionKind::Session => session(&mention.target)?,
+            MentionKind::Symbol => self.symbol(&mention.target)?,
+        };
+        Ok(Some(ContextBlock {
+            mention: mention.raw.clone(),
+            label,
+            content: truncate(content),
+        }))
+    }
+
+    /// Whether `.aiignore` matches `path`; matches are recorded as blocked
+    fn is_ignored(&self, path: &Path) -> bool {
+        let ignored = self.ignore.is_ignored(path);
+        if ignored {
+            let path = normalize_path(&path.to_string_lossy());
+            let mut blocked = self.blocked.borrow_mut();
+            if !blocked.iter().any(|entry| entry.path == path) {
+                blocked.push(BlockedContext::new(path, "mention"));
+            }
+        }
+        ignored
+    }
+
+    fn root(&self) -> Result<&Path, String> {
+        self.workspace_dir
+            .as_deref()
+            .filter(|dir| dir.is_dir())
+            .ok_or_else(|| "没有打开的工作区".to_string())
+    }
+
+    /// `target` below the workspace root; absolute paths must point inside it too
+    fn path(&self, target: &str) -> Result<PathBuf, String> {
+        let root = self.root()?;
+        let path = root.join(target);
+        let inside = match (path.canonicalize(), root.canonicalize()) {
+            // Resolves `..` and symlinks of existing paths.
+            (Ok(resolved), Ok(root)) => resolved.starts_with(root),
+            _ => {
+                path.starts_with(root)
+                    && !path
+                        .components()
+                        .any(|component| matches!(component, std::path::Component::ParentDir))
+            }
+        };
+        if inside {
+            Ok(path)
+        } else {
+            Err("路径不在工作区内".to_string())
+        }
+    }
+
+    fn file(&self, target: &str) -> Result<(String, String), String> {
+        let (path_part, range) = match target.split_once("#L") {
+            Some((path, range)) => (path, Some(parse_line_range(range)?)),
+            None => (target, None),
+        };
+        let path = self.path(path_part)?;
+        if path.is_dir() {
+            return self.folder(path_part);
+        }
+        let metadata = fs::metadata(&path).map_err(|_| "文件不存在".to_string())?;
+        if metadata.len() > MAX_FILE_BYTES {
+            return Err(format!("文件超过 {} KB", MAX_FILE_BYTES / 1024));
+        }
+        let bytes = fs::read(&path).map_err(|e| format!("读取失败：{}", e))?;
+        if looks_binary(&bytes) {
+            return Err("不是文本文件".to_string());
+        }
+        let content = String::from_utf8_lossy(&bytes).into_owned();
+        let label = normalize_path(&path.to_string_lossy());
+        let Some((start, end)) = range else {
+            return Ok((label, content));
+        };
+        let lines: Vec<&str> = content.lines().collect();
+        if start > lines.len() {
+            return Err(format!("文件只有 {} 行", lines.len()));
+        }
+        let end = end.min(lines.len());
+        Ok((
+            format!("{}#L{}-L{}", label, start, end),
+            lines[start - 1..end].join("\n"),
+        ))
+    }
+
+    fn folder(&self, target: &str) -> Result<(String, String), String> {
+        let dir = self.path(target)?;
+        if !dir.is_dir() {
+            return Err("目录不存在".to_string());
+        }
+        let base = self
+            .workspace_dir
+            .as_deref()
+            .filter(|root| dir.starts_with(root))
+            .unwrap_or(&dir);
+        let files = collect_workspace_files(&dir, MAX_FOLDER_ENTRIES);
+        let mut entries: Vec<String> = files
+            .iter()
+            .filter(|path| !self.is_ignored(path))
+            .map(|path| relative_path(base, path))
+            .collect();
+        entries.sort();
+        let mut content = entries.join("\n");
+        if entries.len() >= MAX_FOLDER_ENTRIES {
+            content.push_str("\n... (listing truncated)");
+        } else if entries.is_empty() {
+            content.push_str("(empty directory)");
+        }
+        Ok((format!("folder:{}", normalize_path(&dir.to_string_lossy())), content))
+    }
+
+    fn terminal(&self, target: &str) -> Result<(String, String), String> {
+        let number: usize = target.parse().map_err(|_| "终端编号必须是数字".to_string())?;
+        let terminal = self.terminal.ok_or_else(|| "终端不可用".to_string())?;
+        let (name, history) = terminal
+            .recent_history(number)
+            .ok_or_else(|| format!("没有第 {} 个终端", number))?;
+        if history.is_empty() {
+            return Ok((format!("terminal:{} ({})", number, name), "(no commands run yet)".to_string()));
+        }
+        // Keep the most recent commands that fit.
+        let mut parts: Vec<String> = Vec::new();
+        let mut total = 0;
+        for entry in history.iter().rev() {
+            let part = format!("$ {}\n{}", entry.command, entry.output.trim_end());
+            total += part.len();
+            if total > MAX_BLOCK_CHARS && !parts.is_empty() {
+                break;
+            }
+            parts.push(part);
+        }
+        parts.reverse();
+        Ok((format!("terminal:{} ({})", number, name), parts.join("\n")))
+    }
+
+    fn git(&self, target: &str) -> Result<(String, String), String> {
+        let root = self.root()?;
+        let output = match target {
+            "diff" => run_git(root, &["diff", "HEAD"]).or_else(|_| run_git(root, &["diff"]))?,
+            "staged" => run_git(root, &["diff", "--cached"])?,
+            "status" => run_git(root, &["status", "--short", "--branch"])?,
+            "log" => run_git(
+                root,
+                &[
+                    "log",
+                    &format!("-n{}", GIT_LOG_COUNT),
+                    "--date=short",
+                    "--format=%h %ad %an %s",
+                ],
+            )?,
+            _ => return Err("不支持的 git 引用，可用：diff、staged、status、log".to_string()),
+        };
+        let output = match target {
+            "diff" | "staged" if !self.ignore.is_empty() => self.filter_diff(root, &output)?,
+            _ => output,
+        };
+        let content = if output.trim().is_empty() {
+            "(no changes)".to_string()
+        } else {
+            output
+        };
+        Ok((format!("git:{}", target), content))
+    }
+
+    /// Drop the files matched by `.aiignore` from a `git diff`
+    fn filter_diff(&self, root: &Path, diff: &str) -> Result<String, String> {
+        git::filter_diff(root, diff, |path| !self.is_ignored(path))
+    }
+
+    fn symbol(&self, target: &str) -> Result<(String, String), String> {
+        let root = self.root()?;
+        let name = target.rsplit("::").next().unwrap_or(target);
+        if !name.chars().all(is_ident_char) {
+            return Err("符号名只能包含字母、数字和下划线".to_string());
+        }
+
+        // (keyword rank, relative path, first line, snippet)
+        let mut found: Vec<(usize, String, usize, String)> = Vec::new();
+        for path in collect_workspace_files(root, MAX_SYMBOL_FILES) {
+            // Not recorded as blocked: most ignored files do not mention the symbol.
+            if self.ignore.is_ignored(&path) {
+                continue;
+            }
+            if fs::metadata(&path).map(|m| m.len() > MAX_FILE_BYTES).unwrap_or(true) {
+                continue;
+            }
+            let Ok(bytes) = fs::read(&path) else {
+                continue;
+            };
+            if looks_binary(&bytes) {
+                continue;
+            }
+            let text = String::from_utf8_lossy(&bytes);
+            if !text.contains(name) {
+                continue;
+            }
+            let lines: Vec<&str> = text.lines().collect();
+            for (idx, line) in lines.iter().enumerate() {
+                if let Some(rank) = definition_rank(line, name) {
+                    let end = (idx + SYMBOL_SNIPPET_LINES).min(lines.len());
+                    found.push((rank, relative_path(root, &path), idx + 1, lines[idx..end].join("\n")));
+                }
+            }
+        }
+        if found.is_empty() {
+            return Err("未找到符号定义".to_string());
+        }
+        found.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+        let content = found
+            .into_iter()
+            .take(MAX_SYMBOL_MATCHES)
+            .map(|(_, path, line, snippet)| format!("// {}#L{}\n{}", path, line, snippet))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        Ok((format!("symbol:{}", name), content))
+    }
+}
+
+fn session(target: &str) -> Result<(String, String), String> {
+    let session = chat_session::load_session_by_id(target).map_err(|_| "会话不存在".to_string())?;
+    // Keep the most recent messages that fit.
+    let mut parts: Vec<String> = Vec::new();
+    let mut total = 0;
+    for message in session.messages.iter().rev() {
+        if message.content.trim().is_empty() {
+            continue;
+        }
+        let part = format!("{}: {}", message.role, message.content.trim());
+        total += part.len();
+        if total > MAX_BLOCK_CHARS && !parts.is_empty() {
+            break;
+        }
+        parts.push(part);
+    }
+    parts.reverse();
+    let title = session.name.unwrap_or_else(|| session.first_message_preview.clone());
+    Ok((format!("session:{} ({})", target, title), parts.join("\n\n")))
+}
+
+/// `10`, `10-20` or `10-L20` (the text after `#L`) as a 1-based inclusive range
+fn parse_line_range(range: &str) -> Result<(usize, usize), String> {
+    let invalid = || "行号格式应为 #L10 或 #L10-L20".to_string();
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (start, end.trim_start_matches('L')),
+        None => (range, range),
+    };
+    let start: usize = start.parse().map_err(|_| invalid())?;
+    let end: usize = end.parse().map_err(|_| invalid())?;
+    if start == 0 || end < start {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Rank of the definition keyword when `line` defines `name`
+fn definition_rank(line: &str, name: &str) -> Option<usize> {
+    DEFINITION_KEYWORDS.iter().position(|keyword| {
+        let pattern = format!("{} {}", keyword, name);
+        line.match_indices(&pattern).any(|(idx, _)| {
+            let before = line[..idx].chars().next_back();
+            let after = line[idx + pattern.len()..].chars().next();
+            !before.is_some_and(is_ident_char) && !after.is_some_and(is_ident_char)
+        })
+    })
+}
+
+fn truncate(mut content: String) -> String {
+    if content.len() > MAX_BLOCK_CHARS {
+        let mut cut = MAX_BLOCK_CHARS;
+        while !content.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        content.truncate(cut);
+        content.push_str("\n... (truncated)");
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raws(message: &str) -> Vec<String> {
+        parse_mentions(message).into_iter().map(|m| m.raw).collect()
+    }
+
+    #[test]
+    fn skips_code_blocks_and_inline_code() {
+        let message = "see @src/a.rs and `@src/b.rs` or ``x `@c.rs` y``\n```\n@src/d.rs\n```\n@git:diff";
+        assert_eq!(raws(message), vec!["@src/a.rs", "@git:diff"]);
+    }
+
+    #[test]
+    fn marks_unprefixed_paths_as_bare() {
+        let mentions = parse_mentions("@types/node @file:main.rs, @param user@example.com");
+        assert_eq!(mentions.len(), 2);
+        assert!(mentions[0].bare && mentions[0].target == "types/node");
+        assert!(!mentions[1].bare && mentions[1].target == "main.rs");
+    }
+
+    #[test]
+    fn bare_mentions_that_do_not_resolve_stay_text() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "fn main() {}").unwrap();
+        let ignore = AiIgnore::default();
+        let resolver = MentionResolver::new(dir.path().to_str(), None, &ignore);
+        let resolved = resolver
+            .resolve_all(&parse_mentions("@src/lib.rs @tauri-apps/api @app.route"))
+            .unwrap();
+        assert_eq!(resolved.blocks.len(), 1);
+
+        let resolver = MentionResolver::new(dir.path().to_str(), None, &ignore);
+        assert!(resolver.resolve_all(&parse_mentions("@file:missing.rs")).is_err());
+    }
+
+    #[test]
+    fn paths_stay_inside_the_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let ignore = AiIgnore::default();
+        let resolver = MentionResolver::new(dir.path().to_str(), None, &ignore);
+        assert!(resolver.path("../outside.txt").is_err());
+        assert!(resolver.path("/etc/passwd").is_err());
+        assert!(resolver.path("inside.txt").is_ok());
+    }
+}
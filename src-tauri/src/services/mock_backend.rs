@@ -0,0 +1,269 @@
+//! Mock AI backend module
+//!
+//! Replays scripted responses instead of running a real code CLI, so the chat
+//! UI and automated tests work without codeagent-wrapper or a logged-in CLI.
+//! Selected by choosing a Code CLI whose name contains `mock`.
+//!
+//! Scripts are JSON files in `$CODE_AI_MOCK_FIXTURES` or `<data_dir>/mock-fixtures`.
+//! A file holds one script or an array of scripts:
+//!
+//! ```json
+//! {
+//!   "name": "slow-failure",
+//!   "match": "deploy",
+//!   "session_id": "mock-session-1",
+//!   "steps": [
+//!     { "delay_ms": 300, "stdout": "Working on {prompt}\n" },
+//!     { "delay_ms": 100, "stderr": "warning: rate limited\n" }
+//!   ],
+//!   "exit_code": 1
+//! }
+//! ```
+//!
+//! The first script whose `match` text occurs in the request is used, then the
+//! script named `default`, then a built-in echo script.
+
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tokio::sync::oneshot;
+use tokio::time::{sleep, Duration};
+use tracing::{debug, warn};
+
+use crate::services::audit::{AuditContext, InvocationRecorder};
+use crate::services::direct_cli::{CliStream, DirectCliOutcome};
+use crate::utils::error::{AppError, AppResult};
+
+/// Environment variable overriding the fixture directory
+pub const MOCK_FIXTURES_ENV: &str = "CODE_AI_MOCK_FIXTURES";
+
+/// Delay between chunks of the built-in script
+const BUILTIN_CHUNK_DELAY_MS: u64 = 40;
+
+/// A scripted backend run
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MockScript {
+    /// Script name; defaults to the fixture file stem
+    pub name: String,
+    /// Case-insensitive text the request must contain for this script to be picked
+    #[serde(rename = "match")]
+    pub match_text: Option<String>,
+    /// Session id reported to the caller; the resume id (or a new id) when omitted
+    pub session_id: Option<String>,
+    pub steps: Vec<MockStep>,
+    /// Exit code of the simulated process
+    pub exit_code: i32,
+    /// Fail with this error after the steps instead of exiting
+    pub error: Option<String>,
+    /// Keep running after the steps until cancelled
+    pub hang: bool,
+    /// Delay before a cancellation takes effect, to mimic slow process shutdown
+    pub cancel_delay_ms: u64,
+}
+
+/// One chunk of scripted output; `{prompt}` is replaced by the request text
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MockStep {
+    pub delay_ms: u64,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FixtureFile {
+    Many(Vec<MockScript>),
+    One(MockScript),
+}
+
+/// Whether a Code CLI selection refers to the mock backend
+pub fn is_mock_cli(code_cli: Option<&str>) -> bool {
+    code_cli
+        .map(|c| c.trim().to_lowercase().contains("mock"))
+        .unwrap_or(false)
+}
+
+/// Directory scripts are loaded from
+pub fn fixtures_dir(data_dir: &str) -> PathBuf {
+    std::env::var(MOCK_FIXTURES_ENV)
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(data_dir).join("mock-fixtures"))
+}
+
+/// Load every script in `dir`, ordered by file name; unreadable files are skipped
+pub fn load_scripts(dir: &Path) -> Vec<MockScript> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("json"))
+        .collect();
+    paths.sort();
+
+    let mut scripts = Vec::new();
+    for path in paths {
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(AppError::from)
+            .and_then(|text| serde_json::from_str::<FixtureFile>(&text).map_err(AppError::from));
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        match parsed {
+            Ok(FixtureFile::One(script)) => scripts.push(with_default_name(script, &stem)),
+            Ok(FixtureFile::Many(list)) => {
+                scripts.extend(list.into_iter().map(|script| with_default_name(script, &stem)))
+            }
+            Err(e) => warn!("Skipping mock fixture {}: {}", path.display(), e),
+        }
+    }
+    scripts
+}
+
+fn with_default_name(mut script: MockScript, stem: &str) -> MockScript {
+    if script.name.trim().is_empty() {
+        script.name = stem.to_string();
+    }
+    script
+}
+
+/// Pick the script for `input`
+pub fn select_script(scripts: &[MockScript], input: &str) -> MockScript {
+    let lowered = input.to_lowercase();
+    scripts
+        .iter()
+        .find(|s| {
+            s.match_text
+                .as_deref()
+                .map(|m| !m.is_empty() && lowered.contains(&m.to_lowercase()))
+                .unwrap_or(false)
+        })
+        .or_else(|| scripts.iter().find(|s| s.name == "default"))
+        .cloned()
+        .unwrap_or_else(builtin_script)
+}
+
+/// Echo the request back in a few chunks
+fn builtin_script() -> MockScript {
+    let steps = ["[mock] ", "You said: ", "{prompt}\n"]
+        .iter()
+        .map(|chunk| MockStep {
+            delay_ms: BUILTIN_CHUNK_DELAY_MS,
+            stdout: Some(chunk.to_string()),
+            stderr: None,
+        })
+        .collect();
+    MockScript {
+        name: "builtin".to_string(),
+        steps,
+        ..Default::default()
+    }
+}
+
+/// Replay `script`, passing each chunk to `on_output` like a direct CLI run.
+///
+/// Returns `AppError::Cancelled` if `cancel_rx` fires before the script ends.
+pub(crate) async fn run_mock_backend(
+    script: &MockScript,
+    input: &str,
+    resume_session_id: Option<&str>,
+    cancel_rx: Option<oneshot::Receiver<()>>,
+    audit: Option<AuditContext>,
+    on_output: impl FnMut(CliStream, &str),
+) -> AppResult<DirectCliOutcome> {
+    let mut recorder = InvocationRecorder::start(
+        audit,
+        "mock",
+        Some("mock"),
+        &script.name,
+        &[],
+        &[],
+        None,
+    );
+    let result = replay(script, input, resume_session_id, cancel_rx, on_output, &mut recorder).await;
+    if let Ok(outcome) = &result {
+        recorder.set_exit_code(outcome.exit_code);
+        recorder.set_cli_session_id(outcome.task_id.as_deref());
+    }
+    recorder.finish(&result).await;
+    result
+}
+
+async fn replay(
+    script: &MockScript,
+    input: &str,
+    resume_session_id: Option<&str>,
+    mut cancel_rx: Option<oneshot::Receiver<()>>,
+    mut on_output: impl FnMut(CliStream, &str),
+    recorder: &mut InvocationRecorder,
+) -> AppResult<DirectCliOutcome> {
+    debug!(script = %script.name, steps = script.steps.len(), "Replaying mock backend script");
+
+    let mut output = String::new();
+    let mut stdout_text = String::new();
+    let mut stderr_text = String::new();
+
+    for step in &script.steps {
+        wait_or_cancel(step.delay_ms, &mut cancel_rx, script.cancel_delay_ms).await?;
+        if let Some(text) = step.stdout.as_deref() {
+            let text = text.replace("{prompt}", input.trim());
+            output.push_str(&text);
+            stdout_text.push_str(&text);
+            on_output(CliStream::Stdout, &text);
+        }
+        if let Some(text) = step.stderr.as_deref() {
+            let delta = format!("[stderr] {}", text);
+            output.push_str(&delta);
+            stderr_text.push_str(text);
+            on_output(CliStream::Stderr, &delta);
+        }
+        recorder.set_output(&stdout_text, &stderr_text);
+    }
+
+    if script.hang {
+        wait_or_cancel(u64::MAX, &mut cancel_rx, script.cancel_delay_ms).await?;
+    }
+    if let Some(error) = script.error.as_deref() {
+        return Err(AppError::AiServiceError(error.to_string()));
+    }
+
+    let task_id = script
+        .session_id
+        .clone()
+        .or_else(|| resume_session_id.map(|s| s.to_string()))
+        .unwrap_or_else(|| format!("mock-{}", uuid::Uuid::new_v4()));
+
+    Ok(DirectCliOutcome {
+        output,
+        task_id: Some(task_id),
+        exit_code: script.exit_code,
+    })
+}
+
+/// Sleep for `delay_ms`, returning `Cancelled` early (after `cancel_delay_ms`) if cancelled
+async fn wait_or_cancel(
+    delay_ms: u64,
+    cancel_rx: &mut Option<oneshot::Receiver<()>>,
+    cancel_delay_ms: u64,
+) -> AppResult<()> {
+    let cancelled = tokio::select! {
+        _ = sleep(Duration::from_millis(delay_ms)) => false,
+        _ = async {
+            match cancel_rx.as_mut() {
+                Some(rx) => {
+                    let _ = rx.await;
+                }
+                None => std::future::pending::<()>().await,
+            }
+        } => true,
+    };
+    if cancelled {
+        sleep(Duration::from_millis(cancel_delay_ms)).await;
+        return Err(AppError::Cancelled("mock backend cancelled".to_string()));
+    }
+    Ok(())
+}
@@ -3,6 +3,30 @@
 //! This module contains business logic services for the application.
 
 pub mod ai;
+pub mod ai_ignore;
+pub mod audit;
 pub mod terminal;
 pub mod chat_session;
+pub mod code_review;
+pub mod commit_message;
+pub mod context;
+pub mod direct_cli;
+pub mod git;
+pub mod history;
+pub mod inline_completion;
+pub mod mentions;
+pub mod mock_backend;
+pub mod native_provider;
+pub mod permission_prompt;
+pub mod plan_mode;
+pub mod redaction;
+pub mod response_cache;
+pub mod search_index;
+pub mod selection_edit;
+pub mod session_import;
+pub mod test_fix;
+pub mod workspace_budget;
+pub mod workspace_changes;
 
+pub mod scheduler;
+pub mod request_journal;
@@ -0,0 +1,368 @@
+//! Native provider module
+//!
+//! Talks to model HTTP APIs directly (Anthropic Messages, OpenAI Chat Completions,
+//! Gemini generateContent). Unlike the CLI backends, these providers are stateless:
+//! the app sends the conversation history with every request.
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::services::ai::AiModel;
+use crate::services::context::image_media_type;
+use crate::utils::error::{AppError, AppResult};
+
+/// Default completion budget for native requests
+pub const DEFAULT_MAX_OUTPUT_TOKENS: u32 = 4096;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Largest image accepted as a base64 block
+const MAX_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Wire protocol spoken by a provider endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    Anthropic,
+    OpenAi,
+    Gemini,
+}
+
+impl ProviderKind {
+    /// Guess the protocol from the endpoint URL, falling back to the model name
+    pub fn detect(endpoint: &str, model: &str) -> Self {
+        let endpoint = endpoint.to_lowercase();
+        let model = model.to_lowercase();
+        if endpoint.contains("anthropic") || (!endpoint.contains("openai") && model.contains("claude")) {
+            ProviderKind::Anthropic
+        } else if endpoint.contains("generativelanguage") || model.contains("gemini") {
+            ProviderKind::Gemini
+        } else {
+            ProviderKind::OpenAi
+        }
+    }
+
+    /// Environment variable consulted when the model has no API key configured
+    pub fn api_key_env(&self) -> &'static str {
+        match self {
+            ProviderKind::Anthropic => "ANTHROPIC_API_KEY",
+            ProviderKind::OpenAi => "OPENAI_API_KEY",
+            ProviderKind::Gemini => "GEMINI_API_KEY",
+        }
+    }
+}
+
+/// Base64-encoded image sent inline with a user turn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageData {
+    pub media_type: String,
+    pub data: String,
+}
+
+impl ImageData {
+    /// Read and encode an image attachment from disk
+    pub fn from_file(path: &str) -> AppResult<Self> {
+        let media_type = image_media_type(path)
+            .ok_or_else(|| AppError::ValidationError(format!("不支持的图片格式: {}", path)))?;
+        let size = std::fs::metadata(path)?.len();
+        if size > MAX_IMAGE_BYTES {
+            return Err(AppError::ValidationError(format!(
+                "图片过大 ({} bytes，上限 {} bytes): {}",
+                size, MAX_IMAGE_BYTES, path
+            )));
+        }
+        let bytes = std::fs::read(path)?;
+        Ok(Self {
+            media_type: media_type.to_string(),
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+        })
+    }
+}
+
+/// A single conversation turn sent to a provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderMessage {
+    /// `user` or `assistant`
+    pub role: String,
+    pub content: String,
+    /// Images attached to this turn (user turns only)
+    #[serde(default)]
+    pub images: Vec<ImageData>,
+}
+
+impl ProviderMessage {
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: content.into(),
+            images: Vec::new(),
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content: content.into(),
+            images: Vec::new(),
+        }
+    }
+}
+
+/// HTTP client for one configured model
+#[derive(Debug, Clone)]
+pub struct NativeProvider {
+    kind: ProviderKind,
+    endpoint: String,
+    api_key: String,
+    model: String,
+    client: reqwest::Client,
+}
+
+impl NativeProvider {
+    /// Build a provider from a model entry, resolving the API key from `env` or the process environment
+    pub fn from_model(model: &AiModel, env: &[(String, String)]) -> AppResult<Self> {
+        let kind = ProviderKind::detect(&model.endpoint, &model.name);
+        let api_key = if !model.api_key.trim().is_empty() {
+            model.api_key.trim().to_string()
+        } else {
+            let key_name = kind.api_key_env();
+            env.iter()
+                .find(|(k, _)| k == key_name)
+                .map(|(_, v)| v.clone())
+                .or_else(|| std::env::var(key_name).ok())
+                .filter(|v| !v.trim().is_empty())
+                .ok_or_else(|| {
+                    AppError::ConfigError(format!(
+                        "模型 {} 未配置 API Key（可设置环境变量 {}）",
+                        model.name, key_name
+                    ))
+                })?
+        };
+
+        Ok(Self {
+            kind,
+            endpoint: model.endpoint.clone(),
+            api_key,
+            model: model.name.clone(),
+            client: reqwest::Client::new(),
+        })
+    }
+
+    pub fn kind(&self) -> ProviderKind {
+        self.kind
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Send the conversation and return the assistant's text reply
+    pub async fn complete(
+        &self,
+        system: Option<&str>,
+        messages: &[ProviderMessage],
+        max_tokens: u32,
+    ) -> AppResult<String> {
+        debug!(
+            provider = ?self.kind,
+            model = %self.model,
+            message_count = messages.len(),
+            "Sending native provider request"
+        );
+
+        let request = match self.kind {
+            ProviderKind::Anthropic => self
+                .client
+                .post(&self.endpoint)
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+                .json(&self.anthropic_body(system, messages, max_tokens)),
+            ProviderKind::OpenAi => self
+                .client
+                .post(&self.endpoint)
+                .bearer_auth(&self.api_key)
+                .json(&self.openai_body(system, messages, max_tokens)),
+            ProviderKind::Gemini => self
+                .client
+                .post(&self.endpoint)
+                .query(&[("key", self.api_key.as_str())])
+                .json(&self.gemini_body(system, messages, max_tokens)),
+        };
+
+        let body = self.read_response(request).await?;
+        let text = self.extract_text(&body);
+        if text.trim().is_empty() {
+            return Err(AppError::AiServiceError(format!("{} 未返回有效消息", self.model)));
+        }
+        Ok(text)
+    }
+
+    /// Whether the endpoint takes fill-in-the-middle requests (`prompt` plus
+    /// `suffix`, as OpenAI-compatible `/completions` and `/fim/completions` do)
+    /// instead of a conversation
+    pub fn supports_fim(&self) -> bool {
+        let path = self.endpoint.split('?').next().unwrap_or_default().trim_end_matches('/');
+        self.kind == ProviderKind::OpenAi && path.ends_with("/completions") && !path.ends_with("/chat/completions")
+    }
+
+    /// Return the text that belongs between `prefix` and `suffix`; may be empty.
+    /// Only valid when [`supports_fim`](Self::supports_fim) holds.
+    pub async fn fill_in_middle(&self, prefix: &str, suffix: &str, max_tokens: u32) -> AppResult<String> {
+        debug!(
+            model = %self.model,
+            prefix_chars = prefix.len(),
+            suffix_chars = suffix.len(),
+            "Sending native FIM request"
+        );
+
+        let request = self.client.post(&self.endpoint).bearer_auth(&self.api_key).json(&json!({
+            "model": self.model,
+            "prompt": prefix,
+            "suffix": suffix,
+            "max_tokens": max_tokens,
+            "temperature": 0,
+        }));
+        let body = self.read_response(request).await?;
+        // Legacy completions answer with `text`, Mistral-style FIM endpoints with a message.
+        Ok(body
+            .pointer("/choices/0/text")
+            .or_else(|| body.pointer("/choices/0/message/content"))
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// Send a request and return its JSON body, turning error statuses into `AiServiceError`
+    async fn read_response(&self, request: reqwest::RequestBuilder) -> AppResult<Value> {
+        let response = request.send().await?;
+        let status = response.status();
+        let body: Value = response.json().await?;
+        if !status.is_success() {
+            let detail = body
+                .pointer("/error/message")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| body.to_string());
+            return Err(AppError::AiServiceError(format!(
+                "{} 请求失败 ({}): {}",
+                self.model, status, detail
+            )));
+        }
+        Ok(body)
+    }
+
+    fn anthropic_body(&self, system: Option<&str>, messages: &[ProviderMessage], max_tokens: u32) -> Value {
+        let mut body = json!({
+            "model": self.model,
+            "max_tokens": max_tokens,
+            "messages": messages
+                .iter()
+                .map(|m| {
+                    if m.images.is_empty() {
+                        return json!({ "role": m.role, "content": m.content });
+                    }
+                    let mut blocks: Vec<Value> = m
+                        .images
+                        .iter()
+                        .map(|img| {
+                            json!({
+                                "type": "image",
+                                "source": { "type": "base64", "media_type": img.media_type, "data": img.data },
+                            })
+                        })
+                        .collect();
+                    blocks.push(json!({ "type": "text", "text": m.content }));
+                    json!({ "role": m.role, "content": blocks })
+                })
+                .collect::<Vec<_>>(),
+        });
+        if let Some(system) = system {
+            body["system"] = json!(system);
+        }
+        body
+    }
+
+    fn openai_body(&self, system: Option<&str>, messages: &[ProviderMessage], max_tokens: u32) -> Value {
+        let mut wire: Vec<Value> = Vec::new();
+        if let Some(system) = system {
+            wire.push(json!({ "role": "system", "content": system }));
+        }
+        wire.extend(messages.iter().map(|m| {
+            if m.images.is_empty() {
+                return json!({ "role": m.role, "content": m.content });
+            }
+            let mut parts = vec![json!({ "type": "text", "text": m.content })];
+            parts.extend(m.images.iter().map(|img| {
+                json!({
+                    "type": "image_url",
+                    "image_url": { "url": format!("data:{};base64,{}", img.media_type, img.data) },
+                })
+            }));
+            json!({ "role": m.role, "content": parts })
+        }));
+        json!({
+            "model": self.model,
+            "max_tokens": max_tokens,
+            "messages": wire,
+        })
+    }
+
+    fn gemini_body(&self, system: Option<&str>, messages: &[ProviderMessage], max_tokens: u32) -> Value {
+        let contents: Vec<Value> = messages
+            .iter()
+            .map(|m| {
+                let role = if m.role == "assistant" { "model" } else { "user" };
+                let mut parts = vec![json!({ "text": m.content })];
+                parts.extend(m.images.iter().map(|img| {
+                    json!({ "inline_data": { "mime_type": img.media_type, "data": img.data } })
+                }));
+                json!({ "role": role, "parts": parts })
+            })
+            .collect();
+        let mut body = json!({
+            "contents": contents,
+            "generationConfig": { "maxOutputTokens": max_tokens },
+        });
+        if let Some(system) = system {
+            body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+        }
+        body
+    }
+
+    fn extract_text(&self, body: &Value) -> String {
+        match self.kind {
+            ProviderKind::Anthropic => body
+                .get("content")
+                .and_then(|v| v.as_array())
+                .map(|blocks| {
+                    blocks
+                        .iter()
+                        .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+                        .collect::<Vec<_>>()
+                        .join("")
+                })
+                .unwrap_or_default(),
+            ProviderKind::OpenAi => body
+                .pointer("/choices/0/message/content")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            ProviderKind::Gemini => body
+                .pointer("/candidates/0/content/parts")
+                .and_then(|v| v.as_array())
+                .map(|parts| {
+                    parts
+                        .iter()
+                        .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                        .collect::<Vec<_>>()
+                        .join("")
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
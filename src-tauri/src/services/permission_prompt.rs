@@ -0,0 +1,226 @@
+//! CLI permission prompt module
+//!
+//! Recognizes a CLI asking for permission (to run a command, edit a file, use a
+//! tool) and builds the reply written back to its stdin. Two kinds are handled:
+//!
+//! - plain text questions such as `Allow this command? (y/n)`, matched by the
+//!   answer markers in `TEXT_PROMPTS`; the question usually has no trailing
+//!   newline, so the runner checks a partial line once the output goes idle
+//! - claude's `control_request` / `can_use_tool` events, sent when it runs with
+//!   `--input-format stream-json --permission-prompt-tool stdio`; the runner
+//!   switches claude to those flags itself and shows only the assistant text of
+//!   the JSON events it prints
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+
+/// Answer markers of a plain text question, with the allow and deny replies
+const TEXT_PROMPTS: &[(&str, &str, &str)] = &[
+    ("(y/n)", "y", "n"),
+    ("[y/n]", "y", "n"),
+    ("(yes/no)", "yes", "no"),
+    ("[yes/no]", "yes", "no"),
+];
+
+/// Questions longer than this are treated as ordinary output
+const MAX_PROMPT_CHARS: usize = 2000;
+
+/// A permission question asked by a CLI
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionPrompt {
+    /// The question, as shown to the user
+    pub message: String,
+    /// Tool the CLI wants to use, when it says so
+    pub tool: Option<String>,
+    /// Tool input (command, file path, ...) for display
+    pub input: Option<Value>,
+    #[serde(skip)]
+    answer: PromptAnswer,
+}
+
+#[derive(Debug, Clone)]
+enum PromptAnswer {
+    Text { allow: &'static str, deny: &'static str },
+    ClaudeControl { request_id: String, input: Value },
+}
+
+/// A prompt waiting for the user's decision (`true` allows)
+#[derive(Debug)]
+pub struct PermissionRequest {
+    pub prompt: PermissionPrompt,
+    pub reply: oneshot::Sender<bool>,
+}
+
+impl PermissionPrompt {
+    /// Line to write to the CLI's stdin (including the newline)
+    pub fn reply(&self, allow: bool) -> String {
+        match &self.answer {
+            PromptAnswer::Text { allow: yes, deny: no } => format!("{}\n", if allow { yes } else { no }),
+            PromptAnswer::ClaudeControl { request_id, input } => {
+                let response = if allow {
+                    json!({ "behavior": "allow", "updatedInput": input })
+                } else {
+                    json!({ "behavior": "deny", "message": "The user denied this request" })
+                };
+                let reply = json!({
+                    "type": "control_response",
+                    "response": {
+                        "subtype": "success",
+                        "request_id": request_id,
+                        "response": response,
+                    },
+                });
+                format!("{}\n", reply)
+            }
+        }
+    }
+}
+
+/// Recognize a plain text permission question in the last (possibly unfinished) line of output
+pub fn detect_text_prompt(line: &str) -> Option<PermissionPrompt> {
+    let message = line.trim();
+    if message.is_empty() || message.chars().count() > MAX_PROMPT_CHARS {
+        return None;
+    }
+    let lowered = message.to_lowercase();
+    let (_, allow, deny) = TEXT_PROMPTS
+        .iter()
+        .find(|(marker, _, _)| lowered.contains(marker))?;
+    Some(PermissionPrompt {
+        message: message.to_string(),
+        tool: None,
+        input: None,
+        answer: PromptAnswer::Text { allow, deny },
+    })
+}
+
+/// Recognize a permission request event in a line of JSON output
+pub fn detect_json_prompt(line: &str) -> Option<PermissionPrompt> {
+    let trimmed = line.trim();
+    if !trimmed.starts_with('{') {
+        return None;
+    }
+    let value: Value = serde_json::from_str(trimmed).ok()?;
+    if value.get("type").and_then(|v| v.as_str()) != Some("control_request") {
+        return None;
+    }
+    let request = value.get("request")?;
+    if request.get("subtype").and_then(|v| v.as_str()) != Some("can_use_tool") {
+        return None;
+    }
+    let request_id = value.get("request_id").and_then(|v| v.as_str())?.to_string();
+    let tool = request
+        .get("tool_name")
+        .and_then(|v| v.as_str())
+        .unwrap_or("tool")
+        .to_string();
+    let input = request.get("input").cloned().unwrap_or_else(|| json!({}));
+    Some(PermissionPrompt {
+        message: format!("Allow {} to run?", tool),
+        tool: Some(tool),
+        input: Some(input.clone()),
+        answer: PromptAnswer::ClaudeControl { request_id, input },
+    })
+}
+
+/// Flags that make claude ask its permission questions as JSON events on stdio
+const JSON_IO_ARGS: &[&str] = &[
+    "--input-format",
+    "stream-json",
+    "--output-format",
+    "stream-json",
+    "--verbose",
+    "--permission-prompt-tool",
+    "stdio",
+];
+
+/// Whether a claude run should be switched to `stream-json` so its permission
+/// questions can be answered (not when the user already skips them)
+pub fn uses_json_prompts(backend: Option<&str>, args: &[String]) -> bool {
+    if !backend.is_some_and(|b| b.eq_ignore_ascii_case("claude")) {
+        return false;
+    }
+    let bypass = args.iter().enumerate().any(|(idx, arg)| {
+        arg == "--dangerously-skip-permissions"
+            || arg == "--permission-mode=bypassPermissions"
+            || (arg == "--permission-mode" && args.get(idx + 1).is_some_and(|v| v == "bypassPermissions"))
+    });
+    !bypass
+}
+
+/// `args` with claude's input and output formats replaced by `stream-json` and
+/// permission questions routed to stdio
+pub fn json_prompt_args(args: &[String]) -> Vec<String> {
+    const VALUE_FLAGS: &[&str] = &["--input-format", "--output-format", "--permission-prompt-tool"];
+    let mut result = Vec::with_capacity(args.len() + JSON_IO_ARGS.len());
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if VALUE_FLAGS.contains(&arg.as_str()) {
+            iter.next();
+            continue;
+        }
+        let inline_value = VALUE_FLAGS
+            .iter()
+            .any(|flag| arg.strip_prefix(flag).is_some_and(|rest| rest.starts_with('=')));
+        if inline_value || arg == "--verbose" {
+            continue;
+        }
+        result.push(arg.clone());
+    }
+    result.extend(JSON_IO_ARGS.iter().map(|arg| arg.to_string()));
+    result
+}
+
+/// The task as the first user message of a `stream-json` input
+pub fn json_user_message(task: &str) -> String {
+    let message = json!({
+        "type": "user",
+        "message": { "role": "user", "content": task },
+    });
+    format!("{}\n", message)
+}
+
+/// Whether a JSON output line ends the turn (stdin can be closed)
+pub fn is_json_result(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.starts_with('{')
+        && serde_json::from_str::<Value>(trimmed)
+            .ok()
+            .and_then(|v| v.get("type").and_then(|t| t.as_str()).map(|t| t == "result"))
+            .unwrap_or(false)
+}
+
+/// Text to show for a JSON output line: the text blocks of an assistant message,
+/// or the error of a failed `result`; other events are not shown
+pub fn json_output_text(line: &str) -> Option<String> {
+    let trimmed = line.trim();
+    if !trimmed.starts_with('{') {
+        return None;
+    }
+    let value: Value = serde_json::from_str(trimmed).ok()?;
+    let mut text = match value.get("type").and_then(|v| v.as_str())? {
+        "assistant" => value
+            .get("message")
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_array())?
+            .iter()
+            .filter(|block| block.get("type").and_then(|t| t.as_str()) == Some("text"))
+            .filter_map(|block| block.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "result" if value.get("is_error").and_then(|v| v.as_bool()) == Some(true) => value
+            .get("result")
+            .and_then(|r| r.as_str())
+            .unwrap_or("claude reported an error")
+            .to_string(),
+        _ => return None,
+    };
+    if text.trim().is_empty() {
+        return None;
+    }
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    Some(text)
+}
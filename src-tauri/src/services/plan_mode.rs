@@ -0,0 +1,105 @@
+//! Plan-then-execute module
+//!
+//! A plan run asks the backend for a plan without letting it change anything:
+//! direct CLI runs get the CLI's read-only flags (claude `--permission-mode plan`,
+//! codex `--sandbox read-only`, gemini `--approval-mode default`), codeagent-wrapper
+//! runs never skip permission prompts, and native providers have no tools to
+//! begin with. The task itself is also wrapped in planning instructions.
+//!
+//! Backends without a known read-only mode are refused rather than run with
+//! write access.
+//!
+//! Once the user approves (and possibly edits) the plan, the execution run
+//! continues the same CLI session with the approved plan prepended to the request.
+
+use crate::utils::error::{AppError, AppResult};
+
+/// Backends whose CLI has a known read-only mode
+const READ_ONLY_BACKENDS: &[&str] = &["claude", "codex", "gemini"];
+
+/// Instructions prepended to the task of a plan run
+const PLAN_INSTRUCTIONS: &str = "You are in planning mode. Do not create, modify or delete any files \
+and do not run commands that change the workspace. Read whatever you need, then reply with a \
+concise, numbered implementation plan for the request below: the files to change, what to change \
+in each and how to verify the result. The plan will be reviewed before anything is executed.";
+
+/// Wrap a task for a plan run
+pub fn plan_task(task: &str) -> String {
+    format!("{}\n\n<request>\n{}\n</request>", PLAN_INSTRUCTIONS, task)
+}
+
+/// Message for the execution run: the approved plan followed by the original request
+pub fn execution_message(plan: &str, message: &str) -> String {
+    format!(
+        "The following plan has been reviewed and approved. Implement it now, following the steps in \
+         order; only deviate from it where it is clearly wrong, and say so.\n\n<approved_plan>\n{}\n\
+         </approved_plan>\n\n{}",
+        plan.trim(),
+        message
+    )
+}
+
+/// Fail unless `backend` has a known read-only mode; plan runs never fall back to write access
+pub fn ensure_read_only(backend: Option<&str>) -> AppResult<()> {
+    let name = backend.map(|b| b.trim().to_lowercase()).unwrap_or_default();
+    if READ_ONLY_BACKENDS.contains(&name.as_str()) {
+        Ok(())
+    } else {
+        Err(unsupported(&name))
+    }
+}
+
+/// Add the read-only flags of `backend` to direct CLI args, replacing any that grant write access
+pub fn apply_plan_mode_args(backend: Option<&str>, args: &mut Vec<String>) -> AppResult<()> {
+    let name = backend.map(|b| b.trim().to_lowercase()).unwrap_or_default();
+    match name.as_str() {
+        "claude" => {
+            remove_flag(args, "--dangerously-skip-permissions", false);
+            remove_flag(args, "--permission-mode", true);
+            args.push("--permission-mode".to_string());
+            args.push("plan".to_string());
+        }
+        "codex" => {
+            remove_flag(args, "--dangerously-bypass-approvals-and-sandbox", false);
+            remove_flag(args, "--full-auto", false);
+            remove_flag(args, "--sandbox", true);
+            remove_flag(args, "-s", true);
+            args.push("--sandbox".to_string());
+            args.push("read-only".to_string());
+        }
+        "gemini" => {
+            // Non-interactive runs in the default approval mode refuse tools that need approval.
+            remove_flag(args, "--yolo", false);
+            remove_flag(args, "-y", false);
+            remove_flag(args, "--approval-mode", true);
+            args.push("--approval-mode".to_string());
+            args.push("default".to_string());
+        }
+        _ => return Err(unsupported(&name)),
+    }
+    Ok(())
+}
+
+fn unsupported(backend: &str) -> AppError {
+    if backend.is_empty() {
+        AppError::ValidationError("计划模式无法识别当前 CLI，不能以只读方式运行".to_string())
+    } else {
+        AppError::ValidationError(format!("计划模式不支持 {}：没有已知的只读模式", backend))
+    }
+}
+
+/// Remove every occurrence of `name` (and its value, as `name value` or `name=value`)
+fn remove_flag(args: &mut Vec<String>, name: &str, takes_value: bool) {
+    let prefix = format!("{}=", name);
+    let mut idx = 0;
+    while idx < args.len() {
+        if args[idx] == name {
+            let end = if takes_value { (idx + 2).min(args.len()) } else { idx + 1 };
+            args.drain(idx..end);
+        } else if takes_value && args[idx].starts_with(&prefix) {
+            args.remove(idx);
+        } else {
+            idx += 1;
+        }
+    }
+}
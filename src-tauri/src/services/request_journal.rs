@@ -0,0 +1,196 @@
+//! Request journal module
+//!
+//! Persists the lifecycle of streaming chat requests in the `ai_requests`
+//! table: state, the output streamed so far and the code CLI session id, so
+//! a request that was active when the app exited can be found and resumed.
+//!
+//! Updates are sent through a channel and written by a single background
+//! writer. Streamed output is buffered and flushed once per second, which
+//! keeps database writes off the streaming path.
+
+use sea_orm::DatabaseConnection;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::mpsc;
+use tokio::time::{interval, Duration};
+use tracing::{debug, warn};
+
+use crate::database::repositories::ai_request_repository::{status, AiRequestRecord, AiRequestRepository};
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// Finished requests older than this are pruned at startup
+const RETENTION_MS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+/// A change to a journaled request
+#[derive(Debug)]
+pub enum JournalEvent {
+    Queued(Box<AiRequestRecord>),
+    Running(String),
+    Output { request_id: String, delta: String },
+    CliSessionId { request_id: String, cli_session_id: String },
+    Finished { request_id: String, status: &'static str, error: Option<String> },
+    /// The request's task exited; marks it cancelled unless it already finished
+    Ended(String),
+}
+
+/// Sending half of the journal; cheap to clone, never blocks
+#[derive(Debug, Clone)]
+pub struct RequestJournal {
+    tx: mpsc::UnboundedSender<JournalEvent>,
+}
+
+impl RequestJournal {
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<JournalEvent>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { tx }, rx)
+    }
+
+    pub fn queued(&self, record: AiRequestRecord) {
+        self.send(JournalEvent::Queued(Box::new(record)));
+    }
+
+    pub fn running(&self, request_id: &str) {
+        self.send(JournalEvent::Running(request_id.to_string()));
+    }
+
+    pub fn output(&self, request_id: &str, delta: &str) {
+        if !delta.is_empty() {
+            self.send(JournalEvent::Output {
+                request_id: request_id.to_string(),
+                delta: delta.to_string(),
+            });
+        }
+    }
+
+    pub fn cli_session_id(&self, request_id: &str, cli_session_id: &str) {
+        self.send(JournalEvent::CliSessionId {
+            request_id: request_id.to_string(),
+            cli_session_id: cli_session_id.to_string(),
+        });
+    }
+
+    pub fn finished(&self, request_id: &str, status: &'static str, error: Option<String>) {
+        self.send(JournalEvent::Finished {
+            request_id: request_id.to_string(),
+            status,
+            error,
+        });
+    }
+
+    pub fn ended(&self, request_id: &str) {
+        self.send(JournalEvent::Ended(request_id.to_string()));
+    }
+
+    fn send(&self, event: JournalEvent) {
+        if self.tx.send(event).is_err() {
+            debug!("Request journal writer stopped, dropping event");
+        }
+    }
+}
+
+/// Mark requests left active by a previous run as orphaned, then write journal events until the channel closes
+pub async fn run_writer(db: DatabaseConnection, mut rx: mpsc::UnboundedReceiver<JournalEvent>) {
+    let now = chrono::Utc::now().timestamp_millis();
+    match AiRequestRepository::mark_orphaned(&db, now).await {
+        Ok(0) => {}
+        Ok(count) => warn!("Marked {} interrupted chat request(s) as orphaned", count),
+        Err(e) => warn!("Failed to mark orphaned chat requests: {}", e),
+    }
+    if let Err(e) = AiRequestRepository::prune(&db, now - RETENTION_MS).await {
+        warn!("Failed to prune request journal: {}", e);
+    }
+
+    let mut writer = JournalWriter {
+        db,
+        active: HashSet::new(),
+        pending: HashMap::new(),
+    };
+    let mut ticker = interval(FLUSH_INTERVAL);
+    loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Some(event) => writer.handle(event).await,
+                None => break,
+            },
+            _ = ticker.tick() => writer.flush_all().await,
+        }
+    }
+    writer.flush_all().await;
+}
+
+struct JournalWriter {
+    db: DatabaseConnection,
+    /// Requests inserted by this app instance and not finished yet
+    active: HashSet<String>,
+    /// Output not yet written, per request
+    pending: HashMap<String, String>,
+}
+
+impl JournalWriter {
+    async fn handle(&mut self, event: JournalEvent) {
+        let now = chrono::Utc::now().timestamp_millis();
+        let result = match event {
+            JournalEvent::Queued(record) => {
+                let request_id = record.request_id.clone();
+                let result = AiRequestRepository::insert(&self.db, &record).await;
+                if result.is_ok() {
+                    self.active.insert(request_id);
+                }
+                result
+            }
+            JournalEvent::Running(request_id) if self.active.contains(&request_id) => {
+                AiRequestRepository::set_status(&self.db, &request_id, status::RUNNING, None, now).await
+            }
+            JournalEvent::Output { request_id, delta } if self.active.contains(&request_id) => {
+                self.pending.entry(request_id).or_default().push_str(&delta);
+                Ok(())
+            }
+            JournalEvent::CliSessionId {
+                request_id,
+                cli_session_id,
+            } if self.active.contains(&request_id) => {
+                AiRequestRepository::set_cli_session_id(&self.db, &request_id, &cli_session_id, now).await
+            }
+            JournalEvent::Finished {
+                request_id,
+                status,
+                error,
+            } if self.active.remove(&request_id) => {
+                self.flush(&request_id).await;
+                AiRequestRepository::set_status(&self.db, &request_id, status, error.as_deref(), now).await
+            }
+            JournalEvent::Ended(request_id) if self.active.remove(&request_id) => {
+                self.flush(&request_id).await;
+                AiRequestRepository::set_status(
+                    &self.db,
+                    &request_id,
+                    status::CANCELLED,
+                    Some("Request ended without a final response"),
+                    now,
+                )
+                .await
+            }
+            // Not journaled (e.g. comparison runs) or already finished.
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            warn!("Failed to update request journal: {}", e);
+        }
+    }
+
+    async fn flush(&mut self, request_id: &str) {
+        let Some(output) = self.pending.remove(request_id) else {
+            return;
+        };
+        let now = chrono::Utc::now().timestamp_millis();
+        if let Err(e) = AiRequestRepository::append_output(&self.db, request_id, &output, now).await {
+            warn!("Failed to store partial output of {}: {}", request_id, e);
+        }
+    }
+
+    async fn flush_all(&mut self) {
+        let request_ids: Vec<String> = self.pending.keys().cloned().collect();
+        for request_id in request_ids {
+            self.flush(&request_id).await;
+        }
+    }
+}
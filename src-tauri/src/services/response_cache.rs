@@ -0,0 +1,98 @@
+//! Response cache module
+//!
+//! Opt-in cache of AI responses keyed by backend, model, the fully resolved
+//! task text (attached file contents, snippets and prompt) and what else shapes
+//! the reply: the conversation history of a native request, or the arguments,
+//! resume id and workspace of a CLI run. An identical request can then be
+//! replayed without running the backend again. CLI runs may edit files, which a
+//! replay does not repeat, so they are only cached when `cache_cli_backends` is set.
+
+use sea_orm::DatabaseConnection;
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::config::ResponseCacheSettings;
+use crate::database::repositories::response_cache_repository::{CachedResponse, ResponseCacheRepository};
+use crate::utils::error::AppResult;
+
+/// Cache key for a request: the hex SHA-256 of the backend, model, `context`
+/// (everything besides the task that the reply depends on) and the task.
+pub fn cache_key(backend: &str, model: Option<&str>, context: &[&str], task: &str) -> String {
+    let composite = format!(
+        "{}\u{1f}{}\u{1f}{}\u{1f}{}",
+        backend,
+        model.unwrap_or(""),
+        context.join("\u{1e}"),
+        task
+    );
+    Sha256::digest(composite.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn min_created_at(settings: &ResponseCacheSettings) -> i64 {
+    chrono::Utc::now().timestamp() - settings.ttl_secs.min(i64::MAX as u64) as i64
+}
+
+/// Look up a fresh response; always `None` when the cache is disabled
+pub async fn lookup(
+    db: &DatabaseConnection,
+    settings: &ResponseCacheSettings,
+    key: &str,
+) -> AppResult<Option<CachedResponse>> {
+    if !settings.enabled {
+        return Ok(None);
+    }
+    let entry = ResponseCacheRepository::get(db, key, min_created_at(settings)).await?;
+    debug!(cache_key = %key, hit = entry.is_some(), "Response cache lookup");
+    Ok(entry)
+}
+
+/// Store a response and enforce the TTL and size limits; no-op when the cache is disabled
+pub async fn store(
+    db: &DatabaseConnection,
+    settings: &ResponseCacheSettings,
+    key: &str,
+    backend: &str,
+    model: Option<&str>,
+    response: &str,
+) -> AppResult<()> {
+    if !settings.enabled || response.trim().is_empty() {
+        return Ok(());
+    }
+    ResponseCacheRepository::put(db, key, backend, model, response).await?;
+    let pruned = ResponseCacheRepository::prune(
+        db,
+        min_created_at(settings),
+        settings.max_entries.min(i64::MAX as u64) as i64,
+        settings.max_bytes.min(i64::MAX as u64) as i64,
+    )
+    .await?;
+    if pruned > 0 {
+        debug!(pruned, "Pruned response cache");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_key_is_a_full_sha256() {
+        let key = cache_key("claude-cli", Some("sonnet"), &["--print"], "explain main.rs");
+        assert_eq!(key.len(), 64);
+        assert!(key.chars().all(|c| c.is_ascii_hexdigit()));
+        let again = cache_key("claude-cli", Some("sonnet"), &["--print"], "explain main.rs");
+        assert_eq!(key, again);
+    }
+
+    #[test]
+    fn cache_key_separates_its_parts() {
+        let key = cache_key("claude-cli", None, &["a"], "b");
+        assert_ne!(key, cache_key("claude-cli", None, &["a", "b"], ""));
+        assert_ne!(key, cache_key("claude-cli", Some("a"), &[], "b"));
+        assert_ne!(key, cache_key("codex", None, &["a"], "b"));
+    }
+}
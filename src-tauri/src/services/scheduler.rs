@@ -0,0 +1,283 @@
+//! Job scheduling module
+//!
+//! Parses the cron-like expressions of scheduled agent jobs and computes
+//! their next run time in local time. Supported syntax is the classic
+//! five fields `minute hour day-of-month month day-of-week` with `*`,
+//! lists (`1,15`), ranges (`1-5`) and steps (`*/10`, `8-18/2`), plus the
+//! aliases `@hourly`, `@daily` (`@midnight`), `@weekly`, `@monthly` and
+//! `@yearly` (`@annually`). Day-of-week accepts 0-7 (0 and 7 are Sunday)
+//! and three-letter names; months accept three-letter names.
+//!
+//! As in cron, when both day-of-month and day-of-week are restricted a day
+//! matches if either field matches.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+
+use crate::utils::error::{AppError, AppResult};
+
+/// How far ahead `next_after` searches before giving up (covers Feb 29 schedules)
+const MAX_SEARCH_DAYS: i64 = 366 * 5;
+
+const MONTH_NAMES: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A parsed schedule; each field is a bit set of allowed values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u32,
+    days_of_month: u32,
+    months: u16,
+    days_of_week: u8,
+    /// Day-of-month field was `*`
+    any_day_of_month: bool,
+    /// Day-of-week field was `*`
+    any_day_of_week: bool,
+}
+
+impl CronSchedule {
+    /// Parse an expression such as `0 2 * * 1-5` or `@daily`
+    pub fn parse(expression: &str) -> AppResult<Self> {
+        let expression = expression.trim();
+        let expanded = match expression.to_lowercase().as_str() {
+            "@hourly" => "0 * * * *".to_string(),
+            "@daily" | "@midnight" => "0 0 * * *".to_string(),
+            "@weekly" => "0 0 * * 0".to_string(),
+            "@monthly" => "0 0 1 * *".to_string(),
+            "@yearly" | "@annually" => "0 0 1 1 *".to_string(),
+            other if other.starts_with('@') => {
+                return Err(invalid(expression, "unknown alias"));
+            }
+            _ => expression.to_string(),
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(invalid(expression, "expected 5 fields: minute hour day month weekday"));
+        }
+
+        let minutes = parse_field(fields[0], 0, 59, &[])
+            .map_err(|reason| invalid(expression, &format!("minute: {}", reason)))?;
+        let hours = parse_field(fields[1], 0, 23, &[])
+            .map_err(|reason| invalid(expression, &format!("hour: {}", reason)))?;
+        let days_of_month = parse_field(fields[2], 1, 31, &[])
+            .map_err(|reason| invalid(expression, &format!("day of month: {}", reason)))?;
+        let months = parse_field(fields[3], 1, 12, MONTH_NAMES)
+            .map_err(|reason| invalid(expression, &format!("month: {}", reason)))?;
+        let mut days_of_week = parse_field(fields[4], 0, 7, WEEKDAY_NAMES)
+            .map_err(|reason| invalid(expression, &format!("day of week: {}", reason)))?;
+        // 7 is an alias for Sunday.
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            minutes,
+            hours: hours as u32,
+            days_of_month: days_of_month as u32,
+            months: months as u16,
+            days_of_week: days_of_week as u8,
+            any_day_of_month: fields[2] == "*",
+            any_day_of_week: fields[4] == "*",
+        })
+    }
+
+    /// First matching minute strictly after `after`, or `None` if nothing matches within five years
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = start + Duration::days(MAX_SEARCH_DAYS);
+        let mut candidate = start;
+
+        while candidate < limit {
+            if !self.month_matches(candidate.month()) {
+                candidate = first_of_next_month(candidate.date())?;
+                continue;
+            }
+            if !self.day_matches(candidate.date()) {
+                candidate = (candidate.date() + Duration::days(1)).and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if self.hours & (1 << candidate.hour()) == 0 {
+                candidate = candidate.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if self.minutes & (1 << candidate.minute()) == 0 {
+                candidate += Duration::minutes(1);
+                continue;
+            }
+            // Local times skipped by a DST change do not exist; try the next minute.
+            match Local.from_local_datetime(&candidate).earliest() {
+                Some(time) if time > after => return Some(time),
+                _ => candidate += Duration::minutes(1),
+            }
+        }
+        None
+    }
+
+    /// The next `count` run times after `after`
+    pub fn upcoming(&self, after: DateTime<Local>, count: usize) -> Vec<DateTime<Local>> {
+        let mut times = Vec::with_capacity(count);
+        let mut cursor = after;
+        while times.len() < count {
+            match self.next_after(cursor) {
+                Some(next) => {
+                    times.push(next);
+                    cursor = next;
+                }
+                None => break,
+            }
+        }
+        times
+    }
+
+    fn month_matches(&self, month: u32) -> bool {
+        self.months & (1 << month) != 0
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let dom = self.days_of_month & (1 << date.day()) != 0;
+        let dow = self.days_of_week & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (true, true) => true,
+            (false, true) => dom,
+            (true, false) => dow,
+            (false, false) => dom || dow,
+        }
+    }
+}
+
+fn invalid(expression: &str, reason: &str) -> AppError {
+    AppError::ValidationError(format!("Invalid schedule '{}': {}", expression, reason))
+}
+
+fn first_of_next_month(date: NaiveDate) -> Option<NaiveDateTime> {
+    let (year, month) = if date.month() == 12 {
+        (date.year() + 1, 1)
+    } else {
+        (date.year(), date.month() + 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)
+}
+
+/// Parse one field into a bit set of values in `min..=max`
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("invalid step '{}'", step))?;
+                if step == 0 {
+                    return Err("step must be greater than 0".to_string());
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                parse_value(start, min, max, names)?,
+                parse_value(end, min, max, names)?,
+            )
+        } else {
+            let value = parse_value(range, min, max, names)?;
+            // `5/15` means "from 5 to the end, every 15".
+            if step > 1 {
+                (value, max)
+            } else {
+                (value, value)
+            }
+        };
+        if start > end {
+            return Err(format!("range '{}' is reversed", range));
+        }
+
+        let mut value = start;
+        while value <= end {
+            bits |= 1 << value;
+            value += step;
+        }
+    }
+    Ok(bits)
+}
+
+fn parse_value(text: &str, min: u32, max: u32, names: &[&str]) -> Result<u32, String> {
+    let lowered = text.to_lowercase();
+    let value = match names.iter().position(|name| *name == lowered) {
+        // Month names start at 1, weekday names at 0.
+        Some(index) => index as u32 + min,
+        None => text
+            .parse::<u32>()
+            .map_err(|_| format!("invalid value '{}'", text))?,
+    };
+    if value < min || value > max {
+        return Err(format!("{} is outside {}-{}", value, min, max));
+    }
+    Ok(value)
+}
+
+/// Next run of `expression` after `after` as Unix milliseconds
+pub fn next_run_at(expression: &str, after: DateTime<Local>) -> AppResult<Option<i64>> {
+    let schedule = CronSchedule::parse(expression)?;
+    Ok(schedule.next_after(after).map(|time| time.timestamp_millis()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+    }
+
+    fn next(expression: &str, after: DateTime<Local>) -> DateTime<Local> {
+        CronSchedule::parse(expression).unwrap().next_after(after).unwrap()
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for expression in ["* * * *", "@often", "60 * * * *", "5-1 * * * *", "*/0 * * * *", "0 0 * foo *"] {
+            assert!(CronSchedule::parse(expression).is_err(), "{} should be rejected", expression);
+        }
+    }
+
+    #[test]
+    fn aliases_names_and_sunday_seven_match_numeric_fields() {
+        let parse = |expression| CronSchedule::parse(expression).unwrap();
+        assert_eq!(parse("@daily"), parse("0 0 * * *"));
+        assert_eq!(parse("@weekly"), parse("0 0 * * 7"));
+        assert_eq!(parse("0 0 * JAN mon-fri"), parse("0 0 * 1 1-5"));
+    }
+
+    #[test]
+    fn steps_advance_strictly_after_the_given_time() {
+        assert_eq!(next("*/15 * * * *", local(2026, 1, 5, 10, 7)), local(2026, 1, 5, 10, 15));
+        assert_eq!(next("*/15 * * * *", local(2026, 1, 5, 10, 15)), local(2026, 1, 5, 10, 30));
+        assert_eq!(next("5/20 * * * *", local(2026, 1, 5, 10, 46)), local(2026, 1, 5, 11, 5));
+    }
+
+    #[test]
+    fn weekday_ranges_skip_the_weekend() {
+        // 2026-01-03 is a Saturday.
+        assert_eq!(next("0 9 * * 1-5", local(2026, 1, 3, 12, 0)), local(2026, 1, 5, 9, 0));
+    }
+
+    #[test]
+    fn restricted_day_of_month_and_weekday_match_either() {
+        // 2026-01-02 is a Friday, before the 13th.
+        assert_eq!(next("0 0 13 * 5", local(2026, 1, 1, 12, 0)), local(2026, 1, 2, 0, 0));
+        assert_eq!(next("0 0 13 * *", local(2026, 1, 1, 12, 0)), local(2026, 1, 13, 0, 0));
+    }
+
+    #[test]
+    fn leap_day_schedules_wait_for_the_next_leap_year() {
+        assert_eq!(next("0 0 29 2 *", local(2026, 1, 1, 0, 0)), local(2028, 2, 29, 0, 0));
+        assert!(CronSchedule::parse("0 0 30 2 *").unwrap().next_after(local(2026, 1, 1, 0, 0)).is_none());
+    }
+}
@@ -0,0 +1,435 @@
+//! Lexical search index service
+//!
+//! Maintains a per-workspace BM25 inverted index over identifiers and text,
+//! persisted under `data_dir/search-index` and refreshed incrementally by
+//! comparing file modification times and sizes. Indexes in memory are also
+//! refreshed in the background (`refresh_loaded`), so file changes are picked up
+//! before the next search. Files matched by `.aiignore` are never indexed, so
+//! auto context cannot retrieve them.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::services::ai_ignore::AiIgnore;
+use crate::services::context::ContextSnippet;
+use crate::utils::error::{AppError, AppResult};
+
+/// Bumped whenever the on-disk format changes
+const INDEX_VERSION: u32 = 1;
+/// Lines per indexed chunk
+const CHUNK_LINES: usize = 40;
+/// Files larger than this are not indexed
+const MAX_FILE_BYTES: u64 = 512 * 1024;
+/// Upper bound on files indexed per workspace
+const MAX_FILES: usize = 20_000;
+/// Tokens longer than this are ignored
+const MAX_TOKEN_LEN: usize = 64;
+
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+/// Per-file bookkeeping used for incremental refresh
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedFile {
+    mtime: u64,
+    size: u64,
+    chunk_ids: Vec<u32>,
+}
+
+/// A contiguous block of lines from one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedChunk {
+    path: String,
+    start_line: usize,
+    end_line: usize,
+    len: u32,
+    terms: Vec<String>,
+}
+
+/// Inverted index for a single workspace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchIndex {
+    version: u32,
+    root: String,
+    next_id: u32,
+    total_len: u64,
+    files: HashMap<String, IndexedFile>,
+    chunks: HashMap<u32, IndexedChunk>,
+    /// term -> [(chunk id, term frequency)]
+    postings: HashMap<String, Vec<(u32, u32)>>,
+}
+
+/// Result of a refresh pass
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexRefreshStats {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub total_files: usize,
+    pub total_chunks: usize,
+}
+
+impl SearchIndex {
+    fn new(root: &str) -> Self {
+        Self {
+            version: INDEX_VERSION,
+            root: root.to_string(),
+            next_id: 0,
+            total_len: 0,
+            files: HashMap::new(),
+            chunks: HashMap::new(),
+            postings: HashMap::new(),
+        }
+    }
+
+    /// Re-index files whose mtime/size changed and drop files that disappeared
+    /// or are now ignored
+    pub fn refresh(&mut self, ignore: &AiIgnore) -> IndexRefreshStats {
+        let root = PathBuf::from(&self.root);
+        let mut stats = IndexRefreshStats::default();
+        let mut seen: HashSet<String> = HashSet::new();
+
+        for path in crate::utils::fs::collect_workspace_files(&root, MAX_FILES) {
+            if ignore.is_ignored(&path) {
+                continue;
+            }
+            let metadata = match fs::metadata(&path) {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+            if metadata.len() > MAX_FILE_BYTES {
+                continue;
+            }
+
+            let rel = crate::utils::fs::relative_path(&root, &path);
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let size = metadata.len();
+            seen.insert(rel.clone());
+
+            let existing = self.files.get(&rel).map(|f| (f.mtime, f.size));
+            if existing == Some((mtime, size)) {
+                continue;
+            }
+
+            let bytes = match fs::read(&path) {
+                Ok(b) => b,
+                Err(_) => continue,
+            };
+            if crate::utils::fs::looks_binary(&bytes) {
+                continue;
+            }
+
+            if existing.is_some() {
+                self.remove_file(&rel);
+                stats.updated += 1;
+            } else {
+                stats.added += 1;
+            }
+            let content = String::from_utf8_lossy(&bytes);
+            self.add_file(&rel, &content, mtime, size);
+        }
+
+        let stale: Vec<String> = self
+            .files
+            .keys()
+            .filter(|k| !seen.contains(*k))
+            .cloned()
+            .collect();
+        for rel in stale {
+            self.remove_file(&rel);
+            stats.removed += 1;
+        }
+
+        stats.total_files = self.files.len();
+        stats.total_chunks = self.chunks.len();
+        stats
+    }
+
+    fn add_file(&mut self, rel: &str, content: &str, mtime: u64, size: u64) {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut chunk_ids = Vec::new();
+
+        for (chunk_idx, block) in lines.chunks(CHUNK_LINES).enumerate() {
+            let mut tf: HashMap<String, u32> = HashMap::new();
+            let mut len = 0u32;
+            for line in block {
+                for token in tokenize(line) {
+                    *tf.entry(token).or_insert(0) += 1;
+                    len += 1;
+                }
+            }
+            if tf.is_empty() {
+                continue;
+            }
+
+            let id = self.next_id;
+            self.next_id = self.next_id.wrapping_add(1);
+            for (term, count) in &tf {
+                self.postings.entry(term.clone()).or_default().push((id, *count));
+            }
+
+            let start_line = chunk_idx * CHUNK_LINES + 1;
+            self.chunks.insert(
+                id,
+                IndexedChunk {
+                    path: rel.to_string(),
+                    start_line,
+                    end_line: start_line + block.len() - 1,
+                    len,
+                    terms: tf.into_keys().collect(),
+                },
+            );
+            self.total_len += len as u64;
+            chunk_ids.push(id);
+        }
+
+        self.files.insert(rel.to_string(), IndexedFile { mtime, size, chunk_ids });
+    }
+
+    fn remove_file(&mut self, rel: &str) {
+        let Some(file) = self.files.remove(rel) else {
+            return;
+        };
+        for id in file.chunk_ids {
+            if let Some(chunk) = self.chunks.remove(&id) {
+                self.total_len = self.total_len.saturating_sub(chunk.len as u64);
+                for term in chunk.terms {
+                    if let Some(list) = self.postings.get_mut(&term) {
+                        list.retain(|(chunk_id, _)| *chunk_id != id);
+                        if list.is_empty() {
+                            self.postings.remove(&term);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Score chunks against `query` with BM25 and return the top `k` snippets
+    pub fn search(&self, query: &str, k: usize) -> Vec<ContextSnippet> {
+        let n = self.chunks.len();
+        if n == 0 || k == 0 {
+            return Vec::new();
+        }
+        let avg_len = (self.total_len as f64 / n as f64).max(1.0);
+
+        let terms: HashSet<String> = tokenize(query).collect();
+        let mut scores: HashMap<u32, f64> = HashMap::new();
+        for term in &terms {
+            let Some(list) = self.postings.get(term) else {
+                continue;
+            };
+            let df = list.len() as f64;
+            let idf = ((n as f64 - df + 0.5) / (df + 0.5) + 1.0).ln();
+            for (id, tf) in list {
+                let Some(chunk) = self.chunks.get(id) else {
+                    continue;
+                };
+                let tf = *tf as f64;
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * chunk.len as f64 / avg_len);
+                *scores.entry(*id).or_insert(0.0) += idf * tf * (BM25_K1 + 1.0) / (tf + norm);
+            }
+        }
+
+        let mut ranked: Vec<(u32, f64)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        let root = PathBuf::from(&self.root);
+        ranked
+            .into_iter()
+            .take(k)
+            .filter_map(|(id, score)| {
+                let chunk = self.chunks.get(&id)?;
+                let abs = root.join(&chunk.path);
+                let content = read_line_range(&abs, chunk.start_line, chunk.end_line)?;
+                Some(ContextSnippet {
+                    path: crate::utils::fs::normalize_path(&abs.to_string_lossy()),
+                    start_line: chunk.start_line,
+                    end_line: chunk.end_line,
+                    content,
+                    score,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Split text into lowercase search terms.
+///
+/// Identifiers are emitted whole and also split on `_` and camelCase
+/// boundaries, so `AiService` matches queries for `ai service`.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty() && word.len() <= MAX_TOKEN_LEN)
+        .flat_map(|word| {
+            let mut out = Vec::new();
+            let whole = word.to_lowercase();
+            let parts = split_identifier(word);
+            if parts.len() > 1 {
+                out.extend(parts.into_iter().filter(|p| p.len() >= 2 && *p != whole));
+            }
+            if whole.len() >= 2 {
+                out.push(whole);
+            }
+            out
+        })
+}
+
+fn split_identifier(word: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    for piece in word.split('_').filter(|p| !p.is_empty()) {
+        let mut current = String::new();
+        let mut prev_lower = false;
+        for ch in piece.chars() {
+            if ch.is_uppercase() && prev_lower && !current.is_empty() {
+                parts.push(current.to_lowercase());
+                current.clear();
+            }
+            prev_lower = ch.is_lowercase() || ch.is_ascii_digit();
+            current.push(ch);
+        }
+        if !current.is_empty() {
+            parts.push(current.to_lowercase());
+        }
+    }
+    parts
+}
+
+fn read_line_range(path: &Path, start_line: usize, end_line: usize) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    let text = content
+        .lines()
+        .skip(start_line.saturating_sub(1))
+        .take(end_line + 1 - start_line)
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(text)
+}
+
+/// Service owning the in-memory indexes, keyed by workspace root
+#[derive(Debug, Default)]
+pub struct SearchIndexService {
+    indexes: Mutex<HashMap<String, SearchIndex>>,
+}
+
+impl SearchIndexService {
+    /// Create a new search index service
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn index_file_path(data_dir: &str, root: &str) -> PathBuf {
+        PathBuf::from(data_dir)
+            .join("search-index")
+            .join(format!("{}.json", crate::utils::hash::stable_hash_hex(root)))
+    }
+
+    fn load_from_disk(data_dir: &str, root: &str) -> Option<SearchIndex> {
+        let path = Self::index_file_path(data_dir, root);
+        let content = fs::read_to_string(&path).ok()?;
+        match serde_json::from_str::<SearchIndex>(&content) {
+            Ok(index) if index.version == INDEX_VERSION && index.root == root => Some(index),
+            Ok(_) => None,
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Discarding unreadable search index");
+                None
+            }
+        }
+    }
+
+    fn save_to_disk(data_dir: &str, index: &SearchIndex) -> AppResult<()> {
+        let path = Self::index_file_path(data_dir, &index.root);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string(index)?;
+        fs::write(&path, json)?;
+        Ok(())
+    }
+
+    /// Bring the index for `root` up to date and persist it
+    pub fn refresh(&self, data_dir: &str, root: &str) -> AppResult<IndexRefreshStats> {
+        let root = crate::utils::fs::normalize_path(root);
+        if !Path::new(&root).is_dir() {
+            return Err(AppError::ValidationError(format!("Workspace directory not found: {}", root)));
+        }
+
+        let mut indexes = self
+            .indexes
+            .lock()
+            .map_err(|e| AppError::GenericError(format!("Failed to lock search indexes: {}", e)))?;
+        let index = indexes
+            .entry(root.clone())
+            .or_insert_with(|| Self::load_from_disk(data_dir, &root).unwrap_or_else(|| SearchIndex::new(&root)));
+
+        let ignore = AiIgnore::load(Some(data_dir), Some(&root));
+        let stats = index.refresh(&ignore);
+        if stats.added + stats.updated + stats.removed > 0 {
+            Self::save_to_disk(data_dir, index)?;
+            info!(
+                root = %root,
+                added = stats.added,
+                updated = stats.updated,
+                removed = stats.removed,
+                "Search index refreshed"
+            );
+        } else {
+            debug!(root = %root, "Search index up to date");
+        }
+        Ok(stats)
+    }
+
+    /// Refresh the index for `root` and return the top `k` snippets for `query`
+    pub fn search(&self, data_dir: &str, root: &str, query: &str, k: usize) -> AppResult<Vec<ContextSnippet>> {
+        self.refresh(data_dir, root)?;
+        let root = crate::utils::fs::normalize_path(root);
+        let indexes = self
+            .indexes
+            .lock()
+            .map_err(|e| AppError::GenericError(format!("Failed to lock search indexes: {}", e)))?;
+        Ok(indexes.get(&root).map(|index| index.search(query, k)).unwrap_or_default())
+    }
+
+    /// Refresh every index in memory, forgetting those whose workspace is gone
+    pub fn refresh_loaded(&self, data_dir: &str) {
+        let roots: Vec<String> = match self.indexes.lock() {
+            Ok(indexes) => indexes.keys().cloned().collect(),
+            Err(_) => return,
+        };
+        for root in roots {
+            if !Path::new(&root).is_dir() {
+                if let Ok(mut indexes) = self.indexes.lock() {
+                    indexes.remove(&root);
+                }
+                continue;
+            }
+            if let Err(e) = self.refresh(data_dir, &root) {
+                warn!(root = %root, error = %e, "Failed to refresh search index");
+            }
+        }
+    }
+
+    /// Drop the index for `root` from memory and disk
+    pub fn clear(&self, data_dir: &str, root: &str) -> AppResult<()> {
+        let root = crate::utils::fs::normalize_path(root);
+        if let Ok(mut indexes) = self.indexes.lock() {
+            indexes.remove(&root);
+        }
+        let path = Self::index_file_path(data_dir, &root);
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+}
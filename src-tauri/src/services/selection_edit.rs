@@ -0,0 +1,376 @@
+//! Selection edit module
+//!
+//! Rewrites a line range of a file according to an instruction, without a full
+//! agent run: the selection and the lines around it go to a native provider or a
+//! code CLI, which answers with a `<replacement>` block. Replies that are not a
+//! clean replacement are sent back with the problem for another attempt. The
+//! result is returned as a unified diff limited to the selected range, so the
+//! editor can preview it before applying.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::services::ai::{AiChatOptions, AiService};
+use crate::services::audit::{AuditContext, InvocationRecorder};
+use crate::services::native_provider::{NativeProvider, ProviderMessage};
+use crate::services::redaction::SecretRedactor;
+use crate::services::workspace_changes::range_diff;
+use crate::utils::error::{AppError, AppResult};
+
+/// Attempts made when the request does not say
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+/// Upper bound on `max_attempts`
+const MAX_ATTEMPTS_LIMIT: u32 = 5;
+/// Lines of the file shown before and after the selection
+const CONTEXT_LINES: usize = 40;
+/// Longest selection that can be edited this way
+pub const MAX_SELECTION_LINES: usize = 500;
+/// Completion budget of one attempt
+const MAX_OUTPUT_TOKENS: u32 = 8192;
+
+const SYSTEM_PROMPT: &str = "You rewrite a selected range of a source file. Reply with the new text for the \
+selected lines inside <replacement></replacement> tags and nothing else. Keep the indentation and style of \
+the surrounding code, do not repeat lines outside the selection and do not modify any files.";
+
+/// What to edit and which backend to ask
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectionEditRequest {
+    pub file_path: String,
+    /// First selected line, 1-based
+    pub start_line: usize,
+    /// Last selected line, 1-based and inclusive
+    pub end_line: usize,
+    pub instruction: String,
+    /// Editor buffer, when it has unsaved changes; the file is read otherwise
+    #[serde(default)]
+    pub content: Option<String>,
+    /// Native provider model; takes precedence over `code_cli`
+    #[serde(default)]
+    pub native_model: Option<String>,
+    /// Code CLI run through codeagent-wrapper (e.g. `claude-cli`)
+    #[serde(default)]
+    pub code_cli: Option<String>,
+    #[serde(default)]
+    pub codex_model: Option<String>,
+    #[serde(default)]
+    pub workspace_dir: Option<String>,
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+}
+
+/// Proposed replacement of the selected range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectionEdit {
+    pub file_path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Selected text the edit was made for; the editor should not apply the
+    /// edit once the range holds something else
+    pub original: String,
+    pub replacement: String,
+    /// Unified diff of the range; empty when nothing changed
+    pub diff: String,
+    /// Attempts used, including the accepted one
+    pub attempts: u32,
+}
+
+/// Backend asked for one-off replies (selection edits, commit messages)
+pub enum EditBackend {
+    Native(NativeProvider),
+    CodeCli {
+        code_cli: String,
+        codex_model: Option<String>,
+        workspace_dir: Option<String>,
+        env: Vec<(String, String)>,
+    },
+}
+
+impl EditBackend {
+    /// Send the conversation and return the reply; a code CLI gets it as one
+    /// read-only task, with the system prompt first. The call is recorded in
+    /// the audit log when `audit` is given.
+    pub async fn ask(
+        &self,
+        system_prompt: &str,
+        conversation: &[ProviderMessage],
+        max_tokens: u32,
+        audit: Option<AuditContext>,
+    ) -> AppResult<String> {
+        match self {
+            EditBackend::Native(provider) => {
+                let mut recorder = InvocationRecorder::start(
+                    audit,
+                    "native",
+                    Some(provider.model()),
+                    provider.endpoint(),
+                    &[],
+                    &[],
+                    None,
+                );
+                let result = provider.complete(Some(system_prompt), conversation, max_tokens).await;
+                if let Ok(reply) = &result {
+                    recorder.set_output(reply, "");
+                }
+                recorder.finish(&result).await;
+                result
+            }
+            EditBackend::CodeCli {
+                code_cli,
+                codex_model,
+                workspace_dir,
+                env,
+            } => {
+                let mut task = system_prompt.to_string();
+                for message in conversation {
+                    let label = if message.role == "assistant" { "Your previous reply" } else { "Request" };
+                    task.push_str(&format!("\n\n## {}\n\n{}", label, message.content));
+                }
+                let result = AiService::new()
+                    .send_message_with_options(
+                        &task,
+                        None,
+                        AiChatOptions {
+                            code_cli: Some(code_cli.clone()),
+                            codex_model: codex_model.clone(),
+                            workspace_dir: workspace_dir.clone(),
+                            code_cli_changed: Some(true),
+                            env: env.clone(),
+                            prepared_task: Some(task.clone()),
+                            audit,
+                            plan_mode: true,
+                            ..Default::default()
+                        },
+                    )
+                    .await?;
+                Ok(result.message)
+            }
+        }
+    }
+}
+
+/// The selected lines and the context around them
+pub struct Selection {
+    pub before: Vec<String>,
+    pub selected: Vec<String>,
+    pub after: Vec<String>,
+}
+
+impl Selection {
+    /// Cut `start_line..=end_line` (1-based) out of `content`
+    pub fn from_content(content: &str, start_line: usize, end_line: usize) -> AppResult<Self> {
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        if start_line == 0 || start_line > end_line || end_line > lines.len() {
+            return Err(AppError::ValidationError(format!(
+                "无效的行范围 {}-{}（文件共 {} 行）",
+                start_line,
+                end_line,
+                lines.len()
+            )));
+        }
+        if end_line - start_line + 1 > MAX_SELECTION_LINES {
+            return Err(AppError::ValidationError(format!(
+                "选区过大，最多 {} 行",
+                MAX_SELECTION_LINES
+            )));
+        }
+        let first = start_line - 1;
+        Ok(Self {
+            before: lines[first.saturating_sub(CONTEXT_LINES)..first].to_vec(),
+            selected: lines[first..end_line].to_vec(),
+            after: lines[end_line..(end_line + CONTEXT_LINES).min(lines.len())].to_vec(),
+        })
+    }
+}
+
+/// Ask `backend` for a replacement of the selection, retrying replies that are
+/// not a clean replacement. Secrets are redacted from the prompt and put back
+/// into the accepted replacement. Every attempt is recorded under `audit`.
+pub async fn request_edit(
+    backend: &EditBackend,
+    request: &SelectionEditRequest,
+    selection: &Selection,
+    redactor: &mut SecretRedactor,
+    audit: Option<AuditContext>,
+) -> AppResult<SelectionEdit> {
+    let max_attempts = request
+        .max_attempts
+        .unwrap_or(DEFAULT_MAX_ATTEMPTS)
+        .clamp(1, MAX_ATTEMPTS_LIMIT);
+    let mut conversation = vec![ProviderMessage::user(redactor.redact(&build_prompt(request, selection)))];
+    let mut last_problem = String::new();
+
+    for attempt in 1..=max_attempts {
+        let reply = backend
+            .ask(SYSTEM_PROMPT, &conversation, MAX_OUTPUT_TOKENS, audit.clone())
+            .await?;
+        match parse_replacement(&reply, selection) {
+            Ok(replacement) => {
+                let replacement = redactor.restore(&replacement);
+                let original = selection.selected.join("\n");
+                let diff = range_diff(
+                    &display_path(&request.file_path, request.workspace_dir.as_deref()),
+                    request.start_line,
+                    &selection.selected.iter().map(String::as_str).collect::<Vec<_>>(),
+                    &replacement.lines().collect::<Vec<_>>(),
+                );
+                debug!(
+                    file = %request.file_path,
+                    attempt,
+                    changed = !diff.is_empty(),
+                    "Selection edit accepted"
+                );
+                return Ok(SelectionEdit {
+                    file_path: request.file_path.clone(),
+                    start_line: request.start_line,
+                    end_line: request.end_line,
+                    original,
+                    replacement,
+                    diff,
+                    attempts: attempt,
+                });
+            }
+            Err(problem) => {
+                warn!(file = %request.file_path, attempt, problem = %problem, "Rejected selection edit reply");
+                conversation.push(ProviderMessage::assistant(reply));
+                conversation.push(ProviderMessage::user(format!(
+                    "Your reply could not be used: {} Reply again with only the new text for lines {}-{} inside \
+                     <replacement></replacement> tags.",
+                    problem, request.start_line, request.end_line
+                )));
+                last_problem = problem;
+            }
+        }
+    }
+
+    Err(AppError::AiServiceError(format!(
+        "{} 次尝试后仍未得到有效的替换内容: {}",
+        max_attempts, last_problem
+    )))
+}
+
+fn build_prompt(request: &SelectionEditRequest, selection: &Selection) -> String {
+    let first = request.start_line;
+    let mut prompt = format!(
+        "File: {}\n\n<instruction>\n{}\n</instruction>\n",
+        display_path(&request.file_path, request.workspace_dir.as_deref()),
+        request.instruction.trim()
+    );
+    if !selection.before.is_empty() {
+        prompt.push_str(&format!(
+            "\n<before lines=\"{}-{}\">\n{}\n</before>\n",
+            first - selection.before.len(),
+            first - 1,
+            selection.before.join("\n")
+        ));
+    }
+    prompt.push_str(&format!(
+        "\n<selection lines=\"{}-{}\">\n{}\n</selection>\n",
+        first,
+        request.end_line,
+        selection.selected.join("\n")
+    ));
+    if !selection.after.is_empty() {
+        prompt.push_str(&format!(
+            "\n<after lines=\"{}-{}\">\n{}\n</after>\n",
+            request.end_line + 1,
+            request.end_line + selection.after.len(),
+            selection.after.join("\n")
+        ));
+    }
+    prompt.push_str("\nReply with the new text for the selected lines inside <replacement></replacement> tags.");
+    prompt
+}
+
+/// Extract the replacement from a reply, or describe why it is not a clean one
+pub fn parse_replacement(reply: &str, selection: &Selection) -> Result<String, String> {
+    const OPEN: &str = "<replacement>";
+    const CLOSE: &str = "</replacement>";
+    let blocks: Vec<&str> = reply
+        .match_indices(OPEN)
+        .filter_map(|(start, _)| {
+            let body = &reply[start + OPEN.len()..];
+            body.find(CLOSE).map(|end| &body[..end])
+        })
+        .collect();
+    let body = match blocks.as_slice() {
+        [] => return Err("it has no <replacement></replacement> block.".to_string()),
+        [body] => *body,
+        _ => return Err("it has more than one <replacement> block.".to_string()),
+    };
+
+    // Tags on their own lines add a newline on each side.
+    let body = body.strip_prefix("\r\n").or_else(|| body.strip_prefix('\n')).unwrap_or(body);
+    let body = body.strip_suffix("\r\n").or_else(|| body.strip_suffix('\n')).unwrap_or(body);
+    let body = strip_code_fence(body);
+
+    let lines: Vec<&str> = body.lines().collect();
+    if lines.len() > MAX_SELECTION_LINES * 4 {
+        return Err(format!("it has {} lines, far more than the selection.", lines.len()));
+    }
+    if repeats_context(&selection.before, &selection.selected, &lines, false) {
+        return Err("it repeats lines from before the selection.".to_string());
+    }
+    if repeats_context(&selection.after, &selection.selected, &lines, true) {
+        return Err("it repeats lines from after the selection.".to_string());
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Inner text of a body that is a single markdown code fence
+fn strip_code_fence(body: &str) -> &str {
+    let trimmed = body.trim();
+    if !trimmed.starts_with("```") || !trimmed.ends_with("```") || trimmed.len() < 6 {
+        return body;
+    }
+    let inner = &trimmed[..trimmed.len() - 3];
+    inner
+        .split_once('\n')
+        .map(|(_, rest)| rest.trim_end_matches(['\n', '\r']))
+        .unwrap_or(body)
+}
+
+/// Whether the replacement starts (or, for `at_end`, ends) with the two context
+/// lines next to the selection, when the selection itself does not
+fn repeats_context(context: &[String], selected: &[String], replacement: &[&str], at_end: bool) -> bool {
+    let neighbours: Vec<&str> = if at_end {
+        context.iter().map(String::as_str).filter(|l| !l.trim().is_empty()).take(2).collect()
+    } else {
+        let mut lines: Vec<&str> = context
+            .iter()
+            .rev()
+            .map(String::as_str)
+            .filter(|l| !l.trim().is_empty())
+            .take(2)
+            .collect();
+        lines.reverse();
+        lines
+    };
+    if neighbours.len() < 2 {
+        return false;
+    }
+    let edge = |lines: &[&str]| -> Vec<String> {
+        let non_blank: Vec<String> = lines
+            .iter()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.trim().to_string())
+            .collect();
+        if at_end {
+            non_blank[non_blank.len().saturating_sub(2)..].to_vec()
+        } else {
+            non_blank.into_iter().take(2).collect()
+        }
+    };
+    let expected: Vec<String> = neighbours.iter().map(|l| l.trim().to_string()).collect();
+    let selected: Vec<&str> = selected.iter().map(String::as_str).collect();
+    edge(replacement) == expected && edge(&selected) != expected
+}
+
+/// Path shown in the prompt and the diff: relative to the workspace when inside it
+fn display_path(file_path: &str, workspace_dir: Option<&str>) -> String {
+    workspace_dir
+        .and_then(|dir| Path::new(file_path).strip_prefix(dir).ok())
+        .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_else(|| file_path.to_string())
+}
@@ -0,0 +1,647 @@
+//! Session import module
+//!
+//! Imports the transcripts code CLIs keep on disk into the chat history:
+//!
+//! - Claude Code: `~/.claude/projects/<project>/<session>.jsonl` (or `$CLAUDE_CONFIG_DIR`)
+//! - Codex: `~/.codex/sessions/**/rollout-*.jsonl` (or `$CODEX_HOME`)
+//! - Gemini CLI: `~/.gemini/tmp/<project hash>/chats/session-*.json`
+//!
+//! A transcript belongs to the registered workspace that contains its recorded
+//! working directory. Gemini records only a SHA-256 of the project directory,
+//! which is compared with the hash of each workspace path. Only the text of
+//! user and assistant turns is kept; tool calls and their output are dropped.
+//!
+//! An imported session is stored as `imported-<code cli>-<native id>` with the
+//! native id in `code_cli_task_ids`, so the app can resume it. Importing again
+//! appends the turns of a grown transcript that are not stored yet, keeping the
+//! messages and metadata the app added. Sessions that were started from this
+//! app are already in the history and are skipped.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use crate::services::chat_session::{self, ChatMessage, ChatSession, MessageMetadata};
+use crate::utils::fs::normalize_path;
+
+/// Prefix of the ids of imported sessions
+const IMPORTED_PREFIX: &str = "imported-";
+/// Transcripts larger than this are skipped
+const MAX_TRANSCRIPT_BYTES: u64 = 64 * 1024 * 1024;
+/// Directory levels searched below the Codex sessions directory (`YYYY/MM/DD`)
+const CODEX_MAX_DEPTH: usize = 4;
+/// User turns Codex adds to every session
+const CODEX_INJECTED_PREFIXES: &[&str] = &["<environment_context>", "<user_instructions>", "# AGENTS.md instructions"];
+/// User turns Claude Code records for slash commands
+const CLAUDE_COMMAND_PREFIXES: &[&str] = &["<command-", "<local-command-"];
+
+/// A code CLI whose transcripts can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptSource {
+    Claude,
+    Codex,
+    Gemini,
+}
+
+impl TranscriptSource {
+    pub const ALL: [TranscriptSource; 3] = [Self::Claude, Self::Codex, Self::Gemini];
+
+    /// Code CLI name; the key of the native session id in `code_cli_task_ids`
+    pub fn code_cli(self) -> &'static str {
+        match self {
+            Self::Claude => "claude-cli",
+            Self::Codex => "codex-cli",
+            Self::Gemini => "gemini-cli",
+        }
+    }
+
+    /// Directory the CLI keeps its transcripts in
+    pub fn default_dir(self) -> Option<PathBuf> {
+        let home_dir = |env: &str, name: &str| {
+            std::env::var_os(env)
+                .map(PathBuf::from)
+                .or_else(|| dirs::home_dir().map(|home| home.join(name)))
+        };
+        match self {
+            Self::Claude => home_dir("CLAUDE_CONFIG_DIR", ".claude").map(|dir| dir.join("projects")),
+            Self::Codex => home_dir("CODEX_HOME", ".codex").map(|dir| dir.join("sessions")),
+            Self::Gemini => dirs::home_dir().map(|home| home.join(".gemini").join("tmp")),
+        }
+    }
+
+    fn transcript_files(self, dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        match self {
+            Self::Claude => {
+                for project in subdirs(dir) {
+                    files.extend(files_with_extension(&project, "jsonl"));
+                }
+            }
+            Self::Codex => collect_recursive(dir, "jsonl", CODEX_MAX_DEPTH, &mut files),
+            Self::Gemini => {
+                for project in subdirs(dir) {
+                    files.extend(files_with_extension(&project.join("chats"), "json"));
+                }
+            }
+        }
+        files.sort();
+        files
+    }
+
+    fn parse(self, path: &Path) -> Option<Transcript> {
+        match self {
+            Self::Claude => parse_claude(path),
+            Self::Codex => parse_codex(path),
+            Self::Gemini => parse_gemini(path),
+        }
+    }
+}
+
+/// A registered workspace transcripts can be mapped to
+#[derive(Debug, Clone)]
+pub struct ImportWorkspace {
+    pub id: String,
+    pub path: String,
+}
+
+/// Outcome of an import
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionImportReport {
+    /// New sessions
+    pub imported: usize,
+    /// Previously imported sessions whose transcript grew
+    pub updated: usize,
+    /// Previously imported sessions without new turns
+    pub unchanged: usize,
+    /// Transcripts recorded outside every registered workspace
+    pub unmapped: usize,
+    /// Sessions started from this app, already in the history
+    pub duplicates: usize,
+    /// Transcripts that could not be read or saved
+    pub errors: Vec<String>,
+}
+
+/// One turn of a transcript
+#[derive(Debug, Clone)]
+struct Turn {
+    id: Option<String>,
+    role: &'static str,
+    content: String,
+    timestamp: Option<String>,
+    model: Option<String>,
+}
+
+/// A session read from a CLI's transcript store
+#[derive(Debug, Default)]
+struct Transcript {
+    native_id: String,
+    cwd: Option<String>,
+    /// SHA-256 of the project directory (Gemini)
+    project_hash: Option<String>,
+    title: Option<String>,
+    turns: Vec<Turn>,
+}
+
+impl Transcript {
+    fn push(&mut self, turn: Turn) {
+        let content = turn.content.trim();
+        if content.is_empty() {
+            return;
+        }
+        // CLIs log a reply in several entries; merge consecutive turns of one role.
+        match self.turns.last_mut() {
+            Some(last) if last.role == turn.role => {
+                last.content.push_str("\n\n");
+                last.content.push_str(content);
+                if turn.timestamp.is_some() {
+                    last.timestamp = turn.timestamp;
+                }
+            }
+            _ => self.turns.push(Turn {
+                content: content.to_string(),
+                ..turn
+            }),
+        }
+    }
+}
+
+/// Import the transcripts of `sources` from their default directories
+pub fn import_sessions(sources: &[TranscriptSource], workspaces: &[ImportWorkspace]) -> SessionImportReport {
+    let mut report = SessionImportReport::default();
+    let known = app_session_ids(workspaces);
+    for source in sources {
+        match source.default_dir() {
+            Some(dir) if dir.is_dir() => import_dir(*source, &dir, workspaces, &known, &mut report),
+            _ => debug!(source = ?source, "No transcript directory to import"),
+        }
+    }
+    info!(
+        imported = report.imported,
+        updated = report.updated,
+        unchanged = report.unchanged,
+        unmapped = report.unmapped,
+        duplicates = report.duplicates,
+        errors = report.errors.len(),
+        "Imported code CLI sessions"
+    );
+    report
+}
+
+/// Import the transcripts of one source from `dir`
+pub fn import_dir(
+    source: TranscriptSource,
+    dir: &Path,
+    workspaces: &[ImportWorkspace],
+    known: &HashSet<(String, String)>,
+    report: &mut SessionImportReport,
+) {
+    let code_cli = source.code_cli();
+    for path in source.transcript_files(dir) {
+        if fs::metadata(&path).map(|m| m.len() > MAX_TRANSCRIPT_BYTES).unwrap_or(true) {
+            continue;
+        }
+        let Some(mut transcript) = source.parse(&path) else {
+            report.errors.push(format!("{}: 无法解析会话记录", path.display()));
+            continue;
+        };
+        if transcript.turns.is_empty() || transcript.native_id.is_empty() {
+            continue;
+        }
+        if !is_native_id(&transcript.native_id) {
+            report.errors.push(format!("{}: 无效的会话 ID", path.display()));
+            continue;
+        }
+        if known.contains(&(code_cli.to_string(), transcript.native_id.clone())) {
+            report.duplicates += 1;
+            continue;
+        }
+        let Some(workspace) = match_workspace(workspaces, &transcript) else {
+            report.unmapped += 1;
+            continue;
+        };
+
+        let session_id = format!("{}{}-{}", IMPORTED_PREFIX, code_cli, transcript.native_id);
+        let existing = chat_session::load_session_by_id(&session_id).ok();
+        if let Some(session) = existing.as_ref() {
+            let turns = std::mem::take(&mut transcript.turns);
+            transcript.turns = new_turns(&session.messages, turns);
+            if transcript.turns.is_empty() {
+                report.unchanged += 1;
+                continue;
+            }
+        }
+        let session = to_session(&session_id, code_cli, workspace, transcript, existing.as_ref());
+        match chat_session::write_session(&session) {
+            Ok(()) if existing.is_some() => report.updated += 1,
+            Ok(()) => report.imported += 1,
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Failed to save imported session");
+                report.errors.push(format!("{}: {}", path.display(), e));
+            }
+        }
+    }
+}
+
+/// Native ids become part of the session file name; accept only UUID-like ids
+fn is_native_id(id: &str) -> bool {
+    id.len() <= 64 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Native ids of sessions that were started from this app, as `(code cli, id)`
+pub fn app_session_ids(workspaces: &[ImportWorkspace]) -> HashSet<(String, String)> {
+    workspaces
+        .iter()
+        .filter_map(|workspace| chat_session::load_all_sessions(workspace.id.clone(), None).ok())
+        .flatten()
+        .filter(|session| !session.id.starts_with(IMPORTED_PREFIX))
+        .flat_map(|session| session.code_cli_task_ids.into_iter())
+        .collect()
+}
+
+/// The turns of a transcript that are not among the stored messages yet.
+///
+/// A turn is stored when a message has its native id, or the same role and text
+/// (turns sent from the app are stored under the app's own ids).
+fn new_turns(stored: &[ChatMessage], turns: Vec<Turn>) -> Vec<Turn> {
+    let mut matched = vec![false; stored.len()];
+    turns
+        .into_iter()
+        .filter(|turn| {
+            let found = stored
+                .iter()
+                .enumerate()
+                .find(|(idx, message)| {
+                    !matched[*idx]
+                        && (turn.id.as_deref() == Some(message.id.as_str())
+                            || (message.role == turn.role && message.content.trim() == turn.content.trim()))
+                })
+                .map(|(idx, _)| idx);
+            match found {
+                Some(idx) => {
+                    matched[idx] = true;
+                    false
+                }
+                None => true,
+            }
+        })
+        .collect()
+}
+
+/// Build the session of a transcript; on re-import `transcript` holds only the
+/// new turns, which are appended to the existing messages
+fn to_session(
+    session_id: &str,
+    code_cli: &str,
+    workspace: &ImportWorkspace,
+    transcript: Transcript,
+    existing: Option<&ChatSession>,
+) -> ChatSession {
+    let fallback_time = existing
+        .map(|session| session.created_at.clone())
+        .unwrap_or_else(|| Local::now().to_rfc3339());
+    let mut messages: Vec<ChatMessage> = existing.map(|session| session.messages.clone()).unwrap_or_default();
+    messages.extend(transcript.turns.into_iter().map(|turn| {
+        ChatMessage {
+            id: turn.id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            role: turn.role.to_string(),
+            content: turn.content,
+            timestamp: turn
+                .timestamp
+                .as_deref()
+                .and_then(local_time)
+                .unwrap_or_else(|| fallback_time.clone()),
+            files: None,
+            model: turn.model,
+            session_id: Some(session_id.to_string()),
+            workspace_id: Some(workspace.id.clone()),
+            // Tagged so a handoff knows the CLI's own session has every turn.
+            metadata: (turn.role == "assistant").then(|| MessageMetadata {
+                code_cli: Some(code_cli.to_string()),
+                ..Default::default()
+            }),
+        }
+    }));
+
+    let created_at = messages.first().map(|m| m.timestamp.clone()).unwrap_or(fallback_time);
+    let updated_at = messages.last().map(|m| m.timestamp.clone()).unwrap_or_else(|| created_at.clone());
+    let mut code_cli_task_ids = existing
+        .map(|session| session.code_cli_task_ids.clone())
+        .unwrap_or_default();
+    code_cli_task_ids.insert(code_cli.to_string(), transcript.native_id);
+    ChatSession {
+        id: session_id.to_string(),
+        // A name given in the app wins over the transcript's title.
+        name: existing.and_then(|session| session.name.clone()).or(transcript.title),
+        session_id: Some(session_id.to_string()),
+        workspace_id: Some(workspace.id.clone()),
+        first_message_preview: chat_session::first_message_preview(&messages),
+        message_count: messages.len(),
+        messages,
+        created_at,
+        updated_at,
+        code_cli_task_ids,
+        summary: existing.and_then(|session| session.summary.clone()),
+    }
+}
+
+/// The workspace containing the transcript's directory (the innermost one)
+fn match_workspace<'a>(workspaces: &'a [ImportWorkspace], transcript: &Transcript) -> Option<&'a ImportWorkspace> {
+    if let Some(cwd) = transcript.cwd.as_deref() {
+        let cwd = normalize_path(cwd);
+        let cwd = cwd.trim_end_matches('/');
+        return workspaces
+            .iter()
+            .filter(|workspace| {
+                let root = normalize_path(&workspace.path);
+                let root = root.trim_end_matches('/');
+                cwd == root || cwd.strip_prefix(root).is_some_and(|rest| rest.starts_with('/'))
+            })
+            .max_by_key(|workspace| workspace.path.len());
+    }
+    let hash = transcript.project_hash.as_deref()?;
+    workspaces.iter().find(|workspace| {
+        let trimmed = workspace.path.trim_end_matches(['/', '\\']);
+        sha256_hex(trimmed.as_bytes()) == hash || sha256_hex(workspace.path.as_bytes()) == hash
+    })
+}
+
+fn parse_claude(path: &Path) -> Option<Transcript> {
+    let text = fs::read_to_string(path).ok()?;
+    let mut transcript = Transcript {
+        native_id: file_stem(path),
+        ..Default::default()
+    };
+    for entry in text.lines().filter_map(|line| serde_json::from_str::<Value>(line).ok()) {
+        let kind = entry.get("type").and_then(Value::as_str).unwrap_or_default();
+        if kind == "summary" {
+            transcript.title = str_field(&entry, "summary").or(transcript.title);
+            continue;
+        }
+        let role = match kind {
+            "user" => "user",
+            "assistant" => "assistant",
+            _ => continue,
+        };
+        let flagged = |key: &str| entry.get(key).and_then(Value::as_bool).unwrap_or(false);
+        if flagged("isSidechain") || flagged("isMeta") || flagged("isCompactSummary") {
+            continue;
+        }
+        if transcript.cwd.is_none() {
+            transcript.cwd = str_field(&entry, "cwd");
+        }
+        let Some(message) = entry.get("message") else {
+            continue;
+        };
+        let content = content_text(message.get("content"));
+        if role == "user" && CLAUDE_COMMAND_PREFIXES.iter().any(|p| content.trim_start().starts_with(p)) {
+            continue;
+        }
+        transcript.push(Turn {
+            id: str_field(&entry, "uuid"),
+            role,
+            content,
+            timestamp: str_field(&entry, "timestamp"),
+            // Claude Code logs its own error notices under a `<synthetic>` model.
+            model: str_field(message, "model").filter(|model| model != "<synthetic>"),
+        });
+    }
+    Some(transcript)
+}
+
+fn parse_codex(path: &Path) -> Option<Transcript> {
+    let text = fs::read_to_string(path).ok()?;
+    // `rollout-<time>-<uuid>.jsonl`
+    let stem = file_stem(path);
+    let mut transcript = Transcript {
+        native_id: stem.get(stem.len().saturating_sub(36)..).unwrap_or_default().to_string(),
+        ..Default::default()
+    };
+    for entry in text.lines().filter_map(|line| serde_json::from_str::<Value>(line).ok()) {
+        let timestamp = str_field(&entry, "timestamp");
+        let item = match entry.get("type").and_then(Value::as_str) {
+            Some("session_meta") => {
+                let meta = entry.get("payload").unwrap_or(&Value::Null);
+                transcript.native_id = str_field(meta, "id").unwrap_or(transcript.native_id);
+                transcript.cwd = str_field(meta, "cwd").or(transcript.cwd);
+                continue;
+            }
+            Some("response_item") => entry.get("payload").unwrap_or(&Value::Null),
+            // Older rollouts: a header line, then the items themselves
+            Some("message") => &entry,
+            None => {
+                transcript.native_id = str_field(&entry, "id").unwrap_or(transcript.native_id);
+                continue;
+            }
+            _ => continue,
+        };
+        if item.get("type").and_then(Value::as_str) != Some("message") {
+            continue;
+        }
+        let role = match item.get("role").and_then(Value::as_str) {
+            Some("user") => "user",
+            Some("assistant") => "assistant",
+            _ => continue,
+        };
+        let content = content_text(item.get("content"));
+        if role == "user" && CODEX_INJECTED_PREFIXES.iter().any(|p| content.trim_start().starts_with(p)) {
+            if transcript.cwd.is_none() {
+                transcript.cwd = tag_text(&content, "cwd");
+            }
+            continue;
+        }
+        transcript.push(Turn {
+            id: None,
+            role,
+            content,
+            timestamp,
+            model: None,
+        });
+    }
+    Some(transcript)
+}
+
+fn parse_gemini(path: &Path) -> Option<Transcript> {
+    let text = fs::read_to_string(path).ok()?;
+    let record: Value = serde_json::from_str(&text).ok()?;
+    let mut transcript = Transcript {
+        native_id: str_field(&record, "sessionId")?,
+        project_hash: str_field(&record, "projectHash").or_else(|| {
+            // `<hash>/chats/session-*.json`
+            path.parent()?.parent()?.file_name().map(|name| name.to_string_lossy().into_owned())
+        }),
+        ..Default::default()
+    };
+    for message in record.get("messages").and_then(Value::as_array).into_iter().flatten() {
+        let role = match message.get("type").and_then(Value::as_str) {
+            Some("user") => "user",
+            Some("gemini") | Some("model") => "assistant",
+            _ => continue,
+        };
+        transcript.push(Turn {
+            id: str_field(message, "id"),
+            role,
+            content: content_text(message.get("content")),
+            timestamp: str_field(message, "timestamp"),
+            model: str_field(message, "model"),
+        });
+    }
+    Some(transcript)
+}
+
+/// Text of a message content: a string, or the text parts of a part list
+fn content_text(content: Option<&Value>) -> String {
+    match content {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter(|part| {
+                part.get("type")
+                    .and_then(Value::as_str)
+                    .map_or(true, |kind| matches!(kind, "text" | "input_text" | "output_text"))
+            })
+            .filter_map(|part| part.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+/// Text between `<tag>` and `</tag>`
+fn tag_text(text: &str, tag: &str) -> Option<String> {
+    let start = text.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + text[start..].find(&format!("</{}>", tag))?;
+    Some(text[start..end].trim().to_string()).filter(|s| !s.is_empty())
+}
+
+fn local_time(timestamp: &str) -> Option<String> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|time| time.with_timezone(&Local).to_rfc3339())
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn files_with_extension(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == extension))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn collect_recursive(dir: &Path, extension: &str, depth: usize, files: &mut Vec<PathBuf>) {
+    files.extend(files_with_extension(dir, extension));
+    if depth > 0 {
+        for sub in subdirs(dir) {
+            collect_recursive(&sub, extension, depth - 1, files);
+        }
+    }
+}
+
+/// Hex SHA-256, as Gemini CLI uses for its project directories
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(id: Option<&str>, role: &'static str, content: &str) -> Turn {
+        Turn {
+            id: id.map(str::to_string),
+            role,
+            content: content.to_string(),
+            timestamp: None,
+            model: None,
+        }
+    }
+
+    fn message(id: &str, role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            id: id.to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            timestamp: String::new(),
+            files: None,
+            model: None,
+            session_id: None,
+            workspace_id: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn hashes_like_gemini() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn only_unstored_turns_are_new() {
+        let stored = vec![
+            message("n1", "user", "fix the bug"),
+            message("n2", "assistant", "done"),
+            // Sent from the app after the import
+            message("app-1", "user", "continue"),
+            message("app-2", "assistant", "continued"),
+        ];
+        let turns = vec![
+            turn(Some("n1"), "user", "fix the bug"),
+            turn(Some("n2"), "assistant", "done"),
+            turn(Some("n3"), "user", "continue"),
+            turn(Some("n4"), "assistant", "continued"),
+            turn(Some("n5"), "user", "continue"),
+            turn(None, "assistant", "more"),
+        ];
+        let new: Vec<String> = new_turns(&stored, turns).into_iter().map(|t| t.content).collect();
+        assert_eq!(new, vec!["continue", "more"]);
+    }
+
+    #[test]
+    fn native_ids_are_uuid_like() {
+        assert!(is_native_id("0199a1b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b"));
+        assert!(!is_native_id("../../etc/passwd"));
+        assert!(!is_native_id("a/b"));
+        assert!(!is_native_id("id.json"));
+        assert!(!is_native_id(&"a".repeat(65)));
+    }
+}
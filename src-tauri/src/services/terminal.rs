@@ -2,14 +2,46 @@
 //!
 //! This module handles terminal session management.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
 use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 use tracing::{error, info};
 use uuid::Uuid;
 
 use crate::utils::error::{AppError, AppResult};
 
+/// Commands kept per session for `@terminal:` references
+const MAX_HISTORY_ENTRIES: usize = 20;
+/// Output kept per command, in bytes (the end is kept)
+const MAX_ENTRY_OUTPUT_BYTES: usize = 16 * 1024;
+
+/// A command run in a terminal session and its output
+#[derive(Debug, Clone)]
+pub struct TerminalEntry {
+    pub command: String,
+    /// stdout followed by stderr
+    pub output: String,
+}
+
+/// Output and exit code of a finished command
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// `None` when the process was ended by a signal
+    pub exit_code: Option<i32>,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
 /// Terminal session
 #[derive(Debug)]
 pub struct TerminalSession {
@@ -19,6 +51,10 @@ pub struct TerminalSession {
     pub name: String,
     /// Working directory
     pub cwd: String,
+    /// Creation order, used to number sessions like the terminal tabs
+    seq: u64,
+    /// Recent commands, oldest first
+    history: VecDeque<TerminalEntry>,
     /// Process handle
     process: Option<Child>,
 }
@@ -30,6 +66,8 @@ impl TerminalSession {
             id: Uuid::new_v4().to_string(),
             name,
             cwd,
+            seq: 0,
+            history: VecDeque::new(),
             process: None,
         }
     }
@@ -49,6 +87,8 @@ impl TerminalSession {
 pub struct TerminalService {
     /// Active terminal sessions
     sessions: Arc<Mutex<HashMap<String, TerminalSession>>>,
+    /// Sessions created so far
+    created: AtomicU64,
 }
 
 impl TerminalService {
@@ -56,6 +96,7 @@ impl TerminalService {
     pub fn new() -> Self {
         Self {
             sessions: Arc::new(Mutex::new(HashMap::new())),
+            created: AtomicU64::new(0),
         }
     }
 
@@ -64,7 +105,8 @@ impl TerminalService {
         let session_name = name.unwrap_or_else(|| format!("Terminal {}", self.session_count() + 1));
         let working_dir = cwd.unwrap_or_else(|| ".".to_string());
 
-        let session = TerminalSession::new(session_name, working_dir);
+        let mut session = TerminalSession::new(session_name, working_dir);
+        session.seq = self.created.fetch_add(1, Ordering::Relaxed);
         let session_id = session.id.clone();
 
         let mut sessions = self.sessions.lock().map_err(|e| {
@@ -98,6 +140,23 @@ impl TerminalService {
         shell: &str,
         command_line: &str,
     ) -> AppResult<String> {
+        let output = self.run_command(session_id, shell, command_line, None)?;
+        if !output.stderr.is_empty() {
+            error!("Command stderr: {}", output.stderr);
+        }
+        Ok(output.stdout)
+    }
+
+    /// Execute command in a session and return its output and exit code.
+    ///
+    /// Blocks until the command exits; setting `cancelled` kills it.
+    pub fn run_command(
+        &self,
+        session_id: &str,
+        shell: &str,
+        command_line: &str,
+        cancelled: Option<&AtomicBool>,
+    ) -> AppResult<CommandOutput> {
         // 先在短时间内获取会话工作目录，然后释放锁，避免长时间持有锁阻塞并行执行
         let cwd = {
             let sessions = self.sessions.lock().map_err(|e| {
@@ -111,74 +170,78 @@ impl TerminalService {
             session.cwd.clone()
         };
 
-        // 根据前端选择的 shell 校验并构造具体命令
-        #[cfg(target_os = "windows")]
-        let mut cmd = {
-            let shell_norm = shell.trim().to_lowercase();
-
-            if shell_norm.starts_with("powershell") || shell_norm == "pwsh" {
-                let mut c = Command::new("powershell.exe");
-                c.arg("-NoLogo")
-                    .arg("-NoProfile")
-                    .arg("-Command")
-                    .arg(command_line);
-                c
-            } else if shell_norm == "cmd" || shell_norm == "cmd.exe" {
-                let mut c = Command::new("cmd.exe");
-                c.arg("/C").arg(command_line);
-                c
-            } else {
-                return Err(AppError::ProcessError(format!(
-                    "Unsupported shell on Windows: {}",
-                    shell
-                )));
-            }
-        };
-
-        #[cfg(not(target_os = "windows"))]
-        let mut cmd = {
-            let shell_norm = shell.trim().to_lowercase();
-
-            if shell_norm == "bash" {
-                let mut c = Command::new("bash");
-                c.arg("-lc").arg(command_line);
-                c
-            } else if shell_norm == "zsh" {
-                let mut c = Command::new("zsh");
-                c.arg("-lc").arg(command_line);
-                c
-            } else if shell_norm == "sh" {
-                let mut c = Command::new("sh");
-                c.arg("-lc").arg(command_line);
-                c
-            } else {
-                return Err(AppError::ProcessError(format!(
-                    "Unsupported shell on Unix-like system: {}",
-                    shell
-                )));
-            }
-        };
+        let mut cmd = shell_command(shell, command_line)?;
 
         info!(
             "Executing terminal command in session {} with shell '{}': {}",
             session_id, shell, command_line
         );
 
-        let output = cmd
+        let mut child = cmd
             .current_dir(&cwd)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .output()
+            .spawn()
             .map_err(|e| AppError::ProcessError(e.to_string()))?;
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        // Drain both pipes while waiting, so a chatty command cannot fill one and stall.
+        let stdout_reader = child.stdout.take().map(read_to_end_in_background);
+        let stderr_reader = child.stderr.take().map(read_to_end_in_background);
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(|e| AppError::ProcessError(e.to_string()))? {
+                break status;
+            }
+            if cancelled.is_some_and(|flag| flag.load(Ordering::Relaxed)) {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(AppError::Cancelled(format!("Command cancelled: {}", command_line)));
+            }
+            thread::sleep(Duration::from_millis(100));
+        };
+
+        let collect = |reader: Option<thread::JoinHandle<Vec<u8>>>| {
+            reader
+                .and_then(|handle| handle.join().ok())
+                .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+                .unwrap_or_default()
+        };
+        let output = CommandOutput {
+            stdout: collect(stdout_reader),
+            stderr: collect(stderr_reader),
+            exit_code: status.code(),
+        };
+
+        self.record(session_id, command_line, &format!("{}{}", output.stdout, output.stderr));
+        Ok(output)
+    }
 
-        if !stderr.is_empty() {
-            error!("Command stderr: {}", stderr);
+    fn record(&self, session_id: &str, command_line: &str, output: &str) {
+        let Ok(mut sessions) = self.sessions.lock() else {
+            return;
+        };
+        let Some(session) = sessions.get_mut(session_id) else {
+            return;
+        };
+        let mut start = output.len().saturating_sub(MAX_ENTRY_OUTPUT_BYTES);
+        while !output.is_char_boundary(start) {
+            start += 1;
         }
+        session.history.push_back(TerminalEntry {
+            command: command_line.to_string(),
+            output: output[start..].to_string(),
+        });
+        while session.history.len() > MAX_HISTORY_ENTRIES {
+            session.history.pop_front();
+        }
+    }
 
-        Ok(stdout.to_string())
+    /// Name and recent commands of the `number`-th open session (1-based, in creation order)
+    pub fn recent_history(&self, number: usize) -> Option<(String, Vec<TerminalEntry>)> {
+        let sessions = self.sessions.lock().ok()?;
+        let mut ordered: Vec<&TerminalSession> = sessions.values().collect();
+        ordered.sort_by_key(|session| session.seq);
+        let session = ordered.get(number.checked_sub(1)?)?;
+        Some((session.name.clone(), session.history.iter().cloned().collect()))
     }
 
     /// Get session count
@@ -195,6 +258,53 @@ impl TerminalService {
     }
 }
 
+/// Build the command that runs `command_line` in the shell picked in the UI
+fn shell_command(shell: &str, command_line: &str) -> AppResult<Command> {
+    // 根据前端选择的 shell 校验并构造具体命令
+    let shell_norm = shell.trim().to_lowercase();
+
+    #[cfg(target_os = "windows")]
+    let cmd = if shell_norm.starts_with("powershell") || shell_norm == "pwsh" {
+        let mut c = Command::new("powershell.exe");
+        c.arg("-NoLogo")
+            .arg("-NoProfile")
+            .arg("-Command")
+            .arg(command_line);
+        c
+    } else if shell_norm == "cmd" || shell_norm == "cmd.exe" {
+        let mut c = Command::new("cmd.exe");
+        c.arg("/C").arg(command_line);
+        c
+    } else {
+        return Err(AppError::ProcessError(format!(
+            "Unsupported shell on Windows: {}",
+            shell
+        )));
+    };
+
+    #[cfg(not(target_os = "windows"))]
+    let cmd = if shell_norm == "bash" || shell_norm == "zsh" || shell_norm == "sh" {
+        let mut c = Command::new(&shell_norm);
+        c.arg("-lc").arg(command_line);
+        c
+    } else {
+        return Err(AppError::ProcessError(format!(
+            "Unsupported shell on Unix-like system: {}",
+            shell
+        )));
+    };
+
+    Ok(cmd)
+}
+
+fn read_to_end_in_background(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
 impl Default for TerminalService {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,179 @@
+//! Test-fix loop module
+//!
+//! Runs a test command, cuts the failing part out of its output and asks a code
+//! CLI to fix it, then runs the tests again. The loop ends when the tests pass,
+//! the iteration limit or the workspace budget is reached, or it is cancelled.
+//! This module holds the request, the failure extraction and the prompts; the
+//! loop itself is driven by `tauri::test_fix_commands`.
+
+use serde::{Deserialize, Serialize};
+
+/// Fix attempts made when the request does not say
+pub const DEFAULT_MAX_ITERATIONS: u32 = 3;
+/// Upper bound on `max_iterations`
+const MAX_ITERATIONS_LIMIT: u32 = 10;
+/// Failure output sent with one fix request, in characters (the end is kept)
+const MAX_FAILURE_CHARS: usize = 12_000;
+/// Lines kept before and after each line that looks like a failure
+const LINES_BEFORE_MARKER: usize = 3;
+const LINES_AFTER_MARKER: usize = 20;
+/// Lines kept from the end of the output when nothing looks like a failure
+const FALLBACK_TAIL_LINES: usize = 150;
+
+/// Substrings (compared lowercase) of lines that report a failing test
+const FAILURE_MARKERS: &[&str] = &[
+    "fail",
+    "error",
+    "panicked",
+    "assert",
+    "expected",
+    "traceback",
+    "exception",
+    "✗",
+    "✕",
+];
+
+/// Test command and backend of a test-fix loop
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestFixRequest {
+    /// Command that runs the tests, e.g. `cargo test -p foo`
+    pub test_command: String,
+    /// Shell the command runs in; `bash` (or `powershell` on Windows) when not set
+    #[serde(default)]
+    pub shell: Option<String>,
+    pub workspace_dir: String,
+    #[serde(default)]
+    pub workspace_id: Option<String>,
+    /// Chat session the fix requests are streamed to; a new one when not set
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Code CLI asked for the fixes (e.g. `claude-cli`)
+    pub code_cli: String,
+    #[serde(default)]
+    pub codex_model: Option<String>,
+    #[serde(default)]
+    pub direct_cli: bool,
+    #[serde(default)]
+    pub cli_command: Option<String>,
+    #[serde(default)]
+    pub cli_args: Vec<String>,
+    /// Fix attempts before giving up
+    #[serde(default)]
+    pub max_iterations: Option<u32>,
+}
+
+impl TestFixRequest {
+    pub fn max_iterations(&self) -> u32 {
+        self.max_iterations
+            .unwrap_or(DEFAULT_MAX_ITERATIONS)
+            .clamp(1, MAX_ITERATIONS_LIMIT)
+    }
+
+    pub fn shell(&self) -> String {
+        self.shell
+            .clone()
+            .filter(|shell| !shell.trim().is_empty())
+            .unwrap_or_else(|| if cfg!(windows) { "powershell" } else { "bash" }.to_string())
+    }
+}
+
+/// How a test-fix loop ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestFixStatus {
+    /// The tests pass
+    Passed,
+    /// The tests still fail after the last fix attempt
+    Failed,
+    /// The workspace budget refused a fix request
+    BudgetExhausted,
+    Cancelled,
+    /// The tests could not be run or a fix request failed
+    Error,
+}
+
+/// One run of the tests, and the fix request made for its failures
+#[derive(Debug, Clone, Serialize)]
+pub struct TestFixIteration {
+    /// 1-based; the run after the last fix has `max_iterations + 1`
+    pub iteration: u32,
+    pub passed: bool,
+    pub exit_code: Option<i32>,
+    /// Failing part of the output; empty when the tests pass
+    pub failures: String,
+    /// Chat request that streams the fix; its `files-changed-by-run` event has the diff
+    pub fix_request_id: Option<String>,
+}
+
+/// The part of a test run's output that shows what failed, within `MAX_FAILURE_CHARS`
+pub fn extract_failures(output: &str) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+
+    // cargo test prints the panics of failing tests under `failures:`, then lists their names.
+    if let Some(start) = lines.iter().position(|line| line.trim() == "failures:") {
+        let end = lines
+            .iter()
+            .rposition(|line| line.trim_start().starts_with("test result:"))
+            .filter(|&end| end > start)
+            .map(|end| end + 1)
+            .unwrap_or(lines.len());
+        return keep_end(&lines[start..end].join("\n"));
+    }
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (idx, line) in lines.iter().enumerate() {
+        let lower = line.to_lowercase();
+        if !FAILURE_MARKERS.iter().any(|marker| lower.contains(marker)) || is_passing_summary(&lower) {
+            continue;
+        }
+        let start = idx.saturating_sub(LINES_BEFORE_MARKER);
+        let end = (idx + LINES_AFTER_MARKER + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => ranges.push((start, end)),
+        }
+    }
+    if ranges.is_empty() {
+        ranges.push((lines.len().saturating_sub(FALLBACK_TAIL_LINES), lines.len()));
+    }
+
+    let excerpt = ranges
+        .iter()
+        .map(|&(start, end)| lines[start..end].join("\n"))
+        .collect::<Vec<_>>()
+        .join("\n...\n");
+    keep_end(&excerpt)
+}
+
+/// Summary lines such as `0 failed` or `no errors` that mention a marker without reporting a failure
+fn is_passing_summary(lower: &str) -> bool {
+    lower.contains(" 0 failed") || lower.contains("0 errors") || lower.contains("no errors")
+}
+
+/// The last `MAX_FAILURE_CHARS` characters, starting on a line boundary
+fn keep_end(text: &str) -> String {
+    if text.len() <= MAX_FAILURE_CHARS {
+        return text.to_string();
+    }
+    let mut start = text.len() - MAX_FAILURE_CHARS;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    let tail = &text[start..];
+    let tail = tail.split_once('\n').map(|(_, rest)| rest).unwrap_or(tail);
+    format!("... (earlier output truncated)\n{}", tail)
+}
+
+/// Chat message asking the backend to fix the failures of one test run
+pub fn fix_message(run_id: &str, test_command: &str, iteration: u32, max_iterations: u32, failures: &str) -> String {
+    format!(
+        "Test-fix run {} (attempt {} of {}): `{}` fails in this workspace. Find the cause and fix it by \
+         editing the code. Only change the tests if they are clearly wrong, and say so. The test command \
+         is run again after your changes.\n\n<test-failures>\n{}\n</test-failures>",
+        run_id,
+        iteration,
+        max_iterations,
+        test_command.trim(),
+        failures.trim()
+    )
+}
@@ -0,0 +1,218 @@
+//! Workspace budget module
+//!
+//! Per-workspace guardrails for AI usage: a monthly token and cost budget and a
+//! limit on requests per rolling hour. Budgets and usage live in the settings
+//! table under the `workspace` category (`workspace.<id>.budget` and
+//! `workspace.<id>.usage`).
+//!
+//! The backends do not report token counts, so usage is estimated at about four
+//! characters per token from the request and the streamed reply. Once a hard
+//! limit is reached new requests are refused with `AppError::BudgetExceeded`,
+//! unless the user granted a temporary override.
+
+use chrono::TimeZone;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+
+use crate::database::repositories::settings_repository::SettingsRepository;
+use crate::utils::error::{AppError, AppResult};
+
+/// Settings category of budgets and usage
+pub const SETTINGS_CATEGORY: &str = "workspace";
+/// Length of an override granted without an explicit duration
+pub const DEFAULT_OVERRIDE_MINUTES: u32 = 60;
+const HOUR_MS: i64 = 60 * 60 * 1000;
+
+/// Limits of one workspace; every limit is optional
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkspaceBudget {
+    /// Estimated tokens per calendar month
+    pub monthly_token_limit: Option<u64>,
+    /// Estimated cost per calendar month, in the currency of `cost_per_million_tokens`
+    pub monthly_cost_limit: Option<f64>,
+    /// Price used to turn estimated tokens into cost
+    pub cost_per_million_tokens: f64,
+    /// Requests per rolling hour
+    pub hourly_request_limit: Option<u32>,
+    /// Shares of the monthly budget (0-1) at which a notification is shown
+    pub warn_thresholds: Vec<f64>,
+}
+
+impl Default for WorkspaceBudget {
+    fn default() -> Self {
+        Self {
+            monthly_token_limit: None,
+            monthly_cost_limit: None,
+            cost_per_million_tokens: 0.0,
+            hourly_request_limit: None,
+            warn_thresholds: vec![0.8, 0.95],
+        }
+    }
+}
+
+/// Usage of one workspace in the current month
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkspaceUsage {
+    /// Month the totals belong to, `YYYY-MM`
+    pub month: String,
+    pub tokens: u64,
+    pub cost: f64,
+    pub requests: u64,
+    /// Start times (ms) of the requests of the last hour
+    pub recent_requests: Vec<i64>,
+    /// Highest warning threshold already notified this month
+    pub warned_threshold: f64,
+    /// Hard limits are not enforced before this time (ms)
+    pub override_until: Option<i64>,
+}
+
+impl WorkspaceBudget {
+    /// Share of the monthly budget used: the larger of the token and cost shares
+    pub fn used_fraction(&self, usage: &WorkspaceUsage) -> Option<f64> {
+        let tokens = self
+            .monthly_token_limit
+            .filter(|limit| *limit > 0)
+            .map(|limit| usage.tokens as f64 / limit as f64);
+        let cost = self
+            .monthly_cost_limit
+            .filter(|limit| *limit > 0.0)
+            .map(|limit| usage.cost / limit);
+        match (tokens, cost) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Why a new request must be refused, if it must (overrides are not considered)
+    pub fn exceeded(&self, usage: &WorkspaceUsage) -> Option<String> {
+        if let Some(limit) = self.monthly_token_limit.filter(|limit| *limit > 0) {
+            if usage.tokens >= limit {
+                return Some(format!("本月预算已用尽：约 {} / {} tokens", usage.tokens, limit));
+            }
+        }
+        if let Some(limit) = self.monthly_cost_limit.filter(|limit| *limit > 0.0) {
+            if usage.cost >= limit {
+                return Some(format!("本月费用已达上限：{:.2} / {:.2}", usage.cost, limit));
+            }
+        }
+        if let Some(limit) = self.hourly_request_limit.filter(|limit| *limit > 0) {
+            if usage.recent_requests.len() >= limit as usize {
+                return Some(format!("已达到每小时 {} 次请求的上限", limit));
+            }
+        }
+        None
+    }
+
+    /// The highest warning threshold reached but not yet notified
+    pub fn crossed_threshold(&self, usage: &WorkspaceUsage) -> Option<f64> {
+        let used = self.used_fraction(usage)?;
+        self.warn_thresholds
+            .iter()
+            .copied()
+            .filter(|threshold| *threshold > usage.warned_threshold && used >= *threshold)
+            .reduce(f64::max)
+    }
+}
+
+impl WorkspaceUsage {
+    /// Start over in a new month and forget requests older than an hour
+    pub fn roll(&mut self, now_ms: i64) {
+        let month = month_of(now_ms);
+        if self.month != month {
+            self.month = month;
+            self.tokens = 0;
+            self.cost = 0.0;
+            self.requests = 0;
+            self.warned_threshold = 0.0;
+        }
+        self.recent_requests.retain(|started| now_ms - started < HOUR_MS);
+    }
+
+    pub fn add_request(&mut self, now_ms: i64) {
+        self.requests += 1;
+        self.recent_requests.push(now_ms);
+    }
+
+    pub fn add_tokens(&mut self, budget: &WorkspaceBudget, tokens: u64) {
+        self.tokens += tokens;
+        self.cost += tokens as f64 * budget.cost_per_million_tokens / 1_000_000.0;
+    }
+
+    pub fn is_overridden(&self, now_ms: i64) -> bool {
+        self.override_until.is_some_and(|until| until > now_ms)
+    }
+}
+
+/// `YYYY-MM` of a timestamp, in local time
+pub fn month_of(now_ms: i64) -> String {
+    chrono::Local
+        .timestamp_millis_opt(now_ms)
+        .single()
+        .map(|time| time.format("%Y-%m").to_string())
+        .unwrap_or_default()
+}
+
+fn budget_key(workspace_id: &str) -> String {
+    format!("workspace.{}.budget", workspace_id)
+}
+
+fn usage_key(workspace_id: &str) -> String {
+    format!("workspace.{}.usage", workspace_id)
+}
+
+/// The budget of a workspace, `None` when it has none
+pub async fn load_budget(db: &DatabaseConnection, workspace_id: &str) -> AppResult<Option<WorkspaceBudget>> {
+    let Some(setting) = SettingsRepository::get_by_key(db, &budget_key(workspace_id)).await? else {
+        return Ok(None);
+    };
+    serde_json::from_str(&setting.value)
+        .map(Some)
+        .map_err(|e| AppError::SerializationError(format!("Invalid budget of workspace {}: {}", workspace_id, e)))
+}
+
+pub async fn save_budget(db: &DatabaseConnection, workspace_id: &str, budget: &WorkspaceBudget) -> AppResult<()> {
+    for threshold in &budget.warn_thresholds {
+        if !(0.0..=1.0).contains(threshold) {
+            return Err(AppError::ValidationError(format!("预警阈值必须在 0 到 1 之间：{}", threshold)));
+        }
+    }
+    let value = serde_json::to_string(budget).map_err(|e| AppError::SerializationError(e.to_string()))?;
+    SettingsRepository::upsert(
+        db,
+        &budget_key(workspace_id),
+        &value,
+        SETTINGS_CATEGORY,
+        Some("Workspace AI budget and rate limits"),
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_budget(db: &DatabaseConnection, workspace_id: &str) -> AppResult<()> {
+    SettingsRepository::delete_by_key(db, &budget_key(workspace_id)).await?;
+    Ok(())
+}
+
+/// Usage of a workspace; empty when nothing was recorded yet or the stored value is unreadable
+pub async fn load_usage(db: &DatabaseConnection, workspace_id: &str) -> AppResult<WorkspaceUsage> {
+    let usage = SettingsRepository::get_by_key(db, &usage_key(workspace_id))
+        .await?
+        .and_then(|setting| serde_json::from_str(&setting.value).ok())
+        .unwrap_or_default();
+    Ok(usage)
+}
+
+pub async fn save_usage(db: &DatabaseConnection, workspace_id: &str, usage: &WorkspaceUsage) -> AppResult<()> {
+    let value = serde_json::to_string(usage).map_err(|e| AppError::SerializationError(e.to_string()))?;
+    SettingsRepository::upsert(
+        db,
+        &usage_key(workspace_id),
+        &value,
+        SETTINGS_CATEGORY,
+        Some("Workspace AI usage"),
+    )
+    .await?;
+    Ok(())
+}
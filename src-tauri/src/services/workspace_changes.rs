@@ -0,0 +1,423 @@
+//! Workspace change detection module
+//!
+//! Finds the files an agent run created, modified or deleted. Before the run the
+//! workspace is fingerprinted by path, modification time and size only, and in a
+//! git repository the commit and the tracked files that match it are noted.
+//! After the run the workspace is walked again and only files whose fingerprint
+//! changed are read; their old content is read back from that commit, so a diff
+//! can be shown and a file that was only touched is not reported.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::Serialize;
+
+use crate::services::git::run_git;
+
+/// Files walked per snapshot (same limit as the search index)
+const MAX_FILES: usize = 20_000;
+/// Larger files are reported without a diff
+const MAX_DIFF_FILE_BYTES: u64 = 256 * 1024;
+/// Diffs longer than this are cut off
+const MAX_DIFF_CHARS: usize = 20_000;
+/// Line pairs compared by the LCS diff; bigger changes are shown as a full replacement
+const MAX_LCS_CELLS: usize = 4_000_000;
+/// Unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone)]
+struct FileFingerprint {
+    mtime_ms: u128,
+    size: u64,
+}
+
+/// The commit checked out before a run
+#[derive(Debug)]
+struct GitBase {
+    commit: String,
+    /// Tracked files, relative to the workspace root, whose content matched the commit
+    clean: HashSet<String>,
+}
+
+/// The state of a workspace before a run
+#[derive(Debug)]
+pub struct WorkspaceSnapshot {
+    root: PathBuf,
+    files: HashMap<String, FileFingerprint>,
+    /// The walk stopped at `MAX_FILES`, so a file missing from the snapshot may not be new
+    capped: bool,
+    base: Option<GitBase>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+/// A file changed by a run
+#[derive(Debug, Clone, Serialize)]
+pub struct FileChange {
+    /// Absolute path
+    pub path: String,
+    /// Path relative to the workspace root, `/`-separated
+    pub relative_path: String,
+    pub kind: ChangeKind,
+    /// Unified diff; `None` for binary or large files and when the old content is unknown
+    pub diff: Option<String>,
+}
+
+impl WorkspaceSnapshot {
+    /// Fingerprint every workspace file (blocking; run it off the async runtime)
+    pub fn capture(root: &Path) -> Self {
+        let paths = crate::utils::fs::collect_workspace_files(root, MAX_FILES);
+        let capped = paths.len() >= MAX_FILES;
+        let files = paths
+            .iter()
+            .filter_map(|path| {
+                let metadata = fs::metadata(path).ok()?;
+                let fingerprint = FileFingerprint {
+                    mtime_ms: mtime_ms(&metadata),
+                    size: metadata.len(),
+                };
+                Some((crate::utils::fs::relative_path(root, path), fingerprint))
+            })
+            .collect();
+        Self {
+            root: root.to_path_buf(),
+            files,
+            capped,
+            base: git_base(root),
+        }
+    }
+
+    /// Files that differ from the snapshot now, sorted by path (blocking)
+    pub fn changes(&self) -> Vec<FileChange> {
+        let mut changes = Vec::new();
+        let mut seen = HashSet::new();
+
+        for path in crate::utils::fs::collect_workspace_files(&self.root, MAX_FILES) {
+            let rel = crate::utils::fs::relative_path(&self.root, &path);
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            seen.insert(rel.clone());
+            match self.files.get(&rel) {
+                // A capped snapshot may just not have reached the file.
+                None if self.capped => {}
+                None => {
+                    let diff = read_text(&path, metadata.len()).map(|new| unified_diff(&rel, "", &new, true, false));
+                    changes.push(self.change(rel, ChangeKind::Created, diff));
+                }
+                Some(before) if before.mtime_ms == mtime_ms(&metadata) && before.size == metadata.len() => {}
+                Some(before) => {
+                    let old = self.old_content(&rel, before);
+                    let new = read_text(&path, metadata.len());
+                    let diff = match (&old, &new) {
+                        (Some(old), Some(new)) => {
+                            // Touched but not changed
+                            if old == new {
+                                continue;
+                            }
+                            Some(unified_diff(&rel, old, new, false, false))
+                        }
+                        _ => None,
+                    };
+                    changes.push(self.change(rel, ChangeKind::Modified, diff));
+                }
+            }
+        }
+
+        // A capped walk may stop before files it saw last time; only report real deletions.
+        for (rel, before) in &self.files {
+            if seen.contains(rel) || self.root.join(rel).exists() {
+                continue;
+            }
+            let diff = self
+                .old_content(rel, before)
+                .map(|old| unified_diff(rel, &old, "", false, true));
+            changes.push(self.change(rel.clone(), ChangeKind::Deleted, diff));
+        }
+
+        changes.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        changes
+    }
+
+    /// Content of a file before the run, when it matched the commit checked out then
+    fn old_content(&self, rel: &str, before: &FileFingerprint) -> Option<String> {
+        let base = self.base.as_ref().filter(|base| base.clean.contains(rel))?;
+        if before.size > MAX_DIFF_FILE_BYTES {
+            return None;
+        }
+        let object = format!("{}:./{}", base.commit, rel);
+        text(run_git(&self.root, &["cat-file", "blob", &object]).ok()?.into_bytes())
+    }
+
+    fn change(&self, relative_path: String, kind: ChangeKind, diff: Option<String>) -> FileChange {
+        FileChange {
+            path: crate::utils::fs::normalize_path(&self.root.join(&relative_path).to_string_lossy()),
+            relative_path,
+            kind,
+            diff,
+        }
+    }
+}
+
+/// The commit checked out in `root` and the tracked files below `root` that match it
+fn git_base(root: &Path) -> Option<GitBase> {
+    let commit = run_git(root, &["rev-parse", "--verify", "--quiet", "HEAD"]).ok()?;
+    let commit = commit.trim().to_string();
+    // Both list paths relative to `root`
+    let tracked = run_git(root, &["ls-files", "-z"]).ok()?;
+    let dirty = run_git(root, &["diff", "--name-only", "--relative", "-z", "HEAD"]).ok()?;
+    let dirty: HashSet<&str> = dirty.split('\0').collect();
+    let clean = tracked
+        .split('\0')
+        .filter(|path| !path.is_empty() && !dirty.contains(path))
+        .map(str::to_string)
+        .collect();
+    Some(GitBase { commit, clean })
+}
+
+fn mtime_ms(metadata: &fs::Metadata) -> u128 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+/// Text content of a file of `size` bytes that is small enough to diff
+fn read_text(path: &Path, size: u64) -> Option<String> {
+    if size > MAX_DIFF_FILE_BYTES {
+        return None;
+    }
+    text(fs::read(path).ok()?)
+}
+
+fn text(bytes: Vec<u8>) -> Option<String> {
+    if crate::utils::fs::looks_binary(&bytes) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Unified diff of two texts, with `CONTEXT_LINES` lines of context
+pub fn unified_diff(path: &str, old: &str, new: &str, created: bool, deleted: bool) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    format!(
+        "--- {}\n+++ {}\n{}",
+        if created { "/dev/null".to_string() } else { format!("a/{}", path) },
+        if deleted { "/dev/null".to_string() } else { format!("b/{}", path) },
+        diff_hunks(&old_lines, &new_lines, 0)
+    )
+}
+
+/// Unified diff of a line range that starts at line `first_line` (1-based) of
+/// the file; hunks only contain lines of the range. Empty when nothing changed.
+pub fn range_diff(path: &str, first_line: usize, old: &[&str], new: &[&str]) -> String {
+    let hunks = diff_hunks(old, new, first_line.saturating_sub(1));
+    if hunks.is_empty() {
+        return String::new();
+    }
+    format!("--- a/{}\n+++ b/{}\n{}", path, path, hunks)
+}
+
+/// Hunks of the diff, with line numbers shifted by `line_offset`
+fn diff_hunks(old_lines: &[&str], new_lines: &[&str], line_offset: usize) -> String {
+    let ops = diff_lines(old_lines, new_lines);
+    let changed: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, Op::Equal(..)))
+        .map(|(idx, _)| idx)
+        .collect();
+
+    let mut out = String::new();
+    let mut idx = 0;
+    while idx < changed.len() {
+        // Extend the hunk while the next change is within two contexts of the last one.
+        let start = changed[idx].saturating_sub(CONTEXT_LINES);
+        let mut last = changed[idx];
+        while idx + 1 < changed.len() && changed[idx + 1] <= last + 2 * CONTEXT_LINES {
+            idx += 1;
+            last = changed[idx];
+        }
+        let end = (last + CONTEXT_LINES + 1).min(ops.len());
+        idx += 1;
+
+        let hunk = &ops[start..end];
+        let (old_start, new_start) = hunk_start(&ops, start);
+        let (old_start, new_start) = (old_start + line_offset, new_start + line_offset);
+        let old_count = hunk.iter().filter(|op| !matches!(op, Op::Insert(_))).count();
+        let new_count = hunk.iter().filter(|op| !matches!(op, Op::Delete(_))).count();
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            if old_count == 0 { old_start } else { old_start + 1 },
+            old_count,
+            if new_count == 0 { new_start } else { new_start + 1 },
+            new_count
+        ));
+        for op in hunk {
+            let line = match *op {
+                Op::Equal(i, _) => format!(" {}\n", old_lines[i]),
+                Op::Delete(i) => format!("-{}\n", old_lines[i]),
+                Op::Insert(j) => format!("+{}\n", new_lines[j]),
+            };
+            out.push_str(&line);
+        }
+        if out.len() > MAX_DIFF_CHARS {
+            let mut cut = MAX_DIFF_CHARS;
+            while !out.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            out.truncate(cut);
+            out.push_str("\n... (diff truncated)\n");
+            break;
+        }
+    }
+    out
+}
+
+/// Old and new line index (0-based) at which the op at `at` starts
+fn hunk_start(ops: &[Op], at: usize) -> (usize, usize) {
+    let before = &ops[..at];
+    let old = before.iter().filter(|op| !matches!(op, Op::Insert(_))).count();
+    let new = before.iter().filter(|op| !matches!(op, Op::Delete(_))).count();
+    (old, new)
+}
+
+/// Line edit script: common prefix and suffix, LCS in between when affordable
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut ops: Vec<Op> = (0..prefix).map(|i| Op::Equal(i, i)).collect();
+    if old_mid.len() * new_mid.len() <= MAX_LCS_CELLS {
+        ops.extend(lcs_ops(old_mid, new_mid, prefix));
+    } else {
+        ops.extend((0..old_mid.len()).map(|i| Op::Delete(prefix + i)));
+        ops.extend((0..new_mid.len()).map(|j| Op::Insert(prefix + j)));
+    }
+    let old_tail = old.len() - suffix;
+    let new_tail = new.len() - suffix;
+    ops.extend((0..suffix).map(|k| Op::Equal(old_tail + k, new_tail + k)));
+    ops
+}
+
+fn lcs_ops(old: &[&str], new: &[&str], offset: usize) -> Vec<Op> {
+    let (n, m) = (old.len(), new.len());
+    // lengths[i][j]: LCS length of old[i..] and new[j..]
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i * (m + 1) + j] = if old[i] == new[j] {
+                lengths[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(n + m);
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            ops.push(Op::Equal(offset + i, offset + j));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1] {
+            ops.push(Op::Delete(offset + i));
+            i += 1;
+        } else {
+            ops.push(Op::Insert(offset + j));
+            j += 1;
+        }
+    }
+    ops.extend((i..n).map(|i| Op::Delete(offset + i)));
+    ops.extend((j..m).map(|j| Op::Insert(offset + j)));
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit_all(root: &Path) {
+        run_git(root, &["add", "-A"]).unwrap();
+        let mut args = vec!["-c", "user.name=test", "-c", "user.email=test@example.com"];
+        args.extend(["-c", "commit.gpgsign=false", "commit", "--quiet", "-m", "base"]);
+        run_git(root, &args).unwrap();
+    }
+
+    #[test]
+    fn diffs_changed_files_against_the_checked_out_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        run_git(root, &["init", "--quiet"]).unwrap();
+        fs::write(root.join("edited.txt"), "one\ntwo\n").unwrap();
+        fs::write(root.join("touched.txt"), "same\n").unwrap();
+        fs::write(root.join("removed.txt"), "gone\n").unwrap();
+        commit_all(root);
+
+        let snapshot = WorkspaceSnapshot::capture(root);
+        assert!(!snapshot.capped);
+        fs::write(root.join("edited.txt"), "one\n2\nthree\n").unwrap();
+        let touched = fs::File::options().write(true).open(root.join("touched.txt")).unwrap();
+        touched.set_modified(UNIX_EPOCH).unwrap();
+        fs::remove_file(root.join("removed.txt")).unwrap();
+        fs::write(root.join("created.txt"), "new\n").unwrap();
+
+        let changes = snapshot.changes();
+        let kinds: Vec<(&str, ChangeKind)> = changes
+            .iter()
+            .map(|change| (change.relative_path.as_str(), change.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("created.txt", ChangeKind::Created),
+                ("edited.txt", ChangeKind::Modified),
+                ("removed.txt", ChangeKind::Deleted),
+            ]
+        );
+        let edited = changes[1].diff.as_deref().unwrap();
+        assert!(edited.contains("-two\n+2\n+three\n"));
+        assert!(changes[2].diff.as_deref().unwrap().contains("-gone\n"));
+    }
+
+    #[test]
+    fn files_outside_git_have_no_old_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("notes.txt"), "a\n").unwrap();
+
+        let snapshot = WorkspaceSnapshot::capture(root);
+        fs::write(root.join("notes.txt"), "a\nb\n").unwrap();
+
+        let changes = snapshot.changes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, ChangeKind::Modified);
+        assert!(changes[0].diff.is_none());
+    }
+}
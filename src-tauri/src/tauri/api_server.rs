@@ -0,0 +1,458 @@
+//! Local API server module
+//!
+//! Opt-in HTTP/WebSocket API on `deployment.host:deployment.port` so other
+//! editors and scripts can drive the running desktop app. Every route except
+//! `/api/health` requires the bearer token (header or `?token=` query).
+//!
+//! Routes:
+//! - `POST /api/chat` — send a chat message, streamed back as SSE
+//! - `GET  /api/ws` — WebSocket carrying chat/cancel commands and all AI events
+//! - `GET  /api/sessions`, `GET /api/sessions/:id` — saved chat sessions
+//! - `POST /api/requests/:id/cancel` — cancel a streaming request
+//! - `POST /api/editor/open` — open a file in the editor
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::{App, AppHandle, Listener, Manager};
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, warn};
+
+use crate::config::AppConfig;
+use crate::services::chat_session;
+use crate::utils::error::{AppError, AppResult};
+
+use super::commands::{cancel_streaming_request, send_chat_message_streaming};
+use super::event_handlers::{emit_open_file_request, parse_event_payload};
+
+/// App events forwarded to API clients
+const FORWARDED_EVENTS: &[&str] = &["ai-response", "ai-context"];
+const TOKEN_FILE_NAME: &str = "api-token";
+
+/// An app event as seen by API clients
+#[derive(Debug, Clone)]
+struct ApiEvent {
+    name: String,
+    payload: Value,
+}
+
+impl ApiEvent {
+    fn request_id(&self) -> Option<&str> {
+        self.payload.get("request_id").and_then(|v| v.as_str())
+    }
+
+    fn is_final_response(&self) -> bool {
+        self.name == "ai-response" && self.payload.get("done").and_then(|v| v.as_bool()).unwrap_or(false)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({ "type": "event", "event": self.name, "payload": self.payload })
+    }
+}
+
+#[derive(Clone)]
+struct ApiState {
+    app_handle: AppHandle,
+    token: String,
+    events: broadcast::Sender<ApiEvent>,
+}
+
+/// Body of `POST /api/chat` and of WebSocket `chat` commands
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ChatRequest {
+    message: String,
+    context_files: Option<Vec<String>>,
+    code_cli: Option<String>,
+    codex_model: Option<String>,
+    session_id: Option<String>,
+    workspace_id: Option<String>,
+    workspace_dir: Option<String>,
+    code_cli_changed: Option<bool>,
+    code_cli_task_id: Option<String>,
+    auto_context: Option<bool>,
+    auto_context_top_k: Option<usize>,
+    native_model: Option<String>,
+    plan_mode: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionsQuery {
+    workspace_id: String,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenFileRequest {
+    path: String,
+    line: Option<u32>,
+}
+
+/// Commands accepted over the WebSocket
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum WsCommand {
+    Chat(ChatRequest),
+    Cancel { request_id: String },
+}
+
+/// Start the API server if `deployment.api_enabled` is set.
+///
+/// Configuration problems are logged rather than returned so they never block app startup.
+pub fn init(app: &mut App) -> AppResult<()> {
+    let config = app.state::<AppConfig>().inner().clone();
+    if let Err(e) = start(app, &config) {
+        error!("Failed to start local API server: {}", e);
+    }
+    Ok(())
+}
+
+fn start(app: &mut App, config: &AppConfig) -> AppResult<()> {
+    let deployment = &config.deployment;
+    if !deployment.api_enabled {
+        debug!("Local API server disabled");
+        return Ok(());
+    }
+
+    let host: IpAddr = deployment
+        .host
+        .parse()
+        .map_err(|e| AppError::ConfigError(format!("Invalid API host {}: {}", deployment.host, e)))?;
+    if !host.is_loopback() {
+        return Err(AppError::ConfigError(format!(
+            "Local API only binds to loopback addresses, got {}",
+            host
+        )));
+    }
+    let addr = SocketAddr::new(host, deployment.port);
+    let token = resolve_token(config)?;
+
+    let (events, _) = broadcast::channel(512);
+    for name in FORWARDED_EVENTS {
+        let sender = events.clone();
+        let name = name.to_string();
+        app.handle().listen(name.clone(), move |event| {
+            let _ = sender.send(ApiEvent {
+                name: name.clone(),
+                payload: parse_event_payload(event.payload()),
+            });
+        });
+    }
+
+    let state = ApiState {
+        app_handle: app.handle().clone(),
+        token,
+        events,
+    };
+
+    tauri::async_runtime::spawn(async move {
+        let listener = match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to bind local API server on {}: {}", addr, e);
+                return;
+            }
+        };
+        info!("Local API server listening on http://{}", addr);
+        if let Err(e) = axum::serve(listener, router(state)).await {
+            error!("Local API server stopped: {}", e);
+        }
+    });
+
+    Ok(())
+}
+
+fn router(state: ApiState) -> Router {
+    let protected = Router::new()
+        .route("/api/chat", post(chat))
+        .route("/api/ws", get(websocket))
+        .route("/api/sessions", get(list_sessions))
+        .route("/api/sessions/:id", get(get_session))
+        .route("/api/requests/:id/cancel", post(cancel_request))
+        .route("/api/editor/open", post(open_file))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token));
+
+    Router::new()
+        .route("/api/health", get(health))
+        .merge(protected)
+        .with_state(state)
+}
+
+/// Configured token, or the one stored in the data directory (created on first use)
+fn resolve_token(config: &AppConfig) -> AppResult<String> {
+    if let Some(token) = config.deployment.api_token.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        return Ok(token.to_string());
+    }
+
+    let path = PathBuf::from(&config.app.data_dir).join(TOKEN_FILE_NAME);
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        let existing = existing.trim();
+        if !existing.is_empty() {
+            return Ok(existing.to_string());
+        }
+    }
+
+    let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+    write_token_file(&path, &token)?;
+    info!("Generated local API token at {:?}", path);
+    Ok(token)
+}
+
+/// Write the token to a fresh owner-only file and move it into place, so the
+/// token is never readable by others, not even briefly
+fn write_token_file(path: &std::path::Path, token: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let temp_path = path.with_extension("tmp");
+    let _ = std::fs::remove_file(&temp_path);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&temp_path)?;
+    file.write_all(token.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&temp_path, path)
+}
+
+/// Session ids name files in the sessions directory; accept only `[A-Za-z0-9_-]`
+fn is_session_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn tokens_match(expected: &str, provided: &str) -> bool {
+    expected.len() == provided.len()
+        && expected
+            .bytes()
+            .zip(provided.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+async fn require_token(
+    State(state): State<ApiState>,
+    Query(query): Query<HashMap<String, String>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim().to_string())
+        .or_else(|| query.get("token").cloned());
+
+    match provided {
+        Some(token) if tokens_match(&state.token, &token) => next.run(request).await,
+        _ => error_response(StatusCode::UNAUTHORIZED, "invalid or missing API token"),
+    }
+}
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": message.into() }))).into_response()
+}
+
+async fn health() -> Json<Value> {
+    Json(json!({ "status": "ok", "version": env!("CARGO_PKG_VERSION") }))
+}
+
+async fn start_chat(state: &ApiState, request: ChatRequest) -> Result<String, String> {
+    if request.message.trim().is_empty() {
+        return Err("message must not be empty".to_string());
+    }
+    send_chat_message_streaming(
+        state.app_handle.clone(),
+        request.message,
+        request.context_files,
+        request.code_cli,
+        request.codex_model,
+        request.session_id,
+        request.workspace_id,
+        request.workspace_dir,
+        request.code_cli_changed,
+        request.code_cli_task_id,
+        None,
+        None,
+        None,
+        request.auto_context,
+        request.auto_context_top_k,
+        request.native_model,
+        request.plan_mode,
+        None,
+    )
+    .await
+}
+
+/// `POST /api/chat`: stream the request's events as SSE until the final `ai-response`
+async fn chat(
+    State(state): State<ApiState>,
+    Json(request): Json<ChatRequest>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, Response> {
+    // Subscribe before dispatching so no early chunk is missed.
+    let rx = state.events.subscribe();
+    let request_id = start_chat(&state, request)
+        .await
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, e))?;
+    debug!(request_id = %request_id, "Local API chat request started");
+
+    let accepted = SseEvent::default()
+        .event("accepted")
+        .data(json!({ "request_id": request_id }).to_string());
+    let events = stream::unfold((rx, request_id, false), |(mut rx, request_id, finished)| async move {
+        if finished {
+            return None;
+        }
+        loop {
+            match rx.recv().await {
+                Ok(event) if event.request_id() == Some(request_id.as_str()) => {
+                    let finished = event.is_final_response();
+                    let sse = SseEvent::default().event(event.name.clone()).data(event.payload.to_string());
+                    return Some((Ok::<_, Infallible>(sse), (rx, request_id, finished)));
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "Local API SSE client lagged behind");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(Sse::new(stream::once(async move { Ok::<_, Infallible>(accepted) }).chain(events)).keep_alive(KeepAlive::default()))
+}
+
+async fn websocket(State(state): State<ApiState>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(socket, state))
+}
+
+async fn handle_socket(mut socket: WebSocket, state: ApiState) {
+    let mut rx = state.events.subscribe();
+    loop {
+        tokio::select! {
+            incoming = socket.recv() => {
+                let text = match incoming {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => {
+                        debug!("Local API WebSocket error: {}", e);
+                        break;
+                    }
+                };
+                let reply = match serde_json::from_str::<WsCommand>(&text) {
+                    Ok(WsCommand::Chat(request)) => match start_chat(&state, request).await {
+                        Ok(request_id) => json!({ "type": "accepted", "request_id": request_id }),
+                        Err(e) => json!({ "type": "error", "error": e }),
+                    },
+                    Ok(WsCommand::Cancel { request_id }) => {
+                        match cancel_streaming_request(state.app_handle.clone(), request_id.clone()).await {
+                            Ok(()) => json!({ "type": "cancelled", "request_id": request_id }),
+                            Err(e) => json!({ "type": "error", "request_id": request_id, "error": e }),
+                        }
+                    }
+                    Err(e) => json!({ "type": "error", "error": format!("invalid command: {}", e) }),
+                };
+                if socket.send(Message::Text(reply.to_string())).await.is_err() {
+                    break;
+                }
+            }
+            event = rx.recv() => {
+                match event {
+                    Ok(event) => {
+                        if socket.send(Message::Text(event.to_json().to_string())).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "Local API WebSocket client lagged behind");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+}
+
+async fn list_sessions(Query(query): Query<SessionsQuery>) -> Response {
+    match chat_session::load_all_sessions(query.workspace_id, query.limit) {
+        Ok(sessions) => Json(sessions).into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+async fn get_session(Path(id): Path<String>) -> Response {
+    if !is_session_id(&id) {
+        return error_response(StatusCode::BAD_REQUEST, format!("invalid session id: {}", id));
+    }
+    match chat_session::load_session_by_id(&id) {
+        Ok(session) => Json(session).into_response(),
+        Err(e) => error_response(StatusCode::NOT_FOUND, e),
+    }
+}
+
+async fn cancel_request(State(state): State<ApiState>, Path(id): Path<String>) -> Response {
+    match cancel_streaming_request(state.app_handle.clone(), id.clone()).await {
+        Ok(()) => Json(json!({ "request_id": id, "cancelled": true })).into_response(),
+        Err(e) => error_response(StatusCode::NOT_FOUND, e),
+    }
+}
+
+async fn open_file(State(state): State<ApiState>, Json(request): Json<OpenFileRequest>) -> Response {
+    if !std::path::Path::new(&request.path).is_file() {
+        return error_response(StatusCode::NOT_FOUND, format!("file not found: {}", request.path));
+    }
+    match emit_open_file_request(&state.app_handle, &request.path, request.line) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_ids_cannot_leave_the_sessions_dir() {
+        assert!(is_session_id("3f2a9c1e-7b4d-4e0a-9f1c-2d8b6a5e4c3b"));
+        assert!(is_session_id("claude_import-1"));
+        assert!(!is_session_id(""));
+        assert!(!is_session_id("../config"));
+        assert!(!is_session_id("a/b"));
+        assert!(!is_session_id("a.json"));
+    }
+
+    #[test]
+    fn token_file_is_replaced_and_owner_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(TOKEN_FILE_NAME);
+        std::fs::write(&path, "").unwrap();
+
+        write_token_file(&path, "secret").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "secret");
+        assert!(!path.with_extension("tmp").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
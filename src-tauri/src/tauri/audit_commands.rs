@@ -0,0 +1,51 @@
+//! Tauri commands for the AI invocation audit log
+
+use tauri::AppHandle;
+use tracing::debug;
+
+use crate::database::repositories::ai_invocation_repository::{
+    AiInvocation, AiInvocationFilter, AiInvocationRepository,
+};
+
+/// List recorded AI invocations matching `filter`, newest first
+#[tauri::command]
+pub async fn list_ai_invocations(
+    app_handle: AppHandle,
+    filter: Option<AiInvocationFilter>,
+) -> Result<Vec<AiInvocation>, String> {
+    debug!("Command: list_ai_invocations - filter: {:?}", filter);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    AiInvocationRepository::list(&db, &filter.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Get a single recorded AI invocation
+#[tauri::command]
+pub async fn get_ai_invocation(app_handle: AppHandle, id: i32) -> Result<AiInvocation, String> {
+    debug!("Command: get_ai_invocation - id: {}", id);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    AiInvocationRepository::get(&db, id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("AI invocation not found: {}", id))
+}
+
+/// Remove all recorded AI invocations, returning how many were removed
+#[tauri::command]
+pub async fn clear_ai_invocations(app_handle: AppHandle) -> Result<u64, String> {
+    debug!("Command: clear_ai_invocations");
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    AiInvocationRepository::clear(&db)
+        .await
+        .map_err(|e| e.to_string())
+}
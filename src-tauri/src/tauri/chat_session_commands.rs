@@ -1,7 +1,17 @@
 //! Tauri commands for chat session management
 
-use crate::services::chat_session::{self, ChatMessage, ChatSession};
-use tracing::{debug, error};
+use chrono::Local;
+use tauri::{async_runtime, AppHandle, Manager};
+use tracing::{debug, error, warn};
+
+use crate::core::AppState;
+use crate::services::ai::AiService;
+use crate::services::chat_session::{self, ChatMessage, ChatSession, SessionSummary};
+use crate::services::history::{self, HistoryPolicy};
+use crate::services::native_provider::NativeProvider;
+use crate::services::redaction::SecretRedactor;
+use crate::services::session_import::{self, ImportWorkspace, SessionImportReport, TranscriptSource};
+use crate::database::repositories::workspace_repository::WorkspaceRepository;
 
 /// Save a chat session
 #[tauri::command]
@@ -92,3 +102,111 @@ pub async fn update_chat_session_name(
         }
     }
 }
+
+/// Get the history summary of a chat session
+#[tauri::command]
+pub async fn get_chat_session_summary(session_id: String) -> Result<Option<SessionSummary>, String> {
+    debug!("Command: get_chat_session_summary - session_id: {}", session_id);
+
+    chat_session::load_session_by_id(&session_id).map(|session| session.summary)
+}
+
+/// Edit (or clear, when `content` is None) the history summary of a chat session
+#[tauri::command]
+pub async fn update_chat_session_summary(
+    session_id: String,
+    content: Option<String>,
+) -> Result<ChatSession, String> {
+    debug!("Command: update_chat_session_summary - session_id: {}", session_id);
+
+    let session = chat_session::load_session_by_id(&session_id)?;
+    let summary = match content {
+        Some(content) => {
+            let mut summary = session.summary.unwrap_or(SessionSummary {
+                content: String::new(),
+                summarized_count: 0,
+                last_message_id: None,
+                model: None,
+                edited: false,
+                updated_at: String::new(),
+            });
+            summary.content = content;
+            summary.edited = true;
+            summary.updated_at = Local::now().to_rfc3339();
+            Some(summary)
+        }
+        None => None,
+    };
+
+    chat_session::update_session_summary(&session_id, summary).map_err(|e| {
+        error!("Failed to update chat session summary: {}", e);
+        e
+    })
+}
+
+/// Fold older turns of a chat session into its summary using a native provider
+#[tauri::command]
+pub async fn compact_chat_session(
+    app_handle: AppHandle,
+    session_id: String,
+    native_model: String,
+) -> Result<ChatSession, String> {
+    debug!(
+        "Command: compact_chat_session - session_id: {}, model: {}",
+        session_id, native_model
+    );
+
+    let config = crate::core::app::get_config(app_handle.state::<AppState>());
+    let env = config.env_vars;
+    let model = AiService::new()
+        .find_model(&native_model)
+        .ok_or_else(|| format!("Model not found: {}", native_model))?;
+    let provider = NativeProvider::from_model(&model, &env).map_err(|e| e.to_string())?;
+
+    let session = chat_session::load_session_by_id(&session_id)?;
+    let mut outgoing = session.clone();
+    let mut redactor = SecretRedactor::new(&config.redaction, &env);
+    redactor.redact_session(&mut outgoing);
+    if !redactor.redactions().is_empty() {
+        warn!(
+            session_id = %session_id,
+            count = redactor.redactions().len(),
+            "Redacted secrets from history before compaction"
+        );
+    }
+    match history::compact(&outgoing, &provider, &HistoryPolicy::default())
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        Some(summary) => chat_session::update_session_summary(&session_id, Some(summary)),
+        None => Ok(session),
+    }
+}
+
+/// Import Claude Code / Codex / Gemini CLI transcripts into the chat history
+/// (all three when `sources` is omitted)
+#[tauri::command]
+pub async fn import_cli_sessions(
+    app_handle: AppHandle,
+    sources: Option<Vec<TranscriptSource>>,
+) -> Result<SessionImportReport, String> {
+    debug!("Command: import_cli_sessions - sources: {:?}", sources);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    let workspaces: Vec<ImportWorkspace> = WorkspaceRepository::list_all(&db)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|workspace| ImportWorkspace {
+            id: workspace.id.to_string(),
+            path: workspace.path,
+        })
+        .collect();
+    let sources = sources.unwrap_or_else(|| TranscriptSource::ALL.to_vec());
+
+    async_runtime::spawn_blocking(move || session_import::import_sessions(&sources, &workspaces))
+        .await
+        .map_err(|e| format!("导入会话任务失败: {}", e))
+}
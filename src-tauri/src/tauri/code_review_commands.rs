@@ -0,0 +1,156 @@
+//! Tauri commands for AI code reviews
+
+use std::path::PathBuf;
+
+use tauri::{async_runtime, AppHandle, Manager};
+use tracing::{debug, info, warn};
+
+use crate::core::AppState;
+use crate::database::repositories::code_review_repository::{CodeReview, CodeReviewRepository};
+use crate::services::ai_ignore::AiIgnore;
+use crate::services::code_review::{self, CodeReviewRequest};
+use crate::services::redaction::SecretRedactor;
+
+use super::commands::standalone_audit_context;
+use super::selection_edit_commands::edit_backend;
+
+/// Review the uncommitted changes (or a commit range) of a workspace and store the findings
+#[tauri::command]
+pub async fn review_changes(app_handle: AppHandle, request: CodeReviewRequest) -> Result<CodeReview, String> {
+    debug!(
+        "Command: review_changes - workspace: {}, range: {:?}, native_model: {:?}, code_cli: {:?}",
+        request.workspace_dir, request.range, request.native_model, request.code_cli
+    );
+
+    let root = PathBuf::from(request.workspace_dir.trim());
+    if !root.is_dir() {
+        return Err(format!("工作区目录不存在: {}", request.workspace_dir));
+    }
+    let config = crate::core::app::get_config(app_handle.state::<AppState>());
+    let backend = edit_backend(
+        &config,
+        request.native_model.as_deref(),
+        request.code_cli.as_deref(),
+        request.codex_model.clone(),
+        Some(request.workspace_dir.clone()),
+    )?;
+
+    let ignore = AiIgnore::load(Some(&config.app.data_dir), Some(&request.workspace_dir));
+    let range = request.range.clone();
+    let changes = async_runtime::spawn_blocking(move || code_review::collect_changes(&root, &ignore, range.as_deref()))
+        .await
+        .map_err(|e| format!("读取改动失败: {}", e))?
+        .map_err(|e| e.to_string())?;
+
+    let mut redactor = SecretRedactor::new(&config.redaction, &config.env_vars);
+    let audit = standalone_audit_context(&app_handle).await;
+    let proposed = code_review::request_findings(&backend, &request, &changes, &mut redactor, audit)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !redactor.redactions().is_empty() {
+        warn!(
+            workspace = %request.workspace_dir,
+            count = redactor.redactions().len(),
+            "Redacted secrets from the code review prompt"
+        );
+    }
+
+    let proposed_count = proposed.len();
+    let (findings, dropped, changes) = async_runtime::spawn_blocking(move || {
+        let (findings, dropped) = code_review::locate_findings(&changes, proposed);
+        (findings, dropped, changes)
+    })
+    .await
+    .map_err(|e| format!("定位审查结果失败: {}", e))?;
+
+    let review = CodeReview {
+        id: uuid::Uuid::new_v4().to_string(),
+        workspace_dir: request.workspace_dir.trim().to_string(),
+        commit_hash: changes.commit,
+        range: request.range.map(|range| range.trim().to_string()).filter(|range| !range.is_empty()),
+        backend: request
+            .native_model
+            .filter(|m| !m.trim().is_empty())
+            .or(request.code_cli)
+            .unwrap_or_default(),
+        findings,
+        files: changes.files,
+        excluded_files: changes.excluded_files,
+        truncated: changes.truncated,
+        dropped: dropped as i64,
+        created_at: chrono::Utc::now().timestamp_millis(),
+    };
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    CodeReviewRepository::insert(&db, &review)
+        .await
+        .map_err(|e| e.to_string())?;
+    info!(
+        review_id = %review.id,
+        findings = review.findings.len(),
+        proposed = proposed_count,
+        dropped,
+        "Code review finished"
+    );
+    Ok(review)
+}
+
+/// List the stored reviews of a workspace, newest first, optionally only those of one commit
+#[tauri::command]
+pub async fn list_code_reviews(
+    app_handle: AppHandle,
+    workspace_dir: String,
+    commit_hash: Option<String>,
+    limit: Option<u64>,
+) -> Result<Vec<CodeReview>, String> {
+    debug!(
+        "Command: list_code_reviews - workspace: {}, commit: {:?}",
+        workspace_dir, commit_hash
+    );
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    CodeReviewRepository::list(&db, workspace_dir.trim(), commit_hash.as_deref(), limit)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Get a stored review with its findings moved to the current lines of their files
+#[tauri::command]
+pub async fn get_code_review(app_handle: AppHandle, id: String) -> Result<Option<CodeReview>, String> {
+    debug!("Command: get_code_review - id: {}", id);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(mut review) = CodeReviewRepository::get(&db, &id)
+        .await
+        .map_err(|e| e.to_string())?
+    else {
+        return Ok(None);
+    };
+    review.findings = async_runtime::spawn_blocking(move || {
+        let mut findings = review.findings;
+        code_review::reanchor(&mut findings);
+        findings
+    })
+    .await
+    .map_err(|e| format!("定位审查结果失败: {}", e))?;
+    Ok(Some(review))
+}
+
+/// Delete a stored review
+#[tauri::command]
+pub async fn delete_code_review(app_handle: AppHandle, id: String) -> Result<bool, String> {
+    debug!("Command: delete_code_review - id: {}", id);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    CodeReviewRepository::delete(&db, &id)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -11,16 +11,37 @@ use std::os::windows::process::CommandExt;
 use tauri::{AppHandle, Manager, State};
 use tracing::{error, info, debug, warn};
 use tauri::async_runtime;
-use tokio::io::{AsyncRead, AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::Command;
 use tokio::sync::oneshot;
 use tokio::time::sleep;
-use serde_json::Value;
 use crate::core::{AppState, app::StreamingTaskHandle};
 use crate::services::ai::{AiChatOptions, AiService};
-use crate::services::chat_session::{self, ChatMessage};
-use crate::utils::error::AppError;
-use super::event_handlers::emit_ai_response;
+use crate::services::ai_ignore::AiIgnore;
+use crate::config::ResponseCacheSettings;
+use crate::services::audit::{AuditContext, InvocationRecorder};
+use crate::services::chat_session::{self, ChatMessage, MessageMetadata};
+use crate::services::context::{split_attachments, ContextManifest, ContextSnippet};
+use crate::services::direct_cli::{
+    build_direct_cli_plan, derive_backend_from_command, run_direct_cli, CliStream, DirectCliRun,
+};
+use crate::services::history::{self, estimate_tokens, HistoryPolicy};
+use crate::services::mentions::{parse_mentions, MentionResolver, ResolvedMentions};
+use crate::services::mock_backend::{self, run_mock_backend};
+use crate::services::native_provider::{ImageData, NativeProvider, DEFAULT_MAX_OUTPUT_TOKENS};
+use crate::services::plan_mode;
+use crate::services::redaction::{Redaction, SecretRedactor};
+use crate::services::response_cache;
+use crate::services::workspace_changes::{ChangeKind, WorkspaceSnapshot};
+use crate::database::repositories::ai_request_repository::{status as ai_request_status, AiRequestRecord};
+use crate::utils::error::{AppError, AppResult};
+use super::event_handlers::{
+    emit_ai_context, emit_ai_failure, emit_ai_response, emit_files_changed_by_run, emit_secrets_redacted,
+};
+use super::permissions::permission_bridge;
+use super::request_journal;
+use super::workspace_budget::{check_budget, estimate_request_tokens};
+
+/// Default number of snippets retrieved when auto context is enabled
+const DEFAULT_AUTO_CONTEXT_TOP_K: usize = 5;
 
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
@@ -56,7 +77,60 @@ pub async fn send_chat_message_streaming(
     direct_cli: Option<bool>,
     cli_command: Option<String>,
     cli_args: Option<Vec<String>>,
+    auto_context: Option<bool>,
+    auto_context_top_k: Option<usize>,
+    native_model: Option<String>,
+    plan_mode: Option<bool>,
+    use_cache: Option<bool>,
 ) -> Result<String, String> {
+    start_chat_stream(
+        app_handle,
+        message,
+        context_files,
+        code_cli,
+        codex_model,
+        session_id,
+        workspace_id,
+        workspace_dir,
+        code_cli_changed,
+        code_cli_task_id,
+        direct_cli,
+        cli_command,
+        cli_args,
+        auto_context,
+        auto_context_top_k,
+        native_model,
+        plan_mode,
+        use_cache,
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Start a streaming chat request and return its request id; a refused request
+/// (unresolved mention, exceeded budget, plan run without a read-only mode)
+/// fails with its typed error
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn start_chat_stream(
+    app_handle: AppHandle,
+    message: String,
+    context_files: Option<Vec<String>>,
+    code_cli: Option<String>,
+    codex_model: Option<String>,
+    session_id: Option<String>,
+    workspace_id: Option<String>,
+    workspace_dir: Option<String>,
+    code_cli_changed: Option<bool>,
+    code_cli_task_id: Option<String>,
+    direct_cli: Option<bool>,
+    cli_command: Option<String>,
+    cli_args: Option<Vec<String>>,
+    auto_context: Option<bool>,
+    auto_context_top_k: Option<usize>,
+    native_model: Option<String>,
+    plan_mode: Option<bool>,
+    use_cache: Option<bool>,
+) -> AppResult<String> {
     debug!("Sending chat message (streaming): {}", message);
     debug!(
         code_cli = ?code_cli,
@@ -64,6 +138,10 @@ pub async fn send_chat_message_streaming(
         codex_model = ?codex_model,
         code_cli_changed = ?code_cli_changed,
         code_cli_task_id = ?code_cli_task_id,
+        auto_context = ?auto_context,
+        native_model = ?native_model,
+        plan_mode = ?plan_mode,
+        use_cache = ?use_cache,
         "Streaming chat options"
     );
     // let db = crate::database::connection::get_db_connection(&app_handle)
@@ -86,12 +164,18 @@ pub async fn send_chat_message_streaming(
     let config = crate::core::app::get_config(app_handle.state::<AppState>());
     // let app_handle_clone = app_handle.clone();
     let request_id = uuid::Uuid::new_v4().to_string();
+    let ai_ignore = AiIgnore::load(Some(&config.app.data_dir), workspace_dir.as_deref());
+    let mentions = resolve_mentions(&app_handle, workspace_dir.as_deref(), &message, &ai_ignore).await?;
+    let input_tokens = estimate_request_tokens(&message, context_files.as_deref())
+        + mentions.blocks.iter().map(|block| estimate_tokens(&block.content) as u64).sum::<u64>();
+    check_budget(&app_handle, workspace_id.as_deref(), &request_id, input_tokens).await?;
     let request_id_for_task = request_id.clone();
     let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
 
     // Spawn the streaming task in the background.
     let msg = message.clone();
     let ctx_files = context_files.clone();
+    let files_for_append = context_files.clone().filter(|files| !files.is_empty());
     let msg_for_spawn = msg.clone();
 
     let workspace_id_for_append = workspace_id.clone();
@@ -101,237 +185,404 @@ pub async fn send_chat_message_streaming(
     let workspace_dir_for_task = workspace_dir.clone();
     let code_cli_task_id_for_resume = code_cli_task_id.clone();
     let code_cli_changed_flag = code_cli_changed;
-    let direct_cli_enabled = direct_cli.unwrap_or(false);
+    // The mock backend streams like a direct CLI run, whatever the direct CLI setting is.
+    let mock_backend_enabled = mock_backend::is_mock_cli(code_cli.as_deref());
+    let direct_cli_enabled = direct_cli.unwrap_or(false) || mock_backend_enabled;
+    let data_dir_for_task = config.app.data_dir.clone();
     let cli_command_for_task = cli_command.clone().unwrap_or_default();
     let cli_args_for_task = cli_args.clone().unwrap_or_default();
     let env_vars_for_task = config.env_vars.clone();
+    let auto_context_enabled = auto_context.unwrap_or(false);
+    let auto_context_top_k = auto_context_top_k.unwrap_or(DEFAULT_AUTO_CONTEXT_TOP_K);
+    let native_model_for_task = native_model.clone().filter(|m| !m.trim().is_empty());
+    let plan_mode = plan_mode.unwrap_or(false);
+    // Refuse plan runs that could not be kept read-only before anything is queued.
+    if plan_mode && direct_cli_enabled && !mock_backend_enabled && native_model_for_task.is_none() {
+        let backend = code_cli
+            .as_deref()
+            .and_then(AiService::derive_backend_from_code_cli)
+            .or_else(|| derive_backend_from_command(cli_command.as_deref().unwrap_or_default()));
+        plan_mode::ensure_read_only(backend.as_deref())?;
+    }
+    let handoff = if native_model_for_task.is_some() || mock_backend_enabled {
+        None
+    } else {
+        code_cli_handoff(
+            &session_id,
+            code_cli.as_deref(),
+            code_cli_changed.unwrap_or(false),
+            code_cli_task_id.as_deref(),
+        )
+    };
+    let mut cache_settings = config.response_cache.clone();
+    // A plan describes the workspace as it is now, and its run must leave a session to continue.
+    cache_settings.enabled &= !plan_mode && use_cache.unwrap_or(true);
+    let redaction_settings = config.redaction.clone();
+    let assistant_metadata = plan_mode.then(|| MessageMetadata {
+        plan: true,
+        ..Default::default()
+    });
+
+    // Plan runs are not journaled: resuming one would run it without the read-only restrictions.
+    let journal = request_journal::journal(&app_handle).filter(|_| !plan_mode);
+    if let Some(journal) = journal {
+        let now = chrono::Utc::now().timestamp_millis();
+        journal.queued(AiRequestRecord {
+            request_id: request_id.clone(),
+            session_id: session_id.clone(),
+            workspace_id: workspace_id.clone(),
+            workspace_dir: workspace_dir.clone(),
+            message: message.clone(),
+            context_files: context_files.clone().unwrap_or_default(),
+            code_cli: code_cli.clone(),
+            codex_model: codex_model.clone(),
+            direct_cli: direct_cli.unwrap_or(false),
+            cli_command: cli_command.clone(),
+            cli_args: cli_args_for_task.clone(),
+            native_model: native_model_for_task.clone(),
+            auto_context: auto_context_enabled,
+            status: ai_request_status::QUEUED.to_string(),
+            partial_output: String::new(),
+            cli_session_id: code_cli_task_id.clone(),
+            error: None,
+            created_at: now,
+            updated_at: now,
+        });
+    }
 
     let app_handle_for_task = app_handle.clone();
     let request_id_for_spawn = request_id_for_task.clone();
-    let join_handle = if direct_cli_enabled {
+    let join_handle = if let Some(native_model) = native_model_for_task {
+        async_runtime::spawn(async move {
+            let snippets = prepare_request_context(
+                &app_handle_for_task,
+                &request_id_for_spawn,
+                workspace_dir_for_task.as_deref(),
+                &msg,
+                ctx_files.as_deref(),
+                &mentions,
+                &ai_ignore,
+                auto_context_enabled,
+                auto_context_top_k,
+            )
+            .await;
+            let (text_files, image_files) = split_attachments(ctx_files.as_deref().unwrap_or_default());
+            let mut task = AiService::build_task_with_context(&msg, Some(text_files.as_slice()), &mentions.blocks, &snippets, &ai_ignore);
+            if plan_mode {
+                task = plan_mode::plan_task(&task);
+            }
+            let mut redactor = SecretRedactor::new(&redaction_settings, &env_vars_for_task);
+            let task = redactor.redact(&task);
+            let images = match ai_ignore
+                .allowed(&image_files)
+                .iter()
+                .map(|path| ImageData::from_file(path))
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(images) => images,
+                Err(e) => {
+                    let _ = emit_ai_failure(
+                        &app_handle_for_task,
+                        &request_id_for_spawn,
+                        &format!("[AI error] {}", e),
+                        Some(&session_id),
+                        workspace_id_for_append.as_deref(),
+                    );
+                    return;
+                }
+            };
+
+            // Images are not part of the task text, so requests with images bypass the cache.
+            let cache_key = (cache_settings.enabled && images.is_empty()).then(|| {
+                response_cache::cache_key("native", Some(&native_model), &[&native_history_key(&session_id)], &task)
+            });
+            if let Some(key) = cache_key.as_deref() {
+                if replay_cached_response(
+                    &app_handle_for_task,
+                    &cache_settings,
+                    key,
+                    &request_id_for_spawn,
+                    &session_id,
+                    workspace_id_for_append.as_deref(),
+                    &msg_for_spawn,
+                    files_for_append.clone(),
+                )
+                .await
+                {
+                    return;
+                }
+            }
+
+            let result = tokio::select! {
+                _ = cancel_rx => {
+                    debug!(
+                        request_id = %request_id_for_spawn,
+                        "Native provider request cancelled before completion"
+                    );
+                    return;
+                }
+                result = run_native_chat(
+                    &app_handle_for_task,
+                    &request_id_for_spawn,
+                    &native_model,
+                    &session_id,
+                    task,
+                    images,
+                    &env_vars_for_task,
+                    &mut redactor,
+                ) => result,
+            };
+
+            match result {
+                Ok(reply) => {
+                    if let Some(key) = cache_key.as_deref() {
+                        store_cached_response(
+                            &app_handle_for_task,
+                            &cache_settings,
+                            key,
+                            "native",
+                            Some(&native_model),
+                            &reply,
+                        )
+                        .await;
+                    }
+                    let full_response = emit_chunked_response(
+                        &app_handle_for_task,
+                        &request_id_for_spawn,
+                        &reply,
+                        Some(&session_id),
+                        workspace_id_for_append.as_deref(),
+                        None,
+                    )
+                    .await;
+
+                    let user_message = ChatMessage {
+                        id: uuid::Uuid::new_v4().to_string(),
+                        role: "user".to_string(),
+                        content: msg_for_spawn.clone(),
+                        timestamp: chrono::Local::now().to_rfc3339().to_string(),
+                        files: files_for_append.clone(),
+                        session_id: Some(session_id.clone()),
+                        workspace_id: workspace_id_for_append.clone(),
+                        model: Some(native_model.clone()),
+                        metadata: None,
+                    };
+                    let assistant_message = ChatMessage {
+                        id: request_id_for_spawn.clone(),
+                        role: "assistant".to_string(),
+                        content: full_response,
+                        timestamp: chrono::Local::now().to_rfc3339(),
+                        files: None,
+                        session_id: Some(session_id.clone()),
+                        workspace_id: workspace_id_for_append.clone(),
+                        model: Some(native_model.clone()),
+                        metadata: assistant_metadata,
+                    };
+                    if let Err(e) = chat_session::append_message_to_session(
+                        &session_id,
+                        vec![user_message, assistant_message],
+                        None,
+                        None,
+                    ) {
+                        error!(
+                            "Failed to append chat messages to session {}: {}",
+                            session_id, e
+                        );
+                    }
+                }
+                Err(e) => {
+                    error!("Native provider request failed: {}", e);
+                    let _ = emit_ai_failure(
+                        &app_handle_for_task,
+                        &request_id_for_spawn,
+                        &format!("[AI error] {}", e),
+                        Some(&session_id),
+                        workspace_id_for_append.as_deref(),
+                    );
+                }
+            }
+        })
+    } else if direct_cli_enabled {
         let cancel_rx = cancel_rx;
         async_runtime::spawn(async move {
-            let mut cancel_rx = Some(cancel_rx);
             sleep(Duration::from_millis(30)).await;
 
-            if cli_command_for_task.trim().is_empty() {
-                let _ = emit_ai_response(
+            if !mock_backend_enabled && cli_command_for_task.trim().is_empty() {
+                let _ = emit_ai_failure(
                     &app_handle_for_task,
                     &request_id_for_spawn,
                     "[AI error] Direct CLI enabled but command is empty.",
-                    true,
                     Some(&session_id),
                     workspace_id_for_append.as_deref(),
-                    None,
                 );
                 return;
             }
 
-            let task = AiService::build_task_with_context(&msg, ctx_files.as_deref());
+            let snippets = prepare_request_context(
+                &app_handle_for_task,
+                &request_id_for_spawn,
+                workspace_dir_for_task.as_deref(),
+                &msg,
+                ctx_files.as_deref(),
+                &mentions,
+                &ai_ignore,
+                auto_context_enabled,
+                auto_context_top_k,
+            )
+            .await;
             let workdir = workspace_dir_for_task.clone().unwrap_or_else(|| ".".to_string());
             let backend = code_cli_for_task
                 .as_deref()
                 .and_then(AiService::derive_backend_from_code_cli)
                 .or_else(|| derive_backend_from_command(&cli_command_for_task));
-            let direct_plan = build_direct_cli_plan(
+            let (text_files, image_files) = split_attachments(ctx_files.as_deref().unwrap_or_default());
+            let image_files = ai_ignore.allowed(&image_files);
+            let mut direct_plan = build_direct_cli_plan(
                 backend.as_deref(),
                 &cli_args_for_task,
                 code_cli_task_id_for_resume.as_deref(),
                 code_cli_changed_flag,
+                &image_files,
             );
-            let direct_args = direct_plan.args;
-            let mut direct_task_id = direct_plan.task_id.clone();
-
-            let mut cmd = Command::new(&cli_command_for_task);
-            #[cfg(windows)]
-            {
-                cmd.creation_flags(CREATE_NO_WINDOW);
-            }
-            cmd.args(&direct_args)
-                .stdin(std::process::Stdio::piped())
-                .stdout(std::process::Stdio::piped())
-                .stderr(std::process::Stdio::piped())
-                .current_dir(&workdir);
-            for (key, value) in &env_vars_for_task {
-                cmd.env(key, value);
-            }
-
-            let mut child = match cmd.spawn() {
-                Ok(child) => child,
-                Err(e) => {
-                    let _ = emit_ai_response(
+            if plan_mode && !mock_backend_enabled {
+                if let Err(e) = plan_mode::apply_plan_mode_args(backend.as_deref(), &mut direct_plan.args) {
+                    let _ = emit_ai_failure(
                         &app_handle_for_task,
                         &request_id_for_spawn,
-                        &format!("[AI error] Failed to start CLI: {}", e),
-                        true,
+                        &format!("[AI error] {}", e),
                         Some(&session_id),
                         workspace_id_for_append.as_deref(),
-                        None,
                     );
                     return;
                 }
+            }
+            // Images handed over as `--image` are not listed in the prompt as well.
+            let prompt_files = if direct_plan.images_as_args {
+                text_files
+            } else {
+                ctx_files.clone().unwrap_or_default()
             };
-
-            if let Some(mut stdin) = child.stdin.take() {
-                let mut input = task.clone();
-                if !input.ends_with('\n') {
-                    input.push('\n');
-                }
-                if let Err(e) = stdin.write_all(input.as_bytes()).await {
-                    warn!("Failed to write CLI stdin: {}", e);
-                }
+            let task = AiService::build_task_with_context(&msg, Some(prompt_files.as_slice()), &mentions.blocks, &snippets, &ai_ignore);
+            let mut task = history::with_handoff(handoff.as_deref(), task);
+            if plan_mode {
+                task = plan_mode::plan_task(&task);
             }
+            let mut redactor = SecretRedactor::new(&redaction_settings, &env_vars_for_task);
+            let task = redactor.redact(&task);
 
-            let mut stdout_reader = child.stdout.take().map(BufReader::new);
-            let mut stderr_reader = child.stderr.take().map(BufReader::new);
-            let mut stdout_done = stdout_reader.is_none();
-            let mut stderr_done = stderr_reader.is_none();
-            let mut stdout_line = String::new();
-            let mut stderr_line = String::new();
-            let mut full_response = String::new();
-
-            while !stdout_done || !stderr_done {
-                if let Some(cancel_fut) = cancel_rx.as_mut() {
-                    tokio::select! {
-                        _ = cancel_fut => {
-                            if let Err(e) = child.kill().await {
-                                warn!("Failed to kill direct CLI after cancellation: {}", e);
-                            }
-                            return;
-                        }
-                        read = read_line_if_available(&mut stdout_reader, &mut stdout_line), if !stdout_done => {
-                            match read {
-                                Ok(0) => stdout_done = true,
-                                Ok(_) => {
-                                    if let Some(id) = parse_cli_session_id(&stdout_line, backend.as_deref()) {
-                                        if should_replace_task_id(direct_task_id.as_deref(), &id) {
-                                            direct_task_id = Some(id);
-                                        }
-                                    }
-                                    let delta = stdout_line.clone();
-                                    full_response.push_str(&delta);
-                                    let _ = emit_ai_response(
-                                        &app_handle_for_task,
-                                        &request_id_for_spawn,
-                                        &delta,
-                                        false,
-                                        Some(&session_id),
-                                        workspace_id_for_append.as_deref(),
-                                        None,
-                                    );
-                                }
-                                Err(e) => {
-                                    warn!("Failed to read CLI stdout: {}", e);
-                                    stdout_done = true;
-                                }
-                            }
-                        }
-                        read = read_line_if_available(&mut stderr_reader, &mut stderr_line), if !stderr_done => {
-                            match read {
-                                Ok(0) => stderr_done = true,
-                                Ok(_) => {
-                                    let delta = format!("[stderr] {}", stderr_line);
-                                    full_response.push_str(&delta);
-                                    let _ = emit_ai_response(
-                                        &app_handle_for_task,
-                                        &request_id_for_spawn,
-                                        &delta,
-                                        false,
-                                        Some(&session_id),
-                                        workspace_id_for_append.as_deref(),
-                                        None,
-                                    );
-                                }
-                                Err(e) => {
-                                    warn!("Failed to read CLI stderr: {}", e);
-                                    stderr_done = true;
-                                }
-                            }
-                        }
-                    }
-                } else {
-                    tokio::select! {
-                        read = read_line_if_available(&mut stdout_reader, &mut stdout_line), if !stdout_done => {
-                            match read {
-                                Ok(0) => stdout_done = true,
-                                Ok(_) => {
-                                    if let Some(id) = parse_cli_session_id(&stdout_line, backend.as_deref()) {
-                                        if should_replace_task_id(direct_task_id.as_deref(), &id) {
-                                            direct_task_id = Some(id);
-                                        }
-                                    }
-                                    let delta = stdout_line.clone();
-                                    full_response.push_str(&delta);
-                                    let _ = emit_ai_response(
-                                        &app_handle_for_task,
-                                        &request_id_for_spawn,
-                                        &delta,
-                                        false,
-                                        Some(&session_id),
-                                        workspace_id_for_append.as_deref(),
-                                        None,
-                                    );
-                                }
-                                Err(e) => {
-                                    warn!("Failed to read CLI stdout: {}", e);
-                                    stdout_done = true;
-                                }
-                            }
-                        }
-                        read = read_line_if_available(&mut stderr_reader, &mut stderr_line), if !stderr_done => {
-                            match read {
-                                Ok(0) => stderr_done = true,
-                                Ok(_) => {
-                                    let delta = format!("[stderr] {}", stderr_line);
-                                    full_response.push_str(&delta);
-                                    let _ = emit_ai_response(
-                                        &app_handle_for_task,
-                                        &request_id_for_spawn,
-                                        &delta,
-                                        false,
-                                        Some(&session_id),
-                                        workspace_id_for_append.as_deref(),
-                                        None,
-                                    );
-                                }
-                                Err(e) => {
-                                    warn!("Failed to read CLI stderr: {}", e);
-                                    stderr_done = true;
-                                }
-                            }
-                        }
-                    }
+            let cache_backend = if mock_backend_enabled {
+                "mock".to_string()
+            } else {
+                backend.clone().unwrap_or_else(|| cli_command_for_task.clone())
+            };
+            // The mock backend has no side effects; real CLI runs are only cached when opted in.
+            let cache_key = (cache_settings.enabled && (mock_backend_enabled || cache_settings.cache_cli_backends))
+                .then(|| {
+                    response_cache::cache_key(
+                        &cache_backend,
+                        codex_model_for_task.as_deref(),
+                        &[
+                            &cli_command_for_task,
+                            &direct_plan.args.join("\u{1f}"),
+                            code_cli_task_id_for_resume.as_deref().unwrap_or(""),
+                            &workdir,
+                        ],
+                        &task,
+                    )
+                });
+            if let Some(key) = cache_key.as_deref() {
+                if replay_cached_response(
+                    &app_handle_for_task,
+                    &cache_settings,
+                    key,
+                    &request_id_for_spawn,
+                    &session_id,
+                    workspace_id_for_append.as_deref(),
+                    &msg_for_spawn,
+                    files_for_append.clone(),
+                )
+                .await
+                {
+                    return;
                 }
             }
+            report_redactions(&app_handle_for_task, &request_id_for_spawn, redactor.redactions());
 
-            let exit_status = match child.wait().await {
-                Ok(status) => status,
+            let audit = audit_context(&app_handle_for_task, &request_id_for_spawn, &session_id).await;
+            let snapshot = if mock_backend_enabled {
+                None
+            } else {
+                snapshot_workspace(workspace_dir_for_task.as_deref()).await
+            };
+            let on_output = |_: CliStream, delta: &str| {
+                let _ = emit_ai_response(
+                    &app_handle_for_task,
+                    &request_id_for_spawn,
+                    delta,
+                    false,
+                    Some(&session_id),
+                    workspace_id_for_append.as_deref(),
+                    None,
+                );
+            };
+            let outcome = if mock_backend_enabled {
+                let scripts = mock_backend::load_scripts(&mock_backend::fixtures_dir(&data_dir_for_task));
+                let script = mock_backend::select_script(&scripts, &task);
+                run_mock_backend(
+                    &script,
+                    &task,
+                    code_cli_task_id_for_resume.as_deref(),
+                    Some(cancel_rx),
+                    audit,
+                    on_output,
+                )
+                .await
+            } else {
+                run_direct_cli(
+                    DirectCliRun {
+                        command: &cli_command_for_task,
+                        args: &direct_plan.args,
+                        workdir: &workdir,
+                        env: &env_vars_for_task,
+                        input: &task,
+                        backend: backend.as_deref(),
+                        task_id: direct_plan.task_id.clone(),
+                        audit,
+                        permissions: permission_bridge(&app_handle_for_task, &request_id_for_spawn, &session_id),
+                    },
+                    Some(cancel_rx),
+                    on_output,
+                )
+                .await
+            };
+            let outcome = match outcome {
+                Ok(outcome) => outcome,
+                Err(AppError::Cancelled(_)) => return,
                 Err(e) => {
-                    let _ = emit_ai_response(
+                    let _ = emit_ai_failure(
                         &app_handle_for_task,
                         &request_id_for_spawn,
-                        &format!("[AI error] Failed to wait for CLI: {}", e),
-                        true,
+                        &format!("[AI error] {}", e),
                         Some(&session_id),
                         workspace_id_for_append.as_deref(),
-                        None,
                     );
                     return;
                 }
             };
-            let exit_code = exit_status.code().unwrap_or(-1);
-            let success = exit_code == 0;
+            let mut full_response = outcome.output;
+            let direct_task_id = outcome.task_id;
+            let success = outcome.exit_code == 0;
+            let changed_files =
+                report_changed_files(&app_handle_for_task, &request_id_for_spawn, &session_id, snapshot).await;
             if !success {
-                let delta = format!("[exit {}] CLI exited with errors\n", exit_code);
+                let delta = format!("[exit {}] CLI exited with errors\n", outcome.exit_code);
                 full_response.push_str(&delta);
-                let _ = emit_ai_response(
+                let _ = emit_ai_failure(
                     &app_handle_for_task,
                     &request_id_for_spawn,
                     &delta,
-                    true,
                     Some(&session_id),
                     workspace_id_for_append.as_deref(),
-                    None,
                 );
             } else {
                 let _ = emit_ai_response(
@@ -346,25 +597,38 @@ pub async fn send_chat_message_streaming(
             }
 
             if success {
+                if let Some(key) = cache_key.as_deref() {
+                    store_cached_response(
+                        &app_handle_for_task,
+                        &cache_settings,
+                        key,
+                        &cache_backend,
+                        codex_model_for_task.as_deref(),
+                        &full_response,
+                    )
+                    .await;
+                }
                 let user_message = ChatMessage {
                     id: uuid::Uuid::new_v4().to_string(),
                     role: "user".to_string(),
                     content: msg_for_spawn.clone(),
                     timestamp: chrono::Local::now().to_rfc3339().to_string(),
-                    files: None,
+                    files: files_for_append.clone(),
                     session_id: Some(session_id.clone()),
                     workspace_id: workspace_id_for_append.clone(),
                     model: None,
+                    metadata: None,
                 };
                 let assistant_message = ChatMessage {
                     id: request_id_for_spawn.clone(),
                     role: "assistant".to_string(),
                     content: full_response,
                     timestamp: chrono::Local::now().to_rfc3339(),
-                    files: None,
+                    files: changed_files,
                     session_id: Some(session_id.clone()),
                     workspace_id: workspace_id_for_append.clone(),
                     model: None,
+                    metadata: assistant_metadata,
                 };
                 if let Err(e) = chat_session::append_message_to_session(
                     &session_id,
@@ -382,8 +646,63 @@ pub async fn send_chat_message_streaming(
     } else {
         let cancel_rx = cancel_rx;
         async_runtime::spawn(async move {
+            let snippets = prepare_request_context(
+                &app_handle_for_task,
+                &request_id_for_spawn,
+                workspace_dir_for_task.as_deref(),
+                &msg,
+                ctx_files.as_deref(),
+                &mentions,
+                &ai_ignore,
+                auto_context_enabled,
+                auto_context_top_k,
+            )
+            .await;
+            let cache_backend = code_cli_for_task
+                .as_deref()
+                .and_then(AiService::derive_backend_from_code_cli)
+                .unwrap_or_else(|| "codeagent".to_string());
+            let task = AiService::build_task_with_context(&msg, ctx_files.as_deref(), &mentions.blocks, &snippets, &ai_ignore);
+            let mut task = history::with_handoff(handoff.as_deref(), task);
+            if plan_mode {
+                task = plan_mode::plan_task(&task);
+            }
+            let mut redactor = SecretRedactor::new(&redaction_settings, &env_vars_for_task);
+            let task = redactor.redact(&task);
+            // Wrapper runs may edit files, which a replay does not repeat.
+            let cache_key = (cache_settings.enabled && cache_settings.cache_cli_backends).then(|| {
+                response_cache::cache_key(
+                    &cache_backend,
+                    codex_model_for_task.as_deref(),
+                    &[
+                        code_cli_for_task.as_deref().unwrap_or(""),
+                        code_cli_task_id_for_resume.as_deref().unwrap_or(""),
+                        workspace_dir_for_task.as_deref().unwrap_or(""),
+                    ],
+                    &task,
+                )
+            });
+            if let Some(key) = cache_key.as_deref() {
+                if replay_cached_response(
+                    &app_handle_for_task,
+                    &cache_settings,
+                    key,
+                    &request_id_for_spawn,
+                    &session_id,
+                    workspace_id_for_append.as_deref(),
+                    &msg_for_spawn,
+                    files_for_append.clone(),
+                )
+                .await
+                {
+                    return;
+                }
+            }
+            report_redactions(&app_handle_for_task, &request_id_for_spawn, redactor.redactions());
+            let codex_model_for_cache = codex_model_for_task.clone();
+            let snapshot = snapshot_workspace(workspace_dir_for_task.as_deref()).await;
             let ai = AiService::new();
-            match ai
+            let result = ai
                 .send_message_with_options(
                     &msg,
                     ctx_files,
@@ -395,51 +714,41 @@ pub async fn send_chat_message_streaming(
                         workspace_dir: workspace_dir_for_task,
                         code_cli_changed: code_cli_changed_flag,
                         env: env_vars_for_task,
+                        context_snippets: snippets,
+                        prepared_task: Some(task),
                         cancel_rx: Some(cancel_rx),
+                        audit: audit_context(&app_handle_for_task, &request_id_for_spawn, &session_id).await,
+                        plan_mode,
                     },
                 )
-                .await
-            {   
+                .await;
+            let changed_files = match &result {
+                Err(AppError::Cancelled(_)) => None,
+                _ => report_changed_files(&app_handle_for_task, &request_id_for_spawn, &session_id, snapshot).await,
+            };
+            match result {
                 Ok(result) => {
                     debug!("AI response: {}", result.message);
-                    let chars: Vec<char> = result.message.chars().collect();
-                    let total = chars.len();
-                    let mut buffer = String::new();
-                    let mut full_response = String::new();
-
-                    for (idx, ch) in chars.into_iter().enumerate() {
-                        buffer.push(ch);
-
-                        let is_last = idx + 1 == total;
-                        // Send a chunk once the buffer is big enough or we're at the end.
-                        if buffer.len() >= 32 || is_last {
-                            let delta = buffer.clone();
-                            buffer.clear();
-
-                            let codeagent_session_id = if is_last {
-                                result.codeagent_session_id.clone()
-                            } else {
-                                None
-                            };
-                            full_response.push_str(&delta);
-
-                            if let Err(e) = emit_ai_response(
-                                &app_handle_for_task,
-                                &request_id_for_spawn,
-                                &delta,
-                                is_last,
-                                Some(&session_id),
-                                workspace_id_for_append.as_deref(),
-                                codeagent_session_id.as_deref(),
-                            ) {
-                                error!("Failed to emit AI response chunk: {:?}", e);
-                                break;
-                            }
-
-                            // Simulate streaming delay by sleeping inside the task.
-                            std::thread::sleep(Duration::from_millis(60));
-                        }
+                    if let Some(key) = cache_key.as_deref() {
+                        store_cached_response(
+                            &app_handle_for_task,
+                            &cache_settings,
+                            key,
+                            &cache_backend,
+                            codex_model_for_cache.as_deref(),
+                            &result.message,
+                        )
+                        .await;
                     }
+                    let full_response = emit_chunked_response(
+                        &app_handle_for_task,
+                        &request_id_for_spawn,
+                        &result.message,
+                        Some(&session_id),
+                        workspace_id_for_append.as_deref(),
+                        result.codeagent_session_id.as_deref(),
+                    )
+                    .await;
                     
                     if let Some(task_id) = result.codeagent_session_id.clone() {
                         let user_message = ChatMessage {
@@ -447,20 +756,22 @@ pub async fn send_chat_message_streaming(
                             role: "user".to_string(),
                             content: msg_for_spawn.clone(),
                             timestamp: chrono::Local::now().to_rfc3339().to_string(),
-                            files: None,
+                            files: files_for_append.clone(),
                             session_id: Some(session_id.clone()),
                             workspace_id: workspace_id_for_append.clone(),
                             model: None,
+                            metadata: None,
                         };
                         let assistant_message = ChatMessage {
                             id: request_id_for_spawn.clone(),
                             role: "assistant".to_string(),
                             content: full_response,
                             timestamp: chrono::Local::now().to_rfc3339(),
-                            files: None,
+                            files: changed_files,
                             session_id: Some(session_id.clone()),
                             workspace_id: workspace_id_for_append.clone(),
                             model: None,
+                            metadata: assistant_metadata,
                         };
                         if let Err(e) = chat_session::append_message_to_session(
                             &session_id,
@@ -483,14 +794,12 @@ pub async fn send_chat_message_streaming(
                         );
                     } else {
                         error!("Failed to build AI response for streaming: {}", e);
-                        let _ = emit_ai_response(
+                        let _ = emit_ai_failure(
                             &app_handle_for_task,
                             &request_id_for_spawn,
                             &format!("[AI error] {}", e),
-                            true,
                             None,
                             workspace_id_for_append.as_deref(),
-                            None,
                         );
                     }
                 }
@@ -498,7 +807,24 @@ pub async fn send_chat_message_streaming(
         })
     };
 
+    track_streaming_task(&app_handle, &request_id_for_task, join_handle, cancel_tx);
+
+    // 立即把 request_id 返回给前端，前端可用它在 Chat Messages Area 中关联消息
+    Ok(request_id)
+}
+
+/// Register a running request so it can be cancelled, and drop it once it finishes
+pub(crate) fn track_streaming_task(
+    app_handle: &AppHandle,
+    request_id: &str,
+    join_handle: async_runtime::JoinHandle<()>,
+    cancel_tx: oneshot::Sender<()>,
+) {
     let handle_entry = Arc::new(StreamingTaskHandle::new(join_handle, cancel_tx));
+    let journal = request_journal::journal(app_handle);
+    if let Some(journal) = journal.as_ref() {
+        journal.running(request_id);
+    }
 
     {
         let state = app_handle.state::<AppState>();
@@ -506,11 +832,11 @@ pub async fn send_chat_message_streaming(
             .streaming_tasks
             .lock()
             .unwrap()
-            .insert(request_id_for_task.clone(), handle_entry.clone());
+            .insert(request_id.to_string(), handle_entry.clone());
     }
 
     let cleanup_handle = app_handle.clone();
-    let request_id_for_cleanup = request_id_for_task.clone();
+    let request_id_for_cleanup = request_id.to_string();
     let handle_entry_for_cleanup = handle_entry.clone();
     async_runtime::spawn(async move {
         if let Some(handle) = {
@@ -520,6 +846,9 @@ pub async fn send_chat_message_streaming(
             let _ = handle.await;
         }
         handle_entry_for_cleanup.cancel_tx.lock().unwrap().take();
+        if let Some(journal) = journal {
+            journal.ended(&request_id_for_cleanup);
+        }
         let app_state = cleanup_handle.state::<AppState>();
         let mut tasks = app_state.streaming_tasks.lock().unwrap();
         if let Some(current) = tasks.get(&request_id_for_cleanup) {
@@ -528,265 +857,394 @@ pub async fn send_chat_message_streaming(
             }
         }
     });
-
-    // 立即把 request_id 返回给前端，前端可用它在 Chat Messages Area 中关联消息
-    Ok(request_id)
-}
-
-
-async fn read_line_if_available<R: AsyncRead + Unpin>(
-    reader: &mut Option<BufReader<R>>,
-    buf: &mut String,
-) -> std::io::Result<usize> {
-    if let Some(reader) = reader.as_mut() {
-        buf.clear();
-        reader.read_line(buf).await
-    } else {
-        Ok(0)
-    }
 }
 
-fn build_direct_cli_args(backend: Option<&str>, user_args: &[String]) -> Vec<String> {
-    let mut args = user_args.to_vec();
-    match backend.map(|b| b.to_lowercase()) {
-        Some(ref backend) if backend == "claude" => {
-            if !has_cli_arg(&args, "-p") && !has_cli_arg(&args, "--print") {
-                args.push("--print".to_string());
-            }
-            if !has_cli_arg(&args, "--output-format") {
-                args.push("--output-format".to_string());
-                args.push("text".to_string());
-            }
-        }
-        Some(ref backend) if backend == "codex" => {
-            if !has_codex_subcommand(&args) {
-                args.insert(0, "exec".to_string());
-            }
-        }
-        Some(ref backend) if backend == "gemini" => {
-            if !has_cli_arg(&args, "--output-format") && !has_cli_arg(&args, "-o") {
-                args.push("--output-format".to_string());
-                args.push("text".to_string());
+/// Emit `text` as a series of `ai-response` chunks, marking the last one as done.
+///
+/// Returns the text that was actually emitted (shorter if emitting failed midway).
+pub(crate) async fn emit_chunked_response(
+    app_handle: &AppHandle,
+    request_id: &str,
+    text: &str,
+    session_id: Option<&str>,
+    workspace_id: Option<&str>,
+    final_task_id: Option<&str>,
+) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let total = chars.len();
+    let mut buffer = String::new();
+    let mut full_response = String::new();
+
+    for (idx, ch) in chars.into_iter().enumerate() {
+        buffer.push(ch);
+
+        let is_last = idx + 1 == total;
+        // Send a chunk once the buffer is big enough or we're at the end.
+        if buffer.len() >= 32 || is_last {
+            let delta = std::mem::take(&mut buffer);
+            full_response.push_str(&delta);
+
+            if let Err(e) = emit_ai_response(
+                app_handle,
+                request_id,
+                &delta,
+                is_last,
+                session_id,
+                workspace_id,
+                if is_last { final_task_id } else { None },
+            ) {
+                error!("Failed to emit AI response chunk: {:?}", e);
+                break;
             }
+
+            // Simulate streaming delay between chunks.
+            sleep(Duration::from_millis(60)).await;
         }
-        _ => {}
     }
-    args
-}
 
-struct DirectCliPlan {
-    args: Vec<String>,
-    task_id: Option<String>,
+    full_response
 }
 
-fn build_direct_cli_plan(
-    backend: Option<&str>,
-    user_args: &[String],
-    resume_session_id: Option<&str>,
-    code_cli_changed: Option<bool>,
-) -> DirectCliPlan {
-    let mut args = build_direct_cli_args(backend, user_args);
-    let allow_resume = resume_session_id.filter(|_| !code_cli_changed.unwrap_or(false));
-    let mut task_id: Option<String> = None;
-
-    match backend.map(|b| b.to_lowercase()) {
-        Some(ref backend) if backend == "claude" => {
-            let existing_resume = get_flag_value(&args, "--resume")
-                .or_else(|| get_flag_value(&args, "-r"));
-            let existing_session_id = get_flag_value(&args, "--session-id");
-            let has_continue = has_cli_arg(&args, "--continue") || has_cli_arg(&args, "-c");
-            if let Some(id) = existing_session_id {
-                task_id = Some(id);
-            } else if let Some(id) = existing_resume {
-                task_id = Some(id);
-            } else if has_continue {
-                task_id = Some("latest".to_string());
-            } else if let Some(id) = allow_resume {
-                args.push("--resume".to_string());
-                args.push(id.to_string());
-                task_id = Some(id.to_string());
-            } else {
-                let id = uuid::Uuid::new_v4().to_string();
-                args.push("--session-id".to_string());
-                args.push(id.clone());
-                task_id = Some(id);
-            }
-        }
-        Some(ref backend) if backend == "codex" => {
-            let has_resume = has_codex_resume_subcommand(&args);
-            if let Some(id) = allow_resume {
-                if !has_codex_subcommand(&args) {
-                    args.insert(0, "resume".to_string());
-                } else if has_codex_exec_subcommand(&args) {
-                    replace_codex_subcommand(&mut args, "resume");
-                }
-                let using_resume = has_codex_resume_subcommand(&args);
-                if using_resume {
-                    if id.eq_ignore_ascii_case("last") {
-                        if !has_cli_arg(&args, "--last") {
-                            args.push("--last".to_string());
-                        }
-                    } else {
-                        args.push(id.to_string());
+/// Send a task to a native provider together with the session's (compacted) history.
+///
+/// `task` is expected to be redacted already; stored history turns are redacted
+/// here with the same redactor, and everything replaced is reported before sending.
+#[allow(clippy::too_many_arguments)]
+async fn run_native_chat(
+    app_handle: &AppHandle,
+    request_id: &str,
+    model_name: &str,
+    session_id: &str,
+    task: String,
+    images: Vec<ImageData>,
+    env: &[(String, String)],
+    redactor: &mut SecretRedactor,
+) -> Result<String, AppError> {
+    let model = AiService::new()
+        .find_model(model_name)
+        .ok_or_else(|| AppError::ConfigError(format!("Model not found: {}", model_name)))?;
+    let provider = NativeProvider::from_model(&model, env)?;
+    let policy = HistoryPolicy::default();
+
+    let mut session = chat_session::load_session_by_id(session_id).ok();
+    if let Some(existing) = session.as_mut() {
+        redactor.redact_session(existing);
+    }
+    report_redactions(app_handle, request_id, redactor.redactions());
+    if let Some(existing) = session.as_mut() {
+        if history::needs_compaction(existing, &policy) {
+            match history::compact(existing, &provider, &policy).await {
+                Ok(Some(summary)) => {
+                    existing.summary = Some(summary.clone());
+                    if let Err(e) = chat_session::update_session_summary(session_id, Some(summary)) {
+                        warn!("Failed to store session summary for {}: {}", session_id, e);
                     }
                 }
-                task_id = Some(id.to_string());
-            } else if has_resume {
-                task_id = Some("last".to_string());
-            } else {
-                task_id = Some("last".to_string());
+                Ok(None) => {}
+                Err(e) => warn!("History compaction failed, falling back to windowing: {}", e),
             }
         }
-        Some(ref backend) if backend == "gemini" => {
-            let existing_resume = get_flag_value(&args, "--resume")
-                .or_else(|| get_flag_value(&args, "-r"));
-            if let Some(id) = existing_resume {
-                task_id = Some(id);
-            } else if let Some(id) = allow_resume {
-                args.push("--resume".to_string());
-                args.push(id.to_string());
-                task_id = Some(id.to_string());
-            } else {
-                task_id = Some("latest".to_string());
-            }
-        }
-        _ => {}
     }
 
-    DirectCliPlan { args, task_id }
-}
-
-fn has_cli_arg(args: &[String], name: &str) -> bool {
-    args.iter().any(|arg| arg == name || arg.starts_with(&format!("{}=", name)))
+    let (system, mut messages) = session
+        .as_ref()
+        .map(|s| history::build_history(s, &policy))
+        .unwrap_or_default();
+    history::push_user_turn(&mut messages, task, images);
+
+    let mut recorder = InvocationRecorder::start(
+        audit_context(app_handle, request_id, session_id).await,
+        "native",
+        Some(provider.model()),
+        provider.endpoint(),
+        &[],
+        &[],
+        None,
+    );
+    let result = provider
+        .complete(system.as_deref(), &messages, DEFAULT_MAX_OUTPUT_TOKENS)
+        .await;
+    if let Ok(reply) = &result {
+        recorder.set_output(reply, "");
+    }
+    recorder.finish(&result).await;
+    result
 }
 
-fn has_codex_subcommand(args: &[String]) -> bool {
-    let cmd = args.iter().find(|arg| !arg.starts_with('-'));
-    if let Some(cmd) = cmd {
-        matches!(
-            cmd.as_str(),
-            "exec"
-                | "review"
-                | "login"
-                | "logout"
-                | "mcp"
-                | "mcp-server"
-                | "app-server"
-                | "completion"
-                | "sandbox"
-                | "apply"
-                | "resume"
-                | "cloud"
-                | "features"
-                | "help"
-        )
-    } else {
-        false
+/// The conversation a native request is sent with (summary and history window),
+/// as part of its cache key
+fn native_history_key(session_id: &str) -> String {
+    let Ok(session) = chat_session::load_session_by_id(session_id) else {
+        return String::new();
+    };
+    let (system, messages) = history::build_history(&session, &HistoryPolicy::default());
+    let mut key = system.unwrap_or_default();
+    for message in messages {
+        key.push('\u{1e}');
+        key.push_str(&message.role);
+        key.push('\u{1f}');
+        key.push_str(&message.content);
     }
+    key
 }
 
-fn has_codex_exec_subcommand(args: &[String]) -> bool {
-    let cmd = args.iter().find(|arg| !arg.starts_with('-'));
-    matches!(cmd.map(|s| s.as_str()), Some("exec"))
-}
+/// Replay a cached response for `cache_key` and record the exchange in the session.
+///
+/// Returns `true` on a cache hit; lookups never fail the request.
+#[allow(clippy::too_many_arguments)]
+async fn replay_cached_response(
+    app_handle: &AppHandle,
+    settings: &ResponseCacheSettings,
+    cache_key: &str,
+    request_id: &str,
+    session_id: &str,
+    workspace_id: Option<&str>,
+    user_content: &str,
+    files: Option<Vec<String>>,
+) -> bool {
+    if !settings.enabled {
+        return false;
+    }
+    let db = match crate::database::connection::get_db_connection(app_handle).await {
+        Ok(db) => db,
+        Err(e) => {
+            warn!("Response cache unavailable: {}", e);
+            return false;
+        }
+    };
+    let entry = match response_cache::lookup(&db, settings, cache_key).await {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return false,
+        Err(e) => {
+            warn!("Response cache lookup failed: {}", e);
+            return false;
+        }
+    };
 
-fn has_codex_resume_subcommand(args: &[String]) -> bool {
-    let cmd = args.iter().find(|arg| !arg.starts_with('-'));
-    matches!(cmd.map(|s| s.as_str()), Some("resume"))
-}
+    info!(request_id = %request_id, cache_key = %cache_key, "Replaying cached AI response");
+    let _ = emit_ai_response(
+        app_handle,
+        request_id,
+        &entry.response,
+        true,
+        Some(session_id),
+        workspace_id,
+        None,
+    );
 
-fn replace_codex_subcommand(args: &mut Vec<String>, replacement: &str) {
-    if let Some((idx, _)) = args.iter().enumerate().find(|(_, arg)| !arg.starts_with('-')) {
-        args[idx] = replacement.to_string();
-    } else {
-        args.insert(0, replacement.to_string());
+    let user_message = ChatMessage {
+        id: uuid::Uuid::new_v4().to_string(),
+        role: "user".to_string(),
+        content: user_content.to_string(),
+        timestamp: chrono::Local::now().to_rfc3339(),
+        files,
+        session_id: Some(session_id.to_string()),
+        workspace_id: workspace_id.map(|w| w.to_string()),
+        model: None,
+        metadata: None,
+    };
+    let assistant_message = ChatMessage {
+        id: request_id.to_string(),
+        role: "assistant".to_string(),
+        content: entry.response,
+        timestamp: chrono::Local::now().to_rfc3339(),
+        files: None,
+        session_id: Some(session_id.to_string()),
+        workspace_id: workspace_id.map(|w| w.to_string()),
+        model: entry.model,
+        metadata: Some(MessageMetadata {
+            cached: true,
+            cache_key: Some(cache_key.to_string()),
+            ..Default::default()
+        }),
+    };
+    // Resume ids are left untouched: the backend never saw this exchange.
+    if let Err(e) = chat_session::append_message_to_session(
+        session_id,
+        vec![user_message, assistant_message],
+        None,
+        None,
+    ) {
+        error!("Failed to append chat messages to session {}: {}", session_id, e);
     }
+    true
 }
 
-fn get_flag_value(args: &[String], name: &str) -> Option<String> {
-    let flag = name.to_string();
-    for (idx, arg) in args.iter().enumerate() {
-        if arg == &flag {
-            return args.get(idx + 1).cloned();
-        }
-        if let Some(rest) = arg.strip_prefix(&(flag.clone() + "=")) {
-            return Some(rest.to_string());
+/// Audit log target for a request; `None` (nothing recorded) if the database is unavailable
+pub(crate) async fn audit_context(app_handle: &AppHandle, request_id: &str, session_id: &str) -> Option<AuditContext> {
+    match crate::database::connection::get_db_connection(app_handle).await {
+        Ok(db) => Some(AuditContext::new(db, Some(request_id), Some(session_id))),
+        Err(e) => {
+            warn!("AI invocation audit log unavailable: {}", e);
+            None
         }
     }
-    None
 }
 
-fn should_replace_task_id(current: Option<&str>, incoming: &str) -> bool {
-    if incoming.trim().is_empty() {
-        return false;
+/// Audit log target for a one-off request outside any chat session (selection
+/// edits, commit drafts, reviews), under a fresh request id
+pub(crate) async fn standalone_audit_context(app_handle: &AppHandle) -> Option<AuditContext> {
+    let mut audit = audit_context(app_handle, &uuid::Uuid::new_v4().to_string(), "").await?;
+    audit.session_id = None;
+    Some(audit)
+}
+
+/// Log and emit a `secrets-redacted` warning when anything was replaced
+pub(crate) fn report_redactions(app_handle: &AppHandle, request_id: &str, redactions: &[Redaction]) {
+    if redactions.is_empty() {
+        return;
     }
-    match current {
-        None => true,
-        Some(current) => matches!(current, "latest" | "last"),
+    warn!(
+        request_id = %request_id,
+        count = redactions.len(),
+        kinds = ?redactions.iter().map(|r| r.kind.as_str()).collect::<Vec<_>>(),
+        "Redacted secrets from outgoing request"
+    );
+    if let Err(e) = emit_secrets_redacted(app_handle, request_id, redactions) {
+        warn!("Failed to emit secrets-redacted event: {:?}", e);
     }
 }
 
-fn parse_cli_session_id(line: &str, backend: Option<&str>) -> Option<String> {
-    let trimmed = line.trim();
-    if trimmed.starts_with('{') && trimmed.ends_with('}') {
-        if let Ok(value) = serde_json::from_str::<Value>(trimmed) {
-            if let Some(id) = parse_session_id_from_json(&value, backend) {
-                return Some(id);
-            }
-        }
+/// Fingerprint the workspace before a CLI run that may edit it
+async fn snapshot_workspace(workspace_dir: Option<&str>) -> Option<WorkspaceSnapshot> {
+    let root = PathBuf::from(workspace_dir?.trim());
+    if !root.is_dir() {
+        return None;
     }
+    tokio::task::spawn_blocking(move || WorkspaceSnapshot::capture(&root))
+        .await
+        .ok()
+}
 
-    let lowered = trimmed.to_lowercase();
-    if lowered.starts_with("session id:") {
-        return Some(trimmed["session id:".len()..].trim().to_string());
+/// Emit the files a run changed; returns the created and modified paths for the assistant message
+async fn report_changed_files(
+    app_handle: &AppHandle,
+    request_id: &str,
+    session_id: &str,
+    snapshot: Option<WorkspaceSnapshot>,
+) -> Option<Vec<String>> {
+    let snapshot = snapshot?;
+    let changes = tokio::task::spawn_blocking(move || snapshot.changes()).await.ok()?;
+    if changes.is_empty() {
+        return None;
     }
-
-    if let Some(idx) = lowered.find("session id:") {
-        return Some(trimmed[idx + "session id:".len()..].trim().to_string());
+    info!(request_id = %request_id, count = changes.len(), "Run changed workspace files");
+    if let Err(e) = emit_files_changed_by_run(app_handle, request_id, session_id, &changes) {
+        warn!("Failed to emit files-changed-by-run event: {:?}", e);
     }
-
-    None
+    let files: Vec<String> = changes
+        .into_iter()
+        .filter(|change| change.kind != ChangeKind::Deleted)
+        .map(|change| change.path)
+        .collect();
+    (!files.is_empty()).then_some(files)
 }
 
-fn parse_session_id_from_json(value: &Value, backend: Option<&str>) -> Option<String> {
-    let backend = backend.unwrap_or("").to_lowercase();
-
-    let session_id = value
-        .get("session_id")
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
-    if session_id.is_some() {
-        return session_id;
+/// Store a successful response in the response cache (no-op when disabled)
+async fn store_cached_response(
+    app_handle: &AppHandle,
+    settings: &ResponseCacheSettings,
+    cache_key: &str,
+    backend: &str,
+    model: Option<&str>,
+    response: &str,
+) {
+    if !settings.enabled {
+        return;
+    }
+    let result = match crate::database::connection::get_db_connection(app_handle).await {
+        Ok(db) => response_cache::store(&db, settings, cache_key, backend, model, response).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        warn!("Failed to store response in cache: {}", e);
     }
+}
 
-    if backend == "codex" {
-        if let Some(thread_id) = value.get("thread_id").and_then(|v| v.as_str()) {
-            return Some(thread_id.to_string());
+/// Retrieve auto-context snippets (when enabled) and emit the request's context manifest.
+///
+/// Retrieval failures are logged and treated as "no snippets" so the chat still runs.
+pub(crate) async fn prepare_request_context(
+    app_handle: &AppHandle,
+    request_id: &str,
+    workspace_dir: Option<&str>,
+    message: &str,
+    context_files: Option<&[String]>,
+    mentions: &ResolvedMentions,
+    ignore: &AiIgnore,
+    auto_context: bool,
+    top_k: usize,
+) -> Vec<ContextSnippet> {
+    let mut snippets = Vec::new();
+
+    if let (true, Some(root)) = (auto_context, workspace_dir) {
+        let handle = app_handle.clone();
+        let root = root.to_string();
+        let query = message.to_string();
+        let result = async_runtime::spawn_blocking(move || {
+            let state = handle.state::<AppState>();
+            let data_dir = state.config.lock().unwrap().app.data_dir.clone();
+            state.search_index.search(&data_dir, &root, &query, top_k)
+        })
+        .await;
+
+        match result {
+            Ok(Ok(found)) => snippets = found,
+            Ok(Err(e)) => warn!("Auto context retrieval failed: {}", e),
+            Err(e) => warn!("Auto context retrieval task failed: {}", e),
         }
     }
 
-    if let Some(value_type) = value.get("type").and_then(|v| v.as_str()) {
-        if value_type == "thread.started" {
-            if let Some(thread_id) = value.get("thread_id").and_then(|v| v.as_str()) {
-                return Some(thread_id.to_string());
-            }
+    let mut manifest = ContextManifest::describe(context_files.unwrap_or_default(), &mentions.blocks, &snippets, ignore);
+    manifest.blocked.extend(mentions.blocked.iter().cloned());
+    if !manifest.is_empty() {
+        if let Err(e) = emit_ai_context(app_handle, request_id, &manifest) {
+            warn!("Failed to emit AI context manifest: {:?}", e);
         }
     }
 
-    None
+    snippets
 }
 
-fn derive_backend_from_command(command: &str) -> Option<String> {
-    let normalized = command.to_lowercase();
-    if normalized.contains("claude") {
-        Some("claude".to_string())
-    } else if normalized.contains("codex") {
-        Some("codex".to_string())
-    } else if normalized.contains("gemini") {
-        Some("gemini".to_string())
+/// Catch-up for a code CLI run from the stored session.
+///
+/// A CLI started fresh after a switch (`code_cli_changed`) gets the earlier
+/// conversation; a CLI resumed by its own id gets the turns other backends
+/// handled since its last reply.
+pub(crate) fn code_cli_handoff(
+    session_id: &str,
+    code_cli: Option<&str>,
+    code_cli_changed: bool,
+    resume_id: Option<&str>,
+) -> Option<String> {
+    let session = chat_session::load_session_by_id(session_id).ok()?;
+    if code_cli_changed {
+        history::handoff_context(&session, None)
     } else {
-        None
+        resume_id.and(code_cli).and_then(|cli| history::handoff_context(&session, Some(cli)))
+    }
+}
+
+/// Resolve the `@` references of a message; any unresolved one fails the request before it starts
+pub(crate) async fn resolve_mentions(
+    app_handle: &AppHandle,
+    workspace_dir: Option<&str>,
+    message: &str,
+    ignore: &AiIgnore,
+) -> Result<ResolvedMentions, AppError> {
+    let mentions = parse_mentions(message);
+    if mentions.is_empty() {
+        return Ok(ResolvedMentions::default());
     }
+    let handle = app_handle.clone();
+    let workspace_dir = workspace_dir.map(str::to_string);
+    let ignore = ignore.clone();
+    async_runtime::spawn_blocking(move || {
+        let state = handle.state::<AppState>();
+        MentionResolver::new(workspace_dir.as_deref(), Some(&state.terminal), &ignore).resolve_all(&mentions)
+    })
+    .await
+    .map_err(|e| AppError::GenericError(format!("解析引用任务失败: {}", e)))?
 }
 
 /// Save clipboard image to a temporary file and return its absolute path
@@ -0,0 +1,79 @@
+//! Tauri commands for AI-drafted commit messages
+
+use std::path::PathBuf;
+
+use tauri::{async_runtime, AppHandle, Manager};
+use tracing::{debug, info, warn};
+
+use crate::core::AppState;
+use crate::services::ai_ignore::AiIgnore;
+use crate::services::commit_message::{self, CommitMessageDraft, CommitMessageRequest};
+use crate::services::redaction::SecretRedactor;
+
+use super::commands::standalone_audit_context;
+use super::selection_edit_commands::edit_backend;
+
+/// Draft a commit message (or pull request description) for the staged changes;
+/// nothing is committed
+#[tauri::command]
+pub async fn generate_commit_message(
+    app_handle: AppHandle,
+    request: CommitMessageRequest,
+) -> Result<CommitMessageDraft, String> {
+    debug!(
+        "Command: generate_commit_message - workspace: {}, kind: {:?}, native_model: {:?}, code_cli: {:?}",
+        request.workspace_dir, request.kind, request.native_model, request.code_cli
+    );
+
+    let root = PathBuf::from(request.workspace_dir.trim());
+    if !root.is_dir() {
+        return Err(format!("工作区目录不存在: {}", request.workspace_dir));
+    }
+    let config = crate::core::app::get_config(app_handle.state::<AppState>());
+    let backend = edit_backend(
+        &config,
+        request.native_model.as_deref(),
+        request.code_cli.as_deref(),
+        request.codex_model.clone(),
+        Some(request.workspace_dir.clone()),
+    )?;
+
+    let ignore = AiIgnore::load(Some(&config.app.data_dir), Some(&request.workspace_dir));
+    let (changes, subjects) = async_runtime::spawn_blocking(move || {
+        commit_message::staged_changes(&root, &ignore).map(|changes| (changes, commit_message::recent_subjects(&root)))
+    })
+    .await
+    .map_err(|e| format!("读取暂存区失败: {}", e))?
+    .map_err(|e| e.to_string())?;
+
+    let mut redactor = SecretRedactor::new(&config.redaction, &config.env_vars);
+    let audit = standalone_audit_context(&app_handle).await;
+    let draft = commit_message::draft_message(&backend, &request, changes, &subjects, &mut redactor, audit)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !redactor.redactions().is_empty() {
+        warn!(
+            workspace = %request.workspace_dir,
+            count = redactor.redactions().len(),
+            "Redacted secrets from the commit message prompt"
+        );
+    }
+    Ok(draft)
+}
+
+/// Commit the staged changes with an (edited) drafted message; returns the short commit hash
+#[tauri::command]
+pub async fn commit_staged_changes(workspace_dir: String, message: String) -> Result<String, String> {
+    debug!("Command: commit_staged_changes - workspace: {}", workspace_dir);
+
+    let root = PathBuf::from(workspace_dir.trim());
+    if !root.is_dir() {
+        return Err(format!("工作区目录不存在: {}", workspace_dir));
+    }
+    let hash = async_runtime::spawn_blocking(move || commit_message::commit_staged(&root, &message))
+        .await
+        .map_err(|e| format!("提交任务失败: {}", e))?
+        .map_err(|e| e.to_string())?;
+    info!(workspace = %workspace_dir, commit = %hash, "Committed staged changes");
+    Ok(hash)
+}
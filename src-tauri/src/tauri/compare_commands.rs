@@ -0,0 +1,534 @@
+//! Tauri commands for side-by-side comparison runs
+//!
+//! A comparison sends one prompt, built once from a shared context snapshot,
+//! to several code CLIs at the same time. Each run streams `ai-response`
+//! events under its own sub-request id (and can be cancelled by it); the
+//! replies are stored as sibling assistant messages once all runs finish.
+//! A backend that has not seen the earlier turns also gets a handoff of them.
+
+use std::collections::HashMap;
+
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use tauri::{async_runtime, AppHandle, Manager};
+use tokio::sync::oneshot;
+use tracing::{debug, error, warn};
+
+use super::commands::{
+    audit_context, code_cli_handoff, emit_chunked_response, prepare_request_context, report_redactions, resolve_mentions,
+    track_streaming_task,
+};
+use super::event_handlers::{emit_ai_failure, emit_ai_response, emit_compare_complete};
+use crate::core::AppState;
+use crate::services::ai::{AiChatOptions, AiService};
+use crate::services::ai_ignore::AiIgnore;
+use crate::services::chat_session::{self, ChatMessage, ChatSession, MessageMetadata};
+use crate::services::context::{attached_images_note, split_attachments};
+use crate::services::history;
+use crate::services::direct_cli::{
+    build_direct_cli_plan, derive_backend_from_command, run_direct_cli, CliStream, DirectCliRun,
+};
+use crate::services::mock_backend::{self, run_mock_backend};
+use crate::services::redaction::SecretRedactor;
+use crate::utils::error::{AppError, AppResult};
+
+/// Upper bound on backends in one comparison
+const MAX_COMPARE_TARGETS: usize = 6;
+const DEFAULT_AUTO_CONTEXT_TOP_K: usize = 5;
+
+/// One backend of a comparison, configured like a regular chat request
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompareTarget {
+    /// Code CLI name (e.g. `claude-cli`); also the key of its resume id
+    pub code_cli: String,
+    /// Run the CLI command directly instead of codeagent-wrapper
+    #[serde(default)]
+    pub direct_cli: bool,
+    #[serde(default)]
+    pub cli_command: Option<String>,
+    #[serde(default)]
+    pub cli_args: Vec<String>,
+    #[serde(default)]
+    pub codex_model: Option<String>,
+}
+
+/// A started run of a comparison
+#[derive(Debug, Clone, Serialize)]
+pub struct CompareRun {
+    pub request_id: String,
+    pub code_cli: String,
+}
+
+/// Ids of a started comparison
+#[derive(Debug, Clone, Serialize)]
+pub struct CompareRequest {
+    pub compare_id: String,
+    pub session_id: String,
+    pub runs: Vec<CompareRun>,
+}
+
+/// Comparison winner and the resume id to continue on its backend
+#[derive(Debug, Clone, Serialize)]
+pub struct CompareWinner {
+    pub session: ChatSession,
+    pub code_cli: Option<String>,
+    pub code_cli_task_id: Option<String>,
+}
+
+enum RunOutcome {
+    Completed {
+        response: String,
+        task_id: Option<String>,
+    },
+    Failed(String),
+    Cancelled,
+}
+
+/// Inputs shared by every run of a comparison
+struct CompareShared {
+    app_handle: AppHandle,
+    session_id: String,
+    workspace_id: Option<String>,
+    workdir: String,
+    data_dir: String,
+    env: Vec<(String, String)>,
+    task: String,
+    /// Catch-up transcript per code CLI (see `code_cli_handoff`)
+    handoffs: HashMap<String, String>,
+    images: Vec<String>,
+}
+
+/// Send one prompt to several code CLIs and stream each reply under its own request id
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn send_compare_request(
+    app_handle: AppHandle,
+    message: String,
+    context_files: Option<Vec<String>>,
+    targets: Vec<CompareTarget>,
+    session_id: Option<String>,
+    workspace_id: Option<String>,
+    workspace_dir: Option<String>,
+    auto_context: Option<bool>,
+    auto_context_top_k: Option<usize>,
+) -> Result<CompareRequest, String> {
+    debug!(
+        "Command: send_compare_request - targets: {:?}",
+        targets.iter().map(|t| t.code_cli.as_str()).collect::<Vec<_>>()
+    );
+
+    if targets.len() < 2 {
+        return Err("对比至少需要两个 Code CLI".to_string());
+    }
+    if targets.len() > MAX_COMPARE_TARGETS {
+        return Err(format!("对比最多支持 {} 个 Code CLI", MAX_COMPARE_TARGETS));
+    }
+    let mut names: Vec<&str> = targets.iter().map(|t| t.code_cli.as_str()).collect();
+    names.sort_unstable();
+    names.dedup();
+    if names.len() != targets.len() {
+        return Err("对比的 Code CLI 不能重复".to_string());
+    }
+
+    let config = crate::core::app::get_config(app_handle.state::<AppState>());
+    let session_id = session_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let compare_id = uuid::Uuid::new_v4().to_string();
+    let resume_ids = chat_session::load_session_by_id(&session_id)
+        .map(|s| s.code_cli_task_ids)
+        .unwrap_or_default();
+
+    // Context snapshot: files and snippets are read once and every backend gets the same task.
+    let files = context_files.clone().unwrap_or_default();
+    let ai_ignore = AiIgnore::load(Some(&config.app.data_dir), workspace_dir.as_deref());
+    let mentions = resolve_mentions(&app_handle, workspace_dir.as_deref(), &message, &ai_ignore)
+        .await
+        .map_err(|e| e.to_string())?;
+    let snippets = prepare_request_context(
+        &app_handle,
+        &compare_id,
+        workspace_dir.as_deref(),
+        &message,
+        Some(files.as_slice()),
+        &mentions,
+        &ai_ignore,
+        auto_context.unwrap_or(false),
+        auto_context_top_k.unwrap_or(DEFAULT_AUTO_CONTEXT_TOP_K),
+    )
+    .await;
+    let (text_files, image_files) = split_attachments(&files);
+    let task = AiService::build_task_with_context(&message, Some(text_files.as_slice()), &mentions.blocks, &snippets, &ai_ignore);
+    let mut redactor = SecretRedactor::new(&config.redaction, &config.env_vars);
+    let task = redactor.redact(&task);
+    let handoffs = targets
+        .iter()
+        .filter(|target| !mock_backend::is_mock_cli(Some(target.code_cli.as_str())))
+        .filter_map(|target| {
+            let resume_id = resume_ids.get(&target.code_cli);
+            let handoff = code_cli_handoff(&session_id, Some(&target.code_cli), resume_id.is_none(), resume_id.map(String::as_str))?;
+            Some((target.code_cli.clone(), redactor.redact(&handoff)))
+        })
+        .collect();
+    report_redactions(&app_handle, &compare_id, redactor.redactions());
+
+    let shared = std::sync::Arc::new(CompareShared {
+        app_handle: app_handle.clone(),
+        session_id: session_id.clone(),
+        workspace_id: workspace_id.clone(),
+        workdir: workspace_dir.unwrap_or_else(|| ".".to_string()),
+        data_dir: config.app.data_dir.clone(),
+        env: config.env_vars.clone(),
+        task,
+        handoffs,
+        images: ai_ignore.allowed(&image_files),
+    });
+
+    let mut runs = Vec::new();
+    let mut outcomes = Vec::new();
+    for target in targets {
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
+        let (outcome_tx, outcome_rx) = oneshot::channel::<RunOutcome>();
+        let resume_id = resume_ids.get(&target.code_cli).cloned();
+
+        let shared_for_run = shared.clone();
+        let target_for_run = target.clone();
+        let request_id_for_run = request_id.clone();
+        let join_handle = async_runtime::spawn(async move {
+            let outcome = run_target(
+                &shared_for_run,
+                &target_for_run,
+                &request_id_for_run,
+                resume_id,
+                cancel_rx,
+            )
+            .await;
+            let _ = outcome_tx.send(outcome);
+        });
+        track_streaming_task(&app_handle, &request_id, join_handle, cancel_tx);
+
+        outcomes.push(outcome_rx);
+        runs.push(CompareRun {
+            request_id,
+            code_cli: target.code_cli,
+        });
+    }
+
+    let runs_for_store = runs.clone();
+    let compare_id_for_store = compare_id.clone();
+    let files_for_store = context_files.filter(|f| !f.is_empty());
+    async_runtime::spawn(async move {
+        let results = join_all(outcomes).await;
+        store_compare_results(
+            &shared,
+            &compare_id_for_store,
+            &message,
+            files_for_store,
+            &runs_for_store,
+            results
+                .into_iter()
+                .map(|r| r.unwrap_or(RunOutcome::Cancelled))
+                .collect(),
+        );
+    });
+
+    Ok(CompareRequest {
+        compare_id,
+        session_id,
+        runs,
+    })
+}
+
+/// Pick the reply that continues the conversation; returns its backend and resume id
+#[tauri::command]
+pub async fn select_compare_winner(
+    session_id: String,
+    compare_id: String,
+    message_id: String,
+) -> Result<CompareWinner, String> {
+    debug!(
+        "Command: select_compare_winner - session: {}, compare: {}, message: {}",
+        session_id, compare_id, message_id
+    );
+
+    let session = chat_session::select_compare_winner(&session_id, &compare_id, &message_id)?;
+    let code_cli = session
+        .messages
+        .iter()
+        .find(|m| m.id == message_id)
+        .and_then(|m| m.metadata.as_ref())
+        .and_then(|m| m.code_cli.clone());
+    let code_cli_task_id = code_cli
+        .as_ref()
+        .and_then(|cli| session.code_cli_task_ids.get(cli).cloned());
+
+    Ok(CompareWinner {
+        session,
+        code_cli,
+        code_cli_task_id,
+    })
+}
+
+/// Reply of one backend before it is reported to the UI
+struct TargetReply {
+    response: String,
+    task_id: Option<String>,
+    /// Exit code of a streamed (mock or direct CLI) run; `None` for the wrapper,
+    /// whose reply has not been streamed yet
+    exit_code: Option<i32>,
+}
+
+/// Run one backend of a comparison, streaming its output under `request_id`
+async fn run_target(
+    shared: &CompareShared,
+    target: &CompareTarget,
+    request_id: &str,
+    resume_id: Option<String>,
+    cancel_rx: oneshot::Receiver<()>,
+) -> RunOutcome {
+    let emit = |delta: &str, done: bool, task_id: Option<&str>| {
+        let _ = emit_ai_response(
+            &shared.app_handle,
+            request_id,
+            delta,
+            done,
+            Some(&shared.session_id),
+            shared.workspace_id.as_deref(),
+            task_id,
+        );
+    };
+
+    let emit_failure = |delta: &str| {
+        let _ = emit_ai_failure(
+            &shared.app_handle,
+            request_id,
+            delta,
+            Some(&shared.session_id),
+            shared.workspace_id.as_deref(),
+        );
+    };
+
+    let result = execute_target(shared, target, request_id, resume_id, cancel_rx, |delta| {
+        emit(delta, false, None)
+    })
+    .await;
+    match result {
+        Ok(TargetReply { response, task_id, exit_code: Some(0) }) => {
+            emit("", true, task_id.as_deref());
+            RunOutcome::Completed { response, task_id }
+        }
+        Ok(TargetReply { mut response, exit_code: Some(code), .. }) => {
+            let delta = format!("[exit {}] CLI exited with errors\n", code);
+            emit_failure(&delta);
+            response.push_str(&delta);
+            RunOutcome::Failed(response)
+        }
+        Ok(TargetReply { response, task_id, exit_code: None }) => {
+            let full_response = emit_chunked_response(
+                &shared.app_handle,
+                request_id,
+                &response,
+                Some(&shared.session_id),
+                shared.workspace_id.as_deref(),
+                task_id.as_deref(),
+            )
+            .await;
+            RunOutcome::Completed {
+                response: full_response,
+                task_id,
+            }
+        }
+        Err(AppError::Cancelled(_)) => {
+            debug!(request_id = %request_id, "Comparison run cancelled");
+            RunOutcome::Cancelled
+        }
+        Err(e) => {
+            warn!(request_id = %request_id, code_cli = %target.code_cli, "Comparison run failed: {}", e);
+            let text = format!("[AI error] {}", e);
+            emit_failure(&text);
+            RunOutcome::Failed(text)
+        }
+    }
+}
+
+/// Dispatch to the mock backend, a direct CLI or codeagent-wrapper, like a regular chat request
+async fn execute_target(
+    shared: &CompareShared,
+    target: &CompareTarget,
+    request_id: &str,
+    resume_id: Option<String>,
+    cancel_rx: oneshot::Receiver<()>,
+    mut on_delta: impl FnMut(&str),
+) -> AppResult<TargetReply> {
+    let audit = audit_context(&shared.app_handle, request_id, &shared.session_id).await;
+    let code_cli_changed = Some(resume_id.is_none());
+    let handoff = shared.handoffs.get(&target.code_cli).map(String::as_str);
+    // Images not handed over as `--image` are listed by path at the end of the prompt.
+    let task_for = |images_as_args: bool| {
+        let mut task = shared.task.clone();
+        if !images_as_args {
+            task.push_str(&attached_images_note(&shared.images));
+        }
+        history::with_handoff(handoff, task)
+    };
+    let command = target
+        .cli_command
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty());
+
+    let outcome = if mock_backend::is_mock_cli(Some(target.code_cli.as_str())) {
+        let scripts = mock_backend::load_scripts(&mock_backend::fixtures_dir(&shared.data_dir));
+        let script = mock_backend::select_script(&scripts, &shared.task);
+        run_mock_backend(
+            &script,
+            &shared.task,
+            resume_id.as_deref(),
+            Some(cancel_rx),
+            audit,
+            |_, delta| on_delta(delta),
+        )
+        .await?
+    } else if let (true, Some(command)) = (target.direct_cli, command) {
+        let backend = AiService::derive_backend_from_code_cli(&target.code_cli)
+            .or_else(|| derive_backend_from_command(command));
+        let plan = build_direct_cli_plan(
+            backend.as_deref(),
+            &target.cli_args,
+            resume_id.as_deref(),
+            code_cli_changed,
+            &shared.images,
+        );
+        let task = task_for(plan.images_as_args);
+        run_direct_cli(
+            DirectCliRun {
+                command,
+                args: &plan.args,
+                workdir: &shared.workdir,
+                env: &shared.env,
+                input: &task,
+                backend: backend.as_deref(),
+                task_id: plan.task_id.clone(),
+                audit,
+                permissions: None,
+            },
+            Some(cancel_rx),
+            |_: CliStream, delta: &str| on_delta(delta),
+        )
+        .await?
+    } else {
+        let task = task_for(false);
+        let result = AiService::new()
+            .send_message_with_options(
+                &task,
+                None,
+                AiChatOptions {
+                    code_cli: Some(target.code_cli.clone()),
+                    resume_session_id: resume_id,
+                    codex_model: target.codex_model.clone(),
+                    workspace_dir: Some(shared.workdir.clone()),
+                    code_cli_changed,
+                    env: shared.env.clone(),
+                    prepared_task: Some(task.clone()),
+                    cancel_rx: Some(cancel_rx),
+                    audit,
+                    ..Default::default()
+                },
+            )
+            .await?;
+        return Ok(TargetReply {
+            response: result.message,
+            task_id: result.codeagent_session_id,
+            exit_code: None,
+        });
+    };
+
+    Ok(TargetReply {
+        response: outcome.output,
+        task_id: outcome.task_id,
+        exit_code: Some(outcome.exit_code),
+    })
+}
+
+/// Append the prompt and every finished reply as siblings, and remember each backend's session id
+fn store_compare_results(
+    shared: &CompareShared,
+    compare_id: &str,
+    message: &str,
+    files: Option<Vec<String>>,
+    runs: &[CompareRun],
+    outcomes: Vec<RunOutcome>,
+) {
+    let now = chrono::Local::now().to_rfc3339();
+    let mut messages = vec![ChatMessage {
+        id: uuid::Uuid::new_v4().to_string(),
+        role: "user".to_string(),
+        content: message.to_string(),
+        timestamp: now.clone(),
+        files,
+        session_id: Some(shared.session_id.clone()),
+        workspace_id: shared.workspace_id.clone(),
+        model: None,
+        metadata: Some(MessageMetadata {
+            compare_id: Some(compare_id.to_string()),
+            ..Default::default()
+        }),
+    }];
+    let mut task_ids = Vec::new();
+    let mut summary = Vec::new();
+
+    for (run, outcome) in runs.iter().zip(outcomes) {
+        let (content, status) = match outcome {
+            RunOutcome::Completed { response, task_id } => {
+                if let Some(task_id) = task_id {
+                    task_ids.push((run.code_cli.clone(), task_id));
+                }
+                (response, "completed")
+            }
+            RunOutcome::Failed(text) => (text, "failed"),
+            RunOutcome::Cancelled => {
+                summary.push(serde_json::json!({
+                    "request_id": run.request_id,
+                    "code_cli": run.code_cli,
+                    "status": "cancelled",
+                }));
+                continue;
+            }
+        };
+        summary.push(serde_json::json!({
+            "request_id": run.request_id,
+            "code_cli": run.code_cli,
+            "status": status,
+        }));
+        messages.push(ChatMessage {
+            id: run.request_id.clone(),
+            role: "assistant".to_string(),
+            content,
+            timestamp: chrono::Local::now().to_rfc3339(),
+            files: None,
+            session_id: Some(shared.session_id.clone()),
+            workspace_id: shared.workspace_id.clone(),
+            model: None,
+            metadata: Some(MessageMetadata {
+                compare_id: Some(compare_id.to_string()),
+                code_cli: Some(run.code_cli.clone()),
+                ..Default::default()
+            }),
+        });
+    }
+
+    if messages.len() > 1 {
+        if let Err(e) =
+            chat_session::append_message_to_session(&shared.session_id, messages, None, None)
+        {
+            error!("Failed to append comparison to session {}: {}", shared.session_id, e);
+        } else if !task_ids.is_empty() {
+            if let Err(e) = chat_session::update_code_cli_task_ids(&shared.session_id, task_ids) {
+                error!("Failed to store comparison session ids for {}: {}", shared.session_id, e);
+            }
+        }
+    }
+
+    if let Err(e) = emit_compare_complete(&shared.app_handle, compare_id, &shared.session_id, &summary) {
+        warn!("Failed to emit ai-compare-complete event: {:?}", e);
+    }
+}
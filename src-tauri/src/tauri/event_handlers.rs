@@ -5,6 +5,11 @@
 use tauri::{AppHandle, Emitter, Listener};
 use tracing::debug;
 
+use crate::services::context::ContextManifest;
+use crate::services::permission_prompt::PermissionPrompt;
+use crate::services::redaction::Redaction;
+use crate::services::test_fix::{TestFixIteration, TestFixStatus};
+use crate::services::workspace_changes::FileChange;
 use crate::utils::error::{AppError, AppResult};
 
 /// Register event handlers
@@ -82,6 +87,226 @@ pub fn emit_ai_response(
         .map_err(|e| AppError::TauriError(e))
 }
 
+/// Decode a payload as seen by `Listener::listen`. The emitters send JSON strings
+/// (`payload.to_string()`), which Tauri serializes again, so the raw payload is a
+/// JSON string literal wrapping the object; unwrap it.
+pub fn parse_event_payload(raw: &str) -> serde_json::Value {
+    use serde_json::Value;
+    match serde_json::from_str::<Value>(raw) {
+        Ok(Value::String(inner)) => serde_json::from_str(&inner).unwrap_or(Value::String(inner)),
+        Ok(value) => value,
+        Err(_) => Value::String(raw.to_string()),
+    }
+}
+
+/// An `ai-response` event as seen by backend listeners
+#[derive(Debug, Clone)]
+pub struct AiResponseChunk {
+    pub request_id: String,
+    pub delta: String,
+    pub done: bool,
+    /// Set on the final chunk of a failed request (see `emit_ai_failure`)
+    pub failed: bool,
+}
+
+impl AiResponseChunk {
+    /// Parse a raw `ai-response` payload; `None` when it is not one
+    pub fn parse(payload: &str) -> Option<Self> {
+        let payload = parse_event_payload(payload);
+        let flag = |key: &str| payload.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+        Some(Self {
+            request_id: payload.get("request_id")?.as_str()?.to_string(),
+            delta: payload.get("delta").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+            done: flag("done"),
+            failed: flag("failed"),
+        })
+    }
+}
+
+/// Emit the final chunk of a failed AI response. `delta` is shown in the chat as
+/// the reply's last chunk; `failed: true` tells listeners it is an error, not a reply.
+pub fn emit_ai_failure(
+    app_handle: &AppHandle,
+    request_id: &str,
+    delta: &str,
+    session_id: Option<&str>,
+    workspace_id: Option<&str>,
+) -> AppResult<()> {
+    let payload = serde_json::json!({
+        "request_id": request_id,
+        "delta": delta,
+        "done": true,
+        "failed": true,
+        "session_id": session_id,
+        "workspace_id": workspace_id,
+        "code_cli_task_id": null,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    app_handle.emit("ai-response", payload.to_string())
+        .map_err(|e| AppError::TauriError(e))
+}
+
+/// Emit the context manifest (attached files and auto-context snippets) for a request
+pub fn emit_ai_context(
+    app_handle: &AppHandle,
+    request_id: &str,
+    manifest: &ContextManifest,
+) -> AppResult<()> {
+    let payload = serde_json::json!({
+        "request_id": request_id,
+        "manifest": manifest,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    app_handle.emit("ai-context", payload.to_string())
+        .map_err(|e| AppError::TauriError(e))
+}
+
+/// Warn that secrets were replaced with placeholders before a request was sent
+pub fn emit_secrets_redacted(
+    app_handle: &AppHandle,
+    request_id: &str,
+    redactions: &[Redaction],
+) -> AppResult<()> {
+    let payload = serde_json::json!({
+        "request_id": request_id,
+        "redactions": redactions,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    app_handle.emit("secrets-redacted", payload.to_string())
+        .map_err(|e| AppError::TauriError(e))
+}
+
+/// Report the workspace files a run created, modified or deleted
+pub fn emit_files_changed_by_run(
+    app_handle: &AppHandle,
+    request_id: &str,
+    session_id: &str,
+    changes: &[FileChange],
+) -> AppResult<()> {
+    let payload = serde_json::json!({
+        "request_id": request_id,
+        "session_id": session_id,
+        "changes": changes,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    app_handle.emit("files-changed-by-run", payload.to_string())
+        .map_err(|e| AppError::TauriError(e))
+}
+
+/// Report that every run of a comparison finished and its replies were stored
+pub fn emit_compare_complete(
+    app_handle: &AppHandle,
+    compare_id: &str,
+    session_id: &str,
+    runs: &[serde_json::Value],
+) -> AppResult<()> {
+    let payload = serde_json::json!({
+        "compare_id": compare_id,
+        "session_id": session_id,
+        "runs": runs,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    app_handle.emit("ai-compare-complete", payload.to_string())
+        .map_err(|e| AppError::TauriError(e))
+}
+
+/// Report the outcome of a scheduled job run
+pub fn emit_scheduled_job_finished(
+    app_handle: &AppHandle,
+    job_id: &str,
+    session_id: &str,
+    status: &str,
+    error: Option<&str>,
+) -> AppResult<()> {
+    let payload = serde_json::json!({
+        "job_id": job_id,
+        "session_id": session_id,
+        "status": status,
+        "error": error,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    app_handle.emit("scheduled-job-finished", payload.to_string())
+        .map_err(|e| AppError::TauriError(e))
+}
+
+/// Report a test run of a test-fix loop, and the fix request made for its failures
+pub fn emit_test_fix_progress(
+    app_handle: &AppHandle,
+    run_id: &str,
+    session_id: &str,
+    iteration: &TestFixIteration,
+) -> AppResult<()> {
+    let payload = serde_json::json!({
+        "run_id": run_id,
+        "session_id": session_id,
+        "iteration": iteration,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    app_handle.emit("test-fix-progress", payload.to_string())
+        .map_err(|e| AppError::TauriError(e))
+}
+
+/// Report how a test-fix loop ended
+pub fn emit_test_fix_finished(
+    app_handle: &AppHandle,
+    run_id: &str,
+    session_id: &str,
+    status: TestFixStatus,
+    error: Option<&str>,
+) -> AppResult<()> {
+    let payload = serde_json::json!({
+        "run_id": run_id,
+        "session_id": session_id,
+        "status": status,
+        "error": error,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    app_handle.emit("test-fix-finished", payload.to_string())
+        .map_err(|e| AppError::TauriError(e))
+}
+
+/// Ask the user to answer a CLI permission prompt of a streaming request
+pub fn emit_permission_request(
+    app_handle: &AppHandle,
+    permission_id: &str,
+    request_id: &str,
+    session_id: &str,
+    prompt: &PermissionPrompt,
+) -> AppResult<()> {
+    let payload = serde_json::json!({
+        "permission_id": permission_id,
+        "request_id": request_id,
+        "session_id": session_id,
+        "message": prompt.message,
+        "tool": prompt.tool,
+        "input": prompt.input,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    app_handle.emit("permission-request", payload.to_string())
+        .map_err(|e| AppError::TauriError(e))
+}
+
+/// Ask the editor to open a file (requested through the local API)
+pub fn emit_open_file_request(app_handle: &AppHandle, path: &str, line: Option<u32>) -> AppResult<()> {
+    let payload = serde_json::json!({
+        "path": path,
+        "line": line,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    app_handle.emit("open-file-request", payload.to_string())
+        .map_err(|e| AppError::TauriError(e))
+}
+
 /// Emit log message event
 pub fn emit_log_message(app_handle: &AppHandle, level: &str, message: &str) -> AppResult<()> {
     let payload = serde_json::json!({
@@ -136,3 +361,44 @@ pub fn emit_error(app_handle: &AppHandle, error: &str, details: Option<&str>) ->
     app_handle.emit("error", payload.to_string())
         .map_err(|e| AppError::TauriError(e))
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    /// What a listener receives for `emit(event, payload.to_string())`
+    fn emitted(payload: &serde_json::Value) -> String {
+        serde_json::to_string(&payload.to_string()).unwrap()
+    }
+
+    #[test]
+    fn parse_event_payload_unwraps_emitted_strings() {
+        let payload = serde_json::json!({ "request_id": "r1", "delta": "hi", "done": false });
+        assert_eq!(parse_event_payload(&emitted(&payload)), payload);
+    }
+
+    #[test]
+    fn parse_event_payload_accepts_plain_objects() {
+        let payload = serde_json::json!({ "request_id": "r1" });
+        assert_eq!(parse_event_payload(&payload.to_string()), payload);
+    }
+
+    #[test]
+    fn parse_event_payload_keeps_non_json() {
+        assert_eq!(parse_event_payload("\"text\""), Value::String("text".into()));
+        assert_eq!(parse_event_payload("not json"), Value::String("not json".into()));
+    }
+
+    #[test]
+    fn ai_response_chunk_parses_emitted_payloads() {
+        let payload = serde_json::json!({ "request_id": "r1", "delta": "oops", "done": true, "failed": true });
+        let chunk = AiResponseChunk::parse(&emitted(&payload)).unwrap();
+        assert_eq!(chunk.request_id, "r1");
+        assert_eq!(chunk.delta, "oops");
+        assert!(chunk.done && chunk.failed);
+
+        assert!(AiResponseChunk::parse(&emitted(&serde_json::json!({ "delta": "x" }))).is_none());
+    }
+}
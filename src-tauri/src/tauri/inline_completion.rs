@@ -0,0 +1,229 @@
+//! Inline completion commands
+//!
+//! Ghost-text suggestions for the editor, independent of the chat streaming
+//! pipeline. Every request supersedes the pending one of the same file: the
+//! newest request waits out the debounce, older ones return `superseded` as soon
+//! as a newer one starts, even while their provider call is in flight. A
+//! suggestion that is not ready `deadline_ms` after the keystroke is dropped.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tauri::{App, AppHandle, Manager};
+use tokio::sync::Notify;
+use tracing::debug;
+
+use crate::config::InlineCompletionSettings;
+use crate::core::AppState;
+use crate::services::ai::AiService;
+use crate::services::ai_ignore::AiIgnore;
+use crate::services::inline_completion::{self, CompletionCache, InlineCompletion, InlineCompletionRequest};
+use crate::services::native_provider::NativeProvider;
+use crate::services::redaction::SecretRedactor;
+use crate::utils::error::{AppError, AppResult};
+
+/// Pending requests, recent suggestions and the provider in use
+pub struct InlineCompletions {
+    next_id: AtomicU64,
+    /// Newest request per file
+    latest: Mutex<HashMap<String, u64>>,
+    /// Woken whenever `latest` changes
+    changed: Notify,
+    cache: Mutex<CompletionCache>,
+    /// Provider of the last used model, kept so its connections stay warm
+    provider: Mutex<Option<NativeProvider>>,
+}
+
+impl Default for InlineCompletions {
+    fn default() -> Self {
+        Self {
+            next_id: AtomicU64::new(0),
+            latest: Mutex::new(HashMap::new()),
+            changed: Notify::new(),
+            cache: Mutex::new(CompletionCache::new(InlineCompletionSettings::default().cache_entries)),
+            provider: Mutex::new(None),
+        }
+    }
+}
+
+impl InlineCompletions {
+    /// Register a request as the newest of its file
+    fn begin(&self, file_path: &str) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Ok(mut latest) = self.latest.lock() {
+            latest.insert(file_path.to_string(), id);
+        }
+        self.changed.notify_waiters();
+        id
+    }
+
+    /// Forget the file's request if `id` is still the newest one
+    fn finish(&self, file_path: &str, id: u64) {
+        if let Ok(mut latest) = self.latest.lock() {
+            if latest.get(file_path) == Some(&id) {
+                latest.remove(file_path);
+            }
+        }
+    }
+
+    /// Supersede the pending request of a file without starting a new one
+    fn cancel(&self, file_path: &str) {
+        if let Ok(mut latest) = self.latest.lock() {
+            latest.remove(file_path);
+        }
+        self.changed.notify_waiters();
+    }
+
+    fn is_latest(&self, file_path: &str, id: u64) -> bool {
+        self.latest
+            .lock()
+            .map(|latest| latest.get(file_path) == Some(&id))
+            .unwrap_or(true)
+    }
+
+    /// Resolves once request `id` is no longer the newest of its file
+    async fn superseded(&self, file_path: &str, id: u64) {
+        loop {
+            // Created before the check so a change in between is not missed.
+            let notified = self.changed.notified();
+            if !self.is_latest(file_path, id) {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    fn cached(&self, settings: &InlineCompletionSettings, file_path: &str, prefix: &str, suffix: &str) -> Option<String> {
+        let mut cache = self.cache.lock().ok()?;
+        cache.set_capacity(settings.cache_entries);
+        cache.get(file_path, prefix, suffix)
+    }
+
+    fn store(&self, file_path: &str, prefix: &str, suffix: &str, text: &str) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.put(file_path, prefix, suffix, text);
+        }
+    }
+
+    /// Provider for `model_name`, reusing the previous one when the model is unchanged
+    fn provider(&self, model_name: &str, env: &[(String, String)]) -> AppResult<NativeProvider> {
+        let mut current = self
+            .provider
+            .lock()
+            .map_err(|e| AppError::GenericError(format!("补全状态锁定失败: {}", e)))?;
+        if let Some(provider) = current.as_ref().filter(|p| p.model() == model_name) {
+            return Ok(provider.clone());
+        }
+        let model = AiService::new()
+            .find_model(model_name)
+            .ok_or_else(|| AppError::ConfigError(format!("Model not found: {}", model_name)))?;
+        let provider = NativeProvider::from_model(&model, env)?;
+        *current = Some(provider.clone());
+        Ok(provider)
+    }
+}
+
+/// Register the inline completion state
+pub fn init(app: &mut App) -> AppResult<()> {
+    app.manage(InlineCompletions::default());
+    Ok(())
+}
+
+/// Suggest the text to insert at the editor cursor.
+///
+/// Returns an empty suggestion with `skipped` set when completions are disabled,
+/// the file is excluded by `.aiignore`, a newer request superseded this one or
+/// the deadline passed.
+#[tauri::command]
+pub async fn request_inline_completion(
+    app_handle: AppHandle,
+    request: InlineCompletionRequest,
+) -> Result<InlineCompletion, String> {
+    let started = Instant::now();
+    let elapsed_ms = || started.elapsed().as_millis() as u64;
+
+    let config = crate::core::app::get_config(app_handle.state::<AppState>());
+    let settings = config.inline_completion.clone();
+    if !settings.enabled {
+        return Ok(InlineCompletion::skipped("disabled", elapsed_ms()));
+    }
+    let model_name = settings
+        .model
+        .clone()
+        .filter(|model| !model.trim().is_empty())
+        .ok_or_else(|| "未配置行内补全模型 (inline_completion.model)".to_string())?;
+    let ignore = AiIgnore::load(Some(&config.app.data_dir), request.workspace_dir.as_deref());
+    if ignore.is_ignored(Path::new(&request.file_path)) {
+        return Ok(InlineCompletion::skipped("ignored", elapsed_ms()));
+    }
+
+    let state = app_handle.state::<InlineCompletions>();
+    let file_path = request.file_path.as_str();
+    let id = state.begin(file_path);
+    let (prefix, suffix) = inline_completion::window(&request.prefix, &request.suffix);
+
+    if let Some(text) = state.cached(&settings, file_path, prefix, suffix) {
+        state.finish(file_path, id);
+        return Ok(InlineCompletion {
+            text,
+            cached: true,
+            skipped: None,
+            elapsed_ms: elapsed_ms(),
+        });
+    }
+
+    let deadline = tokio::time::Instant::from_std(started) + Duration::from_millis(settings.deadline_ms);
+    let work = async {
+        tokio::time::sleep(Duration::from_millis(settings.debounce_ms)).await;
+        let provider = state.provider(&model_name, &config.env_vars)?;
+        let mut redactor = SecretRedactor::new(&config.redaction, &config.env_vars);
+        inline_completion::suggest(
+            &provider,
+            file_path,
+            request.language.as_deref(),
+            prefix,
+            suffix,
+            settings.max_tokens,
+            &mut redactor,
+        )
+        .await
+    };
+
+    let outcome = tokio::select! {
+        _ = state.superseded(file_path, id) => Ok(InlineCompletion::skipped("superseded", elapsed_ms())),
+        result = tokio::time::timeout_at(deadline, work) => match result {
+            Err(_) => Ok(InlineCompletion::skipped("timeout", elapsed_ms())),
+            Ok(Ok(text)) => {
+                state.store(file_path, prefix, suffix, &text);
+                Ok(InlineCompletion {
+                    text,
+                    cached: false,
+                    skipped: None,
+                    elapsed_ms: elapsed_ms(),
+                })
+            }
+            Ok(Err(e)) => Err(e.to_string()),
+        },
+    };
+    state.finish(file_path, id);
+
+    debug!(
+        file = %file_path,
+        line = request.line,
+        column = request.column,
+        outcome = ?outcome.as_ref().map(|c| c.skipped.as_deref().unwrap_or("suggested")),
+        elapsed_ms = elapsed_ms(),
+        "Inline completion finished"
+    );
+    outcome
+}
+
+/// Drop the pending inline completion of a file, e.g. when the editor dismissed it
+#[tauri::command]
+pub async fn cancel_inline_completion(app_handle: AppHandle, file_path: String) -> Result<(), String> {
+    app_handle.state::<InlineCompletions>().cancel(&file_path);
+    Ok(())
+}
@@ -1,9 +1,25 @@
 //! Tauri modules
 
+pub mod api_server;
 pub mod commands;
 pub mod event_handlers;
 pub mod settings_commands;
 pub mod workspace_command;
 pub mod fs_command;
 pub mod chat_session_commands;
-pub mod notification_commands;
\ No newline at end of file
+pub mod notification_commands;
+pub mod search_index_commands;
+pub mod response_cache_commands;
+pub mod audit_commands;
+pub mod compare_commands;
+pub mod plan_commands;
+pub mod permissions;
+pub mod scheduler;
+pub mod scheduler_commands;
+pub mod request_journal;
+pub mod workspace_budget;
+pub mod inline_completion;
+pub mod selection_edit_commands;
+pub mod test_fix_commands;
+pub mod commit_message_commands;
+pub mod code_review_commands;
@@ -0,0 +1,88 @@
+//! CLI permission prompts bridged to the UI
+//!
+//! Direct CLI runs hand permission prompts to a channel created here. Each
+//! prompt is emitted as a `permission-request` event under a new permission id;
+//! `respond_permission_request` sends the user's answer back to the waiting run.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tauri::{async_runtime, App, AppHandle, Manager};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+use crate::services::permission_prompt::PermissionRequest;
+use crate::utils::error::AppResult;
+
+use super::event_handlers::emit_permission_request;
+
+/// Prompts waiting for an answer, by permission id
+#[derive(Default)]
+pub struct PendingPermissions {
+    replies: Mutex<HashMap<String, oneshot::Sender<bool>>>,
+}
+
+/// Register the pending prompt registry
+pub fn init(app: &mut App) -> AppResult<()> {
+    app.manage(PendingPermissions::default());
+    Ok(())
+}
+
+/// Channel for the permission prompts of one streaming request.
+///
+/// Prompts left unanswered when the run ends are forgotten (the run is gone).
+pub(crate) fn permission_bridge(
+    app_handle: &AppHandle,
+    request_id: &str,
+    session_id: &str,
+) -> Option<mpsc::UnboundedSender<PermissionRequest>> {
+    app_handle.try_state::<PendingPermissions>()?;
+    let (tx, mut rx) = mpsc::unbounded_channel::<PermissionRequest>();
+    let app_handle = app_handle.clone();
+    let request_id = request_id.to_string();
+    let session_id = session_id.to_string();
+    async_runtime::spawn(async move {
+        let mut asked = Vec::new();
+        while let Some(request) = rx.recv().await {
+            let permission_id = uuid::Uuid::new_v4().to_string();
+            debug!(request_id = %request_id, permission_id = %permission_id, "CLI permission prompt");
+            if let Err(e) =
+                emit_permission_request(&app_handle, &permission_id, &request_id, &session_id, &request.prompt)
+            {
+                // Nobody can be asked; dropping the reply denies.
+                warn!("Failed to emit permission-request event: {:?}", e);
+                continue;
+            }
+            let pending = app_handle.state::<PendingPermissions>();
+            pending.replies.lock().unwrap().insert(permission_id.clone(), request.reply);
+            asked.push(permission_id);
+        }
+        let pending = app_handle.state::<PendingPermissions>();
+        let mut replies = pending.replies.lock().unwrap();
+        for permission_id in asked {
+            replies.remove(&permission_id);
+        }
+    });
+    Some(tx)
+}
+
+/// Answer a `permission-request`; `allow: false` denies
+#[tauri::command]
+pub async fn respond_permission_request(
+    app_handle: AppHandle,
+    permission_id: String,
+    allow: bool,
+) -> Result<(), String> {
+    debug!("Command: respond_permission_request - {} allow: {}", permission_id, allow);
+
+    let reply = app_handle
+        .state::<PendingPermissions>()
+        .replies
+        .lock()
+        .unwrap()
+        .remove(&permission_id)
+        .ok_or_else(|| "权限请求已失效（CLI 已退出或已回答）".to_string())?;
+    reply
+        .send(allow)
+        .map_err(|_| "权限请求已失效（CLI 已退出或已回答）".to_string())
+}
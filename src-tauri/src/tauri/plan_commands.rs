@@ -0,0 +1,74 @@
+//! Tauri commands for plan-then-execute runs
+//!
+//! The plan itself is requested with `send_chat_message_streaming(plan_mode: true)`;
+//! its reply is stored with `metadata.plan` set. `execute_plan` starts the second phase.
+
+use tauri::AppHandle;
+use tracing::debug;
+
+use crate::services::chat_session;
+use crate::services::plan_mode;
+
+use super::commands::send_chat_message_streaming;
+
+/// Run an approved (possibly edited) plan in the session of its plan run; returns the request id
+#[tauri::command]
+pub async fn execute_plan(
+    app_handle: AppHandle,
+    plan: String,
+    message: String,
+    session_id: String,
+    context_files: Option<Vec<String>>,
+    code_cli: Option<String>,
+    codex_model: Option<String>,
+    workspace_id: Option<String>,
+    workspace_dir: Option<String>,
+    code_cli_task_id: Option<String>,
+    direct_cli: Option<bool>,
+    cli_command: Option<String>,
+    cli_args: Option<Vec<String>>,
+    auto_context: Option<bool>,
+    auto_context_top_k: Option<usize>,
+    native_model: Option<String>,
+) -> Result<String, String> {
+    debug!(
+        session_id = %session_id,
+        code_cli = ?code_cli,
+        code_cli_task_id = ?code_cli_task_id,
+        "Command: execute_plan"
+    );
+    if plan.trim().is_empty() {
+        return Err("计划内容不能为空".to_string());
+    }
+
+    // Continue the CLI session the plan was made in, so the backend keeps what it read.
+    let resume_id = code_cli_task_id.filter(|id| !id.trim().is_empty()).or_else(|| {
+        let code_cli = code_cli.as_ref()?;
+        chat_session::load_session_by_id(&session_id)
+            .ok()?
+            .code_cli_task_ids
+            .get(code_cli)
+            .cloned()
+    });
+    send_chat_message_streaming(
+        app_handle,
+        plan_mode::execution_message(&plan, &message),
+        context_files,
+        code_cli,
+        codex_model,
+        Some(session_id),
+        workspace_id,
+        workspace_dir,
+        Some(resume_id.is_none()),
+        resume_id,
+        direct_cli,
+        cli_command,
+        cli_args,
+        auto_context,
+        auto_context_top_k,
+        native_model,
+        Some(false),
+        None,
+    )
+    .await
+}
@@ -0,0 +1,158 @@
+//! Request journal wiring and commands
+//!
+//! Feeds `ai-response` events of journaled requests into the request journal
+//! and lets the UI list and resume requests that were orphaned by a restart.
+
+use tauri::{async_runtime, App, AppHandle, Listener, Manager};
+use tracing::{debug, error};
+
+use crate::database::repositories::ai_request_repository::{status, AiRequestRecord, AiRequestRepository};
+use crate::services::chat_session;
+use crate::services::request_journal::{self, RequestJournal};
+use crate::utils::error::AppResult;
+
+use super::commands::send_chat_message_streaming;
+use super::event_handlers::parse_event_payload;
+
+/// Characters of partial output included when resuming a request
+const RESUME_OUTPUT_CHARS: usize = 8000;
+
+/// Register the journal and start its writer
+pub fn init(app: &mut App) -> AppResult<()> {
+    let (journal, rx) = RequestJournal::channel();
+    app.manage(journal.clone());
+
+    app.handle().listen("ai-response", move |event| {
+        let payload = parse_event_payload(event.payload());
+        let Some(request_id) = payload.get("request_id").and_then(|v| v.as_str()) else {
+            return;
+        };
+        let delta = payload.get("delta").and_then(|v| v.as_str()).unwrap_or_default();
+        journal.output(request_id, delta);
+        if let Some(task_id) = payload.get("code_cli_task_id").and_then(|v| v.as_str()) {
+            journal.cli_session_id(request_id, task_id);
+        }
+        if payload.get("done").and_then(|v| v.as_bool()).unwrap_or(false) {
+            if payload.get("failed").and_then(|v| v.as_bool()).unwrap_or(false) {
+                journal.finished(request_id, status::FAILED, Some(delta.trim().to_string()));
+            } else {
+                journal.finished(request_id, status::FINISHED, None);
+            }
+        }
+    });
+
+    let app_handle = app.handle().clone();
+    async_runtime::spawn(async move {
+        match crate::database::connection::get_db_connection(&app_handle).await {
+            Ok(db) => request_journal::run_writer(db, rx).await,
+            Err(e) => error!("Request journal disabled, database unavailable: {}", e),
+        }
+    });
+    Ok(())
+}
+
+/// Journal handle, if the journal is registered (not in headless mode)
+pub(crate) fn journal(app_handle: &AppHandle) -> Option<RequestJournal> {
+    app_handle.try_state::<RequestJournal>().map(|j| j.inner().clone())
+}
+
+/// List journaled requests, newest first; `status: "orphaned"` lists the resumable ones
+#[tauri::command]
+pub async fn list_ai_requests(
+    app_handle: AppHandle,
+    status: Option<String>,
+    session_id: Option<String>,
+    limit: Option<u64>,
+) -> Result<Vec<AiRequestRecord>, String> {
+    debug!("Command: list_ai_requests - status: {:?}, session: {:?}", status, session_id);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    AiRequestRepository::list(&db, status.as_deref(), session_id.as_deref(), limit)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Run an orphaned request again, continuing its code CLI session; returns the new request id
+#[tauri::command]
+pub async fn resume_ai_request(app_handle: AppHandle, request_id: String) -> Result<String, String> {
+    debug!("Command: resume_ai_request - request_id: {}", request_id);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    let record = AiRequestRepository::get(&db, &request_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Request not found: {}", request_id))?;
+    if record.status != status::ORPHANED {
+        return Err(format!("只能恢复中断的请求（当前状态：{}）", record.status));
+    }
+
+    let resume_id = record.cli_session_id.clone().or_else(|| {
+        let code_cli = record.code_cli.as_ref()?;
+        chat_session::load_session_by_id(&record.session_id)
+            .ok()?
+            .code_cli_task_ids
+            .get(code_cli)
+            .cloned()
+    });
+    let new_request_id = send_chat_message_streaming(
+        app_handle.clone(),
+        resume_message(&record),
+        Some(record.context_files.clone()),
+        record.code_cli.clone(),
+        record.codex_model.clone(),
+        Some(record.session_id.clone()),
+        record.workspace_id.clone(),
+        record.workspace_dir.clone(),
+        Some(resume_id.is_none()),
+        resume_id,
+        Some(record.direct_cli),
+        record.cli_command.clone(),
+        Some(record.cli_args.clone()),
+        Some(record.auto_context),
+        None,
+        record.native_model.clone(),
+        None,
+        None,
+    )
+    .await?;
+
+    let note = format!("Resumed as {}", new_request_id);
+    let now = chrono::Utc::now().timestamp_millis();
+    AiRequestRepository::set_status(&db, &request_id, status::RESUMED, Some(&note), now)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(new_request_id)
+}
+
+/// Forget a journaled request
+#[tauri::command]
+pub async fn dismiss_ai_request(app_handle: AppHandle, request_id: String) -> Result<(), String> {
+    debug!("Command: dismiss_ai_request - request_id: {}", request_id);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    AiRequestRepository::delete(&db, &request_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// The original message, plus the output of the interrupted run so the model can continue it
+fn resume_message(record: &AiRequestRecord) -> String {
+    let output = record.partial_output.trim();
+    if output.is_empty() {
+        return record.message.clone();
+    }
+    let skip = output.chars().count().saturating_sub(RESUME_OUTPUT_CHARS);
+    let tail: String = output.chars().skip(skip).collect();
+    format!(
+        "{}\n\nA previous run of this request was interrupted. Its output so far is below; \
+         continue from where it stopped instead of starting over.\n\n<partial_output>\n{}\n</partial_output>",
+        record.message, tail
+    )
+}
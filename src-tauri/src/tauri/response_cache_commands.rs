@@ -0,0 +1,32 @@
+//! Tauri commands for the AI response cache
+
+use tauri::AppHandle;
+use tracing::debug;
+
+use crate::database::repositories::response_cache_repository::{ResponseCacheRepository, ResponseCacheStats};
+
+/// Number and total size of cached responses
+#[tauri::command]
+pub async fn get_response_cache_stats(app_handle: AppHandle) -> Result<ResponseCacheStats, String> {
+    debug!("Command: get_response_cache_stats");
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    ResponseCacheRepository::stats(&db)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Remove all cached responses, returning how many were removed
+#[tauri::command]
+pub async fn clear_response_cache(app_handle: AppHandle) -> Result<u64, String> {
+    debug!("Command: clear_response_cache");
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    ResponseCacheRepository::clear(&db)
+        .await
+        .map_err(|e| e.to_string())
+}
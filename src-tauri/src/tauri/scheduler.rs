@@ -0,0 +1,246 @@
+//! Scheduled job runner
+//!
+//! Checks the `scheduled_jobs` table every 30 seconds and runs due jobs
+//! through the regular streaming chat pipeline, in the job's own session.
+//! A system notification reports every finished or failed run, so jobs
+//! keep working while the window is hidden in the tray.
+//!
+//! A job never runs twice at the same time; a run that is still going when
+//! the job comes due again simply skips that occurrence. Occurrences missed
+//! while the app was closed are caught up with a single run.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use tauri::{async_runtime, App, AppHandle, Listener, Manager};
+use tokio::sync::mpsc;
+use tokio::time::{interval, timeout, Duration};
+use tracing::{debug, error, info, warn};
+
+use crate::core::notification_manager::NotificationManager;
+use crate::database::repositories::scheduled_job_repository::{ScheduledJob, ScheduledJobRepository};
+use crate::services::scheduler;
+use crate::utils::error::{AppError, AppResult};
+
+use super::commands::{cancel_streaming_request, send_chat_message_streaming};
+use super::event_handlers::{emit_scheduled_job_finished, AiResponseChunk};
+
+const TICK_INTERVAL: Duration = Duration::from_secs(30);
+/// Runs still going after this long are cancelled and reported as failed
+const JOB_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+/// Characters of the reply shown in the notification
+const NOTIFICATION_PREVIEW_CHARS: usize = 160;
+
+/// Ids of jobs with a run in progress
+#[derive(Default)]
+pub struct JobScheduler {
+    running: Mutex<HashSet<String>>,
+}
+
+/// Register the scheduler state and start the background loop
+pub fn init(app: &mut App) -> AppResult<()> {
+    app.manage(JobScheduler::default());
+
+    let app_handle = app.handle().clone();
+    async_runtime::spawn(async move {
+        match crate::database::connection::get_db_connection(&app_handle).await {
+            Ok(db) => match ScheduledJobRepository::mark_interrupted(&db).await {
+                Ok(0) => {}
+                Ok(count) => warn!("Marked {} interrupted scheduled job run(s)", count),
+                Err(e) => warn!("Failed to mark interrupted scheduled jobs: {}", e),
+            },
+            Err(e) => {
+                error!("Scheduler disabled, database unavailable: {}", e);
+                return;
+            }
+        }
+
+        info!("Job scheduler started");
+        let mut ticker = interval(TICK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            run_due_jobs(&app_handle).await;
+        }
+    });
+
+    Ok(())
+}
+
+async fn run_due_jobs(app_handle: &AppHandle) {
+    let db = match crate::database::connection::get_db_connection(app_handle).await {
+        Ok(db) => db,
+        Err(e) => {
+            warn!("Scheduler tick skipped: {}", e);
+            return;
+        }
+    };
+    let now = chrono::Utc::now().timestamp_millis();
+    match ScheduledJobRepository::list_due(&db, now).await {
+        Ok(jobs) => {
+            for job in jobs {
+                start_job(app_handle, job);
+            }
+        }
+        Err(e) => warn!("Failed to load due scheduled jobs: {}", e),
+    }
+}
+
+/// Run `job` in the background; returns `false` if it is already running
+pub(crate) fn start_job(app_handle: &AppHandle, job: ScheduledJob) -> bool {
+    {
+        let scheduler = app_handle.state::<JobScheduler>();
+        let mut running = scheduler.running.lock().unwrap();
+        if !running.insert(job.id.clone()) {
+            debug!(job_id = %job.id, "Scheduled job still running, skipping");
+            return false;
+        }
+    }
+
+    let app_handle = app_handle.clone();
+    async_runtime::spawn(async move {
+        run_job(&app_handle, &job).await;
+        let scheduler = app_handle.state::<JobScheduler>();
+        scheduler.running.lock().unwrap().remove(&job.id);
+    });
+    true
+}
+
+async fn run_job(app_handle: &AppHandle, job: &ScheduledJob) {
+    let db = match crate::database::connection::get_db_connection(app_handle).await {
+        Ok(db) => db,
+        Err(e) => {
+            warn!(job_id = %job.id, "Scheduled job not run: {}", e);
+            return;
+        }
+    };
+
+    info!(job_id = %job.id, name = %job.name, "Running scheduled job");
+    let now = chrono::Local::now();
+    let next_run_at = if job.enabled {
+        scheduler::next_run_at(&job.schedule, now).unwrap_or_else(|e| {
+            warn!(job_id = %job.id, "{}", e);
+            None
+        })
+    } else {
+        None
+    };
+    if let Err(e) =
+        ScheduledJobRepository::mark_started(&db, &job.id, now.timestamp_millis(), next_run_at).await
+    {
+        warn!(job_id = %job.id, "Failed to record scheduled job start: {}", e);
+    }
+
+    let result = execute_job(app_handle, &db, job).await;
+    let (status, error) = match &result {
+        Ok(_) => ("success", None),
+        Err(e) => ("failed", Some(e.to_string())),
+    };
+    if let Err(e) = ScheduledJobRepository::mark_finished(&db, &job.id, status, error.as_deref()).await {
+        warn!(job_id = %job.id, "Failed to record scheduled job result: {}", e);
+    }
+    if let Err(e) = emit_scheduled_job_finished(app_handle, &job.id, &job.session_id, status, error.as_deref()) {
+        warn!("Failed to emit scheduled-job-finished event: {:?}", e);
+    }
+
+    let (title, body) = match &result {
+        Ok(reply) => (format!("定时任务已完成：{}", job.name), preview(reply)),
+        Err(e) => (format!("定时任务失败：{}", job.name), preview(&e.to_string())),
+    };
+    if let Err(e) = app_handle.state::<NotificationManager>().notify(Some(&title), &body) {
+        warn!("Failed to notify scheduled job result: {}", e);
+    }
+}
+
+/// Dispatch the job's prompt and collect the streamed reply
+async fn execute_job(
+    app_handle: &AppHandle,
+    db: &sea_orm::DatabaseConnection,
+    job: &ScheduledJob,
+) -> AppResult<String> {
+    // Listen before dispatching so no early chunk is missed; the request id is only known afterwards.
+    let (tx, rx) = mpsc::unbounded_channel::<AiResponseChunk>();
+    let listener = app_handle.listen("ai-response", move |event| {
+        if let Some(chunk) = AiResponseChunk::parse(event.payload()) {
+            let _ = tx.send(chunk);
+        }
+    });
+
+    let result = dispatch_and_wait(app_handle, db, job, rx).await;
+    app_handle.unlisten(listener);
+    result
+}
+
+async fn dispatch_and_wait(
+    app_handle: &AppHandle,
+    db: &sea_orm::DatabaseConnection,
+    job: &ScheduledJob,
+    mut rx: mpsc::UnboundedReceiver<AiResponseChunk>,
+) -> AppResult<String> {
+    // Each run starts a fresh CLI conversation without a handoff of earlier runs; the
+    // session only collects the results. A recurring prompt must run every time, so
+    // the response cache is skipped.
+    let request_id = send_chat_message_streaming(
+        app_handle.clone(),
+        job.prompt.clone(),
+        None,
+        job.code_cli.clone(),
+        None,
+        Some(job.session_id.clone()),
+        job.workspace_id.clone(),
+        Some(job.workspace_dir.clone()),
+        None,
+        None,
+        Some(job.direct_cli),
+        job.cli_command.clone(),
+        Some(job.cli_args.clone()),
+        Some(job.auto_context),
+        None,
+        job.native_model.clone(),
+        None,
+        Some(false),
+    )
+    .await
+    .map_err(AppError::AiServiceError)?;
+    if let Err(e) = ScheduledJobRepository::set_last_request_id(db, &job.id, &request_id).await {
+        warn!(job_id = %job.id, "Failed to record scheduled job request id: {}", e);
+    }
+
+    let mut reply = String::new();
+    let finished = timeout(JOB_TIMEOUT, async {
+        while let Some(chunk) = rx.recv().await {
+            if chunk.request_id != request_id {
+                continue;
+            }
+            reply.push_str(&chunk.delta);
+            if chunk.done {
+                return Some(chunk.failed);
+            }
+        }
+        None
+    })
+    .await;
+
+    match finished {
+        Ok(Some(false)) => Ok(reply),
+        Ok(Some(true)) => Err(AppError::AiServiceError(reply.trim().to_string())),
+        Ok(None) => Err(AppError::AiServiceError("Response stream closed unexpectedly".to_string())),
+        Err(_) => {
+            let _ = cancel_streaming_request(app_handle.clone(), request_id).await;
+            Err(AppError::ProcessError(format!(
+                "Timed out after {} minutes",
+                JOB_TIMEOUT.as_secs() / 60
+            )))
+        }
+    }
+}
+
+fn preview(text: &str) -> String {
+    let text = text.trim();
+    let mut chars = text.chars();
+    let preview: String = chars.by_ref().take(NOTIFICATION_PREVIEW_CHARS).collect();
+    if chars.next().is_some() {
+        format!("{}...", preview)
+    } else {
+        preview
+    }
+}
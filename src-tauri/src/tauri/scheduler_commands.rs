@@ -0,0 +1,225 @@
+//! Tauri commands for scheduled agent jobs
+
+use std::path::Path;
+
+use serde::Deserialize;
+use tauri::AppHandle;
+use tracing::debug;
+
+use super::scheduler::start_job;
+use crate::database::repositories::scheduled_job_repository::{ScheduledJob, ScheduledJobRepository};
+use crate::services::chat_session;
+use crate::services::scheduler::{self, CronSchedule};
+
+const MAX_PREVIEW_RUNS: usize = 20;
+
+/// User-editable fields of a scheduled job
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduledJobInput {
+    pub name: String,
+    #[serde(default)]
+    pub workspace_id: Option<String>,
+    pub workspace_dir: String,
+    pub prompt: String,
+    pub schedule: String,
+    #[serde(default)]
+    pub code_cli: Option<String>,
+    #[serde(default)]
+    pub direct_cli: bool,
+    #[serde(default)]
+    pub cli_command: Option<String>,
+    #[serde(default)]
+    pub cli_args: Vec<String>,
+    #[serde(default)]
+    pub native_model: Option<String>,
+    #[serde(default)]
+    pub auto_context: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl ScheduledJobInput {
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("任务名称不能为空".to_string());
+        }
+        if self.prompt.trim().is_empty() {
+            return Err("任务提示词不能为空".to_string());
+        }
+        if !Path::new(self.workspace_dir.trim()).is_dir() {
+            return Err(format!("工作区目录不存在: {}", self.workspace_dir));
+        }
+        let has_backend = self.code_cli.as_deref().is_some_and(|c| !c.trim().is_empty())
+            || self.native_model.as_deref().is_some_and(|m| !m.trim().is_empty());
+        if !has_backend {
+            return Err("请选择 Code CLI 或模型".to_string());
+        }
+        CronSchedule::parse(&self.schedule).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn apply(self, job: &mut ScheduledJob) -> Result<(), String> {
+        let now = chrono::Local::now();
+        job.next_run_at = if self.enabled {
+            scheduler::next_run_at(&self.schedule, now).map_err(|e| e.to_string())?
+        } else {
+            None
+        };
+        job.name = self.name.trim().to_string();
+        job.workspace_id = self.workspace_id;
+        job.workspace_dir = self.workspace_dir.trim().to_string();
+        job.prompt = self.prompt;
+        job.schedule = self.schedule.trim().to_string();
+        job.code_cli = self.code_cli.filter(|c| !c.trim().is_empty());
+        job.direct_cli = self.direct_cli;
+        job.cli_command = self.cli_command.filter(|c| !c.trim().is_empty());
+        job.cli_args = self.cli_args;
+        job.native_model = self.native_model.filter(|m| !m.trim().is_empty());
+        job.auto_context = self.auto_context;
+        job.enabled = self.enabled;
+        job.updated_at = now.timestamp_millis();
+        Ok(())
+    }
+}
+
+/// List scheduled jobs, optionally only those of one workspace
+#[tauri::command]
+pub async fn list_scheduled_jobs(
+    app_handle: AppHandle,
+    workspace_id: Option<String>,
+) -> Result<Vec<ScheduledJob>, String> {
+    debug!("Command: list_scheduled_jobs - workspace: {:?}", workspace_id);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    ScheduledJobRepository::list(&db, workspace_id.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Create a job together with the session its results are stored in
+#[tauri::command]
+pub async fn create_scheduled_job(
+    app_handle: AppHandle,
+    input: ScheduledJobInput,
+) -> Result<ScheduledJob, String> {
+    debug!("Command: create_scheduled_job - name: {}", input.name);
+    input.validate()?;
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut job = ScheduledJob {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: String::new(),
+        workspace_id: None,
+        workspace_dir: String::new(),
+        prompt: String::new(),
+        schedule: String::new(),
+        code_cli: None,
+        direct_cli: false,
+        cli_command: None,
+        cli_args: Vec::new(),
+        native_model: None,
+        auto_context: false,
+        enabled: true,
+        session_id: uuid::Uuid::new_v4().to_string(),
+        next_run_at: None,
+        last_run_at: None,
+        last_status: None,
+        last_error: None,
+        last_request_id: None,
+        created_at: now,
+        updated_at: now,
+    };
+    input.apply(&mut job)?;
+
+    chat_session::save_session(
+        Some(job.session_id.clone()),
+        Some(format!("定时任务：{}", job.name)),
+        job.workspace_id.clone(),
+        Vec::new(),
+        None,
+    )?;
+    ScheduledJobRepository::insert(&db, &job)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(job)
+}
+
+/// Update a job; the next run is recomputed from the (new) schedule
+#[tauri::command]
+pub async fn update_scheduled_job(
+    app_handle: AppHandle,
+    id: String,
+    input: ScheduledJobInput,
+) -> Result<ScheduledJob, String> {
+    debug!("Command: update_scheduled_job - id: {}", id);
+    input.validate()?;
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut job = ScheduledJobRepository::get(&db, &id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Scheduled job not found: {}", id))?;
+    input.apply(&mut job)?;
+    ScheduledJobRepository::update(&db, &job)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(job)
+}
+
+/// Delete a job; its session and results are kept
+#[tauri::command]
+pub async fn delete_scheduled_job(app_handle: AppHandle, id: String) -> Result<(), String> {
+    debug!("Command: delete_scheduled_job - id: {}", id);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !ScheduledJobRepository::delete(&db, &id)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        return Err(format!("Scheduled job not found: {}", id));
+    }
+    Ok(())
+}
+
+/// Run a job now, outside its schedule
+#[tauri::command]
+pub async fn run_scheduled_job_now(app_handle: AppHandle, id: String) -> Result<(), String> {
+    debug!("Command: run_scheduled_job_now - id: {}", id);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    let job = ScheduledJobRepository::get(&db, &id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Scheduled job not found: {}", id))?;
+    if !start_job(&app_handle, job) {
+        return Err("任务正在运行中".to_string());
+    }
+    Ok(())
+}
+
+/// Validate a schedule and return its next run times (RFC 3339, local time)
+#[tauri::command]
+pub async fn preview_schedule(schedule: String, count: Option<usize>) -> Result<Vec<String>, String> {
+    let parsed = CronSchedule::parse(&schedule).map_err(|e| e.to_string())?;
+    let count = count.unwrap_or(5).clamp(1, MAX_PREVIEW_RUNS);
+    Ok(parsed
+        .upcoming(chrono::Local::now(), count)
+        .iter()
+        .map(|time| time.to_rfc3339())
+        .collect())
+}
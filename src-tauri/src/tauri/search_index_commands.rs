@@ -0,0 +1,105 @@
+//! Tauri commands for the workspace search index
+//!
+//! `init` starts the background refresh that keeps the indexes of open
+//! workspaces in step with file changes.
+
+use tauri::{App, AppHandle, Manager};
+use tauri::async_runtime;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use tracing::debug;
+
+use crate::core::AppState;
+use crate::services::context::ContextSnippet;
+use crate::services::search_index::IndexRefreshStats;
+use crate::utils::error::AppResult;
+
+/// How often loaded indexes are checked for changed files
+const WATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Refresh the loaded search indexes periodically
+pub fn init(app: &mut App) -> AppResult<()> {
+    let app_handle = app.handle().clone();
+    async_runtime::spawn(async move {
+        let mut ticker = interval(WATCH_INTERVAL);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes immediately; nothing is loaded yet.
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let app_handle = app_handle.clone();
+            let refresh = async_runtime::spawn_blocking(move || {
+                let state = app_handle.state::<AppState>();
+                let Ok(data_dir) = state.config.lock().map(|config| config.app.data_dir.clone()) else {
+                    return;
+                };
+                state.search_index.refresh_loaded(&data_dir);
+            });
+            if let Err(e) = refresh.await {
+                debug!("Search index refresh task failed: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Build or incrementally refresh the search index of a workspace
+#[tauri::command]
+pub async fn refresh_search_index(
+    app_handle: AppHandle,
+    workspace_dir: String,
+) -> Result<IndexRefreshStats, String> {
+    debug!("Command: refresh_search_index - workspace_dir: {}", workspace_dir);
+
+    async_runtime::spawn_blocking(move || {
+        let state = app_handle.state::<AppState>();
+        let data_dir = state.config.lock().map_err(|e| e.to_string())?.app.data_dir.clone();
+        state
+            .search_index
+            .refresh(&data_dir, &workspace_dir)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("刷新索引任务失败: {}", e))?
+}
+
+/// Search the workspace index and return the best matching snippets
+#[tauri::command]
+pub async fn search_workspace_index(
+    app_handle: AppHandle,
+    workspace_dir: String,
+    query: String,
+    top_k: Option<usize>,
+) -> Result<Vec<ContextSnippet>, String> {
+    debug!(
+        "Command: search_workspace_index - workspace_dir: {}, query: {}",
+        workspace_dir, query
+    );
+
+    async_runtime::spawn_blocking(move || {
+        let state = app_handle.state::<AppState>();
+        let data_dir = state.config.lock().map_err(|e| e.to_string())?.app.data_dir.clone();
+        state
+            .search_index
+            .search(&data_dir, &workspace_dir, &query, top_k.unwrap_or(10))
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("搜索索引任务失败: {}", e))?
+}
+
+/// Remove the search index of a workspace
+#[tauri::command]
+pub async fn clear_search_index(app_handle: AppHandle, workspace_dir: String) -> Result<(), String> {
+    debug!("Command: clear_search_index - workspace_dir: {}", workspace_dir);
+
+    async_runtime::spawn_blocking(move || {
+        let state = app_handle.state::<AppState>();
+        let data_dir = state.config.lock().map_err(|e| e.to_string())?.app.data_dir.clone();
+        state
+            .search_index
+            .clear(&data_dir, &workspace_dir)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("清除索引任务失败: {}", e))?
+}
@@ -0,0 +1,92 @@
+//! Tauri command for rewriting an editor selection with an instruction
+
+use std::path::Path;
+
+use tauri::{AppHandle, Manager};
+use tracing::{debug, warn};
+
+use crate::config::AppConfig;
+use crate::core::AppState;
+use crate::services::ai::AiService;
+use crate::services::ai_ignore::AiIgnore;
+use crate::services::native_provider::NativeProvider;
+use crate::services::redaction::SecretRedactor;
+use crate::services::selection_edit::{self, EditBackend, Selection, SelectionEdit, SelectionEditRequest};
+
+use super::commands::standalone_audit_context;
+
+/// Ask the selected backend to rewrite a line range and return the proposed
+/// replacement with its diff; nothing is written to the file
+#[tauri::command]
+pub async fn edit_selection(app_handle: AppHandle, request: SelectionEditRequest) -> Result<SelectionEdit, String> {
+    debug!(
+        "Command: edit_selection - file: {}, lines: {}-{}, native_model: {:?}, code_cli: {:?}",
+        request.file_path, request.start_line, request.end_line, request.native_model, request.code_cli
+    );
+
+    if request.instruction.trim().is_empty() {
+        return Err("编辑指令不能为空".to_string());
+    }
+    let config = crate::core::app::get_config(app_handle.state::<AppState>());
+    let ignore = AiIgnore::load(Some(&config.app.data_dir), request.workspace_dir.as_deref());
+    if ignore.is_ignored(Path::new(&request.file_path)) {
+        return Err(format!("{} 已被 .aiignore 排除，不能发送给模型", request.file_path));
+    }
+
+    let content = match &request.content {
+        Some(content) => content.clone(),
+        None => std::fs::read_to_string(&request.file_path)
+            .map_err(|e| format!("读取文件失败 {}: {}", request.file_path, e))?,
+    };
+    let selection =
+        Selection::from_content(&content, request.start_line, request.end_line).map_err(|e| e.to_string())?;
+
+    let backend = edit_backend(
+        &config,
+        request.native_model.as_deref(),
+        request.code_cli.as_deref(),
+        request.codex_model.clone(),
+        request.workspace_dir.clone(),
+    )?;
+
+    let mut redactor = SecretRedactor::new(&config.redaction, &config.env_vars);
+    let audit = standalone_audit_context(&app_handle).await;
+    let edit = selection_edit::request_edit(&backend, &request, &selection, &mut redactor, audit)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !redactor.redactions().is_empty() {
+        warn!(
+            file = %request.file_path,
+            count = redactor.redactions().len(),
+            "Redacted secrets from the selection edit prompt"
+        );
+    }
+    Ok(edit)
+}
+
+/// The native model when one is named, otherwise the code CLI
+pub(crate) fn edit_backend(
+    config: &AppConfig,
+    native_model: Option<&str>,
+    code_cli: Option<&str>,
+    codex_model: Option<String>,
+    workspace_dir: Option<String>,
+) -> Result<EditBackend, String> {
+    if let Some(model_name) = native_model.filter(|m| !m.trim().is_empty()) {
+        let model = AiService::new()
+            .find_model(model_name)
+            .ok_or_else(|| format!("Model not found: {}", model_name))?;
+        Ok(EditBackend::Native(
+            NativeProvider::from_model(&model, &config.env_vars).map_err(|e| e.to_string())?,
+        ))
+    } else if let Some(code_cli) = code_cli.filter(|c| !c.trim().is_empty()) {
+        Ok(EditBackend::CodeCli {
+            code_cli: code_cli.to_string(),
+            codex_model,
+            workspace_dir,
+            env: config.env_vars.clone(),
+        })
+    } else {
+        Err("请先选择模型或 Code CLI".to_string())
+    }
+}
@@ -0,0 +1,326 @@
+//! Tauri command for test-fix loops
+//!
+//! A loop runs the test command in a terminal session of its own and, while it
+//! fails, sends the failing output to the code CLI through the regular streaming
+//! chat pipeline. Fix requests therefore show up in the chat session, are checked
+//! against the workspace budget and report their diff with `files-changed-by-run`.
+//! Each test run is reported as a `test-fix-progress` event and the outcome as
+//! `test-fix-finished`. The loop is cancelled with `cancel_streaming_request`
+//! and its run id, which also stops the running test command or fix request.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use serde::Serialize;
+use tauri::{async_runtime, AppHandle, Listener, Manager};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{timeout, Duration};
+use tracing::{debug, info, warn};
+
+use crate::core::AppState;
+use crate::services::chat_session;
+use crate::services::terminal::CommandOutput;
+use crate::services::test_fix::{self, TestFixIteration, TestFixRequest, TestFixStatus};
+use crate::utils::error::{AppError, AppResult};
+
+use super::commands::{cancel_streaming_request, start_chat_stream, track_streaming_task};
+use super::event_handlers::{emit_test_fix_finished, emit_test_fix_progress, AiResponseChunk};
+
+/// Fix requests still streaming after this long end the loop with an error
+const FIX_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Ids of a started test-fix loop
+#[derive(Debug, Clone, Serialize)]
+pub struct TestFixRun {
+    pub run_id: String,
+    pub session_id: String,
+}
+
+/// Start a test-fix loop in the background; cancel it with `cancel_streaming_request(run_id)`
+#[tauri::command]
+pub async fn start_test_fix_loop(app_handle: AppHandle, request: TestFixRequest) -> Result<TestFixRun, String> {
+    debug!(
+        "Command: start_test_fix_loop - command: {}, code_cli: {}, max_iterations: {:?}",
+        request.test_command, request.code_cli, request.max_iterations
+    );
+
+    if request.test_command.trim().is_empty() {
+        return Err("测试命令不能为空".to_string());
+    }
+    if request.code_cli.trim().is_empty() {
+        return Err("请先选择 Code CLI".to_string());
+    }
+    if !std::path::Path::new(&request.workspace_dir).is_dir() {
+        return Err(format!("工作区目录不存在: {}", request.workspace_dir));
+    }
+
+    let run_id = uuid::Uuid::new_v4().to_string();
+    let session_id = request
+        .session_id
+        .clone()
+        .filter(|id| !id.trim().is_empty())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
+
+    let app_handle_for_task = app_handle.clone();
+    let run_id_for_task = run_id.clone();
+    let session_id_for_task = session_id.clone();
+    let join_handle = async_runtime::spawn(async move {
+        // Listen before the first fix is dispatched so no early chunk is missed.
+        let (tx, rx) = mpsc::unbounded_channel::<AiResponseChunk>();
+        let listener = app_handle_for_task.listen("ai-response", move |event| {
+            if let Some(chunk) = AiResponseChunk::parse(event.payload()) {
+                let _ = tx.send(chunk);
+            }
+        });
+
+        let mut test_fix_loop = TestFixLoop {
+            app_handle: &app_handle_for_task,
+            run_id: &run_id_for_task,
+            session_id: &session_id_for_task,
+            request: &request,
+            responses: rx,
+            cancel_rx,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        let (status, error) = test_fix_loop.run().await;
+        app_handle_for_task.unlisten(listener);
+
+        info!(run_id = %run_id_for_task, status = ?status, "Test-fix loop finished");
+        if let Err(e) = emit_test_fix_finished(
+            &app_handle_for_task,
+            &run_id_for_task,
+            &session_id_for_task,
+            status,
+            error.as_deref(),
+        ) {
+            warn!("Failed to emit test-fix-finished event: {:?}", e);
+        }
+    });
+    track_streaming_task(&app_handle, &run_id, join_handle, cancel_tx);
+
+    Ok(TestFixRun { run_id, session_id })
+}
+
+struct TestFixLoop<'a> {
+    app_handle: &'a AppHandle,
+    run_id: &'a str,
+    session_id: &'a str,
+    request: &'a TestFixRequest,
+    responses: mpsc::UnboundedReceiver<AiResponseChunk>,
+    cancel_rx: oneshot::Receiver<()>,
+    /// Set on cancellation; kills a running test command
+    cancelled: Arc<AtomicBool>,
+}
+
+impl TestFixLoop<'_> {
+    /// Test and fix until the tests pass or the loop has to stop; returns the outcome and its error
+    async fn run(&mut self) -> (TestFixStatus, Option<String>) {
+        let terminal_id = {
+            let state = self.app_handle.state::<AppState>();
+            match state
+                .terminal
+                .create_session(Some("Test fix".to_string()), Some(self.request.workspace_dir.clone()))
+            {
+                Ok(id) => id,
+                Err(e) => return (TestFixStatus::Error, Some(e.to_string())),
+            }
+        };
+        let result = self.iterate(&terminal_id).await;
+        if let Err(e) = self.app_handle.state::<AppState>().terminal.kill_session(&terminal_id) {
+            warn!("Failed to close test-fix terminal session {}: {}", terminal_id, e);
+        }
+        result
+    }
+
+    async fn iterate(&mut self, terminal_id: &str) -> (TestFixStatus, Option<String>) {
+        let max_iterations = self.request.max_iterations();
+        for iteration in 1..=max_iterations + 1 {
+            let output = match self.run_tests(terminal_id).await {
+                Ok(output) => output,
+                Err(AppError::Cancelled(_)) => return (TestFixStatus::Cancelled, None),
+                Err(e) => return (TestFixStatus::Error, Some(e.to_string())),
+            };
+            let passed = output.success();
+            let mut progress = TestFixIteration {
+                iteration,
+                passed,
+                exit_code: output.exit_code,
+                failures: if passed {
+                    String::new()
+                } else {
+                    test_fix::extract_failures(&format!("{}\n{}", output.stdout, output.stderr))
+                },
+                fix_request_id: None,
+            };
+            debug!(run_id = %self.run_id, iteration, passed, exit_code = ?output.exit_code, "Test run finished");
+
+            if passed || iteration > max_iterations {
+                self.report(&progress);
+                return if passed {
+                    (TestFixStatus::Passed, None)
+                } else {
+                    (TestFixStatus::Failed, None)
+                };
+            }
+
+            let message = test_fix::fix_message(
+                self.run_id,
+                &self.request.test_command,
+                iteration,
+                max_iterations,
+                &progress.failures,
+            );
+            let fix_request_id = match self.dispatch_fix(message).await {
+                Ok(request_id) => request_id,
+                Err(AppError::BudgetExceeded(reason)) => {
+                    self.report(&progress);
+                    return (TestFixStatus::BudgetExhausted, Some(reason));
+                }
+                Err(e) => {
+                    self.report(&progress);
+                    return (TestFixStatus::Error, Some(e.to_string()));
+                }
+            };
+            progress.fix_request_id = Some(fix_request_id.clone());
+            self.report(&progress);
+
+            match self.wait_for_fix(&fix_request_id).await {
+                Ok(()) => {}
+                Err(AppError::Cancelled(_)) => return (TestFixStatus::Cancelled, None),
+                Err(e) => return (TestFixStatus::Error, Some(e.to_string())),
+            }
+        }
+        unreachable!("the last iteration always returns")
+    }
+
+    /// Run the test command in the loop's terminal session, killing it on cancellation
+    async fn run_tests(&mut self, terminal_id: &str) -> AppResult<CommandOutput> {
+        let app_handle = self.app_handle.clone();
+        let terminal_id = terminal_id.to_string();
+        let shell = self.request.shell();
+        let command = self.request.test_command.clone();
+        let cancelled = self.cancelled.clone();
+        let run = async_runtime::spawn_blocking(move || {
+            let state = app_handle.state::<AppState>();
+            state.terminal.run_command(&terminal_id, &shell, &command, Some(cancelled.as_ref()))
+        });
+
+        tokio::select! {
+            _ = &mut self.cancel_rx => {
+                self.cancelled.store(true, Ordering::Relaxed);
+                Err(AppError::Cancelled("Test-fix loop cancelled".to_string()))
+            }
+            result = run => result.map_err(|e| AppError::ProcessError(format!("测试命令任务失败: {}", e)))?,
+        }
+    }
+
+    /// Send a fix request to the session, continuing the code CLI's conversation when it has one
+    async fn dispatch_fix(&self, message: String) -> AppResult<String> {
+        let resume_id = chat_session::load_session_by_id(self.session_id)
+            .ok()
+            .and_then(|session| session.code_cli_task_ids.get(&self.request.code_cli).cloned());
+        start_chat_stream(
+            self.app_handle.clone(),
+            message,
+            None,
+            Some(self.request.code_cli.clone()),
+            self.request.codex_model.clone(),
+            Some(self.session_id.to_string()),
+            self.request.workspace_id.clone(),
+            Some(self.request.workspace_dir.clone()),
+            Some(resume_id.is_none()),
+            resume_id,
+            Some(self.request.direct_cli),
+            self.request.cli_command.clone(),
+            Some(self.request.cli_args.clone()),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+    }
+
+    /// Wait for the fix request's final chunk; cancelling the loop cancels the request
+    async fn wait_for_fix(&mut self, request_id: &str) -> AppResult<()> {
+        let finished = timeout(FIX_TIMEOUT, final_chunk(&mut self.responses, request_id));
+
+        let result = tokio::select! {
+            _ = &mut self.cancel_rx => {
+                let _ = cancel_streaming_request(self.app_handle.clone(), request_id.to_string()).await;
+                return Err(AppError::Cancelled("Test-fix loop cancelled".to_string()));
+            }
+            result = finished => result,
+        };
+        match result {
+            Ok(Some(chunk)) if chunk.failed => Err(AppError::AiServiceError(chunk.delta.trim().to_string())),
+            Ok(Some(_)) => Ok(()),
+            Ok(None) => Err(AppError::AiServiceError("Response stream closed unexpectedly".to_string())),
+            Err(_) => {
+                let _ = cancel_streaming_request(self.app_handle.clone(), request_id.to_string()).await;
+                Err(AppError::ProcessError(format!(
+                    "Fix request timed out after {} minutes",
+                    FIX_TIMEOUT.as_secs() / 60
+                )))
+            }
+        }
+    }
+
+    fn report(&self, iteration: &TestFixIteration) {
+        if let Err(e) = emit_test_fix_progress(self.app_handle, self.run_id, self.session_id, iteration) {
+            warn!("Failed to emit test-fix-progress event: {:?}", e);
+        }
+    }
+}
+
+/// Final chunk of `request_id`'s reply; `None` when the stream closes first
+async fn final_chunk(
+    responses: &mut mpsc::UnboundedReceiver<AiResponseChunk>,
+    request_id: &str,
+) -> Option<AiResponseChunk> {
+    while let Some(chunk) = responses.recv().await {
+        if chunk.request_id == request_id && chunk.done {
+            return Some(chunk);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Feed payloads the way `emit("ai-response", payload.to_string())` delivers them to the listener
+    fn listen(payloads: &[serde_json::Value]) -> mpsc::UnboundedReceiver<AiResponseChunk> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for payload in payloads {
+            let raw = serde_json::to_string(&payload.to_string()).unwrap();
+            if let Some(chunk) = AiResponseChunk::parse(&raw) {
+                tx.send(chunk).unwrap();
+            }
+        }
+        rx
+    }
+
+    #[tokio::test]
+    async fn final_chunk_waits_for_the_fix_request() {
+        let mut responses = listen(&[
+            json!({ "request_id": "other", "delta": "", "done": true }),
+            json!({ "request_id": "fix", "delta": "patching", "done": false }),
+            json!({ "request_id": "fix", "delta": "quota exceeded", "done": true, "failed": true }),
+        ]);
+        let chunk = final_chunk(&mut responses, "fix").await.unwrap();
+        assert!(chunk.failed);
+        assert_eq!(chunk.delta, "quota exceeded");
+    }
+
+    #[tokio::test]
+    async fn final_chunk_ends_with_the_stream() {
+        let mut responses = listen(&[json!({ "request_id": "fix", "delta": "patching", "done": false })]);
+        assert!(final_chunk(&mut responses, "fix").await.is_none());
+    }
+}
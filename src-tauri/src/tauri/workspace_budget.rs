@@ -0,0 +1,290 @@
+//! Workspace budget wiring and commands
+//!
+//! Checks a workspace's budget before a chat request is dispatched, meters the
+//! reply from the request's `ai-response` events and shows a notification when
+//! a warning threshold is crossed. Updates of usage records are serialized so
+//! concurrent requests are all counted.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{async_runtime, App, AppHandle, Listener, Manager};
+use tracing::{debug, info, warn};
+
+use crate::core::notification_manager::NotificationManager;
+use crate::database::repositories::workspace_repository::WorkspaceRepository;
+use crate::services::context::split_attachments;
+use crate::services::history::estimate_tokens;
+use crate::services::workspace_budget::{
+    self, WorkspaceBudget, WorkspaceUsage, DEFAULT_OVERRIDE_MINUTES,
+};
+use crate::utils::error::{AppError, AppResult};
+
+use super::event_handlers::parse_event_payload;
+
+/// Running requests not finished after this long are forgotten
+const STALE_REQUEST_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Usage metering state
+#[derive(Default)]
+pub struct BudgetMeter {
+    /// Held while a usage record is read, changed and written back
+    usage_lock: tokio::sync::Mutex<()>,
+    /// Requests of budgeted workspaces whose reply is still streaming
+    running: Mutex<HashMap<String, RunningRequest>>,
+}
+
+struct RunningRequest {
+    workspace_id: String,
+    reply_chars: usize,
+    started_at: i64,
+}
+
+/// Register the meter and count the replies of budgeted requests
+pub fn init(app: &mut App) -> AppResult<()> {
+    app.manage(BudgetMeter::default());
+
+    let app_handle = app.handle().clone();
+    app.handle().listen("ai-response", move |event| {
+        let payload = parse_event_payload(event.payload());
+        let Some(request_id) = payload.get("request_id").and_then(|v| v.as_str()) else {
+            return;
+        };
+        let delta = payload.get("delta").and_then(|v| v.as_str()).unwrap_or_default();
+        let done = payload.get("done").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let meter = app_handle.state::<BudgetMeter>();
+        let finished = {
+            let Ok(mut running) = meter.running.lock() else {
+                return;
+            };
+            if let Some(request) = running.get_mut(request_id) {
+                request.reply_chars += delta.chars().count();
+            }
+            if done {
+                running.remove(request_id)
+            } else {
+                None
+            }
+        };
+        if let Some(request) = finished {
+            let app_handle = app_handle.clone();
+            async_runtime::spawn(async move {
+                let tokens = request.reply_chars.div_ceil(4) as u64;
+                if let Err(e) = record_tokens(&app_handle, &request.workspace_id, tokens).await {
+                    warn!("Failed to record usage of workspace {}: {}", request.workspace_id, e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Rough token count of a request: the message plus its attached text files
+pub(crate) fn estimate_request_tokens(message: &str, context_files: Option<&[String]>) -> u64 {
+    let (text_files, _) = split_attachments(context_files.unwrap_or_default());
+    let file_bytes: u64 = text_files
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    estimate_tokens(message) as u64 + file_bytes.div_ceil(4)
+}
+
+/// Refuse a request once its workspace is over budget; otherwise count it and meter its reply
+pub(crate) async fn check_budget(
+    app_handle: &AppHandle,
+    workspace_id: Option<&str>,
+    request_id: &str,
+    input_tokens: u64,
+) -> AppResult<()> {
+    let Some(workspace_id) = workspace_id.map(str::trim).filter(|id| !id.is_empty()) else {
+        return Ok(());
+    };
+    let Some(meter) = app_handle.try_state::<BudgetMeter>() else {
+        return Ok(());
+    };
+    match reserve(app_handle, &meter, workspace_id, input_tokens).await {
+        Ok(true) => {}
+        Ok(false) => return Ok(()),
+        Err(e @ AppError::BudgetExceeded(_)) => return Err(e),
+        // Metering is best effort: a storage problem must not block requests.
+        Err(e) => {
+            warn!("Failed to check budget of workspace {}: {}", workspace_id, e);
+            return Ok(());
+        }
+    }
+
+    if let Ok(mut running) = meter.running.lock() {
+        let now = chrono::Utc::now().timestamp_millis();
+        running.retain(|_, request| now - request.started_at < STALE_REQUEST_MS);
+        running.insert(
+            request_id.to_string(),
+            RunningRequest {
+                workspace_id: workspace_id.to_string(),
+                reply_chars: 0,
+                started_at: now,
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Count a new request against the budget; `false` when the workspace has no budget
+async fn reserve(
+    app_handle: &AppHandle,
+    meter: &BudgetMeter,
+    workspace_id: &str,
+    input_tokens: u64,
+) -> AppResult<bool> {
+    let db = crate::database::connection::get_db_connection(app_handle).await?;
+    let Some(budget) = workspace_budget::load_budget(&db, workspace_id).await? else {
+        return Ok(false);
+    };
+
+    let _guard = meter.usage_lock.lock().await;
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut usage = workspace_budget::load_usage(&db, workspace_id).await?;
+    usage.roll(now);
+    if let Some(reason) = budget.exceeded(&usage) {
+        if !usage.is_overridden(now) {
+            info!(workspace_id = %workspace_id, "Request refused: {}", reason);
+            return Err(AppError::BudgetExceeded(reason));
+        }
+        debug!(workspace_id = %workspace_id, "Budget limit overridden: {}", reason);
+    }
+    usage.add_request(now);
+    usage.add_tokens(&budget, input_tokens);
+    warn_on_threshold(app_handle, workspace_id, &budget, &mut usage).await;
+    workspace_budget::save_usage(&db, workspace_id, &usage).await?;
+    Ok(true)
+}
+
+/// Add the tokens of a finished reply to its workspace's usage
+async fn record_tokens(app_handle: &AppHandle, workspace_id: &str, tokens: u64) -> AppResult<()> {
+    let db = crate::database::connection::get_db_connection(app_handle).await?;
+    let Some(budget) = workspace_budget::load_budget(&db, workspace_id).await? else {
+        return Ok(());
+    };
+    let meter = app_handle.state::<BudgetMeter>();
+    let _guard = meter.usage_lock.lock().await;
+    let mut usage = workspace_budget::load_usage(&db, workspace_id).await?;
+    usage.roll(chrono::Utc::now().timestamp_millis());
+    usage.add_tokens(&budget, tokens);
+    warn_on_threshold(app_handle, workspace_id, &budget, &mut usage).await;
+    workspace_budget::save_usage(&db, workspace_id, &usage).await
+}
+
+/// Notify once per month and threshold when the budget use crosses a warning threshold
+async fn warn_on_threshold(
+    app_handle: &AppHandle,
+    workspace_id: &str,
+    budget: &WorkspaceBudget,
+    usage: &mut WorkspaceUsage,
+) {
+    let Some(threshold) = budget.crossed_threshold(usage) else {
+        return;
+    };
+    usage.warned_threshold = threshold;
+    let used = budget.used_fraction(usage).unwrap_or(threshold);
+    let name = workspace_name(app_handle, workspace_id).await;
+    let body = format!("工作区「{}」本月已使用约 {:.0}% 的 AI 预算", name, used * 100.0);
+    if let Err(e) = app_handle
+        .state::<NotificationManager>()
+        .notify(Some("AI 预算提醒"), &body)
+    {
+        warn!("Failed to notify budget threshold: {}", e);
+    }
+}
+
+async fn workspace_name(app_handle: &AppHandle, workspace_id: &str) -> String {
+    let Ok(id) = workspace_id.parse::<i32>() else {
+        return workspace_id.to_string();
+    };
+    let Ok(db) = crate::database::connection::get_db_connection(app_handle).await else {
+        return workspace_id.to_string();
+    };
+    WorkspaceRepository::get_by_id(&db, &id)
+        .await
+        .ok()
+        .flatten()
+        .map(|workspace| workspace.name)
+        .unwrap_or_else(|| workspace_id.to_string())
+}
+
+/// Budget of a workspace, `None` when it has none
+#[tauri::command]
+pub async fn get_workspace_budget(
+    app_handle: AppHandle,
+    workspace_id: String,
+) -> Result<Option<WorkspaceBudget>, String> {
+    debug!("Command: get_workspace_budget - workspace: {}", workspace_id);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    workspace_budget::load_budget(&db, &workspace_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Set the budget of a workspace; `None` removes it
+#[tauri::command]
+pub async fn save_workspace_budget(
+    app_handle: AppHandle,
+    workspace_id: String,
+    budget: Option<WorkspaceBudget>,
+) -> Result<(), String> {
+    debug!("Command: save_workspace_budget - workspace: {}", workspace_id);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    match budget {
+        Some(budget) => workspace_budget::save_budget(&db, &workspace_id, &budget).await,
+        None => workspace_budget::delete_budget(&db, &workspace_id).await,
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Usage of a workspace in the current month and hour
+#[tauri::command]
+pub async fn get_workspace_usage(app_handle: AppHandle, workspace_id: String) -> Result<WorkspaceUsage, String> {
+    debug!("Command: get_workspace_usage - workspace: {}", workspace_id);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut usage = workspace_budget::load_usage(&db, &workspace_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    usage.roll(chrono::Utc::now().timestamp_millis());
+    Ok(usage)
+}
+
+/// Let requests past the workspace's hard limits run for a while; returns when the override ends (ms)
+#[tauri::command]
+pub async fn override_workspace_budget(
+    app_handle: AppHandle,
+    workspace_id: String,
+    minutes: Option<u32>,
+) -> Result<i64, String> {
+    let minutes = minutes.unwrap_or(DEFAULT_OVERRIDE_MINUTES).max(1);
+    info!("Command: override_workspace_budget - workspace: {}, minutes: {}", workspace_id, minutes);
+
+    let db = crate::database::connection::get_db_connection(&app_handle)
+        .await
+        .map_err(|e| e.to_string())?;
+    let meter = app_handle.state::<BudgetMeter>();
+    let _guard = meter.usage_lock.lock().await;
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut usage = workspace_budget::load_usage(&db, &workspace_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    usage.roll(now);
+    let until = now + i64::from(minutes) * 60 * 1000;
+    usage.override_until = Some(until);
+    workspace_budget::save_usage(&db, &workspace_id, &usage)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(until)
+}
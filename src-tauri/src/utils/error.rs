@@ -54,6 +54,10 @@ pub enum AppError {
     #[error("Operation cancelled: {0}")]
     Cancelled(String),
 
+    /// A workspace budget or rate limit was reached
+    #[error("Budget exceeded: {0}")]
+    BudgetExceeded(String),
+
     /// Anyhow errors
     #[error("Other error: {0}")]
     AnyhowError(#[from] anyhow::Error),
@@ -27,3 +27,67 @@ pub fn init_dir(dir: &str) -> Result<(), std::io::Error> {
 pub fn normalize_path(path: &str) -> String {
     path.replace(std::path::MAIN_SEPARATOR, "/")
 }
+
+/// 遍历工作区时默认跳过的目录
+pub const IGNORED_DIR_NAMES: &[&str] = &[
+    ".git",
+    ".svn",
+    ".hg",
+    ".idea",
+    ".vscode",
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    ".next",
+    "__pycache__",
+    ".venv",
+];
+
+/// Recursively collect regular files under `root`, skipping VCS / build / dependency directories.
+///
+/// Stops once `max_files` files have been collected.
+pub fn collect_workspace_files(root: &std::path::Path, max_files: usize) -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            let file_type = match entry.file_type() {
+                Ok(t) => t,
+                Err(_) => continue,
+            };
+            let path = entry.path();
+
+            if file_type.is_dir() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !IGNORED_DIR_NAMES.contains(&name.as_str()) {
+                    stack.push(path);
+                }
+            } else if file_type.is_file() {
+                files.push(path);
+                if files.len() >= max_files {
+                    return files;
+                }
+            }
+        }
+    }
+
+    files
+}
+
+/// Path of `path` relative to `root`, normalized to `/` separators.
+pub fn relative_path(root: &std::path::Path, path: &std::path::Path) -> String {
+    let rel = path.strip_prefix(root).unwrap_or(path);
+    normalize_path(&rel.to_string_lossy())
+}
+
+/// Heuristic binary detection: a NUL byte within the first 8 KiB.
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(8192).any(|b| *b == 0)
+}
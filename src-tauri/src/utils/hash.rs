@@ -0,0 +1,22 @@
+//! Hashing helpers
+//!
+//! Small, dependency-free hashes used for file names
+//! that must stay stable across runs and Rust versions.
+
+/// 64-bit FNV-1a hash
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    let mut hash = OFFSET_BASIS;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(PRIME);
+    }
+    hash
+}
+
+/// Hex-encoded FNV-1a hash of a string, suitable for file names
+pub fn stable_hash_hex(value: &str) -> String {
+    format!("{:016x}", fnv1a64(value.as_bytes()))
+}
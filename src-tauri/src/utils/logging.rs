@@ -95,6 +95,22 @@ pub fn init_tracing(app: &mut App) -> Result<()> {
     Ok(())
 }
 
+/// Initialize logging for headless mode: stderr only, so stdout carries just the AI output.
+///
+/// The level comes from `RUST_LOG` and defaults to `warn`.
+pub fn init_headless_tracing() {
+    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    let _ = tracing_subscriber::registry()
+        .with(env_filter)
+        .with(
+            fmt::layer()
+                .with_timer(build_timer())
+                .with_writer(std::io::stderr)
+                .with_target(true),
+        )
+        .try_init();
+}
+
 
 /// Log a message at debug level
 pub fn debug(message: &str) {
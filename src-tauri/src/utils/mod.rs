@@ -3,4 +3,4 @@
 pub mod error;
 pub mod logging;
 pub mod fs;
-
+pub mod hash;